@@ -1,7 +1,21 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
-#[derive(Debug, Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
+use crate::core::{
+    ChatMessage, Delta,
+    attachments::Attachment,
+    conversation::ToolCall,
+    health::Health,
+    keys::ApiKey,
+    length::ResponseLength,
+    summary::{SummaryRequest, SummaryUse},
+    templates::TemplateField,
+    translate::Translation,
+};
+
+#[derive(Debug, Clone, PartialEq, Display, Serialize, Deserialize)]
 pub enum Action {
     Tick,
     Render,
@@ -9,15 +23,168 @@ pub enum Action {
     Suspend,
     Resume,
     Quit,
+    ForceQuit,      // Quit without asking for confirmation
+    ConfigReloaded, // A config file changed on disk
     ClearScreen,
     Error(String),
+    ToggleDebugOverlay,
+    Notify(String),       // Show a short notice under the input until the next message
+    SetLogFilter(String), // Replace the log filter, e.g. `lazychat::app=debug`
+    CopyLastRequest,      // Copy the last API request as a curl command
+    CopyConversation,     // Copy the whole transcript as Markdown
     Help,
+    QuickPrompt(String), // Send the prompt from the config, filling in the clipboard and selection
+    SendQuickPrompt(String, Option<usize>), // Send the prompt with the message selected, if any
+    ForceSendMessage,    // Send the request held back by the hard budget limit anyway
+    DropHeldRequest,     // Give up the request held back by the hard budget limit
     SendMessage(String),
-    MessageReceived(String),
+    RestoreInput(String), // Put text that wasn't sent back into the input
+    PolishDraft,          // Have the model fix the grammar and clarity of the draft
+    PolishText(String),   // Ask for the fixes of the given draft
+    DraftPolished(String, Box<ChatMessage>), // The model's fixes of the given draft, replacing it
+    MessageDelta(Delta),  // Part of the reply being streamed
+    MessageReceived(Box<ChatMessage>), // Boxed, replies are much larger than other actions
+    CancelReply,          // Stop the reply being streamed, keeping what arrived
+    ContinueReply,        // Ask the model to go on with a reply cut off by max_tokens
+    TakeBackMessage,      // Cancel the reply and put the question back into the input
+    RateLimited(Duration), // The provider refused the request, it is resent after the wait
+    HealthChanged(Health), // Result of a connection check or request
+    RequeueUnanswered,    // The request never reached the provider, queue its question again
     FocusInput,
     FocusChat,
-    ShowDialog(String),      // Show dialog with content
-    HideDialog,              // Hide dialog
-    ShowSystemPromptDialog,  // Show system prompt dialog
-    SetSystemPrompt(String), // Set the system prompt
+    ShowDialog(String),                // Show dialog with content
+    HideDialog,                        // Hide dialog
+    ShowSystemPromptDialog,            // Show system prompt dialog
+    SetSystemPrompt(String),           // Set the system prompt
+    SetModel(String),                  // Use another model for the rest of the conversation
+    SetResponseLength(ResponseLength), // Switch to the brief, normal or detailed preset
+    CycleResponseLength,               // Switch to the next length preset
+    ToggleRecording,                   // Start or stop push-to-talk recording
+    TranscriptReceived(String),
+    ShowStats,                       // Show conversation statistics
+    ShowUsage,                       // Show tokens and cost of the last 30 days
+    ShowModelInfo,                   // Show the current model's settings and latency per model
+    ShowMenu(String, Vec<MenuItem>), // Show a popup menu with a title and items
+    HideMenu,
+    TogglePin(usize),             // Pin or unpin the message at the given index
+    ShowPinned,                   // Show the quick-jump menu of pinned messages
+    JumpToMessage(usize),         // Focus the chat and select the message at the given index
+    SetMark(char, usize),         // Set a vim-style mark on the message at the given index
+    ShowMarks,                    // Show the quick-jump menu of marked messages
+    QuoteMessage(usize),          // Quote the message at the given index into the input
+    CopyMessage(usize),           // Copy the message at the given index to the clipboard
+    EditMessage(usize),           // Open the message at the given index in the editor dialog
+    UpdateMessage(usize, String), // Replace the content of the message at the given index
+    AskOtherModel(usize),         // Pick a model to answer the question of the given reply too
+    AskModel(usize, String),      // Add the given model's answer to that question after the reply
+    RegenerateMessage(usize),     // Drop the given answer (and everything after it) and ask again
+    ShowVersionDiff(usize), // Compare the versions of the regenerated reply at the given index
+    HideVersionDiff,
+    SelectVersion(usize, usize), // Show the given version of the reply at the given index
+    DeleteMessage(usize),
+    ForkConversation(usize), // Save the conversation and continue a copy up to the given message
+    ViewRawMessage(usize),   // Show the message with its metadata as JSON
+    NewChat,                 // Save the conversation as a session and start a fresh one
+    ToggleSidebar,           // Show or hide the session sidebar
+    ToggleZenMode,           // Give the transcript the whole screen, without borders and titles
+    ToggleInputExpanded,     // Grow the input to nearly the whole screen, or shrink it back
+    ToggleDraftPreview,      // Show the draft's Markdown rendered next to the input, or hide it
+    FocusSidebar,
+    ShowSessionFinder,
+    HideSessionFinder,
+    OpenSession(String), // Save the conversation and switch to the session with the given id
+    RenameSession(String, String), // Set the title of the session with the given id
+    ArchiveSession(String, bool), // Archive (true) or unarchive (false) a session
+    DeleteSession(String), // Permanently delete a session
+    ShowMergeTargets,    // Pick a session to merge the current chat into
+    MergeSession(String), // Append the current chat to the session with the given id and open it
+    ShareConversation,   // Show the Markdown transcript that would be shared, for confirmation
+    PublishShare(String), // Upload the given transcript to the configured service
+    SaveNote,            // Append the transcript to the note in the configured notes directory
+    AttachDiff(Option<String>), // Attach `git diff` against the given ref, or HEAD, to the next message
+    AttachTree(Option<String>), // Attach the tree of the given directory, or the current one
+    Attach(Attachment),         // Send the text with the next message
+    ClearAttachments,           // Drop what was attached to the next message
+    ToggleClipboardWatch,       // Start or stop offering newly copied text as an attachment
+    ClipboardCopied(String),    // Text copied while watching, offered until attached or dismissed
+    AttachClipboard,            // Attach the offered clipboard text to the next message
+    DismissClipboard,           // Drop the offer of the copied text
+    WriteCommitMessage,         // Ask the model for a commit message for the staged changes
+    StagedDiffRead(Attachment), // The staged changes to write the commit message for
+    CommitMessageWritten(Box<ChatMessage>), // The model's reply with the commit message
+    SetTranslation(Option<Translation>), // Translate messages sent instead of answering them, or stop
+    TranslateMessage(usize),             // Translate the given message into the target language
+    MessageTranslated(String, Box<ChatMessage>), // The translation into the given language
+    ShowCommitMessage(String),           // Show the commit message for editing before committing
+    RunCommit(String),                   // Commit the staged changes with the given message
+    Summarize(SummaryUse),               // Ask the model for a summary of the conversation
+    SummaryWritten(SummaryRequest, Box<ChatMessage>), // The model's reply with the summary
+    ExtractTodos(Option<String>), // Ask the model for the action items, for a dialog or this file
+    TodosExtracted(Option<String>, Box<ChatMessage>), // The model's reply with the action items
+    IndexDirectory(String), // Chunk and embed the files of the directory for questions about it
+    ClearIndex,             // Stop adding indexed excerpts to questions
+    ShowApiKeyDialog,       // Ask for a new API key for the configured provider
+    SaveApiKey(ApiKey),     // Check the key with the provider and store it if it is accepted
+    ApiKeyChecked(ApiKey),  // The provider accepted the key, store it
+    ShowModelPicker,
+    HideModelPicker,
+    ModelsLoaded(Vec<String>), // Every model the provider offers, for the model picker
+    ToggleFavorite(String),    // Star or unstar the model with the given id
+    ShowToolApproval(ToolCall), // Ask whether a call the model made may run
+    HideToolApproval,
+    ApproveToolCall(ToolCall, bool), // Run the call; true allows the tool for the rest of the session
+    DenyToolCall(ToolCall),
+    ToolResult(ToolCall, String), // Output of a call that ran, for the model
+    ToggleAgentMode,              // Run tool calls without asking, up to the step budget
+    AbortAgent,                   // Stop the reply and every pending tool call at once
+    RunWorkflow(String, String),  // Run the named workflow from the config with the given input
+    ShowTemplates,                // Pick a prompt template from the config
+    UseTemplate(String),          // Insert the named template, asking for its placeholders first
+    ShowPersonas,                 // Pick a persona to start a session as
+    StartPersona(String),         // Start a new session as the named persona
+    StartRoundtable(Vec<String>), // Have the named personas answer every message in turn
+    StopRoundtable,
+    ShowTemplateForm(String, Vec<TemplateField>), // Ask for the values of a template's placeholders
+    HideTemplateForm,
+    FillTemplate(String, Vec<TemplateField>), // Insert the template filled with these values
+
+    // Commands handled by the focused component, bound per mode in the config
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft, // Scroll code blocks and tables that are too wide sideways
+    ScrollRight,
+    SelectPrevious,
+    SelectNext,
+    SelectFirst,
+    SelectLast,
+    Confirm,                 // Send the input, open/choose the selected item or save a dialog
+    Cancel,                  // Close a popup or abort a rename
+    ClearInput,              // Clear the input, asking first if the draft is long
+    Undo,                    // Undo the last edit of the input or editor dialog
+    Redo,                    // Redo the last undone edit
+    QuoteSelected,           // Quote the selected message into the input
+    TogglePinSelected,       // Pin or unpin the selected message
+    MarkSelected,            // Set the mark typed next on the selected message
+    JumpToMarkPrompt,        // Jump to the mark typed next
+    GoToMessagePrompt,       // Jump to the message number typed next
+    ToggleMessageNumbers,    // Show or hide the message number gutter
+    CycleRoleFilter,         // Show all messages, only the user's, the AI's or system and tool ones
+    ToggleReasoningSelected, // Expand or collapse the reasoning of the selected message
+    ToggleExpandSelected,    // Expand or collapse the selected message if it is long
+    PreviousVersionSelected, // Show the previous version of the selected regenerated reply
+    NextVersionSelected,     // Show the next version of the selected regenerated reply
+    RenameSelected,          // Rename the selected session
+    ArchiveSelected,         // Archive or unarchive the selected session
+    DeleteSelected,          // Delete the selected session after confirmation
+    MergeSelected,           // Merge the current chat into the selected session after confirmation
+    ToggleArchived,          // Show or hide archived sessions
+    ToggleFavoriteSelected,  // Star or unstar the selected model
+    ExportStats,             // Export the statistics shown in the dialog
+}
+
+/// An entry of a popup menu and the action it dispatches when chosen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MenuItem {
+    pub label: String,
+    pub action: Action,
 }
@@ -1,20 +1,61 @@
-use std::env;
+use std::{path::PathBuf, time::Instant};
 
-use color_eyre::Result;
+use color_eyre::{Result, eyre::eyre};
 use crossterm::event::KeyEvent;
 use ratatui::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    action::Action,
-    components::{Component, chat_window::ChatWindow, dialog::Dialog, home::Home, input::Input},
-    config::Config,
+    action::{Action, MenuItem},
+    audio::{self, Recorder},
+    clipboard,
+    components::{
+        Component, chat_window::ChatWindow, dialog::Dialog, home::Home, input::Input, menu::Menu,
+        model_picker::ModelPicker, session_finder::SessionFinder, session_sidebar::SessionSidebar,
+        template_form::TemplateForm, tool_approval::ToolApproval, version_diff::VersionDiff,
+    },
+    config::{self, Config, Overrides, normalize_key},
+    core::{
+        ChatMessage, Completion, Conversation, Engine,
+        attachments::{self, Attachment},
+        budget::BudgetStatus,
+        commit,
+        conversation::ToolCall,
+        health::{self, Health},
+        middleware,
+        models::ModelHistory,
+        notes,
+        personas::{self, Persona},
+        prose, provider,
+        rag::Index,
+        session::{self, SessionInfo},
+        share, stream, summarize,
+        summary::{self, SummaryRequest, SummaryUse},
+        templates::{self, PromptTemplate, TemplateValues},
+        todos,
+        tools::{self, ToolPolicy},
+        transcript,
+    },
+    debug::DebugMetrics,
+    logging, schema,
     tui::{Event, Tui},
 };
 
+const NO_NOTES_DIR: &str = "Set notes.dir in the config to save notes";
+
+/// Turns the answer to a request outside the conversation into the action that handles it.
+type AsideReply = Box<dyn FnOnce(Result<ChatMessage>) -> Action + Send>;
+
+/// A request held back because the hard budget limit is reached.
+enum HeldRequest {
+    /// The next reply of the conversation, and the message typed for it if any.
+    Reply(Completion, Option<String>),
+    Aside(Completion, AsideReply),
+}
+
 pub struct App {
     config: Config,
     tick_rate: f64,
@@ -27,54 +68,130 @@ pub struct App {
     action_tx: mpsc::UnboundedSender<Action>,
     action_rx: mpsc::UnboundedReceiver<Action>,
     state: AppState,
+    engine: Engine,
+    recorder: Option<Recorder>,
+    /// Whether the model list for the picker has been fetched or is being fetched.
+    models_requested: bool,
+    /// The request for the reply in progress, or the running tool call, aborted to cancel it
+    /// unless it is a reply that can be stopped with `reply_cancel`.
+    completion_task: Option<tokio::task::JoinHandle<()>>,
+    /// Stops the reply in progress, passing on what was batched up of it before the task ends.
+    reply_cancel: Option<CancellationToken>,
+    /// Whether a tool call is running or waiting for approval.
+    handling_tool_call: bool,
+    /// Whether the approval popup is open, so aborting knows to close it.
+    approving_tool_call: bool,
+    /// Pings the provider in the background, restarted when the config changes.
+    health_checks: Option<tokio::task::JoinHandle<()>>,
+    /// Keeps the config file watcher alive.
+    config_watcher: Option<notify::RecommendedWatcher>,
+    /// Checks the clipboard for newly copied text while `/clipboard` is on.
+    clipboard_watch: Option<tokio::task::JoinHandle<()>>,
+    /// The request for the summary being written, so that compaction isn't asked for twice.
+    summary_task: Option<tokio::task::JoinHandle<()>>,
+    /// A request held back by the hard budget limit until it is sent anyway or given up.
+    held_request: Option<HeldRequest>,
+    /// Metrics for the debug overlay, collected only while it is shown.
+    debug_metrics: Option<DebugMetrics>,
+    /// What was last filled into each prompt template.
+    template_values: TemplateValues,
 }
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Mode {
+    /// Global keybindings, used when the focused component doesn't handle a key.
     #[default]
     Home,
-}
-
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct ChatMessage {
-    pub role: String,
-    pub content: String,
+    Input,
+    Chat,
+    Sidebar,
+    Dialog,
+    /// Popup menus and the session finder.
+    Menu,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct AppState {
-    pub chat_history: Vec<ChatMessage>,
-    pub is_loading: bool,
-    pub system_prompt: String,
+    /// Copy of the engine's conversation.
+    pub conversation: Conversation,
+    pub is_recording: bool,
+    pub is_transcribing: bool,
+    /// Saved sessions, most recently updated first.
+    pub sessions: Vec<SessionInfo>,
+    pub show_sidebar: bool,
+    /// Only the transcript and a borderless input are shown, without the sidebar or status bar.
+    pub zen_mode: bool,
+    /// The input takes up nearly the whole screen for composing long prompts.
+    pub input_expanded: bool,
+    /// The draft's Markdown is shown rendered next to the input.
+    pub draft_preview: bool,
+    /// Short status shown under the input until the next message is sent.
+    pub notice: Option<String>,
+    pub model_history: ModelHistory,
+    pub health: Health,
+    /// Text copied while watching the clipboard, offered as an attachment.
+    pub clipboard_offer: Option<String>,
 }
 
 impl App {
-    pub fn new(tick_rate: f64, frame_rate: f64) -> Result<Self> {
+    pub fn new(config: Config, tick_rate: f64, frame_rate: f64) -> Result<Self> {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
-        let state = AppState::default();
+        let mut engine = Engine::new(config.engine_settings());
+        engine.add_middleware(middleware::Logging);
+        let template_values = TemplateValues::load(&config.config.template_values_file());
+        let state = AppState {
+            conversation: engine.conversation.clone(),
+            sessions: engine.list_sessions().unwrap_or_else(|err| {
+                error!("Failed to list sessions: {err}");
+                Vec::new()
+            }),
+            ..Default::default()
+        };
         Ok(Self {
             tick_rate,
             frame_rate,
             components: vec![
                 Box::new(Home::new()),
+                Box::new(SessionSidebar::new()),
                 Box::new(ChatWindow::new()),
                 Box::new(Input::new()),
                 Box::new(Dialog::new()),
+                Box::new(Menu::new()),
+                Box::new(SessionFinder::new(engine.sessions().clone())),
+                Box::new(ModelPicker::new()),
+                Box::new(ToolApproval::new()),
+                Box::new(TemplateForm::new()),
+                Box::new(VersionDiff::new()),
             ],
             should_quit: false,
             should_suspend: false,
-            config: Config::new()?,
+            config,
             mode: Mode::Home,
             last_tick_key_events: Vec::new(),
             action_tx,
             action_rx,
             state,
+            engine,
+            recorder: None,
+            models_requested: false,
+            completion_task: None,
+            reply_cancel: None,
+            handling_tool_call: false,
+            approving_tool_call: false,
+            health_checks: None,
+            config_watcher: None,
+            clipboard_watch: None,
+            summary_task: None,
+            held_request: None,
+            debug_metrics: None,
+            template_values,
         })
     }
 
     pub async fn run(&mut self) -> Result<()> {
         let mut tui = Tui::new()?
             .mouse(true) // uncomment this line to enable mouse support
+            .paste(true)
             .tick_rate(self.tick_rate)
             .frame_rate(self.frame_rate);
         tui.enter()?;
@@ -85,12 +202,18 @@ impl App {
         for component in self.components.iter_mut() {
             component.register_config_handler(self.config.clone())?;
         }
-        for component in self.components.iter_mut() {
-            component.register_state_handler(self.state.clone())?;
+        self.start_health_checks();
+        self.config_watcher = config::watch(self.action_tx.clone())
+            .inspect_err(|err| error!("Not watching the config directory: {err}"))
+            .ok();
+        if self.config.attachments.watch_clipboard {
+            self.clipboard_watch = Some(self.watch_clipboard());
         }
+        self.sync_state()?;
         for component in self.components.iter_mut() {
             component.init(tui.size()?)?;
         }
+        self.report_config_problems(&self.config.keybinding_diagnostics())?;
 
         let action_tx = self.action_tx.clone();
         loop {
@@ -111,6 +234,18 @@ impl App {
         Ok(())
     }
 
+    /// Show `notice` once the app is running.
+    pub fn notify(&self, notice: String) -> Result<()> {
+        self.action_tx.send(Action::Notify(notice))?;
+        Ok(())
+    }
+
+    /// The conversation as Markdown, or `None` if nothing was said.
+    pub fn transcript(&self) -> Option<String> {
+        let conversation = &self.engine.conversation;
+        (!conversation.messages.is_empty()).then(|| share::markdown(conversation))
+    }
+
     async fn handle_events(&mut self, tui: &mut Tui) -> Result<()> {
         let Some(event) = tui.next_event().await else {
             return Ok(());
@@ -153,6 +288,7 @@ impl App {
         let Some(keymap) = self.config.keybindings.get(&self.mode) else {
             return Ok(());
         };
+        let key = normalize_key(key);
         match keymap.get(&vec![key]) {
             Some(action) => {
                 info!("Got action: {action:?}");
@@ -175,132 +311,1097 @@ impl App {
 
     async fn handle_actions(&mut self, tui: &mut Tui) -> Result<()> {
         while let Ok(action) = self.action_rx.try_recv() {
-            if action != Action::Tick && action != Action::Render {
+            if !matches!(
+                action,
+                Action::Tick | Action::Render | Action::MessageDelta(_)
+            ) {
                 debug!("{action:?}");
             }
+            if let Some(metrics) = &mut self.debug_metrics {
+                metrics.record_queue_depth(self.action_rx.len());
+                metrics.record_action(&action);
+            }
             match &action {
                 Action::Tick => {
                     self.last_tick_key_events.drain(..);
+                    if let Some(metrics) = &mut self.debug_metrics {
+                        metrics.record_tick();
+                    }
                 }
-                Action::Quit => self.should_quit = true,
+                Action::ToggleDebugOverlay => {
+                    self.debug_metrics = match self.debug_metrics {
+                        Some(_) => None,
+                        None => Some(DebugMetrics::default()),
+                    };
+                    self.action_tx.send(Action::Render)?;
+                }
+                Action::Quit if self.engine.conversation.is_loading => {
+                    // Don't throw away a long generation because of a stray Ctrl+C
+                    let items = vec![
+                        MenuItem {
+                            label: "Quit anyway".to_string(),
+                            action: Action::ForceQuit,
+                        },
+                        MenuItem {
+                            label: "Stop the reply, keep what arrived".to_string(),
+                            action: Action::CancelReply,
+                        },
+                        MenuItem {
+                            label: "Keep waiting".to_string(),
+                            action: Action::HideMenu,
+                        },
+                    ];
+                    self.action_tx.send(Action::ShowMenu(
+                        "A response is still generating, quit anyway?".to_string(),
+                        items,
+                    ))?;
+                }
+                Action::Quit | Action::ForceQuit => self.should_quit = true,
+                Action::ConfigReloaded => self.reload_config()?,
                 Action::Suspend => self.should_suspend = true,
                 Action::Resume => self.should_suspend = false,
                 Action::ClearScreen => tui.terminal.clear()?,
                 Action::Resize(w, h) => self.handle_resize(tui, *w, *h)?,
                 Action::Render => self.render(tui)?,
                 Action::Error(err) => {
-                    // Clear loading state on error and show error message
-                    self.state.is_loading = false;
-                    self.state.chat_history.push(ChatMessage {
-                        role: "system".to_string(),
-                        content: format!("Error: {err}"),
-                    });
-                    // Update state in all components
-                    for component in self.components.iter_mut() {
-                        component.register_state_handler(self.state.clone())?;
+                    if self.engine.conversation.is_loading {
+                        self.engine.stop_workflow();
                     }
+                    // Clear loading state on error and show error message
+                    self.state.is_transcribing = false;
+                    self.engine.conversation.push_error(err);
+                    self.sync_state()?;
                     self.render(tui)?;
                 }
+                Action::SendQuickPrompt(prompt, selected) => {
+                    let action = match self.quick_prompt(prompt, *selected) {
+                        Ok(message) => Action::SendMessage(message),
+                        Err(err) => Action::Error(err.to_string()),
+                    };
+                    self.action_tx.send(action)?;
+                }
                 Action::SendMessage(message) => {
-                    self.state.chat_history.push(ChatMessage {
-                        role: "user".to_string(),
-                        content: message.clone(),
-                    });
-                    debug!("Message sent: {}", message);
-
-                    // Set loading state
-                    self.state.is_loading = true;
-                    // Update state in all components
-                    for component in self.components.iter_mut() {
-                        component.register_state_handler(self.state.clone())?;
+                    // The expanded input collapses once its text is on its way
+                    if self.state.input_expanded {
+                        self.state.input_expanded = false;
+                        self.sync_state()?;
                     }
-                    // Force immediate render to show loading state
+                    // Keep the order: nothing overtakes messages already waiting
+                    if self.state.health == Health::Offline
+                        || !self.engine.conversation.queued.is_empty()
+                    {
+                        let queued = &mut self.engine.conversation.queued;
+                        self.state.notice = Some(if self.state.health == Health::Offline {
+                            "Offline, the message will be sent once the connection is back"
+                                .to_string()
+                        } else {
+                            match queued.len() {
+                                1 => "Queued behind 1 unsent message".to_string(),
+                                count => format!("Queued behind {count} unsent messages"),
+                            }
+                        });
+                        queued.push(message.clone());
+                        self.send_queued(tui)?;
+                        self.sync_state()?;
+                    } else {
+                        self.send_message(tui, message)?;
+                    }
+                }
+                Action::ForceSendMessage => match self.held_request.take() {
+                    Some(HeldRequest::Reply(completion, _)) => {
+                        self.send_completion(tui, completion)?;
+                    }
+                    Some(HeldRequest::Aside(completion, reply)) => {
+                        self.send_aside(completion, reply);
+                    }
+                    None => {}
+                },
+                Action::DropHeldRequest => {
+                    if let Some(HeldRequest::Reply(_, typed)) = self.held_request.take() {
+                        self.engine.stop_workflow();
+                        self.engine.conversation.cancel_reply();
+                        // A typed message goes back into the input as if it was never sent
+                        if let Some(text) = typed {
+                            self.engine.conversation.messages.pop();
+                            self.action_tx.send(Action::RestoreInput(text))?;
+                        }
+                        self.sync_state()?;
+                    }
+                }
+                Action::MessageDelta(delta) => {
+                    self.engine.receive_delta(delta);
+                    self.sync_state()?;
+                }
+                // A reply that arrives after it was cancelled is dropped
+                Action::MessageReceived(message) if self.engine.conversation.is_loading => {
+                    if message.metadata.is_truncated() {
+                        self.state.notice = Some(
+                            "The reply hit max_tokens and was cut off, Alt+C continues it"
+                                .to_string(),
+                        );
+                    }
+                    self.engine.receive(message.as_ref().clone());
+                    if self.state.notice.is_none()
+                        && let BudgetStatus::Soft(reason) = self.engine.budget_status()
+                    {
+                        self.state.notice = Some(reason);
+                    }
+                    self.sync_state()?;
+                    self.run_tool_calls(tui)?;
+                    if self.engine.compaction_due(&self.config.compaction)
+                        && self
+                            .summary_task
+                            .as_ref()
+                            .is_none_or(|task| task.is_finished())
+                    {
+                        self.action_tx
+                            .send(Action::Summarize(SummaryUse::Compact))?;
+                    }
+                    // Force immediate render to show response
                     self.render(tui)?;
-
-                    // Spawn API call in background to avoid blocking the event loop
+                }
+                Action::HealthChanged(health) if *health != self.state.health => {
+                    self.state.health = *health;
+                    self.send_queued(tui)?;
+                    self.sync_state()?;
+                }
+                Action::CancelReply | Action::AbortAgent if self.reply_cancel.is_some() => {
+                    self.stop_reply().await;
+                    // Handled again after the rest of the reply that was stopped
+                    self.action_tx.send(action.clone())?;
+                }
+                Action::CancelReply => {
+                    self.held_request = None;
+                    if let Some(task) = self.completion_task.take() {
+                        task.abort();
+                    }
+                    self.engine.stop_workflow();
+                    if self.engine.conversation.cancel_reply() {
+                        self.state.notice = Some("Reply cancelled".to_string());
+                        self.send_queued(tui)?;
+                        self.sync_state()?;
+                    } else if self.handling_tool_call {
+                        self.answer_pending_tool_calls("Cancelled by the user")?;
+                        self.state.notice = Some("Tool calls cancelled".to_string());
+                        self.sync_state()?;
+                    }
+                }
+                Action::AbortAgent => {
+                    self.held_request = None;
+                    if let Some(task) = self.completion_task.take() {
+                        task.abort();
+                    }
+                    self.engine.stop_workflow();
+                    self.engine.conversation.cancel_reply();
+                    self.answer_pending_tool_calls("Aborted by the user")?;
+                    self.state.notice = Some("Agent aborted".to_string());
+                    self.sync_state()?;
+                }
+                Action::ToggleAgentMode => {
+                    let conversation = &mut self.engine.conversation;
+                    if !self.config.tools.enabled && !conversation.agent {
+                        self.action_tx.send(Action::Error(
+                            "Agent mode needs tools, set `tools.enabled` in the config".to_string(),
+                        ))?;
+                    } else {
+                        conversation.agent = !conversation.agent;
+                        let notice = match (conversation.agent, self.config.tools.agent_steps) {
+                            (false, _) => "Agent mode off".to_string(),
+                            (true, 0) => "Agent mode on: tools run without asking".to_string(),
+                            (true, steps) => format!(
+                                "Agent mode on: tools run without asking, up to {steps} calls"
+                            ),
+                        };
+                        self.action_tx.send(Action::Notify(notice))?;
+                        self.sync_state()?;
+                    }
+                }
+                Action::ApproveToolCall(call, for_session) => {
+                    self.approving_tool_call = false;
+                    if *for_session {
+                        let tools = &mut self.engine.conversation.allowed_tools;
+                        tools.insert(call.name.clone());
+                    }
+                    self.start_tool_call(call.clone());
+                }
+                Action::DenyToolCall(call) if self.handling_tool_call => {
+                    self.handling_tool_call = false;
+                    self.approving_tool_call = false;
+                    let conversation = &mut self.engine.conversation;
+                    conversation.push_tool_result(call, "The user denied this call");
+                    self.sync_state()?;
+                    self.run_tool_calls(tui)?;
+                }
+                Action::ToolResult(call, output) if self.handling_tool_call => {
+                    self.handling_tool_call = false;
+                    self.state.notice = None;
+                    self.engine.push_tool_output(call, output.clone());
+                    self.sync_state()?;
+                    self.run_tool_calls(tui)?;
+                }
+                Action::TakeBackMessage => {
+                    self.reply_cancel = None;
+                    if let Some(task) = self.completion_task.take() {
+                        task.abort();
+                    }
+                    self.engine.stop_workflow();
+                    if let Some(text) = self.engine.conversation.take_back_unanswered() {
+                        self.action_tx.send(Action::RestoreInput(text))?;
+                        self.action_tx.send(Action::FocusInput)?;
+                    }
+                    self.sync_state()?;
+                }
+                Action::RateLimited(wait) => {
+                    if let Some(partial) = &mut self.engine.conversation.partial {
+                        partial.retry_after(*wait);
+                    }
+                    self.sync_state()?;
+                }
+                Action::RequeueUnanswered => {
+                    self.engine.conversation.requeue_unanswered();
+                    self.state.notice = Some(
+                        "Couldn't reach the provider, the message will be sent once the connection is back"
+                            .to_string(),
+                    );
+                    self.sync_state()?;
+                }
+                Action::Notify(notice) => {
+                    self.state.notice = Some(notice.clone());
+                    self.sync_state()?;
+                }
+                Action::SetLogFilter(filter) => {
+                    let notice = match logging::set_filter(filter) {
+                        Ok(()) => format!("Log filter set to `{filter}`"),
+                        Err(err) => format!("Invalid log filter: {err}"),
+                    };
+                    self.action_tx.send(Action::Notify(notice))?;
+                }
+                Action::SetModel(model) => {
+                    self.engine.set_model(model);
+                    self.action_tx
+                        .send(Action::Notify(format!("Model set to {model}")))?;
+                    self.sync_state()?;
+                }
+                Action::SetResponseLength(length) => {
+                    self.engine.conversation.length = *length;
+                    self.action_tx
+                        .send(Action::Notify(format!("Replies set to {length}")))?;
+                    self.sync_state()?;
+                }
+                Action::CycleResponseLength => {
+                    let length = self.engine.conversation.length.next();
+                    self.action_tx.send(Action::SetResponseLength(length))?;
+                }
+                Action::ShowModelPicker | Action::AskOtherModel(_) if !self.models_requested => {
+                    self.models_requested = true;
+                    let provider = self.engine.provider();
                     let action_tx = self.action_tx.clone();
-                    let chat_history = self.state.chat_history.clone();
-                    let system_prompt = self.state.system_prompt.clone();
                     tokio::spawn(async move {
-                        let result = async {
-                            let client = reqwest::Client::new();
-
-                            // Prepare messages with optional system prompt
-                            let mut messages = Vec::new();
-
-                            // Add system prompt if it exists and is not empty
-                            if !system_prompt.is_empty() {
-                                messages.push(json!({
-                                    "role": "system",
-                                    "content": system_prompt
-                                }));
-                            }
-
-                            // Add chat history
-                            messages.extend(chat_history.iter().map(|msg| {
-                                json!({
-                                    "role": msg.role,
-                                    "content": msg.content
-                                })
-                            }));
-
-                            let response = client
-                                .post("https://openrouter.ai/api/v1/chat/completions")
-                                .header("Content-Type", "application/json")
-                                .bearer_auth(env::var("OPENROUTER_API_KEY").map_err(|_| {
-                                    color_eyre::eyre::eyre!(
-                                        "OPENROUTER_API_KEY environment variable not set"
-                                    )
-                                })?)
-                                .body(
-                                    json!({
-                                        "model": "mistralai/mistral-nemo",
-                                        "messages": messages
-                                    })
-                                    .to_string(),
+                        let models = provider.models().await.unwrap_or_else(|err| {
+                            let _ = action_tx
+                                .send(Action::Error(format!("Failed to list models: {err}")));
+                            Vec::new()
+                        });
+                        let _ = action_tx.send(Action::ModelsLoaded(models));
+                    });
+                }
+                // Try again next time the picker opens
+                Action::ModelsLoaded(models) if models.is_empty() => self.models_requested = false,
+                Action::ToggleFavorite(model) => {
+                    let action = match self.engine.toggle_favorite(model) {
+                        Ok(true) => Action::Notify(format!("Starred {model}")),
+                        Ok(false) => Action::Notify(format!("Unstarred {model}")),
+                        Err(err) => Action::Error(format!("Failed to save favorites: {err}")),
+                    };
+                    self.action_tx.send(action)?;
+                    self.sync_state()?;
+                }
+                Action::SetSystemPrompt(prompt) => {
+                    self.engine.conversation.system_prompt = prompt.clone();
+                    self.sync_state()?;
+                }
+                Action::ToggleRecording => self.toggle_recording().await?,
+                Action::TranscriptReceived(_) => {
+                    self.state.is_transcribing = false;
+                    self.sync_state()?;
+                }
+                Action::TogglePin(index) => {
+                    self.engine.conversation.toggle_pin(*index);
+                    self.sync_state()?;
+                }
+                Action::ShowPinned => {
+                    let items = self
+                        .engine
+                        .conversation
+                        .messages
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, message)| message.pinned)
+                        .map(|(index, message)| MenuItem {
+                            label: format!("{}: {}", message.role, summarize(&message.content, 50)),
+                            action: Action::JumpToMessage(index),
+                        })
+                        .collect();
+                    self.action_tx
+                        .send(Action::ShowMenu("Pinned Messages".to_string(), items))?;
+                }
+                Action::SetMark(mark, index) => {
+                    self.engine.conversation.set_mark(*mark, *index);
+                    self.sync_state()?;
+                }
+                Action::ShowMarks => {
+                    let mut marks: Vec<_> = self
+                        .engine
+                        .conversation
+                        .messages
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(index, message)| {
+                            message
+                                .marks
+                                .iter()
+                                .map(move |mark| (*mark, index, message))
+                        })
+                        .collect();
+                    marks.sort_by_key(|(mark, ..)| *mark);
+                    let items = marks
+                        .into_iter()
+                        .map(|(mark, index, message)| MenuItem {
+                            label: format!(
+                                "'{mark}  {}: {}",
+                                message.role,
+                                summarize(&message.content, 45)
+                            ),
+                            action: Action::JumpToMessage(index),
+                        })
+                        .collect();
+                    self.action_tx
+                        .send(Action::ShowMenu("Marks".to_string(), items))?;
+                }
+                Action::CopyMessage(index) => {
+                    if let Some(message) = self.engine.conversation.messages.get(*index)
+                        && let Err(err) = clipboard::copy(&message.content)
+                    {
+                        self.action_tx
+                            .send(Action::Error(format!("Clipboard Error: {err}")))?;
+                    }
+                }
+                Action::CopyConversation => {
+                    let conversation = &self.engine.conversation;
+                    let result = if conversation.messages.is_empty() {
+                        Err("Nothing to copy yet".to_string())
+                    } else {
+                        clipboard::copy(&share::markdown(conversation))
+                            .map(|()| {
+                                format!(
+                                    "Copied {} messages as Markdown",
+                                    conversation.messages.len()
                                 )
-                                .send()
-                                .await?;
-                            let response_text = response.text().await?;
-                            let response_json: serde_json::Value =
-                                serde_json::from_str(&response_text)?;
-                            let content = response_json["choices"][0]["message"]["content"]
-                                .as_str()
-                                .unwrap();
-                            Ok::<String, color_eyre::eyre::Error>(content.to_string())
+                            })
+                            .map_err(|err| format!("Clipboard Error: {err}"))
+                    };
+                    self.action_tx.send(match result {
+                        Ok(notice) => Action::Notify(notice),
+                        Err(err) => Action::Error(err),
+                    })?;
+                }
+                Action::SaveNote => {
+                    if self.engine.conversation.messages.is_empty() {
+                        self.action_tx
+                            .send(Action::Notify("Nothing to save yet".to_string()))?;
+                    } else {
+                        self.action_tx.send(self.save_note(None))?;
+                    }
+                }
+                Action::CopyLastRequest => {
+                    let result = match self.engine.last_request() {
+                        Some(body) => clipboard::copy(&transcript::curl_command(
+                            provider::API_URL,
+                            provider::API_KEY_ENV,
+                            &body,
+                        ))
+                        .map(|()| "Copied the last request as a curl command".to_string())
+                        .map_err(|err| format!("Clipboard Error: {err}")),
+                        None => Err("No request has been sent yet".to_string()),
+                    };
+                    self.action_tx.send(match result {
+                        Ok(notice) => Action::Notify(notice),
+                        Err(err) => Action::Error(err),
+                    })?;
+                }
+                Action::UpdateMessage(index, content) => {
+                    self.engine.conversation.update_message(*index, content);
+                    self.sync_state()?;
+                }
+                Action::AskModel(index, model) => match self.engine.ask_model(*index, model) {
+                    Some(completion) => self.request_completion(tui, completion)?,
+                    None => self.action_tx.send(Action::Error(
+                        "Only replies can be asked again, once no reply is awaited".to_string(),
+                    ))?,
+                },
+                Action::SelectVersion(index, version) => {
+                    let message = self.engine.conversation.messages.get_mut(*index);
+                    if let Some(message) = message
+                        && message.select_version(*version)
+                    {
+                        let notice = format!(
+                            "Showing version {} of {}, which later replies build on",
+                            version + 1,
+                            message.versions.len() + 1
+                        );
+                        self.action_tx.send(Action::Notify(notice))?;
+                        self.sync_state()?;
+                    }
+                }
+                Action::RegenerateMessage(index) => match self.engine.regenerate(*index) {
+                    Some(completion) => self.request_completion(tui, completion)?,
+                    None => self.sync_state()?,
+                },
+                Action::RunWorkflow(name, _) if self.engine.conversation.is_loading => {
+                    self.action_tx.send(Action::Error(format!(
+                        "Wait for the reply before running workflow {name}"
+                    )))?;
+                }
+                Action::RunWorkflow(name, input) => match self.engine.start_workflow(name, input) {
+                    Ok(completion) => {
+                        self.state.notice = self.workflow_progress();
+                        self.request_completion(tui, completion)?;
+                    }
+                    Err(err) => self.action_tx.send(Action::Error(err.to_string()))?,
+                },
+                Action::ShowTemplates => {
+                    let templates = self.templates();
+                    if templates.is_empty() {
+                        self.action_tx.send(Action::Error(
+                            "No prompt templates, add some under `templates` in the config or as \
+                             Markdown files in the templates directory"
+                                .to_string(),
+                        ))?;
+                    } else {
+                        let items = templates
+                            .iter()
+                            .map(|template| MenuItem {
+                                label: template.label(),
+                                action: Action::UseTemplate(template.name.clone()),
+                            })
+                            .collect();
+                        self.action_tx
+                            .send(Action::ShowMenu("Prompt templates".to_string(), items))?;
+                    }
+                }
+                Action::ShowPersonas => {
+                    let personas = self.personas();
+                    if personas.is_empty() {
+                        self.action_tx.send(Action::Error(
+                            "No personas, add some under `personas` in the config or as JSON \
+                             files or character cards in the personas directory"
+                                .to_string(),
+                        ))?;
+                    } else {
+                        let items = personas
+                            .iter()
+                            .map(|persona| MenuItem {
+                                label: persona.label(),
+                                action: Action::StartPersona(persona.name.clone()),
+                            })
+                            .collect();
+                        self.action_tx
+                            .send(Action::ShowMenu("Personas".to_string(), items))?;
+                    }
+                }
+                Action::StartPersona(name) => {
+                    match self
+                        .personas()
+                        .into_iter()
+                        .find(|persona| persona.name == *name)
+                    {
+                        Some(persona) => {
+                            let result = self.engine.start_persona(&persona);
+                            self.report(result, "Failed to save session")?;
+                            self.action_tx
+                                .send(Action::Notify(format!("Chatting with {name}")))?;
+                            self.action_tx.send(Action::FocusInput)?;
                         }
-                        .await;
-
-                        match result {
-                            Ok(content) => {
-                                let _ = action_tx.send(Action::MessageReceived(content));
+                        None => self
+                            .action_tx
+                            .send(Action::Error(format!("No persona named {name}")))?,
+                    }
+                }
+                Action::StartRoundtable(names) => {
+                    let personas = self.personas();
+                    let seats: Result<Vec<Persona>, String> = names
+                        .iter()
+                        .map(|name| {
+                            personas
+                                .iter()
+                                .find(|persona| persona.name == *name)
+                                .cloned()
+                                .ok_or_else(|| format!("No persona named {name}"))
+                        })
+                        .collect();
+                    let result = seats.and_then(|seats| {
+                        self.engine
+                            .start_roundtable(seats)
+                            .map_err(|err| err.to_string())
+                    });
+                    self.action_tx.send(match result {
+                        Ok(()) => Action::Notify(format!(
+                            "Roundtable: {} answer in turn, /roundtable off ends it",
+                            names.join(", ")
+                        )),
+                        Err(err) => Action::Error(err),
+                    })?;
+                    self.sync_state()?;
+                }
+                Action::StopRoundtable => {
+                    let notice = match self.engine.stop_roundtable() {
+                        Some(roundtable) => format!("Roundtable of {} ended", roundtable.names()),
+                        None => "No roundtable is being held".to_string(),
+                    };
+                    self.action_tx.send(Action::Notify(notice))?;
+                    self.sync_state()?;
+                }
+                Action::UseTemplate(name) => match self.template(name) {
+                    Some(template) => {
+                        self.apply_template(&template)?;
+                        let fields = self.template_values.fields(name, &template.text);
+                        if fields.is_empty() {
+                            self.action_tx.send(Action::RestoreInput(template.text))?;
+                            self.action_tx.send(Action::FocusInput)?;
+                        } else {
+                            self.action_tx
+                                .send(Action::ShowTemplateForm(name.clone(), fields))?;
+                        }
+                    }
+                    None => self
+                        .action_tx
+                        .send(Action::Error(format!("No template named {name}")))?,
+                },
+                Action::FillTemplate(name, fields) => {
+                    if let Some(template) = self.template(name) {
+                        self.template_values.remember(name, fields);
+                        let path = self.config.config.template_values_file();
+                        if let Err(err) = self.template_values.save(&path) {
+                            error!("Failed to save template values: {err}");
+                        }
+                        let text = templates::fill(&template.text, fields);
+                        self.action_tx.send(Action::RestoreInput(text))?;
+                        self.action_tx.send(Action::FocusInput)?;
+                    }
+                }
+                Action::ContinueReply => match self.engine.continue_reply() {
+                    Some(completion) => {
+                        self.state.notice = None;
+                        self.request_completion(tui, completion)?;
+                    }
+                    None => self.action_tx.send(Action::Error(
+                        "Only a reply cut off by max_tokens can be continued".to_string(),
+                    ))?,
+                },
+                Action::DeleteMessage(index) => {
+                    self.engine.conversation.delete_message(*index);
+                    self.sync_state()?;
+                }
+                Action::ForkConversation(index) => {
+                    // Keep the full conversation as a session, then continue on a copy
+                    let messages = self.engine.conversation.fork(*index);
+                    self.start_conversation(messages)?;
+                }
+                // Model and system prompt carry over to the new conversation, except a persona's
+                Action::NewChat => {
+                    let result = self.engine.new_chat();
+                    self.report(result, "Failed to save session")?;
+                }
+                Action::ToggleSidebar => {
+                    // The sidebar brings the rest of the chrome back with it
+                    self.state.zen_mode = false;
+                    self.state.show_sidebar = !self.state.show_sidebar;
+                    self.sync_state()?;
+                    self.action_tx.send(if self.state.show_sidebar {
+                        Action::FocusSidebar
+                    } else {
+                        Action::FocusInput
+                    })?;
+                }
+                Action::ToggleInputExpanded => {
+                    self.state.input_expanded = !self.state.input_expanded;
+                    self.sync_state()?;
+                    if self.state.input_expanded {
+                        self.action_tx.send(Action::FocusInput)?;
+                    }
+                }
+                Action::ToggleDraftPreview => {
+                    self.state.draft_preview = !self.state.draft_preview;
+                    self.sync_state()?;
+                }
+                Action::ToggleZenMode => {
+                    self.state.zen_mode = !self.state.zen_mode;
+                    self.sync_state()?;
+                    // Don't leave the keys with the hidden sidebar
+                    if self.state.zen_mode && self.state.show_sidebar {
+                        self.action_tx.send(Action::FocusInput)?;
+                    }
+                }
+                Action::OpenSession(id) => {
+                    let result = self.engine.open_session(id);
+                    self.report(result, "Failed to open session")?;
+                }
+                Action::RenameSession(id, title) => {
+                    let result = self.engine.rename_session(id, title);
+                    self.report(result, "Failed to rename session")?;
+                }
+                Action::ArchiveSession(id, archived) => {
+                    let result = self.engine.archive_session(id, *archived);
+                    self.report(result, "Failed to archive session")?;
+                }
+                Action::DeleteSession(id) => {
+                    let result = self.engine.delete_session(id);
+                    self.report(result, "Failed to delete session")?;
+                }
+                Action::ShowMergeTargets => {
+                    let current = &self.engine.conversation.id;
+                    let items: Vec<MenuItem> = self
+                        .state
+                        .sessions
+                        .iter()
+                        .filter(|session| &session.id != current)
+                        .map(|session| MenuItem {
+                            label: session.title.clone(),
+                            action: Action::MergeSession(session.id.clone()),
+                        })
+                        .collect();
+                    let action = if items.is_empty() {
+                        Action::Error("There is no other session to merge into".to_string())
+                    } else {
+                        Action::ShowMenu("Merge this chat into".to_string(), items)
+                    };
+                    self.action_tx.send(action)?;
+                }
+                Action::MergeSession(id) => {
+                    let result = self.engine.merge_into(id);
+                    self.report(result, "Failed to merge sessions")?;
+                }
+                Action::SaveApiKey(key) => {
+                    let provider = self.engine.provider();
+                    let key = key.clone();
+                    let action_tx = self.action_tx.clone();
+                    self.action_tx
+                        .send(Action::Notify("Checking the API key…".to_string()))?;
+                    tokio::spawn(async move {
+                        let action = match provider.check_key(&key).await {
+                            Ok(()) => Action::ApiKeyChecked(key),
+                            Err(err) => Action::Error(format!("The key was not saved: {err}")),
+                        };
+                        let _ = action_tx.send(action);
+                    });
+                }
+                Action::ApiKeyChecked(key) => {
+                    let action = match self.engine.set_api_key(key.clone()) {
+                        Ok(()) => Action::Notify(format!(
+                            "Saved API key {}, used from the next request",
+                            key.masked()
+                        )),
+                        Err(err) => Action::Error(format!("Failed to save the API key: {err}")),
+                    };
+                    self.action_tx.send(action)?;
+                }
+                Action::AttachDiff(reference) => {
+                    let reference = reference.clone();
+                    let attachments_config = self.config.attachments.clone();
+                    let action_tx = self.action_tx.clone();
+                    tokio::spawn(async move {
+                        let dir = std::env::current_dir().unwrap_or_default();
+                        let action = match attachments::git_diff(
+                            &dir,
+                            reference.as_deref(),
+                            &attachments_config,
+                        )
+                        .await
+                        {
+                            Ok(attachment) => Action::Attach(attachment),
+                            Err(err) => Action::Error(format!("Nothing attached: {err}")),
+                        };
+                        let _ = action_tx.send(action);
+                    });
+                }
+                Action::AttachTree(dir) => {
+                    let dir = expand_home(dir.as_deref().unwrap_or("."));
+                    let attachments_config = self.config.attachments.clone();
+                    let action_tx = self.action_tx.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let action = match attachments::tree(&dir, &attachments_config) {
+                            Ok(attachment) => Action::Attach(attachment),
+                            Err(err) => Action::Error(format!("Nothing attached: {err}")),
+                        };
+                        let _ = action_tx.send(action);
+                    });
+                }
+                Action::Attach(attachment) => {
+                    let mut notice = format!(
+                        "Attached {} ({} lines), sent with your next message",
+                        attachment.label,
+                        attachment.lines()
+                    );
+                    if attachment.omitted_lines > 0 {
+                        notice.push_str(&format!(
+                            ", {} more lines left out",
+                            attachment.omitted_lines
+                        ));
+                    }
+                    self.engine
+                        .conversation
+                        .attachments
+                        .push(attachment.clone());
+                    self.sync_state()?;
+                    self.action_tx.send(Action::Notify(notice))?;
+                }
+                Action::ClearAttachments => {
+                    self.engine.conversation.attachments.clear();
+                    self.sync_state()?;
+                    self.action_tx
+                        .send(Action::Notify("Removed the attachments".to_string()))?;
+                }
+                Action::ToggleClipboardWatch => {
+                    let notice = match self.clipboard_watch.take() {
+                        Some(task) if !task.is_finished() => {
+                            task.abort();
+                            self.state.clipboard_offer = None;
+                            self.sync_state()?;
+                            "Stopped watching the clipboard"
+                        }
+                        _ => {
+                            self.clipboard_watch = Some(self.watch_clipboard());
+                            "Watching the clipboard, copied text is offered as an attachment"
+                        }
+                    };
+                    self.action_tx.send(Action::Notify(notice.to_string()))?;
+                }
+                Action::ClipboardCopied(text) => {
+                    self.state.clipboard_offer = Some(text.clone());
+                    self.sync_state()?;
+                }
+                Action::AttachClipboard => {
+                    if let Some(text) = self.state.clipboard_offer.take() {
+                        self.action_tx.send(Action::Attach(Attachment::new(
+                            "Clipboard",
+                            "",
+                            &text,
+                            self.config.attachments.max_bytes,
+                        )))?;
+                        self.sync_state()?;
+                    }
+                }
+                Action::DismissClipboard => {
+                    self.state.clipboard_offer = None;
+                    self.sync_state()?;
+                }
+                Action::WriteCommitMessage => {
+                    let attachments_config = self.config.attachments.clone();
+                    let action_tx = self.action_tx.clone();
+                    tokio::spawn(async move {
+                        let dir = std::env::current_dir().unwrap_or_default();
+                        let action = match attachments::staged_diff(&dir, &attachments_config).await
+                        {
+                            Ok(diff) => Action::StagedDiffRead(diff),
+                            Err(err) => Action::Error(err.to_string()),
+                        };
+                        let _ = action_tx.send(action);
+                    });
+                }
+                Action::StagedDiffRead(diff) => {
+                    let completion = self
+                        .engine
+                        .ask_aside(commit::SYSTEM_PROMPT, &commit::question(diff));
+                    self.action_tx.send(Action::Notify(format!(
+                        "Writing a commit message with {}…",
+                        self.engine.conversation.model
+                    )))?;
+                    self.request_aside(completion, |result| match result {
+                        Ok(reply) => Action::CommitMessageWritten(Box::new(reply)),
+                        Err(err) => {
+                            Action::Error(format!("Writing the commit message failed: {err}"))
+                        }
+                    })?;
+                }
+                Action::PolishText(draft) => {
+                    self.action_tx.send(Action::Notify(format!(
+                        "Polishing the draft with {}…",
+                        self.engine.conversation.model
+                    )))?;
+                    let draft = draft.clone();
+                    let completion = self.engine.ask_aside(prose::SYSTEM_PROMPT, &draft);
+                    self.request_aside(completion, |result| match result {
+                        Ok(reply) => Action::DraftPolished(draft, Box::new(reply)),
+                        Err(err) => Action::Error(format!("Polishing failed: {err}")),
+                    })?;
+                }
+                // The input replaces the draft
+                Action::DraftPolished(_, reply) => self.engine.record_usage(reply),
+                Action::SetTranslation(translation) => {
+                    let notice = match translation {
+                        Some(translation) => format!(
+                            "Translating {}, /translate off to stop",
+                            translation.label()
+                        ),
+                        None => "Stopped translating".to_string(),
+                    };
+                    self.engine.conversation.translation = translation.clone();
+                    self.action_tx.send(Action::Notify(notice))?;
+                    self.sync_state()?;
+                }
+                Action::TranslateMessage(index) => {
+                    let conversation = &self.engine.conversation;
+                    if let Some(translation) = conversation.translation.clone()
+                        && let Some(message) = conversation.messages.get(*index)
+                    {
+                        let text = message.content.clone();
+                        self.action_tx.send(Action::Notify(format!(
+                            "Translating message {} into {}…",
+                            index + 1,
+                            translation.target
+                        )))?;
+                        let completion = self.engine.ask_aside(&translation.system_prompt(), &text);
+                        self.request_aside(completion, |result| match result {
+                            Ok(reply) => {
+                                Action::MessageTranslated(translation.target, Box::new(reply))
                             }
+                            Err(err) => Action::Error(format!("Translating failed: {err}")),
+                        })?;
+                    }
+                }
+                Action::MessageTranslated(target, reply) => {
+                    self.engine.record_usage(reply);
+                    self.action_tx.send(Action::ShowDialog(format!(
+                        "Translation into {target}:\n\n{}",
+                        reply.content.trim()
+                    )))?;
+                }
+                Action::CommitMessageWritten(reply) => {
+                    self.engine.record_usage(reply);
+                    self.action_tx
+                        .send(Action::ShowCommitMessage(commit::message(&reply.content)))?;
+                }
+                Action::RunCommit(message) => {
+                    let message = message.clone();
+                    let action_tx = self.action_tx.clone();
+                    tokio::spawn(async move {
+                        let dir = std::env::current_dir().unwrap_or_default();
+                        let action = match commit::commit(&dir, &message).await {
+                            Ok(summary) => Action::Notify(format!("Committed {summary}")),
+                            Err(err) => Action::Error(err.to_string()),
+                        };
+                        let _ = action_tx.send(action);
+                    });
+                }
+                Action::Summarize(SummaryUse::Note) if self.config.notes.dir.is_none() => {
+                    self.action_tx
+                        .send(Action::Error(NO_NOTES_DIR.to_string()))?;
+                }
+                Action::Summarize(target) => {
+                    let conversation = &self.engine.conversation;
+                    let keep = self.config.compaction.keep_messages;
+                    let messages = match target {
+                        SummaryUse::Compact => summary::compactable(&conversation.messages, keep),
+                        _ => conversation.messages.len(),
+                    };
+                    if messages == 0 {
+                        let notice = match target {
+                            SummaryUse::Compact => {
+                                format!("Nothing to compact, the last {keep} messages are kept")
+                            }
+                            _ => "Nothing to summarize yet".to_string(),
+                        };
+                        self.action_tx.send(Action::Notify(notice))?;
+                    } else {
+                        let request = SummaryRequest {
+                            target: *target,
+                            conversation: conversation.id.clone(),
+                            messages,
+                            fingerprint: summary::fingerprint(&conversation.messages[..messages]),
+                        };
+                        self.action_tx.send(Action::Notify(format!(
+                            "Summarizing {messages} messages with {}…",
+                            conversation.model
+                        )))?;
+                        let completion = self.engine.summarize(messages);
+                        self.summary_task =
+                            self.request_aside(completion, |result| match result {
+                                Ok(reply) => Action::SummaryWritten(request, Box::new(reply)),
+                                Err(err) => Action::Error(format!("Summarizing failed: {err}")),
+                            })?;
+                    }
+                }
+                Action::SummaryWritten(request, reply) => {
+                    self.engine.record_usage(reply);
+                    let text = reply.content.trim().to_string();
+                    if request.target == SummaryUse::Show {
+                        self.action_tx.send(Action::ShowDialog(format!(
+                            "Summary of the conversation:\n\n{text}"
+                        )))?;
+                    } else if request.conversation != self.engine.conversation.id {
+                        self.action_tx.send(Action::Error(
+                            "The summary is of another session than the open one, it wasn't saved"
+                                .to_string(),
+                        ))?;
+                    } else if request.target == SummaryUse::Compact
+                        && self
+                            .engine
+                            .conversation
+                            .messages
+                            .get(..request.messages)
+                            .is_none_or(|messages| {
+                                summary::fingerprint(messages) != request.fingerprint
+                            })
+                    {
+                        self.action_tx.send(Action::Error(
+                            "The messages changed while they were summarized, nothing was compacted"
+                                .to_string(),
+                        ))?;
+                    } else if request.target == SummaryUse::Note {
+                        self.action_tx.send(self.save_note(Some(&text)))?;
+                    } else {
+                        let notice = if request.target == SummaryUse::Describe {
+                            self.engine.conversation.description = Some(text);
+                            "Saved the summary as the session description".to_string()
+                        } else {
+                            summary::compact(
+                                &mut self.engine.conversation.messages,
+                                request.messages,
+                                &text,
+                            );
+                            format!("Compacted {} messages into a summary", request.messages)
+                        };
+                        let result = self.engine.save();
+                        self.report(result, "Failed to save session")?;
+                        self.action_tx.send(Action::Notify(notice))?;
+                    }
+                }
+                Action::ExtractTodos(path) => {
+                    if self.engine.conversation.messages.is_empty() {
+                        self.action_tx
+                            .send(Action::Notify("Nothing to extract yet".to_string()))?;
+                    } else {
+                        self.action_tx.send(Action::Notify(format!(
+                            "Extracting action items with {}…",
+                            self.engine.conversation.model
+                        )))?;
+                        let path = path.clone();
+                        let completion = self.engine.extract_todos();
+                        self.request_aside(completion, |result| match result {
+                            Ok(reply) => Action::TodosExtracted(path, Box::new(reply)),
                             Err(err) => {
-                                let _ = action_tx.send(Action::Error(format!("API Error: {err}")));
+                                Action::Error(format!("Extracting the action items failed: {err}"))
                             }
-                        }
+                        })?;
+                    }
+                }
+                Action::TodosExtracted(path, reply) => {
+                    self.engine.record_usage(reply);
+                    let items = todos::checklist(&reply.content);
+                    if items.is_empty() {
+                        self.action_tx.send(Action::Notify(
+                            "No action items in this conversation".to_string(),
+                        ))?;
+                    } else if let Some(path) = path {
+                        let conversation = &self.engine.conversation;
+                        let title = conversation
+                            .title
+                            .clone()
+                            .unwrap_or_else(|| session::auto_title(&conversation.messages));
+                        let path = expand_home(path);
+                        let action = match todos::append(&path, &title, &items) {
+                            Ok(()) => Action::Notify(format!(
+                                "Added {} action items to {}",
+                                items.len(),
+                                path.display()
+                            )),
+                            Err(err) => Action::Error(err.to_string()),
+                        };
+                        self.action_tx.send(action)?;
+                    } else {
+                        self.action_tx.send(Action::ShowDialog(format!(
+                            "Action items:\n\n{}",
+                            items.join("\n")
+                        )))?;
+                    }
+                }
+                Action::IndexDirectory(dir) => {
+                    let dir = expand_home(dir);
+                    let rag_config = self.config.rag.clone();
+                    let index_file = self.config.config.index_file();
+                    let index = self.engine.index().clone();
+                    let action_tx = self.action_tx.clone();
+                    self.action_tx
+                        .send(Action::Notify(format!("Indexing {}…", dir.display())))?;
+                    tokio::spawn(async move {
+                        let progress_tx = action_tx.clone();
+                        let result = Index::build(&rag_config, &dir, |done, total| {
+                            let _ = progress_tx.send(Action::Notify(format!(
+                                "Indexing {}… {done}/{total} chunks",
+                                dir.display()
+                            )));
+                        })
+                        .await;
+                        let result = match result {
+                            Ok(built) => index.store(built, &index_file).await,
+                            Err(err) => Err(err),
+                        };
+                        let action = match result {
+                            Ok(index) => Action::Notify(format!(
+                                "Indexed {} chunks of {} files, questions now include the closest ones",
+                                index.chunks.len(),
+                                index.files
+                            )),
+                            Err(err) => Action::Error(format!("Indexing failed: {err}")),
+                        };
+                        let _ = action_tx.send(action);
                     });
                 }
-                Action::MessageReceived(content) => {
-                    self.state.chat_history.push(ChatMessage {
-                        role: "AI".to_string(),
-                        content: content.clone(),
+                Action::ClearIndex => {
+                    let index_file = self.config.config.index_file();
+                    let action = if !index_file.exists() {
+                        Action::Notify("No directory is indexed".to_string())
+                    } else {
+                        match std::fs::remove_file(&index_file) {
+                            Ok(()) => {
+                                self.engine.index().clear();
+                                Action::Notify("Removed the index".to_string())
+                            }
+                            Err(err) => Action::Error(format!("Failed to remove the index: {err}")),
+                        }
+                    };
+                    self.action_tx.send(action)?;
+                }
+                Action::PublishShare(text) => {
+                    let conversation = &self.engine.conversation;
+                    let title = conversation
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| session::auto_title(&conversation.messages));
+                    let share_config = self.config.share.clone();
+                    let text = text.clone();
+                    let action_tx = self.action_tx.clone();
+                    self.action_tx.send(Action::Notify(format!(
+                        "Uploading to {}…",
+                        share_config.service
+                    )))?;
+                    tokio::spawn(async move {
+                        let action = match share::publish(&share_config, &title, &text).await {
+                            Ok(url) => match clipboard::copy(&url) {
+                                Ok(()) => {
+                                    Action::Notify(format!("Shared at {url}, copied the link"))
+                                }
+                                Err(_) => Action::Notify(format!("Shared at {url}")),
+                            },
+                            Err(err) => Action::Error(format!("Sharing failed: {err}")),
+                        };
+                        let _ = action_tx.send(action);
                     });
-
-                    // Clear loading state
-                    self.state.is_loading = false;
-                    // Update state in all components
-                    for component in self.components.iter_mut() {
-                        component.register_state_handler(self.state.clone())?;
-                    }
-                    // Force immediate render to show response
-                    self.render(tui)?;
                 }
-                Action::SetSystemPrompt(prompt) => {
-                    self.state.system_prompt = prompt.clone();
-                    // Update state in all components
-                    for component in self.components.iter_mut() {
-                        component.register_state_handler(self.state.clone())?;
+                Action::ViewRawMessage(index) => {
+                    if let Some(message) = self.engine.conversation.messages.get(*index) {
+                        self.action_tx
+                            .send(Action::ShowDialog(serde_json::to_string_pretty(message)?))?;
                     }
                 }
                 Action::FocusInput | Action::FocusChat => {
@@ -317,6 +1418,501 @@ impl App {
         Ok(())
     }
 
+    /// Send a prepared request in the background; the reply arrives as
+    /// `Action::MessageReceived` or `Action::Error`.
+    fn request_completion(&mut self, tui: &mut Tui, completion: Completion) -> Result<()> {
+        if !self.within_budget(&completion)? {
+            self.held_request = Some(HeldRequest::Reply(completion, None));
+            return self.sync_state();
+        }
+        self.send_completion(tui, completion)
+    }
+
+    /// Send a prepared request in the background, whatever the budget says.
+    fn send_completion(&mut self, tui: &mut Tui, completion: Completion) -> Result<()> {
+        self.sync_state()?;
+        // Force immediate render to show loading state
+        self.render(tui)?;
+
+        // Spawn API call in background to avoid blocking the event loop
+        let action_tx = self.action_tx.clone();
+        let retry_tx = action_tx.clone();
+        let completion = completion.on_retry(move |wait| {
+            let _ = retry_tx.send(Action::RateLimited(wait));
+        });
+        let flush_interval = std::time::Duration::from_millis(self.config.ui.stream_flush_ms);
+        let cancel = CancellationToken::new();
+        self.reply_cancel = Some(cancel.clone());
+        self.completion_task = Some(tokio::spawn(async move {
+            // Deltas are batched so that every token doesn't update the UI on its own; the
+            // batching ends once the reply is complete or cancelled and with it the channel
+            let (chunk_tx, chunk_rx) = mpsc::unbounded_channel();
+            let delta_tx = action_tx.clone();
+            let (result, ()) = tokio::join!(
+                cancel.run_until_cancelled(completion.run(move |delta| {
+                    let _ = chunk_tx.send(delta);
+                })),
+                stream::coalesce(chunk_rx, flush_interval, move |delta| {
+                    let _ = delta_tx.send(Action::MessageDelta(delta));
+                }),
+            );
+
+            let Some(result) = result else {
+                return;
+            };
+            match result {
+                Ok(message) => {
+                    let _ = action_tx.send(Action::HealthChanged(Health::Online));
+                    let _ = action_tx.send(Action::MessageReceived(Box::new(message)));
+                }
+                Err(err) => {
+                    let health = Health::from_error(&err);
+                    let _ = action_tx.send(Action::HealthChanged(health));
+                    let _ = action_tx.send(if health == Health::Offline {
+                        Action::RequeueUnanswered
+                    } else {
+                        Action::Error(format!("API Error: {err}"))
+                    });
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    /// Stop the reply in progress and wait until the deltas batched up of it are sent.
+    async fn stop_reply(&mut self) {
+        if let Some(cancel) = self.reply_cancel.take() {
+            cancel.cancel();
+        }
+        if let Some(task) = self.completion_task.take() {
+            let _ = task.await;
+        }
+    }
+
+    /// Ping the provider every `health.interval_secs` and report the result as
+    /// `Action::HealthChanged`, replacing any checks started before.
+    fn start_health_checks(&mut self) {
+        if let Some(task) = self.health_checks.take() {
+            task.abort();
+        }
+        let config = self.config.health.clone();
+        if config.interval_secs == 0 {
+            return;
+        }
+        let provider = self.engine.provider();
+        let action_tx = self.action_tx.clone();
+        self.health_checks = Some(tokio::spawn(async move {
+            loop {
+                let health = health::check(&provider, &config).await;
+                if action_tx.send(Action::HealthChanged(health)).is_err() {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(config.interval_secs)).await;
+            }
+        }));
+    }
+
+    /// `prompt` with `{{clipboard}}` and `{{selection}}` replaced by the clipboard text and the
+    /// message at `selected`; other placeholders get their default.
+    fn quick_prompt(&self, prompt: &str, selected: Option<usize>) -> Result<String> {
+        let mut fields = templates::fields(prompt);
+        for field in &mut fields {
+            match field.name.as_str() {
+                "clipboard" => {
+                    field.value = clipboard::paste()?;
+                    if field.value.trim().is_empty() {
+                        return Err(eyre!("There is no text on the clipboard"));
+                    }
+                }
+                "selection" => {
+                    field.value = selected
+                        .and_then(|index| self.engine.conversation.messages.get(index))
+                        .map(|message| message.content.clone())
+                        .ok_or_else(|| eyre!("Select a message first (Tab), the prompt uses it"))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(templates::fill(prompt, &fields))
+    }
+
+    /// Offer text copied from now on as an attachment, see `clipboard::watch`.
+    fn watch_clipboard(&self) -> tokio::task::JoinHandle<()> {
+        let interval = std::time::Duration::from_millis(self.config.attachments.clipboard_poll_ms);
+        clipboard::watch(self.action_tx.clone(), interval)
+    }
+
+    /// Save the current conversation and replace it with a new one.
+    fn start_conversation(&mut self, messages: Vec<ChatMessage>) -> Result<()> {
+        let result = self.engine.start_conversation(messages);
+        self.report(result, "Failed to save session")
+    }
+
+    /// Send a typed message, remembering it in case the request is held back at the hard budget
+    /// limit.
+    fn send_message(&mut self, tui: &mut Tui, message: &str) -> Result<()> {
+        self.state.notice = None;
+        debug!("Message sent: {}", message);
+        let completion = self.engine.send(message);
+        self.request_completion(tui, completion)?;
+        if let Some(HeldRequest::Reply(_, typed)) = &mut self.held_request {
+            *typed = Some(message.to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether `completion` may go out; at the hard budget limit, or if its estimated cost would
+    /// cross it, asks whether to send it anyway once it is held back.
+    fn within_budget(&mut self, completion: &Completion) -> Result<bool> {
+        let BudgetStatus::Hard(reason) = self.engine.request_budget_status(completion) else {
+            return Ok(true);
+        };
+        let items = vec![
+            MenuItem {
+                label: "Send anyway".to_string(),
+                action: Action::ForceSendMessage,
+            },
+            MenuItem {
+                label: "Don't send".to_string(),
+                action: Action::DropHeldRequest,
+            },
+        ];
+        self.action_tx
+            .send(Action::ShowMenu(format!("{reason}. Send anyway?"), items))?;
+        Ok(false)
+    }
+
+    /// Send a request outside the conversation in the background, or hold it back at the hard
+    /// budget limit; `reply` turns the answer into the action handling it.
+    fn request_aside(
+        &mut self,
+        completion: Completion,
+        reply: impl FnOnce(Result<ChatMessage>) -> Action + Send + 'static,
+    ) -> Result<Option<tokio::task::JoinHandle<()>>> {
+        if !self.within_budget(&completion)? {
+            self.held_request = Some(HeldRequest::Aside(completion, Box::new(reply)));
+            return Ok(None);
+        }
+        Ok(Some(self.send_aside(completion, Box::new(reply))))
+    }
+
+    fn send_aside(&self, completion: Completion, reply: AsideReply) -> tokio::task::JoinHandle<()> {
+        let action_tx = self.action_tx.clone();
+        tokio::spawn(async move {
+            let _ = action_tx.send(reply(completion.run(|_| {}).await));
+        })
+    }
+
+    /// Work through the calls of the last reply: run the allowed ones, ask about the others and
+    /// send the results back to the model once every call is answered.
+    fn run_tool_calls(&mut self, tui: &mut Tui) -> Result<()> {
+        if self.handling_tool_call {
+            return Ok(());
+        }
+        while let Some(call) = self.engine.conversation.pending_tool_calls().first() {
+            let call = (*call).clone();
+            let policy = self.engine.tool_policy(&call.name);
+            if policy != ToolPolicy::Deny && self.engine.agent_budget_spent() {
+                let steps = self.engine.conversation.agent_steps;
+                self.answer_pending_tool_calls(&format!(
+                    "Not run: the agent's limit of {steps} tool calls was reached"
+                ))?;
+                self.state.notice = Some(format!("Agent stopped after {steps} tool calls"));
+                return self.sync_state();
+            }
+            match policy {
+                ToolPolicy::Auto => {
+                    self.start_tool_call(call);
+                    return Ok(());
+                }
+                ToolPolicy::Ask => {
+                    self.handling_tool_call = true;
+                    self.approving_tool_call = true;
+                    self.action_tx.send(Action::ShowToolApproval(call))?;
+                    return Ok(());
+                }
+                ToolPolicy::Deny => {
+                    let output = format!("The user's tool policy doesn't allow {}", call.name);
+                    self.engine.conversation.push_tool_result(&call, output);
+                }
+            }
+        }
+        let answered = self
+            .engine
+            .conversation
+            .messages
+            .last()
+            .is_some_and(|message| message.role == "tool");
+        if answered && !self.engine.conversation.is_loading {
+            let completion = self.engine.begin_completion();
+            return self.request_completion(tui, completion);
+        }
+        if let Some(run) = self.engine.workflow() {
+            let name = run.name.clone();
+            match self.engine.advance_workflow() {
+                Some(completion) => {
+                    self.state.notice = self.workflow_progress();
+                    return self.request_completion(tui, completion);
+                }
+                None => self.state.notice = Some(format!("Workflow {name} done")),
+            }
+        }
+        if let Some(completion) = self.engine.advance_roundtable() {
+            return self.request_completion(tui, completion);
+        }
+        self.send_queued(tui)
+    }
+
+    /// Which step the running workflow is at, for the notice under the input.
+    fn workflow_progress(&self) -> Option<String> {
+        let run = self.engine.workflow()?;
+        let (step, total) = run.progress();
+        Some(format!("Workflow {}: step {step} of {total}", run.name))
+    }
+
+    /// The templates from the config and the templates directory, read afresh so edits show up.
+    fn templates(&self) -> Vec<PromptTemplate> {
+        templates::load_all(&self.config.templates, &self.config.config.templates_dir())
+    }
+
+    /// The personas from the config and the personas directory, read afresh like templates.
+    fn personas(&self) -> Vec<Persona> {
+        personas::load_all(&self.config.personas, &self.config.config.personas_dir())
+    }
+
+    fn template(&self, name: &str) -> Option<PromptTemplate> {
+        self.templates()
+            .into_iter()
+            .find(|template| template.name == name)
+    }
+
+    /// Set up the session the way a template's frontmatter asks for.
+    fn apply_template(&mut self, template: &PromptTemplate) -> Result<()> {
+        let mut changes = Vec::new();
+        if let Some(model) = &template.model {
+            self.engine.set_model(model);
+            changes.push(format!("model {model}"));
+        }
+        if let Some(temperature) = template.temperature {
+            self.engine.conversation.temperature = Some(temperature);
+            changes.push(format!("temperature {temperature}"));
+        }
+        if let Some(prompt) = &template.system_prompt {
+            self.engine.conversation.system_prompt = prompt.clone();
+            changes.push("system prompt".to_string());
+        }
+        if !changes.is_empty() {
+            self.action_tx.send(Action::Notify(format!(
+                "Template {}: {}",
+                template.name,
+                changes.join(", ")
+            )))?;
+            self.sync_state()?;
+        }
+        Ok(())
+    }
+
+    /// Run a tool call in the background; the output arrives as `Action::ToolResult`.
+    fn start_tool_call(&mut self, call: ToolCall) {
+        self.handling_tool_call = true;
+        if self.engine.conversation.agent {
+            self.engine.conversation.agent_steps += 1;
+        }
+        self.state.notice = Some(format!("Running {}…", call.name));
+        let action_tx = self.action_tx.clone();
+        let config = self.config.tools.clone();
+        self.reply_cancel = None;
+        self.completion_task = Some(tokio::spawn(async move {
+            let output = tools::run(&call, &config)
+                .await
+                .unwrap_or_else(|err| format!("Error: {err}"));
+            let _ = action_tx.send(Action::ToolResult(call, output));
+        }));
+    }
+
+    /// Answer every call still waiting with `output`, so the conversation can go on.
+    fn answer_pending_tool_calls(&mut self, output: &str) -> Result<()> {
+        self.handling_tool_call = false;
+        let conversation = &mut self.engine.conversation;
+        let pending: Vec<ToolCall> = conversation
+            .pending_tool_calls()
+            .into_iter()
+            .cloned()
+            .collect();
+        for call in &pending {
+            conversation.push_tool_result(call, output);
+        }
+        if std::mem::take(&mut self.approving_tool_call) {
+            self.action_tx.send(Action::HideToolApproval)?;
+        }
+        Ok(())
+    }
+
+    /// Send the next queued message unless the provider is known to be unreachable.
+    fn send_queued(&mut self, tui: &mut Tui) -> Result<()> {
+        if self.state.health == Health::Offline {
+            return Ok(());
+        }
+        match self.engine.send_queued() {
+            Some(completion) => self.request_completion(tui, completion),
+            None => Ok(()),
+        }
+    }
+
+    /// Save the conversation, or its `summary`, to the notes directory.
+    fn save_note(&self, summary: Option<&str>) -> Action {
+        let Some(dir) = &self.config.notes.dir else {
+            return Action::Error(NO_NOTES_DIR.to_string());
+        };
+        let result = notes::save(
+            &self.config.notes,
+            &expand_home(dir),
+            &notes::today(),
+            &self.engine.conversation,
+            summary,
+        );
+        match result {
+            Ok(path) => Action::Notify(format!(
+                "Saved the {} to {}",
+                if summary.is_some() {
+                    "summary"
+                } else {
+                    "conversation"
+                },
+                path.display()
+            )),
+            Err(err) => Action::Error(err.to_string()),
+        }
+    }
+
+    /// Show a failed session operation in the chat and refresh the session list either way.
+    fn report(&mut self, result: Result<()>, context: &str) -> Result<()> {
+        if let Err(err) = result {
+            self.action_tx
+                .send(Action::Error(format!("{context}: {err}")))?;
+        }
+        self.refresh_sessions();
+        self.sync_state()
+    }
+
+    /// Show what is wrong in the config file and the keybinding conflicts in one dialog, if
+    /// anything; returns whether the config file itself has problems.
+    fn report_config_problems(&self, keybindings: &[String]) -> Result<bool> {
+        let problems = schema::check_dir(&config::get_config_dir());
+        if problems.is_empty() && keybindings.is_empty() {
+            return Ok(false);
+        }
+        let mut text = String::new();
+        if !problems.is_empty() {
+            text.push_str("Problems in the config file:\n\n");
+            for problem in &problems {
+                text.push_str(&format!("{problem}\n"));
+            }
+        }
+        if !keybindings.is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str("Keybinding conflicts:\n\n");
+            for diagnostic in keybindings {
+                warn!("Keybinding conflict: {diagnostic}");
+                text.push_str(&format!("{diagnostic}\n"));
+            }
+        }
+        self.action_tx.send(Action::ShowDialog(text))?;
+        Ok(!problems.is_empty())
+    }
+
+    /// Re-read the config files and hand the new config to every component.
+    fn reload_config(&mut self) -> Result<()> {
+        // The data directory can't move while sessions are open
+        let overrides = Overrides {
+            data_dir: Some(self.config.config.data_dir.clone()),
+            ..self.config.overrides.clone()
+        };
+        let config = match Config::load(&overrides) {
+            Ok(config) => config,
+            Err(err) => {
+                // Keep running with the previous config until the file is fixed
+                if !self.report_config_problems(&[])? {
+                    self.action_tx
+                        .send(Action::Error(format!("Failed to reload config: {err}")))?;
+                }
+                return Ok(());
+            }
+        };
+        info!("Config reloaded");
+        self.report_config_problems(&config.keybinding_diagnostics())?;
+        self.engine.set_settings(config.engine_settings());
+        self.config = config;
+        self.start_health_checks();
+        for component in self.components.iter_mut() {
+            component.register_config_handler(self.config.clone())?;
+        }
+        self.sync_state()?;
+        self.action_tx.send(Action::Render)?;
+        Ok(())
+    }
+
+    fn refresh_sessions(&mut self) {
+        match self.engine.list_sessions() {
+            Ok(sessions) => self.state.sessions = sessions,
+            Err(err) => error!("Failed to list sessions: {err}"),
+        }
+    }
+
+    fn sync_state(&mut self) -> Result<()> {
+        self.state.conversation = self.engine.conversation.clone();
+        self.state.model_history = self.engine.model_history().clone();
+        for component in self.components.iter_mut() {
+            component.register_state_handler(self.state.clone())?;
+        }
+        Ok(())
+    }
+
+    async fn toggle_recording(&mut self) -> Result<()> {
+        if let Some(recorder) = self.recorder.take() {
+            self.state.is_recording = false;
+            match recorder.stop().await {
+                Ok(path) => {
+                    self.state.is_transcribing = true;
+                    let action_tx = self.action_tx.clone();
+                    let audio_config = self.config.audio.clone();
+                    tokio::spawn(async move {
+                        match audio::transcribe(&audio_config, &path).await {
+                            Ok(text) => {
+                                let _ = action_tx.send(Action::TranscriptReceived(text));
+                            }
+                            Err(err) => {
+                                let _ = action_tx
+                                    .send(Action::Error(format!("Transcription Error: {err}")));
+                            }
+                        }
+                        let _ = tokio::fs::remove_file(&path).await;
+                    });
+                }
+                Err(err) => {
+                    self.action_tx
+                        .send(Action::Error(format!("Recording Error: {err}")))?;
+                }
+            }
+        } else {
+            let path = self.config.config.cache_dir().join("recording.wav");
+            match Recorder::start(&self.config.audio, path) {
+                Ok(recorder) => {
+                    self.recorder = Some(recorder);
+                    self.state.is_recording = true;
+                }
+                Err(err) => {
+                    self.action_tx
+                        .send(Action::Error(format!("Recording Error: {err}")))?;
+                }
+            }
+        }
+        self.sync_state()?;
+        Ok(())
+    }
+
     fn handle_resize(&mut self, tui: &mut Tui, w: u16, h: u16) -> Result<()> {
         tui.resize(Rect::new(0, 0, w, h))?;
         self.render(tui)?;
@@ -324,23 +1920,40 @@ impl App {
     }
 
     fn render(&mut self, tui: &mut Tui) -> Result<()> {
+        let started = Instant::now();
+        let mut draw_times = Vec::new();
         tui.draw(|frame| {
             let main_area = frame.area();
 
+            // Put the session sidebar to the left of everything else when it is shown
+            let (sidebar_area, content_area) = if self.state.show_sidebar && !self.state.zen_mode {
+                let [sidebar_area, content_area] =
+                    Layout::horizontal([Constraint::Length(32), Constraint::Min(0)])
+                        .areas(main_area);
+                (sidebar_area, content_area)
+            } else {
+                (Rect::default(), main_area)
+            };
+
             // Create main layout: chat area + input area
+            let input_height = if self.state.input_expanded {
+                Constraint::Percentage(90) // Leave a glimpse of the transcript
+            } else if self.state.zen_mode {
+                Constraint::Length(3) // A separator line and two lines of text
+            } else {
+                Constraint::Ratio(1, 4) // Input area 1/4 of the screen
+            };
             let main_layout = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Ratio(3, 4), // Chat area 3/4 of the screen
-                    Constraint::Ratio(1, 4), // Input area 1/4 of the screen
-                ])
-                .split(main_area);
+                .constraints([Constraint::Min(0), input_height])
+                .split(content_area);
 
             let chat_area = main_layout[0];
             let input_area = main_layout[1];
 
             // Render components in their designated areas
             for component in self.components.iter_mut() {
+                let draw_started = Instant::now();
                 let result = match component.as_any().type_id() {
                     id if id == std::any::TypeId::of::<ChatWindow>() => {
                         component.draw(frame, chat_area)
@@ -348,6 +1961,9 @@ impl App {
                     id if id == std::any::TypeId::of::<Input>() => {
                         component.draw(frame, input_area)
                     }
+                    id if id == std::any::TypeId::of::<SessionSidebar>() => {
+                        component.draw(frame, sidebar_area)
+                    }
                     id if id == std::any::TypeId::of::<Dialog>() => {
                         // Dialog should render over the entire screen
                         component.draw(frame, main_area)
@@ -358,13 +1974,30 @@ impl App {
                     }
                 };
 
+                draw_times.push((component.name(), draw_started.elapsed()));
+
                 if let Err(err) = result {
                     let _ = self
                         .action_tx
                         .send(Action::Error(format!("Failed to draw: {err:?}")));
                 }
             }
+
+            if let Some(metrics) = &self.debug_metrics {
+                metrics.draw(frame, main_area, self.tick_rate, self.frame_rate);
+            }
         })?;
+        if let Some(metrics) = &mut self.debug_metrics {
+            metrics.record_frame(started, draw_times);
+        }
         Ok(())
     }
 }
+
+/// `dir` with a leading `~/` replaced by the home directory.
+fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
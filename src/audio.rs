@@ -0,0 +1,99 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use tokio::process::{Child, Command};
+use tracing::debug;
+
+use crate::config::AudioConfig;
+
+/// A running microphone recording.
+///
+/// Recording is delegated to an external command (`sox` by default) so that lazychat does not
+/// need to link against platform audio libraries. The command is expected to write a WAV file to
+/// the path substituted for `{file}` and to finalize it when interrupted.
+pub struct Recorder {
+    child: Child,
+    path: PathBuf,
+}
+
+impl Recorder {
+    pub fn start(config: &AudioConfig, path: PathBuf) -> Result<Self> {
+        let (program, args) = config
+            .record_command
+            .split_first()
+            .ok_or_else(|| eyre!("audio.record_command is empty"))?;
+        let file = path.display().to_string();
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.replace("{file}", &file))
+            .collect();
+        debug!("Starting recording: {program} {args:?}");
+
+        let child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| eyre!("Failed to start `{program}`: {err}"))?;
+        Ok(Self { child, path })
+    }
+
+    /// Stop the recording and return the path of the recorded file.
+    pub async fn stop(mut self) -> Result<PathBuf> {
+        // Interrupt rather than kill so the recorder gets a chance to write the WAV header.
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGINT);
+            }
+        }
+        #[cfg(not(unix))]
+        self.child.start_kill()?;
+        self.child.wait().await?;
+        Ok(self.path)
+    }
+}
+
+/// Send a recorded file to an OpenAI-compatible transcription endpoint.
+///
+/// Both the OpenAI `/v1/audio/transcriptions` API and the whisper.cpp server `/inference` endpoint
+/// accept a multipart `file` field and reply with `{"text": "..."}`.
+pub async fn transcribe(config: &AudioConfig, path: &Path) -> Result<String> {
+    let bytes = tokio::fs::read(path).await?;
+    let file = reqwest::multipart::Part::bytes(bytes)
+        .file_name("recording.wav")
+        .mime_str("audio/wav")?;
+    let mut form = reqwest::multipart::Form::new()
+        .part("file", file)
+        .text("model", config.model.clone())
+        .text("response_format", "json");
+    if let Some(language) = &config.language {
+        form = form.text("language", language.clone());
+    }
+
+    let mut request = reqwest::Client::new()
+        .post(&config.transcription_url)
+        .multipart(form);
+    if let Some(key_env) = &config.api_key_env {
+        let key = env::var(key_env).map_err(|_| eyre!("{key_env} environment variable not set"))?;
+        request = request.bearer_auth(key);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    let response_text = response.text().await?;
+    if !status.is_success() {
+        return Err(eyre!("Transcription failed ({status}): {response_text}"));
+    }
+    let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+    let text = response_json["text"]
+        .as_str()
+        .ok_or_else(|| eyre!("Transcription response did not contain text"))?;
+    Ok(text.trim().to_string())
+}
@@ -1,6 +1,11 @@
-use clap::Parser;
+use std::path::PathBuf;
 
-use crate::config::{get_config_dir, get_data_dir};
+use clap::{Parser, Subcommand};
+
+use crate::{
+    config::{get_config_dir, get_data_dir},
+    core::provider::ProviderKind,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
@@ -12,6 +17,67 @@ pub struct Cli {
     /// Frame rate, i.e. number of frames per second
     #[arg(short, long, value_name = "FLOAT", default_value_t = 60.0)]
     pub frame_rate: f64,
+
+    /// Directory for sessions, drafts, caches and logs (overrides `data_dir` in the config file)
+    #[arg(long, value_name = "PATH")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Where replies come from; `mock` answers offline with canned responses
+    #[arg(long, value_enum)]
+    pub provider: Option<ProviderKind>,
+
+    /// Report keybinding conflicts in the config file and exit
+    #[arg(long)]
+    pub check_keys: bool,
+
+    /// Print the conversation as Markdown once the TUI is closed, to pipe or redirect it
+    #[arg(long)]
+    pub print_on_exit: bool,
+
+    /// Send a single message without starting the TUI, print the reply and exit
+    #[arg(short, long, value_name = "TEXT")]
+    pub prompt: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print tokens and cost per day and per model
+    Usage {
+        /// How many days back to report, today included
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+    },
+    /// Print the request, error and latency metrics recorded locally when `metrics.enabled` is set
+    Stats,
+    /// Run a workflow from the config without the TUI, printing the last step's reply
+    Workflow {
+        /// Its name in the config
+        name: String,
+        /// Text for `{{input}}` in its prompts
+        input: Vec<String>,
+    },
+    /// Write a commit message for the staged changes and commit with it after editing it
+    Commit {
+        /// Only print the message
+        #[arg(long)]
+        print: bool,
+    },
+    /// Print custom tool definitions for the endpoints of an OpenAPI 3 spec (JSON or YAML), to
+    /// merge into the config file
+    ImportOpenapi {
+        /// The spec file
+        spec: PathBuf,
+        /// Where requests go, instead of the spec's first server
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+        /// A header sent with every request, e.g. "Authorization: Bearer ${API_TOKEN}"; `${NAME}`
+        /// is read from the environment when the tool runs
+        #[arg(long = "header", value_name = "NAME: VALUE")]
+        headers: Vec<String>,
+    },
 }
 
 const VERSION_MESSAGE: &str = concat!(
@@ -0,0 +1,92 @@
+use std::{sync::Mutex, time::Duration};
+
+use arboard::Clipboard;
+use color_eyre::{Result, eyre::eyre};
+use lazy_static::lazy_static;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::action::Action;
+
+lazy_static! {
+    // On X11 and Wayland the clipboard contents are served by the process that set them, so the
+    // handle is kept alive for the lifetime of the application instead of dropped after copying.
+    static ref CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+    // What lazychat copied last, so that watching the clipboard doesn't offer it back.
+    static ref COPIED: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Copy text to the system clipboard.
+pub fn copy(text: &str) -> Result<()> {
+    let mut clipboard = CLIPBOARD
+        .lock()
+        .map_err(|_| eyre!("Clipboard lock poisoned"))?;
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new()?);
+    }
+    if let Some(clipboard) = clipboard.as_mut() {
+        clipboard.set_text(text)?;
+    }
+    if let Ok(mut copied) = COPIED.lock() {
+        *copied = Some(text.to_string());
+    }
+    Ok(())
+}
+
+/// The text on the system clipboard, empty if it holds something else, like an image.
+pub fn paste() -> Result<String> {
+    let mut clipboard = CLIPBOARD
+        .lock()
+        .map_err(|_| eyre!("Clipboard lock poisoned"))?;
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new()?);
+    }
+    match clipboard.as_mut().map(|clipboard| clipboard.get_text()) {
+        Some(Err(arboard::Error::ContentNotAvailable)) | None => Ok(String::new()),
+        Some(text) => Ok(text?),
+    }
+}
+
+/// Shortest time between two looks at the clipboard, so that a poll interval of 0 doesn't keep a
+/// core busy.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Check the clipboard every `interval`, but at most every [`MIN_POLL_INTERVAL`], and send
+/// `Action::ClipboardCopied` when text other than what was there before is copied, until the task
+/// is aborted.
+pub fn watch(
+    action_tx: UnboundedSender<Action>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let interval = interval.max(MIN_POLL_INTERVAL);
+    tokio::spawn(async move {
+        // What was on the clipboard before watching started isn't offered
+        let mut last: Option<String> = None;
+        loop {
+            let text = match tokio::task::spawn_blocking(paste).await {
+                Ok(Ok(text)) => text,
+                Ok(Err(err)) => {
+                    let _ = action_tx.send(Action::Error(format!(
+                        "Stopped watching the clipboard: {err}"
+                    )));
+                    break;
+                }
+                Err(_) => break,
+            };
+            let copied_here = COPIED
+                .lock()
+                .is_ok_and(|copied| copied.as_deref() == Some(text.as_str()));
+            if last.is_some()
+                && last.as_deref() != Some(text.as_str())
+                && !text.trim().is_empty()
+                && !copied_here
+                && action_tx
+                    .send(Action::ClipboardCopied(text.clone()))
+                    .is_err()
+            {
+                break;
+            }
+            last = Some(text);
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -0,0 +1,222 @@
+use crate::{
+    action::Action,
+    core::{summary::SummaryUse, translate::Translation},
+};
+
+/// Parse a slash command typed into the input, e.g. `/log lazychat::app=debug`.
+///
+/// Returns `None` for text that isn't a known command so that it is sent as a message.
+pub fn parse(text: &str) -> Option<Action> {
+    let text = text.trim();
+    let (name, args) = text
+        .strip_prefix('/')?
+        .split_once(char::is_whitespace)
+        .map_or((&text[1..], ""), |(name, args)| (name, args.trim()));
+    match name {
+        "log" if args.is_empty() => Some(Action::Error(
+            "Usage: /log <filter>, e.g. /log lazychat::app=debug".to_string(),
+        )),
+        "log" => Some(Action::SetLogFilter(args.to_string())),
+        "curl" => Some(Action::CopyLastRequest),
+        "copy" => Some(Action::CopyConversation),
+        "usage" => Some(Action::ShowUsage),
+        "info" => Some(Action::ShowModelInfo),
+        "stop" => Some(Action::CancelReply),
+        "continue" => Some(Action::ContinueReply),
+        "agent" => Some(Action::ToggleAgentMode),
+        "merge" => Some(Action::ShowMergeTargets),
+        "share" => Some(Action::ShareConversation),
+        "zen" => Some(Action::ToggleZenMode),
+        "preview" => Some(Action::ToggleDraftPreview),
+        "key" => Some(Action::ShowApiKeyDialog),
+        "diff" if args.is_empty() => Some(Action::AttachDiff(None)),
+        "diff" => Some(Action::AttachDiff(Some(args.to_string()))),
+        "tree" if args.is_empty() => Some(Action::AttachTree(None)),
+        "tree" => Some(Action::AttachTree(Some(args.to_string()))),
+        "detach" => Some(Action::ClearAttachments),
+        "clipboard" => Some(Action::ToggleClipboardWatch),
+        "commit" => Some(Action::WriteCommitMessage),
+        "summarize" if args.is_empty() => Some(Action::Summarize(SummaryUse::Show)),
+        "summarize" if args == "save" => Some(Action::Summarize(SummaryUse::Describe)),
+        "summarize" => Some(Action::Error(
+            "Usage: /summarize to show a summary, /summarize save to keep it as the description"
+                .to_string(),
+        )),
+        "note" if args.is_empty() => Some(Action::SaveNote),
+        "note" if args == "summary" => Some(Action::Summarize(SummaryUse::Note)),
+        "note" => Some(Action::Error(
+            "Usage: /note to save the conversation to your notes, /note summary to save a summary"
+                .to_string(),
+        )),
+        "compact" => Some(Action::Summarize(SummaryUse::Compact)),
+        "todos" if args.is_empty() => Some(Action::ExtractTodos(None)),
+        "todos" => Some(Action::ExtractTodos(Some(args.to_string()))),
+        "index" if args.is_empty() => Some(Action::Error(
+            "Usage: /index <dir> to index a directory, /index clear to stop using it".to_string(),
+        )),
+        "index" if args == "clear" => Some(Action::ClearIndex),
+        "index" => Some(Action::IndexDirectory(args.to_string())),
+        "persona" if args.is_empty() => Some(Action::ShowPersonas),
+        "persona" => Some(Action::StartPersona(args.to_string())),
+        "translate" if args == "off" => Some(Action::SetTranslation(None)),
+        "translate" => Some(match Translation::parse(args) {
+            Some(translation) => Action::SetTranslation(Some(translation)),
+            None => Action::Error(
+                "Usage: /translate <language> or /translate <from> -> <to>, /translate off to stop"
+                    .to_string(),
+            ),
+        }),
+        "roundtable" if args.is_empty() => Some(Action::Error(
+            "Usage: /roundtable <persona>, <persona>... to have personas answer in turn, \
+             /roundtable off to end it"
+                .to_string(),
+        )),
+        "roundtable" if args == "off" => Some(Action::StopRoundtable),
+        "roundtable" => {
+            let names: Vec<String> = if args.contains(',') {
+                args.split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            } else {
+                args.split_whitespace().map(str::to_string).collect()
+            };
+            Some(Action::StartRoundtable(names))
+        }
+        "template" if args.is_empty() => Some(Action::ShowTemplates),
+        "template" => Some(Action::UseTemplate(args.to_string())),
+        "workflow" if args.is_empty() => Some(Action::Error(
+            "Usage: /workflow <name> [input], with workflows defined in the config".to_string(),
+        )),
+        "workflow" => {
+            let (name, input) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            Some(Action::RunWorkflow(
+                name.to_string(),
+                input.trim().to_string(),
+            ))
+        }
+        "length" => match args.parse() {
+            Ok(length) => Some(Action::SetResponseLength(length)),
+            Err(_) => Some(Action::Error(
+                "Usage: /length brief|normal|detailed".to_string(),
+            )),
+        },
+        "model" if args.is_empty() => Some(Action::ShowModelPicker),
+        "model" => Some(Action::SetModel(args.to_string())),
+        "star" if args.is_empty() => Some(Action::Error(
+            "Usage: /star <id>, e.g. /star anthropic/claude-3.5-sonnet".to_string(),
+        )),
+        "star" => Some(Action::ToggleFavorite(args.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::length::ResponseLength;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("/log  lazychat=debug "),
+            Some(Action::SetLogFilter("lazychat=debug".to_string()))
+        );
+        assert!(matches!(parse("/log"), Some(Action::Error(_))));
+        assert_eq!(parse("/usr/bin is a directory"), None);
+        assert_eq!(parse("/curl"), Some(Action::CopyLastRequest));
+        assert_eq!(parse("/copy"), Some(Action::CopyConversation));
+        assert_eq!(parse("/usage"), Some(Action::ShowUsage));
+        assert_eq!(parse("/info"), Some(Action::ShowModelInfo));
+        assert_eq!(parse("/stop"), Some(Action::CancelReply));
+        assert_eq!(parse("/continue"), Some(Action::ContinueReply));
+        assert_eq!(
+            parse("/length brief"),
+            Some(Action::SetResponseLength(ResponseLength::Brief))
+        );
+        assert!(matches!(parse("/length short"), Some(Action::Error(_))));
+        assert_eq!(parse("/agent"), Some(Action::ToggleAgentMode));
+        assert_eq!(parse("/merge"), Some(Action::ShowMergeTargets));
+        assert_eq!(parse("/share"), Some(Action::ShareConversation));
+        assert_eq!(parse("/zen"), Some(Action::ToggleZenMode));
+        assert_eq!(parse("/preview"), Some(Action::ToggleDraftPreview));
+        assert_eq!(parse("/key"), Some(Action::ShowApiKeyDialog));
+        assert_eq!(
+            parse("/index ~/src/lazychat"),
+            Some(Action::IndexDirectory("~/src/lazychat".to_string()))
+        );
+        assert_eq!(parse("/index clear"), Some(Action::ClearIndex));
+        assert_eq!(parse("/diff"), Some(Action::AttachDiff(None)));
+        assert_eq!(
+            parse("/diff main"),
+            Some(Action::AttachDiff(Some("main".to_string())))
+        );
+        assert_eq!(parse("/detach"), Some(Action::ClearAttachments));
+        assert_eq!(parse("/clipboard"), Some(Action::ToggleClipboardWatch));
+        assert_eq!(
+            parse("/summarize save"),
+            Some(Action::Summarize(SummaryUse::Describe))
+        );
+        assert_eq!(parse("/note"), Some(Action::SaveNote));
+        assert_eq!(
+            parse("/persona Ada"),
+            Some(Action::StartPersona("Ada".to_string()))
+        );
+        assert_eq!(
+            parse("/roundtable Ada Lovelace, Critic"),
+            Some(Action::StartRoundtable(vec![
+                "Ada Lovelace".to_string(),
+                "Critic".to_string()
+            ]))
+        );
+        assert_eq!(
+            parse("/roundtable Ada Critic"),
+            Some(Action::StartRoundtable(vec![
+                "Ada".to_string(),
+                "Critic".to_string()
+            ]))
+        );
+        assert_eq!(parse("/roundtable off"), Some(Action::StopRoundtable));
+        assert_eq!(
+            parse("/translate en -> de"),
+            Some(Action::SetTranslation(Some(Translation {
+                source: Some("en".to_string()),
+                target: "de".to_string()
+            })))
+        );
+        assert_eq!(parse("/translate off"), Some(Action::SetTranslation(None)));
+        assert_eq!(
+            parse("/note summary"),
+            Some(Action::Summarize(SummaryUse::Note))
+        );
+        assert_eq!(
+            parse("/compact"),
+            Some(Action::Summarize(SummaryUse::Compact))
+        );
+        assert_eq!(
+            parse("/tree src"),
+            Some(Action::AttachTree(Some("src".to_string())))
+        );
+        assert_eq!(parse("/commit"), Some(Action::WriteCommitMessage));
+        assert_eq!(
+            parse("/workflow haiku  rain on a window"),
+            Some(Action::RunWorkflow(
+                "haiku".to_string(),
+                "rain on a window".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("/model openai/gpt-4o"),
+            Some(Action::SetModel("openai/gpt-4o".to_string()))
+        );
+        assert_eq!(parse("/model"), Some(Action::ShowModelPicker));
+        assert_eq!(
+            parse("/star openai/gpt-4o"),
+            Some(Action::ToggleFavorite("openai/gpt-4o".to_string()))
+        );
+        assert_eq!(parse("hello"), None);
+    }
+}
@@ -1,18 +1,78 @@
 use color_eyre::Result;
-use crossterm::event::{KeyEvent, MouseEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent};
 use ratatui::{
     Frame,
     layout::{Rect, Size},
+    style::Style,
 };
 use std::any::Any;
 use tokio::sync::mpsc::UnboundedSender;
+use tui_textarea::TextArea;
 
-use crate::{action::Action, app::AppState, config::Config, tui::Event};
+use crate::{
+    action::Action,
+    app::{AppState, Mode},
+    config::Config,
+    tui::Event,
+};
 
 pub mod chat_window;
 pub mod dialog;
 pub mod home;
 pub mod input;
+pub mod menu;
+pub mod model_picker;
+pub mod session_finder;
+pub mod session_sidebar;
+pub mod template_form;
+#[cfg(test)]
+pub mod testing;
+pub mod tool_approval;
+pub mod version_diff;
+
+/// Whether a key types a character into a text field, i.e. has no Ctrl or Alt modifier.
+pub fn is_text_key(key: KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Char(_))
+        && !key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+}
+
+/// Whether a key pressed in a text field should be left to the global keybindings.
+///
+/// Characters are always typed, so e.g. `q` only quits when no text field has focus.
+pub fn is_global_key(config: &Config, key: KeyEvent) -> bool {
+    !is_text_key(key) && config.keybindings.action(Mode::Home, key).is_some()
+}
+
+/// Pasted text with the carriage returns terminals send as line breaks turned into `\n`.
+pub fn pasted_text(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Pasted text on one line, for search and form fields.
+pub fn pasted_line(text: &str) -> String {
+    pasted_text(text).lines().collect::<Vec<_>>().join(" ")
+}
+
+/// An empty text field that remembers `ui.undo_history` edits for undo and redo.
+pub fn text_field(config: &Config) -> TextArea<'static> {
+    let mut textarea = TextArea::default();
+    textarea.set_max_histories(config.ui.undo_history);
+    textarea
+}
+
+/// Number the lines of a text field if `ui.line_numbers` is on, returning the cursor position as
+/// `line:col` to show with it.
+pub fn line_numbers(textarea: &mut TextArea, config: &Config) -> Option<String> {
+    if !config.ui.line_numbers {
+        textarea.remove_line_number();
+        return None;
+    }
+    textarea.set_line_number_style(Style::default().fg(config.ui.theme.theme().muted));
+    let (row, col) = textarea.cursor();
+    Some(format!("{}:{}", row + 1, col + 1))
+}
 
 /// `Component` is a trait that represents a visual and interactive element of the user interface.
 ///
@@ -22,6 +82,12 @@ pub trait Component {
     /// Get a reference to Any for downcasting purposes
     fn as_any(&self) -> &dyn Any;
 
+    /// Name shown in the debug overlay.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
     /// Register an action handler that can send actions for processing if necessary.
     ///
     /// # Arguments
@@ -87,6 +153,7 @@ pub trait Component {
         let action = match event {
             Some(Event::Key(key_event)) => self.handle_key_event(key_event)?,
             Some(Event::Mouse(mouse_event)) => self.handle_mouse_event(mouse_event)?,
+            Some(Event::Paste(text)) => self.handle_paste_event(text)?,
             _ => None,
         };
         Ok(action)
@@ -117,6 +184,19 @@ pub trait Component {
         let _ = mouse; // to appease clippy
         Ok(None)
     }
+    /// Handle text pasted into the terminal and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The pasted text.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_paste_event(&mut self, text: String) -> Result<Option<Action>> {
+        let _ = text; // to appease clippy
+        Ok(None)
+    }
     /// Update the state of the component based on a received action. (REQUIRED)
     ///
     /// # Arguments
@@ -1,10 +1,25 @@
 use color_eyre::Result;
 use ratatui::{prelude::*, widgets::*};
-use std::any::Any;
+use std::{any::Any, borrow::Cow, collections::HashSet};
 use tokio::sync::mpsc::UnboundedSender;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use super::Component;
-use crate::{action::Action, app::AppState, config::Config};
+use crate::{
+    action::{Action, MenuItem},
+    app::{AppState, Mode},
+    config::Config,
+    core::{
+        ChatMessage, MessageMetadata,
+        conversation::{PartialReply, ToolCall},
+    },
+    locale::{t, tf},
+    theme::Theme,
+    tokens,
+};
+
+/// Columns scrolled sideways per key press.
+const SIDEWAYS_STEP: usize = 8;
 
 #[derive(Default)]
 pub struct ChatWindow {
@@ -12,6 +27,68 @@ pub struct ChatWindow {
     config: Config,
     state: Option<AppState>,
     scroll_offset: usize, // Add scroll offset for navigation
+    h_scroll: usize,      // Columns that code blocks and tables are scrolled sideways
+    overflow: usize,      // Columns by which the widest code or table line exceeds the window
+    context_tokens: usize,
+    is_focused: bool,
+    selected: Option<usize>, // Index of the selected message while the chat is focused
+    scroll_to_selected: bool,
+    resume_focus: bool, // Whether to take focus back once a popup menu closes
+    pending_key: Option<PendingKey>,
+    previous_jump: Option<usize>, // Selection before the last jump, for `''`
+    show_message_numbers: bool,
+    role_filter: RoleFilter,
+    command_line: Option<String>, // Digits typed after `:` to jump to a message number
+    expanded_reasoning: HashSet<usize>, // Messages whose reasoning is shown in full
+    expanded: HashSet<usize>,     // Long messages shown in full instead of collapsed
+    conversation_id: String,      // Expanded messages are reset when the conversation changes
+}
+
+/// Messages of which roles the transcript shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RoleFilter {
+    #[default]
+    All,
+    User,
+    Assistant,
+    /// System messages and tool results.
+    Other,
+}
+
+impl RoleFilter {
+    fn next(self) -> Self {
+        match self {
+            Self::All => Self::User,
+            Self::User => Self::Assistant,
+            Self::Assistant => Self::Other,
+            Self::Other => Self::All,
+        }
+    }
+
+    fn shows(self, role: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::User => role == "user",
+            Self::Assistant => !matches!(role, "user" | "system" | "tool"),
+            Self::Other => matches!(role, "system" | "tool"),
+        }
+    }
+
+    fn label(self) -> Option<&'static str> {
+        match self {
+            Self::All => None,
+            Self::User => Some("user"),
+            Self::Assistant => Some("AI"),
+            Self::Other => Some("system/tool"),
+        }
+    }
+}
+
+/// First key of a two-key mark command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingKey {
+    SetMark,
+    JumpToMark,
 }
 
 impl ChatWindow {
@@ -21,7 +98,216 @@ impl ChatWindow {
             config: Config::default(),
             state: None,
             scroll_offset: 0,
+            h_scroll: 0,
+            overflow: 0,
+            context_tokens: 0,
+            is_focused: false,
+            selected: None,
+            scroll_to_selected: false,
+            resume_focus: false,
+            pending_key: None,
+            previous_jump: None,
+            show_message_numbers: false,
+            role_filter: RoleFilter::All,
+            command_line: None,
+            expanded_reasoning: HashSet::new(),
+            expanded: HashSet::new(),
+            conversation_id: String::new(),
+        }
+    }
+
+    fn message_count(&self) -> usize {
+        self.state
+            .as_ref()
+            .map_or(0, |state| state.conversation.messages.len())
+    }
+
+    /// Whether the message at `index` passes the role filter.
+    fn is_shown(&self, index: usize) -> bool {
+        self.state
+            .as_ref()
+            .and_then(|state| state.conversation.messages.get(index))
+            .is_some_and(|message| self.role_filter.shows(&message.role))
+    }
+
+    /// Select the message at `index`, or the closest one before it that is shown, or after it.
+    fn select(&mut self, index: usize) {
+        let count = self.message_count();
+        if count == 0 {
+            return;
+        }
+        let index = index.min(count - 1);
+        let shown = (0..=index)
+            .rev()
+            .chain(index + 1..count)
+            .find(|&index| self.is_shown(index));
+        if let Some(index) = shown {
+            self.selected = Some(index);
+            self.scroll_to_selected = true;
+        }
+    }
+
+    fn select_previous(&mut self) {
+        match self.selected {
+            Some(index) => {
+                if let Some(previous) = (0..index).rev().find(|&index| self.is_shown(index)) {
+                    self.select(previous);
+                }
+            }
+            None => self.select(usize::MAX),
+        }
+    }
+
+    fn select_next(&mut self) {
+        match self.selected {
+            Some(index) => {
+                let count = self.message_count();
+                if let Some(next) = (index + 1..count).find(|&index| self.is_shown(index)) {
+                    self.select(next);
+                }
+            }
+            None => self.select(usize::MAX),
+        }
+    }
+
+    fn message_actions_menu(&self, index: usize) -> Action {
+        let message = self
+            .state
+            .as_ref()
+            .and_then(|state| state.conversation.messages.get(index));
+        let full_output = message.and_then(|message| message.full_output.clone());
+        let is_reply = message.is_some_and(|message| message.role == "AI");
+        let regenerated = message.is_some_and(|message| !message.versions.is_empty());
+        let translation = self
+            .state
+            .as_ref()
+            .and_then(|state| state.conversation.translation.as_ref())
+            .map(|translation| format!("Translate into {}", translation.target));
+        let items: Vec<MenuItem> = [
+            ("Copy", Action::CopyMessage(index)),
+            ("Edit", Action::EditMessage(index)),
+            ("Regenerate", Action::RegenerateMessage(index)),
+        ]
+        .into_iter()
+        .chain(
+            translation
+                .as_deref()
+                .map(|label| (label, Action::TranslateMessage(index))),
+        )
+        .chain(regenerated.then_some(("Compare versions", Action::ShowVersionDiff(index))))
+        .chain(is_reply.then_some(("Ask another model", Action::AskOtherModel(index))))
+        .chain([
+            ("Delete", Action::DeleteMessage(index)),
+            ("Fork from here", Action::ForkConversation(index)),
+            ("View raw", Action::ViewRawMessage(index)),
+        ])
+        // The model only saw the start of a long tool output
+        .chain(full_output.map(|output| ("View full output", Action::ShowDialog(output))))
+        .map(|(label, action)| MenuItem {
+            label: label.to_string(),
+            action,
+        })
+        .collect();
+        Action::ShowMenu(format!("Message {}", index + 1), items)
+    }
+
+    fn find_mark(&self, mark: char) -> Option<usize> {
+        self.state.as_ref().and_then(|state| {
+            state
+                .conversation
+                .messages
+                .iter()
+                .position(|message| message.marks.contains(&mark))
+        })
+    }
+
+    fn handle_mark_key(&mut self, pending: PendingKey, mark: char) -> Option<Action> {
+        match (pending, mark) {
+            (PendingKey::SetMark, 'a'..='z' | 'A'..='Z') => {
+                self.selected.map(|index| Action::SetMark(mark, index))
+            }
+            (PendingKey::JumpToMark, '\'' | '`') => self.previous_jump.map(Action::JumpToMessage),
+            (PendingKey::JumpToMark, _) => self.find_mark(mark).map(Action::JumpToMessage),
+            _ => None,
+        }
+    }
+
+    fn handle_command_line_key(&mut self, key: crossterm::event::KeyEvent) -> Option<Action> {
+        use crossterm::event::KeyCode;
+
+        let command_line = self.command_line.as_mut()?;
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() => command_line.push(c),
+            KeyCode::Backspace if command_line.is_empty() => self.command_line = None,
+            KeyCode::Backspace => {
+                command_line.pop();
+            }
+            KeyCode::Enter => {
+                let number = command_line.parse::<usize>().ok();
+                self.command_line = None;
+                // Message numbers are 1-based like the gutter
+                if let Some(number) = number.filter(|n| *n > 0) {
+                    return Some(Action::JumpToMessage(number - 1));
+                }
+            }
+            KeyCode::Esc => self.command_line = None,
+            _ => {}
         }
+        Some(Action::Render)
+    }
+
+    /// Run a command bound in `Mode::Chat`; anything that isn't a chat command is passed on.
+    fn perform(&mut self, action: Action) -> Option<Action> {
+        match action {
+            Action::ScrollUp => self.scroll_offset = self.scroll_offset.saturating_sub(10),
+            Action::ScrollDown => self.scroll_offset += 10,
+            Action::ScrollLeft => self.h_scroll = self.h_scroll.saturating_sub(SIDEWAYS_STEP),
+            Action::ScrollRight => {
+                self.h_scroll = (self.h_scroll + SIDEWAYS_STEP).min(self.overflow);
+            }
+            Action::SelectPrevious => self.select_previous(),
+            Action::SelectNext => self.select_next(),
+            Action::SelectFirst => self.select(0),
+            Action::SelectLast => self.select(usize::MAX),
+            Action::Confirm => return self.selected.map(|index| self.message_actions_menu(index)),
+            Action::QuoteSelected => return self.selected.map(Action::QuoteMessage),
+            Action::TogglePinSelected => return self.selected.map(Action::TogglePin),
+            Action::MarkSelected => self.pending_key = Some(PendingKey::SetMark),
+            Action::JumpToMarkPrompt => self.pending_key = Some(PendingKey::JumpToMark),
+            Action::GoToMessagePrompt => self.command_line = Some(String::new()),
+            Action::ToggleMessageNumbers => self.show_message_numbers = !self.show_message_numbers,
+            Action::CycleRoleFilter => {
+                self.role_filter = self.role_filter.next();
+                if let Some(index) = self.selected {
+                    self.select(index);
+                }
+            }
+            Action::ToggleReasoningSelected => {
+                if let Some(index) = self.selected {
+                    toggle(&mut self.expanded_reasoning, index);
+                }
+            }
+            Action::ToggleExpandSelected => {
+                if let Some(index) = self.selected {
+                    toggle(&mut self.expanded, index);
+                    self.scroll_to_selected = true;
+                }
+            }
+            Action::PreviousVersionSelected | Action::NextVersionSelected => {
+                let index = self.selected?;
+                let message = self.state.as_ref()?.conversation.messages.get(index)?;
+                let version = if action == Action::PreviousVersionSelected {
+                    message.version.checked_sub(1)?
+                } else {
+                    message.version + 1
+                };
+                // Past the newest version there is nothing to show
+                return (version <= message.versions.len())
+                    .then_some(Action::SelectVersion(index, version));
+            }
+            action => return Some(action),
+        }
+        Some(Action::Render)
     }
 }
 
@@ -36,11 +322,28 @@ impl Component for ChatWindow {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.show_message_numbers = config.ui.show_message_numbers;
         self.config = config;
         Ok(())
     }
 
     fn register_state_handler(&mut self, state: AppState) -> Result<()> {
+        // Count once per state change rather than on every frame
+        self.context_tokens = tokens::count_prompt_tokens(
+            &state.conversation.model,
+            &state.conversation.system_prompt,
+            &state.conversation.messages,
+        );
+        if state.conversation.id != self.conversation_id {
+            self.conversation_id = state.conversation.id.clone();
+            self.expanded_reasoning.clear();
+            self.expanded.clear();
+        }
+        if let Some(index) = self.selected
+            && index >= state.conversation.messages.len()
+        {
+            self.selected = state.conversation.messages.len().checked_sub(1);
+        }
         self.state = Some(state);
         Ok(())
     }
@@ -48,36 +351,24 @@ impl Component for ChatWindow {
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
         use crossterm::event::KeyCode;
 
-        match key.code {
-            KeyCode::Up | KeyCode::Char('k') => {
-                if self.scroll_offset > 0 {
-                    self.scroll_offset -= 1;
-                }
-                Ok(None)
-            }
-            KeyCode::Down | KeyCode::Char('j') => {
-                self.scroll_offset += 1;
-                Ok(None)
-            }
-            KeyCode::PageUp => {
-                self.scroll_offset = self.scroll_offset.saturating_sub(10);
-                Ok(None)
-            }
-            KeyCode::PageDown => {
-                self.scroll_offset += 10;
-                Ok(None)
+        let action = self.config.keybindings.action(Mode::Chat, key);
+        if !self.is_focused {
+            // While typing, only the scroll keys move the transcript
+            if let Some(action @ (Action::ScrollUp | Action::ScrollDown)) = action {
+                self.perform(action);
             }
-            KeyCode::Home => {
-                self.scroll_offset = 0;
-                Ok(None)
-            }
-            KeyCode::End => {
-                // Will be handled in draw() to scroll to bottom
-                self.scroll_offset = usize::MAX;
-                Ok(None)
-            }
-            _ => Ok(None),
+            return Ok(None);
+        }
+        if self.command_line.is_some() {
+            return Ok(self.handle_command_line_key(key));
+        }
+        if let Some(pending) = self.pending_key.take() {
+            return Ok(match key.code {
+                KeyCode::Char(mark) => self.handle_mark_key(pending, mark),
+                _ => None,
+            });
         }
+        Ok(action.and_then(|action| self.perform(action)))
     }
 
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
@@ -85,7 +376,8 @@ impl Component for ChatWindow {
             Action::Tick => {
                 // Request render on every tick when loading to animate spinner
                 if let Some(ref state) = self.state
-                    && state.is_loading
+                    && state.conversation.is_loading
+                    && !self.config.ui.reduced_motion
                 {
                     return Ok(Some(Action::Render));
                 }
@@ -93,69 +385,395 @@ impl Component for ChatWindow {
             Action::Render => {
                 // add any logic here that should run on every render
             }
+            Action::QuickPrompt(prompt) => {
+                return Ok(Some(Action::SendQuickPrompt(prompt, self.selected)));
+            }
+            Action::FocusChat => {
+                self.is_focused = true;
+                if self.selected.is_none() {
+                    self.select(usize::MAX);
+                }
+                return Ok(Some(Action::Render));
+            }
+            Action::FocusInput
+            | Action::ShowDialog(_)
+            | Action::ShowSystemPromptDialog
+            | Action::ShowStats
+            | Action::ShareConversation
+            | Action::ShowApiKeyDialog
+            | Action::ShowCommitMessage(_)
+            | Action::ShowUsage
+            | Action::ShowModelInfo
+            | Action::EditMessage(_)
+            | Action::FocusSidebar => {
+                self.is_focused = false;
+                return Ok(Some(Action::Render));
+            }
+            Action::ShowMenu(..)
+            | Action::ShowSessionFinder
+            | Action::ShowModelPicker
+            | Action::AskOtherModel(_)
+            | Action::ShowToolApproval(_)
+            | Action::ShowTemplateForm(..)
+            | Action::ShowVersionDiff(_) => {
+                self.resume_focus = self.is_focused;
+                self.is_focused = false;
+            }
+            Action::HideMenu
+            | Action::HideSessionFinder
+            | Action::HideModelPicker
+            | Action::HideToolApproval
+            | Action::HideTemplateForm
+            | Action::HideVersionDiff => {
+                self.is_focused = self.resume_focus;
+                self.resume_focus = false;
+            }
+            Action::JumpToMessage(index) => {
+                self.previous_jump = self.selected;
+                self.select(index);
+                return Ok(Some(Action::FocusChat));
+            }
             _ => {}
         }
         Ok(None)
     }
 
     fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
-        let block = Block::bordered()
-            .title("Chat Window")
-            .title_bottom("↑↓: scroll | PgUp/PgDn: fast scroll | Home/End: top/bottom")
-            .border_style(Style::default().fg(Color::White));
+        let command_line = self
+            .command_line
+            .as_ref()
+            .map(|digits| format!(":{digits}"));
+        let hint = if let Some(command_line) = &command_line {
+            command_line.as_str()
+        } else if self.is_focused {
+            &t(
+                "↑↓/jk: select | Enter: actions | r: quote | :n: go to | p: pin | m: mark | Tab: input",
+            )
+        } else {
+            &t("PgUp/PgDn: scroll | Tab: select messages")
+        };
+        let theme = self.config.ui.theme.theme();
+        let icons = self.config.ui.icons();
+        let border_color = if self.is_focused {
+            theme.focus
+        } else {
+            Color::White
+        };
+        let zen_mode = self.state.as_ref().is_some_and(|state| state.zen_mode);
+        let mut block = if zen_mode {
+            Block::new()
+        } else {
+            let title = match self.role_filter.label() {
+                Some(role) => tf("Chat Window: {role} only (f)", &[("role", &role)]).into(),
+                None => t("Chat Window"),
+            };
+            Block::bordered()
+                .title(title)
+                .title(
+                    Line::from(tf("{count} tokens", &[("count", &self.context_tokens)]))
+                        .right_aligned(),
+                )
+                .title_bottom(hint)
+                .border_style(Style::default().fg(border_color))
+        };
 
         let inner_area = block.inner(area);
-        frame.render_widget(block, area);
 
         if let Some(ref state) = self.state {
+            let mut overflow = 0;
             // Calculate wrapped text for all messages
             let mut wrapped_messages = Vec::new();
+            let mut message_lines = Vec::with_capacity(state.conversation.messages.len());
             let available_width = inner_area.width.saturating_sub(2) as usize; // Account for padding
+            let number_width = state.conversation.messages.len().to_string().len();
+            // A continuation streams in right below the reply it continues
+            let continuing = state
+                .conversation
+                .partial
+                .as_ref()
+                .is_some_and(|partial| partial.continues);
+            let last = state.conversation.messages.len().saturating_sub(1);
+            // Tag each reply with its model once the session has used more than one, counting
+            // the reply being streamed
+            let streaming_model = state
+                .conversation
+                .is_loading
+                .then_some(state.conversation.model.as_str());
+            let mut models = state
+                .conversation
+                .messages
+                .iter()
+                .filter_map(|message| message.metadata.model.as_deref())
+                .chain(streaming_model);
+            let switched_models = models
+                .next()
+                .is_some_and(|first| models.any(|model| model != first));
+            // Replies are labeled with the persona the model plays, if any, and colored by it
+            // once several took part
+            let assistant = state.conversation.persona.as_deref().unwrap_or("AI");
+            let mut personas: Vec<&str> = Vec::new();
+            for persona in state
+                .conversation
+                .messages
+                .iter()
+                .filter_map(|message| message.metadata.persona.as_deref())
+                .chain(
+                    (state.conversation.persona.as_deref())
+                        .filter(|_| state.conversation.is_loading),
+                )
+            {
+                if !personas.contains(&persona) {
+                    personas.push(persona);
+                }
+            }
+            let persona_style = |persona: &str| match personas.iter().position(|p| *p == persona) {
+                Some(index) if personas.len() > 1 => theme.persona(index),
+                _ => theme.assistant,
+            };
 
-            for msg in &state.chat_history {
-                let style = if msg.role == "user" {
-                    Style::default().fg(Color::White).bg(Color::Black)
-                } else {
-                    Style::default().fg(Color::Black).bg(Color::Blue)
+            for (index, msg) in state.conversation.messages.iter().enumerate() {
+                if !self.role_filter.shows(&msg.role) {
+                    message_lines.push(wrapped_messages.len()..wrapped_messages.len());
+                    continue;
+                }
+                let mut style = match &msg.metadata.persona {
+                    Some(persona) if msg.role == "AI" => persona_style(persona),
+                    _ => theme.role(&msg.role),
                 };
+                if self.is_focused && self.selected == Some(index) {
+                    style = style.add_modifier(Modifier::REVERSED | Modifier::BOLD);
+                }
 
                 // Create role prefix
-                let role_prefix = format!("{}: ", msg.role);
-                let prefix_len = role_prefix.len();
-
-                // Wrap the content text
-                let wrapped_lines =
-                    wrap_text(&msg.content, available_width.saturating_sub(prefix_len));
+                let icon = icons.role(&msg.role);
+                let role = if msg.role == "AI" {
+                    msg.metadata.persona.as_deref().unwrap_or(assistant)
+                } else {
+                    &msg.role
+                };
+                let mut role_prefix = if self.show_message_numbers {
+                    format!("{:>number_width$} {icon}{role}", index + 1)
+                } else {
+                    format!("{icon}{role}")
+                };
+                if switched_models && let Some(model) = &msg.metadata.model {
+                    role_prefix.push_str(&model_tag(model));
+                }
+                if !msg.versions.is_empty() {
+                    role_prefix.push_str(&format!(
+                        " [v{}/{}]",
+                        msg.version + 1,
+                        msg.versions.len() + 1
+                    ));
+                }
+                if msg.alternative {
+                    role_prefix.push_str(" [alternative]");
+                }
+                if msg.pinned {
+                    role_prefix.push_str(" [pinned]");
+                }
+                for mark in &msg.marks {
+                    role_prefix.push_str(&format!(" '{mark}"));
+                }
+                role_prefix.push_str(": ");
+                let prefix_len = role_prefix.width();
 
-                // First line includes the role prefix
-                if let Some(first_line) = wrapped_lines.first() {
-                    wrapped_messages.push((format!("{role_prefix}{first_line}"), style));
+                // Wrap the content text, below the reasoning if there is any
+                let content_width = available_width.saturating_sub(prefix_len);
+                let mut reasoning_style = Style::default()
+                    .fg(theme.muted)
+                    .add_modifier(Modifier::ITALIC);
+                if self.is_focused && self.selected == Some(index) {
+                    reasoning_style = reasoning_style.add_modifier(Modifier::REVERSED);
+                }
+                let expanded = self.expanded_reasoning.contains(&index);
+                let content = displayed_content(msg, self.expanded.contains(&index));
+                let (mut content_lines, content_overflow) =
+                    wrap_scrolled(&content, content_width, self.h_scroll);
+                overflow = overflow.max(content_overflow);
+                if msg.content.is_empty() && !msg.tool_calls.is_empty() {
+                    // Replies that only call tools start right with the calls
+                    content_lines.clear();
+                }
+                // Collapse long messages to keep the transcript navigable; tool results down to
+                // the name of the call they answer
+                let mut limit = self.config.ui.collapse_after_lines;
+                if msg.role == "tool" {
+                    let name = state
+                        .conversation
+                        .tool_call(msg)
+                        .map_or("unknown call", |call| call.name.as_str());
+                    content_lines.insert(0, format!("↳ {name}"));
+                    limit = 1;
+                }
+                let hidden = if limit > 0 && !self.expanded.contains(&index) {
+                    content_lines.len().saturating_sub(limit)
+                } else {
+                    0
+                };
+                content_lines.truncate(content_lines.len() - hidden);
+                let footer_style = reasoning_style.remove_modifier(Modifier::ITALIC);
+                let mut tool_style = Style::default().fg(theme.accent);
+                if self.is_focused && self.selected == Some(index) {
+                    tool_style = tool_style.add_modifier(Modifier::REVERSED);
+                }
+                let tool_lines = tool_call_lines(
+                    &msg.tool_calls,
+                    self.expanded.contains(&index),
+                    content_width,
+                );
+                let wrapped_lines = reasoning_lines(&msg.reasoning, expanded, content_width)
+                    .into_iter()
+                    .map(|line| (line, reasoning_style))
+                    .chain(content_lines.into_iter().map(|line| (line, style)))
+                    .chain((hidden > 0).then(|| {
+                        let lines = if hidden == 1 { "line" } else { "lines" };
+                        (format!("… {hidden} more {lines} (o: expand)"), footer_style)
+                    }))
+                    .chain(tool_lines.into_iter().map(|line| (line, tool_style)));
 
-                    // Subsequent lines are indented
-                    for line in wrapped_lines.iter().skip(1) {
-                        let indent = " ".repeat(prefix_len);
-                        wrapped_messages.push((format!("{indent}{line}"), style));
-                    }
+                let first_line = wrapped_messages.len();
+                // The first line includes the role prefix, subsequent lines are indented
+                let indent = " ".repeat(prefix_len);
+                let marked_indent = theme.indent(&msg.role, prefix_len);
+                for (line_index, (line, style)) in wrapped_lines.enumerate() {
+                    let prefix = if line_index == 0 {
+                        &role_prefix
+                    } else {
+                        &marked_indent
+                    };
+                    wrapped_messages.push((format!("{prefix}{line}"), style));
+                }
+                if let Some((warning, color)) = finish_warning(&msg.metadata, &theme)
+                    && !(continuing && index == last)
+                {
+                    wrapped_messages.push((
+                        format!("{indent}⚠ {warning}"),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ));
                 }
+                // Usage of the selected reply, so it doesn't clutter the whole transcript
+                if self.is_focused
+                    && self.selected == Some(index)
+                    && let Some(usage) = usage_summary(&msg.metadata)
+                {
+                    wrapped_messages
+                        .push((format!("{indent}{usage}"), Style::default().fg(theme.muted)));
+                }
+                message_lines.push(first_line..wrapped_messages.len());
             }
 
             // Add loading indicator if loading
-            if state.is_loading {
-                let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-                let spinner_index = (std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis()
-                    / 100)
-                    % spinner_chars.len() as u128;
-                let spinner_char = spinner_chars[spinner_index as usize];
+            if state.conversation.is_loading {
+                let spinner_char = spinner(self.config.ui.reduced_motion);
+
+                let loading_style = Style::default().fg(theme.warning);
+                match state
+                    .conversation
+                    .partial
+                    .as_ref()
+                    .filter(|partial| !partial.is_empty())
+                {
+                    Some(partial) => {
+                        // Show the reply as it streams in, with its progress underneath
+                        let mut role_prefix = format!("{}{assistant}", icons.assistant);
+                        if switched_models && let Some(model) = streaming_model {
+                            role_prefix.push_str(&model_tag(model));
+                        }
+                        role_prefix.push_str(": ");
+                        let indent = theme.indent("AI", role_prefix.width());
+                        let prefix = if partial.continues {
+                            &indent
+                        } else {
+                            &role_prefix
+                        };
+                        let style = persona_style(assistant);
+                        let reasoning_style = Style::default()
+                            .fg(theme.muted)
+                            .add_modifier(Modifier::ITALIC);
+                        let width = available_width.saturating_sub(prefix.width());
+                        // Follow the reasoning until the answer starts
+                        let expanded = partial.content.is_empty();
+                        let (content_lines, content_overflow) =
+                            wrap_scrolled(&partial.content, width, self.h_scroll);
+                        overflow = overflow.max(content_overflow);
+                        let lines = reasoning_lines(&partial.reasoning, expanded, width)
+                            .into_iter()
+                            .map(|line| (line, reasoning_style))
+                            .chain(content_lines.into_iter().map(|line| (line, style)));
+                        for (index, (line, style)) in lines.enumerate() {
+                            let prefix = if index == 0 { prefix } else { &indent };
+                            wrapped_messages.push((format!("{prefix}{line}"), style));
+                        }
+                        wrapped_messages.push((
+                            format!("{indent}{spinner_char} {}", stream_progress(partial)),
+                            loading_style,
+                        ));
+                    }
+                    None => {
+                        let partial = state.conversation.partial.as_ref();
+                        let status = match partial.and_then(|partial| {
+                            Some((partial.rate_limited, partial.throttled_for()?))
+                        }) {
+                            Some((rate_limited, wait)) => {
+                                let seconds = wait.as_secs_f64().ceil() as u64;
+                                if rate_limited {
+                                    format!("Rate limited, retrying in {seconds}s")
+                                } else {
+                                    format!("Throttled, sending in {seconds}s")
+                                }
+                            }
+                            None => {
+                                let elapsed = partial
+                                    .map(|partial| {
+                                        format!(" {:.1}s", partial.elapsed().as_secs_f64())
+                                    })
+                                    .unwrap_or_default();
+                                format!("Thinking...{elapsed}")
+                            }
+                        };
+                        wrapped_messages.push((
+                            format!("{}{assistant}: {spinner_char} {status}", icons.assistant),
+                            loading_style,
+                        ));
+                    }
+                }
+            }
 
+            // Progress of an agent run, until the model stops calling tools
+            let conversation = &state.conversation;
+            if conversation.agent
+                && (conversation.is_loading || !conversation.pending_tool_calls().is_empty())
+            {
+                let steps = match self.config.tools.agent_steps {
+                    0 => format!("{} tool calls", conversation.agent_steps),
+                    budget => format!("{} of {budget} tool calls", conversation.agent_steps),
+                };
                 wrapped_messages.push((
-                    format!("AI: {spinner_char} Thinking..."),
-                    Style::default().fg(Color::Yellow),
+                    format!("{} Agent: {steps} | Alt+X: abort", icons.agent),
+                    Style::default().fg(theme.accent),
                 ));
             }
 
+            // Messages waiting for the connection to come back
+            let queued_style = Style::default()
+                .fg(theme.muted)
+                .add_modifier(Modifier::ITALIC);
+            let queued = if self.role_filter.shows("user") {
+                state.conversation.queued.as_slice()
+            } else {
+                &[]
+            };
+            for text in queued {
+                let prefix = "user (unsent): ";
+                let indent = " ".repeat(prefix.width());
+                let width = available_width.saturating_sub(prefix.width());
+                for (index, line) in wrap_text(text, width).into_iter().enumerate() {
+                    let prefix = if index == 0 { prefix } else { &indent };
+                    wrapped_messages.push((format!("{prefix}{line}"), queued_style));
+                }
+            }
+
             // Convert to ListItems
             let items: Vec<ListItem> = wrapped_messages
                 .iter()
@@ -166,53 +784,709 @@ impl Component for ChatWindow {
             let total_items = items.len();
             let visible_lines = inner_area.height as usize;
 
-            let mut list_state = ListState::default();
+            // Bring the selected message into view after the selection moved
+            if self.scroll_to_selected
+                && let Some(lines) = self.selected.and_then(|index| message_lines.get(index))
+            {
+                if lines.start < self.scroll_offset {
+                    self.scroll_offset = lines.start;
+                } else if lines.end > self.scroll_offset + visible_lines {
+                    // Show the end of the message, but never scroll past its first line
+                    self.scroll_offset = lines.end.saturating_sub(visible_lines).min(lines.start);
+                }
+            }
+            self.scroll_to_selected = false;
 
             // Clamp scroll offset to valid range
             let max_scroll = total_items.saturating_sub(visible_lines);
-            if self.scroll_offset == usize::MAX {
-                // End key was pressed - scroll to bottom
-                self.scroll_offset = max_scroll;
-            } else {
-                self.scroll_offset = self.scroll_offset.min(max_scroll);
-            }
+            self.scroll_offset = self.scroll_offset.min(max_scroll);
 
-            if total_items > 0 {
-                let selected_index = if total_items <= visible_lines {
-                    // All items fit, no scrolling needed
-                    None
-                } else {
-                    // Set selection to control what's visible
-                    Some(self.scroll_offset + visible_lines.saturating_sub(1))
-                };
-                list_state.select(selected_index);
-            }
+            let mut list_state = ListState::default().with_offset(self.scroll_offset);
 
             let chat_history_widget = List::new(items).style(Style::default());
 
+            // Point out lines that go on off-screen, and how far they are scrolled
+            self.overflow = overflow;
+            self.h_scroll = self.h_scroll.min(overflow);
+            if overflow > 0 && !zen_mode {
+                block = block.title(
+                    Line::from(tf(
+                        " ←/→ {offset}/{overflow} ",
+                        &[("offset", &self.h_scroll), ("overflow", &overflow)],
+                    ))
+                    .centered(),
+                );
+            }
+            frame.render_widget(block, area);
             frame.render_stateful_widget(chat_history_widget, inner_area, &mut list_state);
+        } else {
+            frame.render_widget(block, area);
         }
 
         Ok(())
     }
 }
 
-// Helper function to wrap text to fit within the specified width
+/// A warning for replies that didn't finish normally.
+fn finish_warning(metadata: &MessageMetadata, theme: &Theme) -> Option<(String, Color)> {
+    match metadata.finish_reason.as_deref()? {
+        "stop" | "tool_calls" => None,
+        "length" => Some((
+            "Cut off by the max_tokens limit, Alt+C: continue".to_string(),
+            theme.warning,
+        )),
+        "content_filter" => Some(("Stopped by the content filter".to_string(), theme.error)),
+        "cancelled" => Some((
+            "Cancelled, the reply is incomplete".to_string(),
+            theme.muted,
+        )),
+        reason => Some((format!("Stopped early ({reason})"), theme.warning)),
+    }
+}
+
+/// The content of a message as shown, with errors recorded by lazychat in the language of the UI.
+fn displayed_content(message: &ChatMessage, expanded: bool) -> Cow<'_, str> {
+    match message.content.strip_prefix("Error: ") {
+        Some(error) if message.role == "system" => {
+            tf("Error: {error}", &[("error", &error)]).into()
+        }
+        _ => collapsed_content(message, expanded),
+    }
+}
+
+/// The content of a message with each of its collapsed parts, like a large paste, as a
+/// placeholder line unless the message is expanded.
+fn collapsed_content(message: &ChatMessage, expanded: bool) -> Cow<'_, str> {
+    if expanded || message.collapsed.is_empty() {
+        return Cow::Borrowed(&message.content);
+    }
+    let mut text = String::new();
+    let mut end = 0;
+    for range in &message.collapsed {
+        let Some(part) = message
+            .content
+            .get(range.clone())
+            .filter(|_| range.start >= end)
+        else {
+            continue;
+        };
+        text.push_str(&message.content[end..range.start]);
+        // An attachment is its label, then the text in a code block
+        let label = part
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(':');
+        text.push_str(&format!(
+            "[{label}: {} lines, {} KB] (o: expand)",
+            part.lines().count().saturating_sub(3),
+            part.len().div_ceil(1024)
+        ));
+        end = range.end;
+    }
+    text.push_str(&message.content[end..]);
+    Cow::Owned(text)
+}
+
+fn toggle(set: &mut HashSet<usize>, index: usize) {
+    if !set.remove(&index) {
+        set.insert(index);
+    }
+}
+
+/// Lines of a reasoning block: a summary line when collapsed, the whole text when expanded.
+fn reasoning_lines(reasoning: &str, expanded: bool, width: usize) -> Vec<String> {
+    let reasoning = reasoning.trim();
+    if reasoning.is_empty() {
+        return Vec::new();
+    }
+    if !expanded {
+        let words = reasoning.split_whitespace().count();
+        return vec![format!("▸ Reasoning ({words} words, t: expand)")];
+    }
+    let mut lines = vec!["▾ Reasoning".to_string()];
+    lines.extend(
+        wrap_text(reasoning, width.saturating_sub(2))
+            .into_iter()
+            .map(|line| format!("│ {line}")),
+    );
+    lines
+}
+
+/// Lines of the calls a reply asks for: name and arguments on one line each when collapsed,
+/// the arguments pretty-printed when expanded.
+fn tool_call_lines(calls: &[ToolCall], expanded: bool, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for call in calls {
+        let arguments = serde_json::from_str::<serde_json::Value>(&call.arguments).ok();
+        if !expanded {
+            // Compact JSON, or the raw text if the model wrote something invalid
+            let arguments =
+                arguments.map_or_else(|| call.arguments.clone(), |args| args.to_string());
+            lines.push(truncate(&format!("⚙ {}({arguments})", call.name), width));
+            continue;
+        }
+        lines.push(format!("⚙ {}", call.name));
+        let arguments = arguments
+            .and_then(|args| serde_json::to_string_pretty(&args).ok())
+            .unwrap_or_else(|| call.arguments.clone());
+        lines.extend(
+            wrap_text(&arguments, width.saturating_sub(2))
+                .into_iter()
+                .map(|line| format!("│ {line}")),
+        );
+    }
+    lines
+}
+
+/// The frame of the loading spinner for now, or a static indicator with `reduced_motion`.
+fn spinner(reduced_motion: bool) -> char {
+    if reduced_motion {
+        return '•';
+    }
+    let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    let spinner_index = (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        / 100)
+        % spinner_chars.len() as u128;
+    spinner_chars[spinner_index as usize]
+}
+
+/// Tokens received, generation speed and elapsed time of a streaming reply.
+fn stream_progress(partial: &PartialReply) -> String {
+    let mut progress = format!("~{} tokens", partial.tokens);
+    if let Some(speed) = partial.tokens_per_second() {
+        progress.push_str(&format!(" · {speed:.1} tok/s"));
+    }
+    progress.push_str(&format!(" · {:.1}s", partial.elapsed().as_secs_f64()));
+    progress
+}
+
+/// The model's name without its provider, e.g. ` · claude-3.5-sonnet`, to follow the role.
+fn model_tag(model: &str) -> String {
+    let name = model.rsplit_once('/').map_or(model, |(_, name)| name);
+    format!(" · {name}")
+}
+
+/// Model, finish reason, token usage, cost and latency of a reply, as far as they are known.
+fn usage_summary(metadata: &MessageMetadata) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(model) = &metadata.model {
+        parts.push(model.clone());
+    }
+    if let Some(reason) = &metadata.finish_reason {
+        parts.push(reason.clone());
+    }
+    let mut cache = String::new();
+    if let Some(cached) = metadata.cached_tokens.filter(|tokens| *tokens > 0) {
+        cache.push_str(&format!(", {cached} cached"));
+    }
+    if let Some(written) = metadata.cache_write_tokens.filter(|tokens| *tokens > 0) {
+        cache.push_str(&format!(", {written} written to cache"));
+    }
+    match (metadata.prompt_tokens, metadata.completion_tokens) {
+        (Some(prompt), Some(completion)) => parts.push(format!(
+            "{prompt} prompt{cache} + {completion} completion tokens"
+        )),
+        (None, Some(completion)) => parts.push(format!("{completion} completion tokens")),
+        _ => {}
+    }
+    if let Some(cost) = metadata.cost {
+        parts.push(format!("${cost:.6}"));
+    }
+    if let Some(speed) = metadata.tokens_per_second {
+        parts.push(format!("{speed:.1} tok/s"));
+    }
+    if let Some(latency_ms) = metadata.latency_ms {
+        parts.push(format!("{:.1}s", latency_ms as f64 / 1000.0));
+    }
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
+
+/// `line` cut off with `…` to fit in `width` columns; wide characters take two.
+fn truncate(line: &str, width: usize) -> String {
+    if width == 0 || line.width() <= width {
+        return line.to_string();
+    }
+    let mut kept = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width >= width {
+            break;
+        }
+        used += char_width;
+        kept.push(c);
+    }
+    kept.push('…');
+    kept
+}
+
+/// `line` scrolled `offset` columns to the left and cut off to `width`, with `…` where it goes
+/// on off-screen.
+fn clip(line: &str, offset: usize, width: usize) -> String {
+    let mut skipped = 0;
+    let rest = line.trim_start_matches(|c: char| {
+        if skipped >= offset {
+            return false;
+        }
+        skipped += c.width().unwrap_or(0);
+        true
+    });
+    if skipped == 0 {
+        truncate(rest, width)
+    } else if rest.is_empty() {
+        String::new()
+    } else {
+        format!("…{}", truncate(rest, width.saturating_sub(1)))
+    }
+}
+
+/// Wrap `text` to lines of at most `max_width` columns, measured in display width so that CJK
+/// characters and emoji take two columns.
 fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
+    wrap_scrolled(text, max_width, 0).0
+}
+
+/// Wrap `text` like [`wrap_text`], except that lines of fenced code blocks and Markdown tables
+/// are scrolled `offset` columns sideways and cut off rather than wrapped, and wrapped lines stay
+/// indented like the line they continue, so that generated code keeps its shape.
+///
+/// Also returns by how many columns the widest of the lines that aren't wrapped is too wide.
+fn wrap_scrolled(text: &str, max_width: usize, offset: usize) -> (Vec<String>, usize) {
     if max_width == 0 {
-        return vec![text.to_string()];
+        return (vec![text.to_string()], 0);
     }
 
-    // Use textwrap for better word wrapping
-    let options = textwrap::Options::new(max_width)
-        .break_words(true)
-        .word_separator(textwrap::WordSeparator::AsciiSpace);
+    let mut lines = Vec::new();
+    let mut overflow = 0;
+    let mut in_code = false;
+    for line in text.split('\n') {
+        let fence = line.trim_start().starts_with("```");
+        if in_code || fence || line.trim_start().starts_with('|') {
+            let line = line.replace('\t', "    ");
+            // One more column for the `…` in front once scrolled
+            if line.width() > max_width {
+                overflow = overflow.max(line.width() - max_width + 1);
+            }
+            lines.push(clip(&line, offset, max_width));
+        } else {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            // Deep indentation would leave no room for the text
+            let indent = if indent.width() * 2 < max_width {
+                indent
+            } else {
+                ""
+            };
+            // Break where Unicode allows, e.g. between CJK characters that have no spaces
+            // between them
+            let options = textwrap::Options::new(max_width)
+                .break_words(true)
+                .word_separator(textwrap::WordSeparator::UnicodeBreakProperties)
+                .subsequent_indent(indent);
+            let wrapped = textwrap::wrap(line, &options);
+            if wrapped.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(wrapped.into_iter().map(|cow| cow.into_owned()));
+        }
+        if fence {
+            in_code = !in_code;
+        }
+    }
+    (lines, overflow)
+}
 
-    let wrapped = textwrap::wrap(text, &options);
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+    use pretty_assertions::assert_eq;
 
-    if wrapped.is_empty() {
-        vec![String::new()]
-    } else {
-        wrapped.into_iter().map(|cow| cow.into_owned()).collect()
+    use super::*;
+    use crate::{
+        components::testing::{conversation, render, state},
+        core::attachments::{self, Attachment},
+    };
+
+    #[test]
+    fn test_render_empty() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state(&[]))?;
+        assert_snapshot!(render(&mut chat, 40, 6));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_throttled() -> Result<()> {
+        let mut state = state(&[("user", "Hello")]);
+        state.conversation.is_loading = true;
+        let mut partial = PartialReply::new();
+        partial.throttled_until =
+            Some(std::time::Instant::now() + std::time::Duration::from_secs(5));
+        state.conversation.partial = Some(partial);
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        let screen = render(&mut chat, 50, 5).to_string();
+        assert!(screen.contains("Throttled, sending in 5s"), "{screen}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_rate_limited() -> Result<()> {
+        let mut state = state(&[("user", "Hello")]);
+        state.conversation.is_loading = true;
+        let mut partial = PartialReply::new();
+        partial.retry_after(std::time::Duration::from_secs(12));
+        state.conversation.partial = Some(partial);
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        let screen = render(&mut chat, 50, 5).to_string();
+        assert!(screen.contains("Rate limited, retrying in 12s"), "{screen}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_agent_steps() -> Result<()> {
+        let mut state = state(&[("user", "Tidy up the notes")]);
+        state.conversation.is_loading = true;
+        state.conversation.agent = true;
+        state.conversation.agent_steps = 3;
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        let screen = render(&mut chat, 60, 6).to_string();
+        assert!(
+            screen.contains("@ Agent: 3 of 10 tool calls | Alt+X: abort"),
+            "{screen}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_reduced_motion() -> Result<()> {
+        let mut state = state(&[("user", "Hello")]);
+        state.conversation.is_loading = true;
+        let mut chat = ChatWindow::new();
+        let mut config = Config::default();
+        config.ui.reduced_motion = true;
+        chat.register_config_handler(config)?;
+        chat.register_state_handler(state)?;
+        assert_snapshot!(render(&mut chat, 40, 5));
+        assert_eq!(chat.update(Action::Tick)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_role_markers() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        let mut config = Config::default();
+        config.ui.theme = crate::theme::ThemeName::HighContrast;
+        chat.register_config_handler(config)?;
+        chat.register_state_handler(state(&[("user", "Two\nlines"), ("AI", "One\nTwo\nThree")]))?;
+        assert_snapshot!(render(&mut chat, 30, 7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_icons() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        let mut config = Config::default();
+        config.ui.use_icons = Some(true);
+        chat.register_config_handler(config)?;
+        chat.register_state_handler(state(&[("user", "Hi"), ("AI", "Hello!")]))?;
+        let screen = render(&mut chat, 30, 4).to_string();
+        assert!(screen.contains("\u{f007} user: Hi"), "{screen}");
+        assert!(screen.contains("\u{f06a9} AI: Hello!"), "{screen}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_wide_characters() -> Result<()> {
+        assert_eq!(wrap_text("你好世界你好", 5), ["你好", "世界", "你好"]);
+        assert_eq!(truncate("⚙ 天气(北京)", 8), "⚙ 天气(…");
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state(&[
+            ("user", "日本語のテキストを折り返す"),
+            ("AI", "🙂 emoji take two columns 🙂"),
+        ]))?;
+        assert_snapshot!(render(&mut chat, 24, 9));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_code() {
+        let text = "Run it:\n```rust\nfn main() {\n\tprintln!(\"a long line of code\");\n}\n```\n  - an indented item that wraps";
+        assert_eq!(
+            wrap_text(text, 20),
+            [
+                "Run it:",
+                "```rust",
+                "fn main() {",
+                "    println!(\"a lon…",
+                "}",
+                "```",
+                "  - an indented item",
+                "  that wraps"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_scrolled_sideways() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state(&[(
+            "AI",
+            "```\nlet total = prices.iter().sum::<u64>(); // in cents\n```\n| a | b | c | d | e | f | g |",
+        )]))?;
+        assert_snapshot!("sideways_start", render(&mut chat, 50, 7));
+        chat.perform(Action::ScrollRight);
+        chat.perform(Action::ScrollRight);
+        assert_snapshot!("sideways_end", render(&mut chat, 50, 7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_role_filter() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state(&[
+            ("user", "First question"),
+            ("AI", "First answer"),
+            ("user", "Second question"),
+            ("AI", "Second answer"),
+        ]))?;
+        chat.update(Action::FocusChat)?;
+        chat.perform(Action::CycleRoleFilter);
+        assert_eq!(chat.selected, Some(2));
+        chat.perform(Action::SelectPrevious);
+        assert_eq!(chat.selected, Some(0));
+        chat.perform(Action::SelectNext);
+        assert_eq!(chat.selected, Some(2));
+        chat.update(Action::FocusInput)?;
+        assert_snapshot!(render(&mut chat, 40, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_persona() -> Result<()> {
+        let mut state = state(&[("AI", "Welcome, User."), ("user", "Hello Ada")]);
+        state.conversation.persona = Some("Ada".to_string());
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!(render(&mut chat, 40, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_roundtable() -> Result<()> {
+        let mut state = state(&[
+            ("user", "Tabs or spaces?"),
+            ("AI", "Tabs."),
+            ("AI", "Spaces."),
+        ]);
+        state.conversation.messages[1].metadata.persona = Some("Ada".to_string());
+        state.conversation.messages[2].metadata.persona = Some("Critic".to_string());
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!(render(&mut chat, 40, 6));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_zen_mode() -> Result<()> {
+        let mut state = state(&[("user", "Hi"), ("AI", "Hello!")]);
+        state.zen_mode = true;
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!(render(&mut chat, 30, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_switched_models() -> Result<()> {
+        let mut state = state(&[
+            ("user", "Hi"),
+            ("AI", "Hello!"),
+            ("user", "And you?"),
+            ("AI", "Hey."),
+        ]);
+        state.conversation.messages[1].metadata.model = Some("mock-1".to_string());
+        state.conversation.messages[3].metadata.model = Some("openai/gpt-4o".to_string());
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state.clone())?;
+        assert_snapshot!(render(&mut chat, 40, 8));
+
+        // A reply from another model is tagged while it streams in
+        state.conversation.messages.truncate(2);
+        state.conversation.model = "mock-2".to_string();
+        state.conversation.is_loading = true;
+        let mut partial = PartialReply::new();
+        partial.push(&crate::core::Delta::Content("Hey".to_string()), 1);
+        state.conversation.partial = Some(partial);
+        chat.register_state_handler(state)?;
+        let screen = render(&mut chat, 40, 8).to_string();
+        assert!(screen.contains("AI · mock-1: Hello!"), "{screen}");
+        assert!(screen.contains("AI · mock-2: Hey"), "{screen}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_queued() -> Result<()> {
+        let mut state = state(&[("user", "Hello"), ("AI", "Hi!")]);
+        state.conversation.queued = vec![
+            "Are you still there?".to_string(),
+            "This one waits for the connection too".to_string(),
+        ];
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!(render(&mut chat, 40, 8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_conversation() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(conversation())?;
+        assert_snapshot!("conversation_wide", render(&mut chat, 80, 8));
+        assert_snapshot!("conversation_narrow", render(&mut chat, 30, 14));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_selection_with_numbers_pins_and_marks() -> Result<()> {
+        let mut state = conversation();
+        state.conversation.messages[0].pinned = true;
+        state.conversation.messages[2].marks = vec!['a'];
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        chat.update(Action::FocusChat)?;
+        chat.perform(Action::ToggleMessageNumbers);
+        chat.select(0);
+        assert_snapshot!(render(&mut chat, 60, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_finish_reason_and_usage() -> Result<()> {
+        let mut state = conversation();
+        state.conversation.messages[1].metadata = MessageMetadata {
+            prompt_tokens: Some(12),
+            completion_tokens: Some(20),
+            cost: Some(0.0002),
+            latency_ms: Some(1500),
+            finish_reason: Some("length".to_string()),
+            ..Default::default()
+        };
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        chat.update(Action::FocusChat)?;
+        chat.select(1);
+        assert_snapshot!(render(&mut chat, 60, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_reasoning() -> Result<()> {
+        let mut state = conversation();
+        state.conversation.messages[1].reasoning =
+            "The user asks about Rust. Explain ownership briefly.".to_string();
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!("reasoning_collapsed", render(&mut chat, 50, 10));
+
+        chat.update(Action::FocusChat)?;
+        chat.select(1);
+        chat.perform(Action::ToggleReasoningSelected);
+        chat.update(Action::FocusInput)?;
+        assert_snapshot!("reasoning_expanded", render(&mut chat, 50, 12));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_collapsed_message() -> Result<()> {
+        let long = (1..=8).map(|n| format!("line {n}")).collect::<Vec<_>>();
+        let mut chat = ChatWindow::new();
+        let mut config = Config::default();
+        config.ui.collapse_after_lines = 3;
+        chat.register_config_handler(config)?;
+        chat.register_state_handler(state(&[("user", &long.join("\n")), ("AI", "ok")]))?;
+        assert_snapshot!("collapsed", render(&mut chat, 40, 8));
+
+        chat.update(Action::FocusChat)?;
+        chat.select(0);
+        chat.perform(Action::ToggleExpandSelected);
+        chat.update(Action::FocusInput)?;
+        assert_snapshot!("expanded", render(&mut chat, 40, 12));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_collapsed_paste() -> Result<()> {
+        let log = (1..=40)
+            .map(|n| format!("log line {n}"))
+            .collect::<Vec<_>>();
+        let (text, collapsed) = attachments::attach("Why?", &[Attachment::pasted(&log.join("\n"))]);
+        let mut state = state(&[("user", &text), ("AI", "A timeout.")]);
+        state.conversation.messages[0].collapsed = collapsed;
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!(render(&mut chat, 50, 8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_tool_calls() -> Result<()> {
+        let mut state = state(&[
+            ("user", "Weather in Oslo?"),
+            ("AI", ""),
+            ("tool", "{\"temp\": -3,\n\"sky\": \"snow\"}"),
+            ("AI", "It's -3°C and snowing."),
+        ]);
+        state.conversation.messages[1].tool_calls = vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "weather".to_string(),
+            arguments: r#"{"city": "Oslo", "unit": "celsius"}"#.to_string(),
+        }];
+        state.conversation.messages[2].tool_call_id = Some("call_1".to_string());
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(state)?;
+        assert_snapshot!("tool_calls_collapsed", render(&mut chat, 50, 8));
+
+        chat.update(Action::FocusChat)?;
+        for index in [1, 2] {
+            chat.select(index);
+            chat.perform(Action::ToggleExpandSelected);
+        }
+        chat.update(Action::FocusInput)?;
+        assert_snapshot!("tool_calls_expanded", render(&mut chat, 50, 12));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_scrolled_to_selection() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(conversation())?;
+        chat.update(Action::FocusChat)?;
+        chat.select(usize::MAX);
+        assert_snapshot!(render(&mut chat, 30, 6));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quick_prompt_selection() -> Result<()> {
+        let mut chat = ChatWindow::new();
+        chat.register_state_handler(conversation())?;
+        let prompt = "Explain {{selection}}".to_string();
+        assert_eq!(
+            chat.update(Action::QuickPrompt(prompt.clone()))?,
+            Some(Action::SendQuickPrompt(prompt.clone(), None))
+        );
+        chat.update(Action::FocusChat)?;
+        chat.select(0);
+        assert_eq!(
+            chat.update(Action::QuickPrompt(prompt.clone()))?,
+            Some(Action::SendQuickPrompt(prompt, Some(0)))
+        );
+        Ok(())
     }
 }
@@ -1,12 +1,30 @@
 use color_eyre::Result;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent};
 use ratatui::{prelude::*, widgets::*};
 use std::any::Any;
 use tokio::sync::mpsc::UnboundedSender;
 use tui_textarea::TextArea;
 
-use super::Component;
-use crate::{action::Action, app::AppState, config::Config};
+use super::{
+    Component, is_global_key, is_text_key, line_numbers, pasted_line, pasted_text, text_field,
+};
+use crate::{
+    action::Action,
+    app::{AppState, Mode},
+    config::Config,
+    core::{
+        keys::{ApiKey, KeyStore},
+        provider,
+        provider::ModelParams,
+        session, share,
+        usage::{UsageLog, latency_lines},
+    },
+    locale::{t, tf},
+    stats::ConversationStats,
+};
+
+/// How many days the usage dialog covers.
+const USAGE_DAYS: u64 = 30;
 
 #[derive(Default)]
 pub struct Dialog {
@@ -17,6 +35,8 @@ pub struct Dialog {
     is_visible: bool,
     is_focused: bool,
     dialog_type: DialogType,
+    stats: Option<ConversationStats>,
+    status: Option<String>,
 }
 
 #[derive(Default, Clone, PartialEq)]
@@ -24,6 +44,26 @@ enum DialogType {
     #[default]
     SystemPrompt,
     Generic,
+    Stats,
+    Usage,
+    ModelInfo,
+    EditMessage(usize),
+    /// The transcript `/share` uploads, editable to redact parts of it.
+    Share,
+    /// A new API key for the configured provider, masked while typed.
+    ApiKey,
+    /// The commit message the model wrote for the staged changes, committed when saved.
+    Commit,
+}
+
+impl DialogType {
+    /// Reports can only be scrolled, not edited.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            DialogType::Stats | DialogType::Usage | DialogType::ModelInfo
+        )
+    }
 }
 
 impl Dialog {
@@ -36,11 +76,13 @@ impl Dialog {
             is_visible: false,
             is_focused: true, // Default to focused when created
             dialog_type: DialogType::default(),
+            stats: None,
+            status: None,
         }
     }
 
     pub fn show(&mut self, content: String) {
-        self.textarea = TextArea::default();
+        self.textarea = text_field(&self.config);
         if !content.is_empty() {
             self.textarea.insert_str(content);
         }
@@ -50,7 +92,7 @@ impl Dialog {
     }
 
     pub fn show_system_prompt(&mut self, content: String) {
-        self.textarea = TextArea::default();
+        self.textarea = text_field(&self.config);
         if !content.is_empty() {
             self.textarea.insert_str(content);
         }
@@ -59,10 +101,149 @@ impl Dialog {
         self.dialog_type = DialogType::SystemPrompt;
     }
 
+    pub fn show_message_editor(&mut self, index: usize, content: String) {
+        self.textarea = text_field(&self.config);
+        self.textarea.insert_str(content);
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::EditMessage(index);
+    }
+
+    pub fn show_share(&mut self, markdown: String) {
+        self.textarea = text_field(&self.config);
+        self.textarea.insert_str(markdown);
+        self.textarea.move_cursor(tui_textarea::CursorMove::Top);
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::Share;
+    }
+
+    pub fn show_api_key(&mut self) {
+        self.textarea = text_field(&self.config);
+        self.textarea.set_mask_char('•');
+        self.textarea
+            .set_placeholder_text("Paste the new key, it is checked before it is saved");
+        let provider = self.config.chat.provider;
+        let stored = KeyStore::new(self.config.config.keys_file()).get(provider.name());
+        self.status = Some(match stored {
+            Ok(Some(key)) => format!("now {}", key.masked()),
+            Ok(None) if std::env::var(provider::API_KEY_ENV).is_ok() => {
+                format!("now from ${}", provider::API_KEY_ENV)
+            }
+            Ok(None) => "none set".to_string(),
+            Err(err) => format!("unreadable: {err}"),
+        });
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::ApiKey;
+    }
+
+    pub fn show_commit(&mut self, message: String) {
+        self.textarea = text_field(&self.config);
+        self.textarea.insert_str(message);
+        self.textarea.move_cursor(tui_textarea::CursorMove::Top);
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::Commit;
+    }
+
+    pub fn show_stats(&mut self, stats: ConversationStats) {
+        self.textarea = TextArea::new(stats.lines());
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::Stats;
+        self.stats = Some(stats);
+    }
+
+    pub fn show_usage(&mut self) {
+        let log = UsageLog::new(self.config.config.usage_file());
+        let lines = match log.summary(session::now(), USAGE_DAYS) {
+            Ok(summary) => summary.lines(),
+            Err(err) => vec![format!("Failed to read usage: {err}")],
+        };
+        self.textarea = TextArea::new(lines);
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::Usage;
+    }
+
+    pub fn show_model_info(&mut self) {
+        let (model, favorite) = self.state.as_ref().map_or_else(Default::default, |state| {
+            let model = state.conversation.model.clone();
+            let favorite = state.model_history.is_favorite(&model);
+            (model, favorite)
+        });
+        let params = ModelParams::for_model(&model, &self.config.chat, &self.config.models);
+        let setting =
+            |value: Option<String>| value.unwrap_or_else(|| "provider default".to_string());
+        let mut lines = vec![
+            format!("Model:       {model}{}", if favorite { " ★" } else { "" }),
+            format!(
+                "Temperature: {}",
+                setting(params.temperature.map(|t| t.to_string()))
+            ),
+            format!(
+                "Top p:       {}",
+                setting(params.top_p.map(|p| p.to_string()))
+            ),
+            format!(
+                "Max tokens:  {}",
+                setting(params.max_tokens.map(|t| t.to_string()))
+            ),
+            String::new(),
+            "Latency over all sessions, p50/p90/p99, fastest first".to_string(),
+        ];
+        match UsageLog::new(self.config.config.usage_file()).latency() {
+            Ok(stats) if stats.is_empty() => lines.push("(no replies yet)".to_string()),
+            Ok(stats) => lines.extend(latency_lines(&stats)),
+            Err(err) => lines.push(format!("Failed to read usage: {err}")),
+        }
+        self.textarea = TextArea::new(lines);
+        self.is_visible = true;
+        self.is_focused = true; // Focus when showing
+        self.dialog_type = DialogType::ModelInfo;
+    }
+
     pub fn hide(&mut self) {
         self.is_visible = false;
         self.is_focused = false; // Unfocus when hiding
-        self.textarea = TextArea::default();
+        self.textarea = text_field(&self.config);
+        self.stats = None;
+        self.status = None;
+    }
+
+    fn export_stats(&mut self) {
+        let Some(stats) = &self.stats else {
+            return;
+        };
+        self.status = Some(match stats.export(&self.config.config.data_dir) {
+            Ok(path) => format!("Exported to {}", path.display()),
+            Err(err) => format!("Export failed: {err}"),
+        });
+    }
+
+    /// Apply the edited text and close the dialog.
+    fn save(&mut self) -> Action {
+        let text = self.get_text();
+        let action = match self.dialog_type {
+            DialogType::SystemPrompt => Some(Action::SetSystemPrompt(text)),
+            DialogType::EditMessage(index) => Some(Action::UpdateMessage(index, text)),
+            DialogType::Share => Some(Action::PublishShare(text)),
+            DialogType::ApiKey if text.trim().is_empty() => None,
+            DialogType::ApiKey => Some(Action::SaveApiKey(ApiKey::new(&text))),
+            DialogType::Commit if text.trim().is_empty() => None,
+            DialogType::Commit => Some(Action::RunCommit(text)),
+            // Read-only dialogs are just closed
+            DialogType::Generic | DialogType::Stats | DialogType::Usage | DialogType::ModelInfo => {
+                None
+            }
+        };
+        if let Some(action) = action
+            && let Some(tx) = &self.command_tx
+        {
+            let _ = tx.send(action);
+        }
+        Action::HideDialog
     }
 
     pub fn get_text(&self) -> String {
@@ -90,39 +271,62 @@ impl Component for Dialog {
         Ok(())
     }
 
+    fn handle_paste_event(&mut self, text: String) -> Result<Option<Action>> {
+        if !self.is_visible || !self.is_focused || self.dialog_type.is_read_only() {
+            return Ok(None);
+        }
+        // Keys are a single line
+        let text = if self.dialog_type == DialogType::ApiKey {
+            pasted_line(&text)
+        } else {
+            pasted_text(&text)
+        };
+        self.textarea.insert_str(text);
+        Ok(Some(Action::Render))
+    }
+
     fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
         // Only handle events when dialog is visible and focused
         if !self.is_visible || !self.is_focused {
             return Ok(None);
         }
 
-        match key.code {
-            KeyCode::Esc => Ok(Some(Action::HideDialog)),
-
-            KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => {
-                // Ctrl+S to submit
-                let text = self.get_text();
-                let action_to_send = match self.dialog_type {
-                    DialogType::SystemPrompt => {
-                        if let Some(tx) = &self.command_tx {
-                            // Send the system prompt action separately
-                            let _ = tx.send(Action::SetSystemPrompt(text));
-                        }
-                        Action::HideDialog
-                    }
-                    DialogType::Generic => {
-                        // For generic dialogs, just hide
-                        Action::HideDialog
-                    }
-                };
-                Ok(Some(action_to_send))
-            }
-            _ => {
-                // Let tui-textarea handle all other key events
-                self.textarea.input(key);
-                Ok(None)
+        // Keys are a single line, Enter is done
+        if self.dialog_type == DialogType::ApiKey && key.code == KeyCode::Enter {
+            return Ok(Some(self.save()));
+        }
+        if !is_text_key(key) {
+            match self.config.keybindings.action(Mode::Dialog, key) {
+                Some(Action::Cancel) => return Ok(Some(Action::HideDialog)),
+                Some(Action::Confirm) => return Ok(Some(self.save())),
+                Some(Action::ExportStats) if self.dialog_type == DialogType::Stats => {
+                    self.export_stats();
+                    return Ok(Some(Action::Render));
+                }
+                Some(Action::ExportStats) => {}
+                Some(Action::Undo) if !self.dialog_type.is_read_only() => {
+                    self.textarea.undo();
+                    return Ok(Some(Action::Render));
+                }
+                Some(Action::Redo) if !self.dialog_type.is_read_only() => {
+                    self.textarea.redo();
+                    return Ok(Some(Action::Render));
+                }
+                Some(action) => return Ok(Some(action)),
+                None if is_global_key(&self.config, key) => return Ok(None),
+                None => {}
             }
         }
+
+        let is_navigation = matches!(
+            key.code,
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown
+        );
+        if !self.dialog_type.is_read_only() || is_navigation {
+            self.textarea.input(key);
+        }
+        // The dialog is modal, swallow everything else
+        Ok(Some(Action::Render))
     }
 
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
@@ -135,7 +339,7 @@ impl Component for Dialog {
             Action::ShowSystemPromptDialog => {
                 // Get current system prompt from state if available
                 let current_prompt = if let Some(state) = &self.state {
-                    state.system_prompt.clone()
+                    state.conversation.system_prompt.clone()
                 } else {
                     String::new()
                 };
@@ -143,6 +347,48 @@ impl Component for Dialog {
                 // When dialog is shown, it should take focus and input should lose focus
                 Ok(Some(Action::Render))
             }
+            Action::EditMessage(index) => {
+                let content = self
+                    .state
+                    .as_ref()
+                    .and_then(|state| state.conversation.messages.get(index))
+                    .map(|message| message.content.clone());
+                if let Some(content) = content {
+                    self.show_message_editor(index, content);
+                }
+                Ok(Some(Action::Render))
+            }
+            Action::ShareConversation => {
+                if let Some(state) = &self.state {
+                    self.show_share(share::markdown(&state.conversation));
+                }
+                Ok(Some(Action::Render))
+            }
+            Action::ShowApiKeyDialog => {
+                self.show_api_key();
+                Ok(Some(Action::Render))
+            }
+            Action::ShowCommitMessage(message) => {
+                self.show_commit(message);
+                Ok(Some(Action::Render))
+            }
+            Action::ShowStats => {
+                let history = self
+                    .state
+                    .as_ref()
+                    .map(|state| state.conversation.messages.as_slice())
+                    .unwrap_or_default();
+                self.show_stats(ConversationStats::from_history(history));
+                Ok(Some(Action::Render))
+            }
+            Action::ShowUsage => {
+                self.show_usage();
+                Ok(Some(Action::Render))
+            }
+            Action::ShowModelInfo => {
+                self.show_model_info();
+                Ok(Some(Action::Render))
+            }
             Action::HideDialog => {
                 self.hide();
                 // When dialog is hidden, input should regain focus
@@ -159,7 +405,11 @@ impl Component for Dialog {
 
         // Create a centered dialog area (larger for text editing)
         let dialog_width = area.width.min(80);
-        let dialog_height = area.height.min(30);
+        let dialog_height = if self.dialog_type == DialogType::ApiKey {
+            area.height.min(3)
+        } else {
+            area.height.min(30)
+        };
 
         let dialog_area = Rect {
             x: (area.width.saturating_sub(dialog_width)) / 2,
@@ -174,23 +424,65 @@ impl Component for Dialog {
 
         // Create the dialog block with appropriate title and instructions
         let (title, bottom_title) = match self.dialog_type {
-            DialogType::SystemPrompt => ("System Prompt Editor", " Ctrl+S: Save | Esc: Cancel"),
-            DialogType::Generic => ("Text Editor", "Ctrl+S: Submit | Esc: Cancel"),
+            DialogType::SystemPrompt => {
+                (t("System Prompt Editor"), t(" Ctrl+S: Save | Esc: Cancel"))
+            }
+            DialogType::Generic => (t("Text Editor"), t("Ctrl+S: Submit | Esc: Cancel")),
+            DialogType::EditMessage(_) => (t("Edit Message"), t("Ctrl+S: Save | Esc: Cancel")),
+            DialogType::Share => (
+                tf(
+                    "Share via {service}",
+                    &[("service", &self.config.share.service)],
+                )
+                .into(),
+                t("Ctrl+S: Upload exactly this | Esc: Cancel"),
+            ),
+            DialogType::ApiKey => (
+                tf(
+                    "{provider} API key ({status})",
+                    &[
+                        ("provider", &self.config.chat.provider.name()),
+                        ("status", &self.status.as_deref().unwrap_or_default()),
+                    ],
+                )
+                .into(),
+                t("Enter: check and save | Esc: Cancel"),
+            ),
+            DialogType::Commit => (
+                t("Commit Message (staged changes)"),
+                t("Ctrl+S: git commit | Esc: Cancel"),
+            ),
+            DialogType::Stats => (
+                t("Conversation Statistics"),
+                self.status.as_deref().map_or_else(
+                    || t("Ctrl+E: Export JSON | Esc: Close"),
+                    |status| status.to_string().into(),
+                ),
+            ),
+            DialogType::Usage => (t("Usage"), t("Esc: Close")),
+            DialogType::ModelInfo => (t("Model Info"), t("Esc: Close")),
         };
 
         // Set border color based on focus state
         let border_color = if self.is_focused {
-            Color::Blue
+            self.config.ui.theme.theme().focus
         } else {
             Color::Gray
         };
 
-        let block = Block::default()
+        let mut block = Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(border_color))
             .style(Style::default().bg(Color::Black))
             .title(title)
             .title_bottom(bottom_title);
+        // A key is a single masked line, there is nothing to number
+        if !self.dialog_type.is_read_only()
+            && self.dialog_type != DialogType::ApiKey
+            && let Some(position) = line_numbers(&mut self.textarea, &self.config)
+        {
+            block = block.title_bottom(Line::from(format!(" {position} ")).right_aligned());
+        }
 
         let inner_area = block.inner(dialog_area);
         frame.render_widget(block, dialog_area);
@@ -201,3 +493,73 @@ impl Component for Dialog {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::components::testing::{conversation, render};
+
+    #[test]
+    fn test_render_system_prompt() -> Result<()> {
+        let mut state = conversation();
+        state.conversation.system_prompt =
+            "You are a helpful assistant.\nAnswer briefly.".to_string();
+        let mut dialog = Dialog::new();
+        dialog.register_state_handler(state)?;
+        dialog.update(Action::ShowSystemPromptDialog)?;
+        assert_snapshot!("system_prompt_large", render(&mut dialog, 80, 20));
+        assert_snapshot!("system_prompt_small", render(&mut dialog, 40, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_message_editor() -> Result<()> {
+        let mut dialog = Dialog::new();
+        dialog.register_state_handler(conversation())?;
+        dialog.update(Action::EditMessage(0))?;
+        assert_snapshot!(render(&mut dialog, 60, 16));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_share() -> Result<()> {
+        let mut dialog = Dialog::new();
+        dialog.register_state_handler(conversation())?;
+        dialog.update(Action::ShareConversation)?;
+        assert_snapshot!(render(&mut dialog, 70, 16));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_commit() -> Result<()> {
+        let mut dialog = Dialog::new();
+        dialog.update(Action::ShowCommitMessage(
+            "feat(ui): add zen mode\n\nHides borders and titles.".to_string(),
+        ))?;
+        assert_snapshot!(render(&mut dialog, 60, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_key() -> Result<()> {
+        let mut dialog = Dialog::new();
+        dialog.update(Action::ShowApiKeyDialog)?;
+        dialog.textarea.insert_str("sk-or-1234");
+        let screen = render(&mut dialog, 60, 3).to_string();
+        assert!(!screen.contains("sk-or"), "{screen}");
+        assert_eq!(
+            dialog.save(),
+            Action::HideDialog,
+            "the dialog closes once the key is handed on"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_hidden() {
+        let mut dialog = Dialog::new();
+        assert_snapshot!(render(&mut dialog, 20, 3));
+    }
+}
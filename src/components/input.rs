@@ -1,18 +1,37 @@
 use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ratatui::{prelude::*, widgets::Block};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
 use std::any::Any;
 use tokio::sync::mpsc::UnboundedSender;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
-use super::Component;
-use crate::{action::Action, config::Config};
+use super::{Component, is_global_key, is_text_key, line_numbers, pasted_text, text_field};
+use crate::{
+    action::{Action, MenuItem},
+    app::{AppState, Mode},
+    commands,
+    config::Config,
+    core::{ChatMessage, attachments::Attachment, health::Health, length::ResponseLength, prose},
+    locale::{t, tf},
+    theme::Theme,
+    tokens,
+};
 
 pub struct Input {
     command_tx: Option<UnboundedSender<Action>>,
     config: Config,
+    state: Option<AppState>,
     textarea: TextArea<'static>,
     is_focused: bool,
+    resume_focus: bool, // Whether to take focus back once a popup menu closes
+    /// The draft as last counted and its characters, words and tokens, so that it is only
+    /// tokenized again when it changes.
+    counted: (String, [usize; 3]),
+    /// Whether clearing the long draft was asked for once and the next clear goes through.
+    clear_pending: bool,
 }
 
 impl Default for Input {
@@ -28,8 +47,12 @@ impl Input {
         Self {
             command_tx: None,
             config: Config::default(),
+            state: None,
             textarea,
             is_focused: true,
+            resume_focus: false,
+            counted: (String::new(), [0; 3]),
+            clear_pending: false,
         }
     }
 
@@ -42,15 +65,168 @@ impl Input {
     }
 
     pub fn clear(&mut self) {
-        self.textarea = TextArea::default();
+        self.textarea = text_field(&self.config);
+    }
+
+    /// Clear the draft as an edit, so that undo brings it back.
+    fn clear_draft(&mut self) {
+        self.textarea.select_all();
+        self.textarea.cut();
+    }
+
+    /// Clear the draft, or for a draft longer than `ui.confirm_clear_lines` ask to clear it again.
+    fn clear_input(&mut self) -> Action {
+        let lines = self.textarea.lines().len();
+        let limit = self.config.ui.confirm_clear_lines;
+        if limit > 0 && lines > limit && !self.clear_pending {
+            self.clear_pending = true;
+            return Action::Notify(format!(
+                "Clear all {lines} lines of the draft? Press again to clear, Ctrl+U undoes it"
+            ));
+        }
+        self.clear_draft();
+        Action::Render
+    }
+
+    /// Type pasted text into the draft, or for more than `ui.confirm_paste_chars` characters
+    /// ask whether to attach it collapsed instead, with its size and estimated tokens.
+    fn paste(&mut self, text: &str) -> Action {
+        let text = pasted_text(text);
+        let chars = text.chars().count();
+        let limit = self.config.ui.confirm_paste_chars;
+        if limit == 0 || chars <= limit {
+            self.textarea.insert_str(text);
+            return Action::Render;
+        }
+        let model = match &self.state {
+            Some(state) => state.conversation.model.as_str(),
+            None => self.config.chat.model.as_str(),
+        };
+        let title = format!(
+            "Paste {} lines, {} KB, ~{} tokens?",
+            text.lines().count(),
+            text.len().div_ceil(1024),
+            tokens::count_tokens(model, &text)
+        );
+        let items = vec![
+            MenuItem {
+                label: "Attach collapsed, sent in full with the next message".to_string(),
+                action: Action::Attach(Attachment::pasted(&text)),
+            },
+            MenuItem {
+                label: "Type into the draft".to_string(),
+                action: Action::RestoreInput(text),
+            },
+        ];
+        Action::ShowMenu(title, items)
+    }
+
+    /// Replace the abbreviation right before the cursor with its snippet from the config, as an
+    /// edit that can be undone; returns whether there was one.
+    fn expand_snippet(&mut self) -> bool {
+        let (row, col) = self.textarea.cursor();
+        let before: String = self.textarea.lines()[row].chars().take(col).collect();
+        let word = before
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default();
+        let Some(snippet) = self.config.snippets.get(word).cloned() else {
+            return false;
+        };
+        self.textarea.start_selection();
+        for _ in 0..word.chars().count() {
+            self.textarea.move_cursor(CursorMove::Back);
+        }
+        self.textarea.insert_str(snippet);
+        true
+    }
+
+    /// Ask for the fixes of the draft, unless there is nothing to fix.
+    fn polish_draft(&self) -> Action {
+        let draft = self.get_text();
+        if draft.trim().is_empty() {
+            Action::Notify("Nothing to polish yet".to_string())
+        } else {
+            Action::PolishText(draft)
+        }
+    }
+
+    /// Replace the draft with its polished `text` as an edit, so that undo brings it back, or
+    /// show the text if the draft changed in the meantime.
+    fn replace_draft(&mut self, draft: &str, text: &str) -> Action {
+        if self.get_text() != draft {
+            return Action::ShowDialog(format!(
+                "The draft changed while it was polished, the polished text:\n\n{text}"
+            ));
+        }
+        self.textarea.select_all();
+        self.textarea.insert_str(text);
+        Action::Notify("Polished the draft, Ctrl+U twice brings the original back".to_string())
+    }
+
+    /// Prepend a quote of a chat message to the draft, leaving the cursor after it.
+    fn quote(&mut self, message: &ChatMessage, number: usize) {
+        let mut lines = vec![format!("> {} (message {number}) wrote:", message.role)];
+        lines.extend(message.content.lines().map(|line| {
+            if line.is_empty() {
+                ">".to_string()
+            } else {
+                format!("> {line}")
+            }
+        }));
+        lines.push(String::new());
+
+        // Inserted as an edit, so that it can be undone
+        self.textarea.move_cursor(CursorMove::Jump(0, 0));
+        self.textarea.insert_str(lines.join("\n") + "\n");
+    }
+
+    /// Characters, words and estimated tokens of the draft for the conversation's model, or
+    /// `None` while it is empty.
+    fn counter(&mut self) -> Option<String> {
+        let text = self.get_text();
+        if text.trim().is_empty() {
+            return None;
+        }
+        if text != self.counted.0 {
+            let model = match &self.state {
+                Some(state) => state.conversation.model.as_str(),
+                None => self.config.chat.model.as_str(),
+            };
+            let counts = [
+                text.chars().count(),
+                text.split_whitespace().count(),
+                tokens::count_tokens(model, &text),
+            ];
+            self.counted = (text, counts);
+        }
+        let [chars, words, tokens] = self.counted.1;
+        Some(format!("{chars} chars · {words} words · ~{tokens} tokens"))
+    }
+
+    /// The draft rendered as Markdown, warning about a code block that is never closed.
+    fn draw_preview(&self, frame: &mut Frame, area: Rect) {
+        let theme = self.config.ui.theme.theme();
+        let (lines, open_fence) = markdown_lines(&self.get_text());
+        let mut block = Block::bordered()
+            .title(t("Preview (Alt+P: hide)"))
+            .border_style(Style::default().fg(theme.muted));
+        if open_fence {
+            block = block.title_bottom(
+                Line::from(t("unclosed ``` code block")).style(Style::default().fg(theme.warning)),
+            );
+        }
+        let preview = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(preview, area);
     }
 
-    #[allow(dead_code)]
     fn submit(&mut self) -> Option<Action> {
         let text = self.get_text();
         if !text.trim().is_empty() {
             self.clear();
-            Some(Action::SendMessage(text))
+            Some(commands::parse(&text).unwrap_or(Action::SendMessage(text)))
         } else {
             None
         }
@@ -68,42 +244,69 @@ impl Component for Input {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        // Setting the depth drops the undo history, so only when it changes
+        if self.textarea.max_histories() != config.ui.undo_history {
+            self.textarea.set_max_histories(config.ui.undo_history);
+        }
         self.config = config;
         Ok(())
     }
 
+    fn register_state_handler(&mut self, state: AppState) -> Result<()> {
+        self.state = Some(state);
+        Ok(())
+    }
+
     fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
         if !self.is_focused {
             return Ok(None);
         }
 
-        match key.code {
-            KeyCode::Enter => {
-                let text = self.get_text();
-                if !text.trim().is_empty() {
-                    self.clear();
-                    Ok(Some(Action::SendMessage(text)))
-                } else {
-                    Ok(None)
+        let action = self.config.keybindings.action(Mode::Input, key);
+        // Any other key takes back the request to clear a long draft
+        self.clear_pending &= matches!(action, Some(Action::ClearInput | Action::Cancel));
+        // Tab only moves the focus if there is no abbreviation to expand, space is typed anyway
+        if key.modifiers == KeyModifiers::NONE {
+            match key.code {
+                KeyCode::Tab if self.expand_snippet() => return Ok(Some(Action::Render)),
+                KeyCode::Char(' ') => {
+                    self.expand_snippet();
                 }
+                _ => {}
             }
-            KeyCode::Esc => {
-                // Clear input on Escape
-                self.clear();
-                Ok(None)
-            }
-            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
-                // Ctrl+C to quit
-                Ok(Some(Action::Quit))
-            }
-            _ => {
-                {
-                    // Let tui-textarea handle all other key events
-                    self.textarea.input(key);
-                    Ok(None)
+        }
+        if !is_text_key(key) {
+            match action {
+                Some(Action::Confirm) => return Ok(self.submit().or(Some(Action::Render))),
+                // Cancel still clears for configs written before ClearInput
+                Some(Action::ClearInput | Action::Cancel) => {
+                    return Ok(Some(self.clear_input()));
+                }
+                Some(Action::PolishDraft) => return Ok(Some(self.polish_draft())),
+                Some(Action::Undo) => {
+                    self.textarea.undo();
+                    return Ok(Some(Action::Render));
                 }
+                Some(Action::Redo) => {
+                    self.textarea.redo();
+                    return Ok(Some(Action::Render));
+                }
+                Some(action) => return Ok(Some(action)),
+                None if is_global_key(&self.config, key) => return Ok(None),
+                None => {}
             }
         }
+        // Let tui-textarea handle all other key events
+        self.textarea.input(key);
+        Ok(Some(Action::Render))
+    }
+
+    fn handle_paste_event(&mut self, text: String) -> Result<Option<Action>> {
+        if !self.is_focused {
+            return Ok(None);
+        }
+        self.clear_pending = false;
+        Ok(Some(self.paste(&text)))
     }
 
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
@@ -112,7 +315,55 @@ impl Component for Input {
                 self.set_focus(true);
                 return Ok(Some(Action::Render));
             }
-            Action::ShowDialog(_) | Action::ShowSystemPromptDialog => {
+            Action::DraftPolished(draft, reply) => {
+                let text = prose::text(&reply.content, &draft);
+                return Ok(Some(self.replace_draft(&draft, &text)));
+            }
+            Action::TranscriptReceived(text) | Action::RestoreInput(text) => {
+                self.textarea.insert_str(text);
+                return Ok(Some(Action::Render));
+            }
+            Action::QuoteMessage(index) => {
+                let message = self
+                    .state
+                    .as_ref()
+                    .and_then(|state| state.conversation.messages.get(index))
+                    .cloned();
+                if let Some(message) = message {
+                    self.quote(&message, index + 1);
+                }
+                return Ok(Some(Action::FocusInput));
+            }
+            Action::ShowMenu(..)
+            | Action::ShowSessionFinder
+            | Action::ShowModelPicker
+            | Action::AskOtherModel(_)
+            | Action::ShowToolApproval(_)
+            | Action::ShowTemplateForm(..)
+            | Action::ShowVersionDiff(_) => {
+                self.resume_focus = self.is_focused;
+                self.set_focus(false);
+            }
+            Action::HideMenu
+            | Action::HideSessionFinder
+            | Action::HideModelPicker
+            | Action::HideToolApproval
+            | Action::HideTemplateForm
+            | Action::HideVersionDiff => {
+                self.set_focus(self.resume_focus);
+                self.resume_focus = false;
+            }
+            Action::FocusChat
+            | Action::ShowDialog(_)
+            | Action::ShowSystemPromptDialog
+            | Action::ShowStats
+            | Action::ShareConversation
+            | Action::ShowApiKeyDialog
+            | Action::ShowCommitMessage(_)
+            | Action::ShowUsage
+            | Action::ShowModelInfo
+            | Action::EditMessage(_)
+            | Action::FocusSidebar => {
                 // When dialog is shown, input should lose focus
                 self.set_focus(false);
                 return Ok(Some(Action::Render));
@@ -128,18 +379,136 @@ impl Component for Input {
         Ok(None)
     }
 
-    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+    fn draw(&mut self, frame: &mut Frame, mut area: Rect) -> Result<()> {
+        if self.state.as_ref().is_some_and(|state| state.draft_preview) {
+            let [editor, preview] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(area);
+            area = editor;
+            self.draw_preview(frame, preview);
+        }
+
         // Set border color based on focus state
+        let theme = self.config.ui.theme.theme();
         let border_color = if self.is_focused {
-            Color::Blue
+            theme.focus
         } else {
             Color::Gray
         };
 
-        let block = Block::bordered()
-            .title("Input")
-            .title_bottom("Esc: clear | Ctrl+C: quit | Use arrow keys, Page Up/Down to navigate")
+        let title = match &self.state {
+            Some(state) if state.is_recording => tf(
+                "Input {icon} Recording (Ctrl+T to stop)",
+                &[("icon", &self.config.ui.icons().recording)],
+            ),
+            Some(state) if state.is_transcribing => t("Input (transcribing...)").into(),
+            Some(state) if state.input_expanded => t("Input (F11: collapse)").into(),
+            Some(state) if !state.conversation.attachments.is_empty() => {
+                let labels: Vec<&str> = state
+                    .conversation
+                    .attachments
+                    .iter()
+                    .map(|attachment| attachment.label.as_str())
+                    .collect();
+                tf(
+                    "Input + {attachments} (/detach to drop)",
+                    &[("attachments", &labels.join(", "))],
+                )
+            }
+            _ => t("Input").into(),
+        };
+
+        let mut counter = self.counter();
+        let position = line_numbers(&mut self.textarea, &self.config);
+        let hint = match self
+            .state
+            .as_ref()
+            .and_then(|state| state.notice.as_deref())
+        {
+            Some(notice) => Line::from(notice).style(Style::default().fg(theme.warning)),
+            // The keys are for getting started, once there is a draft its size matters more
+            None => match counter.take() {
+                Some(counter) => Line::from(counter).style(Style::default().fg(theme.muted)),
+                None => Line::from(t("Esc: clear | Tab: select messages | Ctrl+C: quit")),
+            },
+        };
+        let mut block = Block::bordered()
+            .title(title)
+            .title_bottom(hint)
             .border_style(Style::default().fg(border_color));
+        if let Some(text) = self
+            .state
+            .as_ref()
+            .and_then(|state| state.clipboard_offer.as_deref())
+        {
+            block = block.title(
+                Line::from(format!(
+                    " Copied {} Alt+Y attach | Alt+N dismiss ",
+                    clipboard_preview(text)
+                ))
+                .style(Style::default().fg(Color::Black).bg(theme.success))
+                .right_aligned(),
+            );
+        }
+        if let Some(position) = position {
+            block = block.title_bottom(Line::from(format!(" {position}")).right_aligned());
+        }
+        if let Some(counter) = counter {
+            block = block.title_bottom(
+                Line::from(format!(" {counter}"))
+                    .style(Style::default().fg(theme.muted))
+                    .right_aligned(),
+            );
+        }
+        if let Some(state) = &self.state
+            && state.conversation.agent
+        {
+            block = block.title_bottom(
+                Line::from(" agent (Alt+A)")
+                    .style(Style::default().fg(theme.accent))
+                    .right_aligned(),
+            );
+        }
+        if let Some(state) = &self.state
+            && state.conversation.length != ResponseLength::Normal
+        {
+            block = block.title_bottom(
+                Line::from(format!(" {} (Alt+L)", state.conversation.length))
+                    .style(Style::default().fg(Color::Cyan))
+                    .right_aligned(),
+            );
+        }
+        if let Some(state) = &self.state
+            && let Some(translation) = &state.conversation.translation
+        {
+            block = block.title_bottom(
+                Line::from(format!(" {}", translation.label()))
+                    .style(Style::default().fg(theme.accent))
+                    .right_aligned(),
+            );
+        }
+        if let Some(state) = &self.state
+            && let Some(color) = health_color(state.health, &theme)
+        {
+            block = block.title_bottom(
+                Line::from(format!(
+                    " {} {}",
+                    self.config.ui.icons().health,
+                    state.health
+                ))
+                .style(Style::default().fg(color))
+                .right_aligned(),
+            );
+        }
+
+        if self.state.as_ref().is_some_and(|state| state.zen_mode) {
+            // Just a line between the transcript and the text
+            block = Block::new()
+                .borders(Borders::TOP)
+                .border_style(Style::default().fg(if self.is_focused {
+                    theme.focus
+                } else {
+                    theme.muted
+                }));
+        }
 
         let inner_area = block.inner(area);
         frame.render_widget(block, area);
@@ -147,3 +516,322 @@ impl Component for Input {
         Ok(())
     }
 }
+
+/// Styled lines of Markdown `text`: headings, lists, quotes, code blocks and inline code, and
+/// whether a code block is left open at the end.
+fn markdown_lines(text: &str) -> (Vec<Line<'static>>, bool) {
+    let code = Style::default().fg(Color::Yellow);
+    let mut in_code = false;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            lines.push(Line::from(line.to_string()).style(Style::default().fg(Color::DarkGray)));
+        } else if in_code {
+            lines.push(Line::from(line.to_string()).style(code));
+        } else if let Some(heading) = trimmed
+            .strip_prefix('#')
+            .map(|rest| rest.trim_start_matches('#'))
+            .and_then(|rest| rest.strip_prefix(' '))
+        {
+            lines.push(
+                Line::from(heading.to_string()).style(
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            );
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            lines.push(
+                Line::from(format!("{indent}│ {}", quote.trim_start())).style(
+                    Style::default()
+                        .fg(Color::Gray)
+                        .add_modifier(Modifier::ITALIC),
+                ),
+            );
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|bullet| trimmed.strip_prefix(bullet))
+        {
+            let mut spans = vec![Span::raw(format!("{indent}• "))];
+            spans.extend(inline_code(item, code));
+            lines.push(Line::from(spans));
+        } else {
+            let mut spans = vec![Span::raw(indent.to_string())];
+            spans.extend(inline_code(trimmed, code));
+            lines.push(Line::from(spans));
+        }
+    }
+    (lines, in_code)
+}
+
+/// `text` with its `code` spans styled; an unmatched backtick is left as it is.
+fn inline_code(text: &str, style: Style) -> Vec<Span<'static>> {
+    let parts: Vec<&str> = text.split('`').collect();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| match index % 2 {
+            1 if index + 1 < parts.len() => Span::styled(part.to_string(), style),
+            1 => Span::raw(format!("`{part}")),
+            _ => Span::raw(part.to_string()),
+        })
+        .collect()
+}
+
+/// The first line of copied text, shortened, and how many lines there are.
+fn clipboard_preview(text: &str) -> String {
+    const WIDTH: usize = 16;
+    let text = text.trim();
+    let first = text.lines().next().unwrap_or_default().trim();
+    let mut preview: String = first.chars().take(WIDTH).collect();
+    if first.chars().count() > WIDTH {
+        preview.push('…');
+    }
+    match text.lines().count() {
+        1 => format!("\"{preview}\""),
+        lines => format!("\"{preview}\" ({lines} lines)"),
+    }
+}
+
+/// Color of the connection indicator, or `None` to hide it until the first check.
+fn health_color(health: Health, theme: &Theme) -> Option<Color> {
+    match health {
+        Health::Unknown => None,
+        Health::Online => Some(theme.success),
+        Health::Degraded => Some(theme.warning),
+        Health::Offline => Some(theme.error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        components::testing::{render, state},
+        core::{attachments::Attachment, translate::Translation},
+    };
+
+    #[test]
+    fn test_render_empty() {
+        let mut input = Input::new();
+        assert_snapshot!(render(&mut input, 60, 5));
+    }
+
+    #[test]
+    fn test_render_quote_and_notice() -> Result<()> {
+        let mut state = state(&[("AI", "First line\n\nThird line")]);
+        state.notice = Some("Log filter set to `debug`".to_string());
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        input.update(Action::QuoteMessage(0))?;
+        input.textarea.insert_str("Why?");
+        assert_snapshot!(render(&mut input, 60, 8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_clear() -> Result<()> {
+        let mut input = Input::new();
+        input.register_state_handler(state(&[("AI", "Answer")]))?;
+        input.textarea.insert_str("Why?");
+        input.update(Action::QuoteMessage(0))?;
+        assert!(matches!(input.clear_input(), Action::Notify(_)));
+        assert_eq!(input.textarea.lines().len(), 4);
+        assert_eq!(input.clear_input(), Action::Render);
+        assert_eq!(input.textarea.lines(), [""]);
+        input.textarea.undo();
+        assert_eq!(
+            input.textarea.lines(),
+            ["> AI (message 1) wrote:", "> Answer", "", "Why?"]
+        );
+        input.textarea.undo();
+        assert_eq!(input.textarea.lines(), ["Why?"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_polish_draft() -> Result<()> {
+        let mut input = Input::new();
+        assert!(matches!(input.polish_draft(), Action::Notify(_)));
+        input.textarea.insert_str("this are\nfixd");
+        let draft = "this are\nfixd".to_string();
+        assert_eq!(input.polish_draft(), Action::PolishText(draft.clone()));
+        let reply = Box::new(ChatMessage::new("AI", "```\nThis is\nfixed.\n```"));
+        input.update(Action::DraftPolished(draft.clone(), reply.clone()))?;
+        assert_eq!(input.textarea.lines(), ["This is", "fixed."]);
+        input.textarea.undo();
+        input.textarea.undo();
+        assert_eq!(input.textarea.lines(), ["this are", "fixd"]);
+
+        // Typing on while waiting keeps what was typed
+        input.textarea.insert_str("!");
+        assert!(matches!(
+            input.update(Action::DraftPolished(draft, reply))?,
+            Some(Action::ShowDialog(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_paste() -> Result<()> {
+        let mut input = Input::new();
+        input.config.ui.confirm_paste_chars = 10;
+        input.handle_paste_event("short\r\ntext".to_string())?;
+        assert_eq!(input.textarea.lines(), ["short", "text"]);
+
+        let Some(Action::ShowMenu(title, items)) =
+            input.handle_paste_event("a much longer paste".to_string())?
+        else {
+            panic!("expected a confirmation");
+        };
+        assert!(title.starts_with("Paste 1 lines, 1 KB, ~"), "{title}");
+        assert_eq!(
+            items[0].action,
+            Action::Attach(Attachment::pasted("a much longer paste"))
+        );
+        assert_eq!(
+            items[1].action,
+            Action::RestoreInput("a much longer paste".to_string())
+        );
+        assert_eq!(input.textarea.lines(), ["short", "text"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_health() -> Result<()> {
+        let mut state = state(&[]);
+        state.health = Health::Degraded;
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        assert_snapshot!(render(&mut input, 60, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_response_length() -> Result<()> {
+        let mut state = state(&[]);
+        state.health = Health::Online;
+        state.conversation.length = ResponseLength::Brief;
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        assert_snapshot!(render(&mut input, 80, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_translation() -> Result<()> {
+        let mut state = state(&[]);
+        state.conversation.translation = Translation::parse("en -> de");
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        assert_snapshot!(render(&mut input, 60, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_expanded() -> Result<()> {
+        let mut state = state(&[]);
+        state.input_expanded = true;
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        input.textarea.insert_str("A long prompt");
+        assert_snapshot!(render(&mut input, 60, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_recording() -> Result<()> {
+        let mut state = state(&[]);
+        state.is_recording = true;
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        assert_snapshot!(render(&mut input, 60, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_attachments() -> Result<()> {
+        let mut state = state(&[]);
+        state.conversation.attachments.push(Attachment::new(
+            "git diff HEAD",
+            "diff",
+            "+new\n",
+            1000,
+        ));
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        assert_snapshot!(render(&mut input, 60, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_clipboard_offer() -> Result<()> {
+        let mut state = state(&[]);
+        state.clipboard_offer = Some(
+            "error[E0382]: borrow of moved value: `config`\n --> src/main.rs:4:5\n".to_string(),
+        );
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        assert_snapshot!(render(&mut input, 80, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_snippet() -> Result<()> {
+        let mut config = Config::default();
+        config
+            .snippets
+            .insert(";sig".to_string(), "Best,\nAda".to_string());
+        let mut input = Input::new();
+        input.register_config_handler(config)?;
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        input.textarea.insert_str("Thanks ;sig");
+        input.handle_key_event(key(KeyCode::Tab))?;
+        assert_eq!(input.textarea.lines(), ["Thanks Best,", "Ada"]);
+        input.textarea.insert_str(" x;sig");
+        input.handle_key_event(key(KeyCode::Char(' ')))?;
+        input.textarea.insert_str(";sig");
+        input.handle_key_event(key(KeyCode::Char(' ')))?;
+        assert_eq!(
+            input.textarea.lines(),
+            ["Thanks Best,", "Ada x;sig Best,", "Ada "]
+        );
+        input.textarea.undo();
+        input.textarea.undo();
+        input.textarea.undo();
+        assert_eq!(input.textarea.lines(), ["Thanks Best,", "Ada x;sig ;sig"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_line_numbers() -> Result<()> {
+        let mut config = Config::default();
+        config.ui.line_numbers = true;
+        let mut input = Input::new();
+        input.register_config_handler(config)?;
+        input.register_state_handler(state(&[]))?;
+        input.textarea.insert_str("fn main() {\n    todo!()");
+        assert_snapshot!(render(&mut input, 60, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_draft_preview() -> Result<()> {
+        let mut state = state(&[]);
+        state.draft_preview = true;
+        let mut input = Input::new();
+        input.register_state_handler(state)?;
+        input.textarea.insert_str(
+            "# Plan\n- run `cargo test`\n  * then ship\n> quoted\n```rust\nfn main() {}",
+        );
+        assert_snapshot!(render(&mut input, 80, 8));
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use color_eyre::Result;
+use crossterm::event::KeyEvent;
+use ratatui::{prelude::*, widgets::*};
+use std::any::Any;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::Component;
+use crate::{
+    action::{Action, MenuItem},
+    app::Mode,
+    config::Config,
+    locale::t,
+};
+
+/// A modal popup listing items to pick from; choosing an item dispatches its action.
+#[derive(Default)]
+pub struct Menu {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    title: String,
+    items: Vec<MenuItem>,
+    list_state: ListState,
+    is_visible: bool,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn show(&mut self, title: String, items: Vec<MenuItem>) {
+        self.title = title;
+        self.items = items;
+        self.list_state = ListState::default();
+        if !self.items.is_empty() {
+            self.list_state.select_first();
+        }
+        self.is_visible = true;
+    }
+
+    pub fn hide(&mut self) {
+        self.is_visible = false;
+        self.items.clear();
+    }
+}
+
+impl Component for Menu {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if !self.is_visible {
+            return Ok(None);
+        }
+
+        match self.config.keybindings.action(Mode::Menu, key) {
+            Some(Action::Cancel) => Ok(Some(Action::HideMenu)),
+            Some(Action::SelectPrevious) => {
+                self.list_state.select_previous();
+                Ok(Some(Action::Render))
+            }
+            Some(Action::SelectNext) => {
+                self.list_state.select_next();
+                Ok(Some(Action::Render))
+            }
+            Some(Action::SelectFirst) => {
+                self.list_state.select_first();
+                Ok(Some(Action::Render))
+            }
+            Some(Action::SelectLast) => {
+                self.list_state.select_last();
+                Ok(Some(Action::Render))
+            }
+            Some(Action::Confirm) => {
+                let Some(item) = self
+                    .list_state
+                    .selected()
+                    .and_then(|index| self.items.get(index))
+                else {
+                    return Ok(Some(Action::HideMenu));
+                };
+                let action = item.action.clone();
+                // Close the menu before the chosen action runs so focus is restored first
+                if action != Action::HideMenu
+                    && let Some(tx) = &self.command_tx
+                {
+                    tx.send(Action::HideMenu)?;
+                }
+                Ok(Some(action))
+            }
+            // The menu is modal, swallow everything else
+            _ => Ok(Some(Action::Render)),
+        }
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::ShowMenu(title, items) => {
+                self.show(title, items);
+                Ok(Some(Action::Render))
+            }
+            Action::HideMenu => {
+                self.hide();
+                Ok(Some(Action::Render))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if !self.is_visible {
+            return Ok(());
+        }
+
+        let menu_width = area.width.min(60);
+        let menu_height = area.height.min(self.items.len().max(1) as u16 + 2);
+
+        let menu_area = Rect {
+            x: (area.width.saturating_sub(menu_width)) / 2,
+            y: (area.height.saturating_sub(menu_height)) / 2,
+            width: menu_width,
+            height: menu_height,
+        };
+
+        frame.render_widget(Clear, menu_area);
+
+        let block = Block::bordered()
+            .border_style(Style::default().fg(Color::Blue))
+            .style(Style::default().bg(Color::Black))
+            .title(self.title.as_str())
+            .title_bottom(t("Enter: select | Esc: close"));
+
+        if self.items.is_empty() {
+            frame.render_widget(
+                Paragraph::new("(empty)")
+                    .style(Style::default().fg(Color::Gray))
+                    .block(block),
+                menu_area,
+            );
+            return Ok(());
+        }
+
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .map(|item| ListItem::new(item.label.as_str()))
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+
+        frame.render_stateful_widget(list, menu_area, &mut self.list_state);
+        Ok(())
+    }
+}
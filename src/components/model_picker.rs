@@ -0,0 +1,364 @@
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use std::any::Any;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Component, is_text_key, pasted_line, session_finder::fuzzy_score};
+use crate::{
+    action::Action,
+    app::{AppState, Mode},
+    config::Config,
+    core::models::ModelHistory,
+};
+
+/// Why a model is listed where it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Favorite,
+    Recent,
+    All,
+}
+
+/// A model that matched the current query.
+struct Entry {
+    model: String,
+    section: Section,
+}
+
+/// A modal popup to switch models: favorites, then recently used ones, then every model the
+/// provider offers.
+#[derive(Default)]
+pub struct ModelPicker {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    history: ModelHistory,
+    current: String,
+    /// Fetched from the provider the first time the picker opens.
+    models: Option<Vec<String>>,
+    query: String,
+    entries: Vec<Entry>,
+    list_state: ListState,
+    is_visible: bool,
+    /// The reply whose question the picked model is asked, instead of switching to it.
+    ask_for: Option<usize>,
+}
+
+impl ModelPicker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn show(&mut self) {
+        self.query.clear();
+        self.search();
+        self.is_visible = true;
+    }
+
+    fn search(&mut self) {
+        self.entries = pick(
+            &self.history,
+            self.models.as_deref().unwrap_or_default(),
+            &self.query.trim().to_lowercase(),
+        );
+        self.list_state = ListState::default();
+        if !self.entries.is_empty() {
+            self.list_state.select_first();
+        }
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.list_state
+            .selected()
+            .and_then(|index| self.entries.get(index))
+    }
+}
+
+/// List favorites, recent models and the rest, each once, keeping those that match `query`.
+///
+/// Without a query the sections keep their own order; with one, the full list is ranked by score.
+fn pick(history: &ModelHistory, models: &[String], query: &str) -> Vec<Entry> {
+    let matches =
+        |model: &str| query.is_empty() || fuzzy_score(query, &model.to_lowercase()).is_some();
+    let mut entries: Vec<Entry> = Vec::new();
+    let sections = [
+        (Section::Favorite, &history.favorites),
+        (Section::Recent, &history.recent),
+    ];
+    for (section, list) in sections {
+        for model in list {
+            if matches(model) && !entries.iter().any(|entry| &entry.model == model) {
+                entries.push(Entry {
+                    model: model.clone(),
+                    section,
+                });
+            }
+        }
+    }
+    let mut rest: Vec<(i64, &String)> = models
+        .iter()
+        .filter(|model| !entries.iter().any(|entry| &entry.model == *model))
+        .filter_map(|model| Some((fuzzy_score(query, &model.to_lowercase())?, model)))
+        .collect();
+    // Stable sort keeps the alphabetical order among equal scores
+    rest.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    entries.extend(rest.into_iter().map(|(_, model)| Entry {
+        model: model.clone(),
+        section: Section::All,
+    }));
+    entries
+}
+
+impl Component for ModelPicker {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn register_state_handler(&mut self, state: AppState) -> Result<()> {
+        self.current = state.conversation.model;
+        if state.model_history != self.history {
+            self.history = state.model_history;
+            if self.is_visible {
+                let selected = self.selected().map(|entry| entry.model.clone());
+                self.search();
+                if let Some(index) = selected
+                    .and_then(|model| self.entries.iter().position(|entry| entry.model == model))
+                {
+                    self.list_state.select(Some(index));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_paste_event(&mut self, text: String) -> Result<Option<Action>> {
+        if !self.is_visible {
+            return Ok(None);
+        }
+        self.query.push_str(&pasted_line(&text));
+        self.search();
+        Ok(Some(Action::Render))
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if !self.is_visible {
+            return Ok(None);
+        }
+
+        if let KeyCode::Char(c) = key.code
+            && is_text_key(key)
+        {
+            self.query.push(c);
+            self.search();
+            return Ok(Some(Action::Render));
+        }
+        match self.config.keybindings.action(Mode::Menu, key) {
+            Some(Action::Cancel) => return Ok(Some(Action::HideModelPicker)),
+            Some(Action::SelectPrevious) => self.list_state.select_previous(),
+            Some(Action::SelectNext) => self.list_state.select_next(),
+            Some(Action::SelectFirst) => self.list_state.select_first(),
+            Some(Action::SelectLast) => self.list_state.select_last(),
+            Some(Action::ToggleFavoriteSelected) => {
+                if let Some(entry) = self.selected() {
+                    return Ok(Some(Action::ToggleFavorite(entry.model.clone())));
+                }
+            }
+            Some(Action::Confirm) => {
+                // Typing an id that isn't listed still switches to it
+                let model = match self.selected() {
+                    Some(entry) => entry.model.clone(),
+                    None if !self.query.trim().is_empty() => self.query.trim().to_string(),
+                    None => return Ok(Some(Action::Render)),
+                };
+                // Close first so focus is restored before the model changes
+                if let Some(tx) = &self.command_tx {
+                    tx.send(Action::HideModelPicker)?;
+                }
+                return Ok(Some(match self.ask_for {
+                    Some(index) => Action::AskModel(index, model),
+                    None => Action::SetModel(model),
+                }));
+            }
+            _ if key.code == KeyCode::Backspace => {
+                self.query.pop();
+                self.search();
+            }
+            _ => {}
+        }
+        // The picker is modal, swallow everything else
+        Ok(Some(Action::Render))
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::ShowModelPicker => {
+                self.ask_for = None;
+                self.show();
+                Ok(Some(Action::Render))
+            }
+            Action::AskOtherModel(index) => {
+                self.ask_for = Some(index);
+                self.show();
+                Ok(Some(Action::Render))
+            }
+            Action::HideModelPicker => {
+                self.is_visible = false;
+                Ok(Some(Action::Render))
+            }
+            Action::ModelsLoaded(models) => {
+                self.models = Some(models);
+                if self.is_visible {
+                    self.search();
+                }
+                Ok(Some(Action::Render))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if !self.is_visible {
+            return Ok(());
+        }
+
+        let width = area.width.min(70);
+        let height = area.height.min(20);
+        let popup_area = Rect {
+            x: (area.width.saturating_sub(width)) / 2,
+            y: (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup_area);
+
+        let (title, hint) = match self.ask_for {
+            Some(_) => (
+                "Ask another model",
+                "Enter: ask | Ctrl+F: star | Esc: close",
+            ),
+            None => ("Models", "Enter: use | Ctrl+F: star | Esc: close"),
+        };
+        let title = match &self.models {
+            Some(_) => format!("{title} ({})", self.entries.len()),
+            None => format!("{title} (loading…)"),
+        };
+        let block = Block::bordered()
+            .border_style(Style::default().fg(Color::Blue))
+            .style(Style::default().bg(Color::Black))
+            .title(title)
+            .title_bottom(hint);
+        let inner = block.inner(popup_area);
+        frame.render_widget(block, popup_area);
+
+        let [query_area, list_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        frame.render_widget(
+            Paragraph::new(format!("> {}▏", self.query)).style(Style::default().fg(Color::Yellow)),
+            query_area,
+        );
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let star = if self.history.is_favorite(&entry.model) {
+                    Span::styled("★ ", Style::default().fg(Color::Yellow))
+                } else {
+                    Span::raw("  ")
+                };
+                let mut spans = vec![star, Span::raw(entry.model.clone())];
+                if entry.model == self.current {
+                    spans.push(Span::styled(
+                        " (current)",
+                        Style::default().fg(Color::Green),
+                    ));
+                } else if entry.section == Section::Recent {
+                    spans.push(Span::styled(
+                        " (recent)",
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::components::testing::{conversation, render};
+
+    fn models(entries: &[Entry]) -> Vec<(&str, Section)> {
+        entries
+            .iter()
+            .map(|entry| (entry.model.as_str(), entry.section))
+            .collect()
+    }
+
+    #[test]
+    fn test_pick_lists_favorites_and_recents_first() {
+        let history = ModelHistory {
+            favorites: vec!["openai/gpt-4o".to_string()],
+            recent: vec![
+                "mistralai/mistral-nemo".to_string(),
+                "openai/gpt-4o".to_string(),
+            ],
+        };
+        let all = [
+            "anthropic/claude-3.5-sonnet",
+            "mistralai/mistral-nemo",
+            "openai/gpt-4o",
+        ]
+        .map(str::to_string);
+
+        assert_eq!(
+            models(&pick(&history, &all, "")),
+            vec![
+                ("openai/gpt-4o", Section::Favorite),
+                ("mistralai/mistral-nemo", Section::Recent),
+                ("anthropic/claude-3.5-sonnet", Section::All),
+            ]
+        );
+        assert_eq!(
+            models(&pick(&history, &all, "claude")),
+            vec![("anthropic/claude-3.5-sonnet", Section::All)]
+        );
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut picker = ModelPicker::new();
+        let mut state = conversation();
+        state.model_history.favorites = vec!["anthropic/claude-3.5-sonnet".to_string()];
+        state.model_history.recent = vec!["openai/gpt-4o".to_string()];
+        picker.register_state_handler(state).unwrap();
+        picker.update(Action::ShowModelPicker).unwrap();
+        picker
+            .update(Action::ModelsLoaded(vec![
+                "anthropic/claude-3.5-sonnet".to_string(),
+                "google/gemini-flash-1.5".to_string(),
+                "openai/gpt-4o".to_string(),
+            ]))
+            .unwrap();
+        assert_snapshot!(render(&mut picker, 60, 10));
+    }
+}
@@ -0,0 +1,289 @@
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use std::any::Any;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::error;
+
+use super::{Component, is_text_key, pasted_line};
+use crate::{
+    action::Action,
+    app::Mode,
+    config::Config,
+    core::session::{Session, SessionStore},
+    locale::{t, tf},
+};
+
+/// Titles are what people remember, so a title match outranks a match in the messages.
+const TITLE_BONUS: i64 = 1000;
+
+/// A session that matched the current query.
+struct Hit {
+    id: String,
+    title: String,
+    archived: bool,
+    /// The matching line of the description or a message when the query matched content rather
+    /// than the title.
+    snippet: Option<String>,
+    score: i64,
+}
+
+/// A modal popup that fuzzy-searches every saved session, archived ones included.
+pub struct SessionFinder {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    store: SessionStore,
+    sessions: Vec<Session>,
+    query: String,
+    hits: Vec<Hit>,
+    list_state: ListState,
+    is_visible: bool,
+}
+
+impl SessionFinder {
+    pub fn new(store: SessionStore) -> Self {
+        Self {
+            command_tx: None,
+            config: Config::default(),
+            store,
+            sessions: Vec::new(),
+            query: String::new(),
+            hits: Vec::new(),
+            list_state: ListState::default(),
+            is_visible: false,
+        }
+    }
+
+    fn show(&mut self) {
+        self.sessions = self.store.load_all().unwrap_or_else(|err| {
+            error!("Failed to load sessions: {err}");
+            Vec::new()
+        });
+        self.query.clear();
+        self.search();
+        self.is_visible = true;
+    }
+
+    fn hide(&mut self) {
+        self.is_visible = false;
+        self.sessions.clear();
+        self.hits.clear();
+    }
+
+    fn search(&mut self) {
+        let query = self.query.trim().to_lowercase();
+        self.hits = self
+            .sessions
+            .iter()
+            .filter_map(|session| match_session(session, &query))
+            .collect();
+        // Stable sort keeps the most recently updated session first among equal scores
+        self.hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+        self.list_state = ListState::default();
+        if !self.hits.is_empty() {
+            self.list_state.select_first();
+        }
+    }
+}
+
+fn match_session(session: &Session, query: &str) -> Option<Hit> {
+    let hit = |score, snippet| Hit {
+        id: session.id.clone(),
+        title: session.title.clone(),
+        archived: session.archived,
+        snippet,
+        score,
+    };
+    if query.is_empty() {
+        return Some(hit(0, None));
+    }
+    if let Some(score) = fuzzy_score(query, &session.title.to_lowercase()) {
+        return Some(hit(TITLE_BONUS + score, None));
+    }
+    let description = session.description.as_deref().unwrap_or_default();
+    description
+        .lines()
+        .chain(
+            session
+                .messages
+                .iter()
+                .flat_map(|message| message.content.lines()),
+        )
+        .find(|line| line.to_lowercase().contains(query))
+        .map(|line| hit(0, Some(line.trim().to_string())))
+}
+
+/// Score `text` against `query` if every character of the query appears in order.
+///
+/// Consecutive characters and matches at the start of a word score higher.
+pub(super) fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars() {
+        let index = next + chars[next..].iter().position(|c| *c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|last| last + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || !chars[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(index);
+        next = index + 1;
+    }
+    Some(score)
+}
+
+impl Component for SessionFinder {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn handle_paste_event(&mut self, text: String) -> Result<Option<Action>> {
+        if !self.is_visible {
+            return Ok(None);
+        }
+        self.query.push_str(&pasted_line(&text));
+        self.search();
+        Ok(Some(Action::Render))
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if !self.is_visible {
+            return Ok(None);
+        }
+
+        if let KeyCode::Char(c) = key.code
+            && is_text_key(key)
+        {
+            self.query.push(c);
+            self.search();
+            return Ok(Some(Action::Render));
+        }
+        match self.config.keybindings.action(Mode::Menu, key) {
+            Some(Action::Cancel) => return Ok(Some(Action::HideSessionFinder)),
+            Some(Action::SelectPrevious) => self.list_state.select_previous(),
+            Some(Action::SelectNext) => self.list_state.select_next(),
+            Some(Action::SelectFirst) => self.list_state.select_first(),
+            Some(Action::SelectLast) => self.list_state.select_last(),
+            Some(Action::Confirm) => {
+                let Some(hit) = self
+                    .list_state
+                    .selected()
+                    .and_then(|index| self.hits.get(index))
+                else {
+                    return Ok(Some(Action::Render));
+                };
+                let action = Action::OpenSession(hit.id.clone());
+                // Close first so focus is restored before the session is swapped in
+                if let Some(tx) = &self.command_tx {
+                    tx.send(Action::HideSessionFinder)?;
+                }
+                return Ok(Some(action));
+            }
+            _ if key.code == KeyCode::Backspace => {
+                self.query.pop();
+                self.search();
+            }
+            _ => {}
+        }
+        // The finder is modal, swallow everything else
+        Ok(Some(Action::Render))
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::ShowSessionFinder => {
+                self.show();
+                Ok(Some(Action::Render))
+            }
+            Action::HideSessionFinder => {
+                self.hide();
+                Ok(Some(Action::Render))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if !self.is_visible {
+            return Ok(());
+        }
+
+        let width = area.width.min(80);
+        let height = area.height.min(20);
+        let popup_area = Rect {
+            x: (area.width.saturating_sub(width)) / 2,
+            y: (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup_area);
+
+        let block = Block::bordered()
+            .border_style(Style::default().fg(Color::Blue))
+            .style(Style::default().bg(Color::Black))
+            .title(tf("Find session ({count})", &[("count", &self.hits.len())]))
+            .title_bottom(t("Enter: open | ↑/↓: select | Esc: close"));
+        let inner = block.inner(popup_area);
+        frame.render_widget(block, popup_area);
+
+        let [query_area, list_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        frame.render_widget(
+            Paragraph::new(format!("> {}▏", self.query)).style(Style::default().fg(Color::Yellow)),
+            query_area,
+        );
+
+        let items: Vec<ListItem> = self
+            .hits
+            .iter()
+            .map(|hit| {
+                let mut spans = vec![Span::raw(hit.title.clone())];
+                if hit.archived {
+                    spans.push(Span::styled(
+                        " (archived)",
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                if let Some(snippet) = &hit.snippet {
+                    spans.push(Span::styled(
+                        format!("  {snippet}"),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("rst", "rust async"), Some(11));
+        assert_eq!(fuzzy_score("tsr", "rust async"), None);
+        // Contiguous and word-start matches rank higher than scattered ones
+        assert!(fuzzy_score("as", "rust async") > fuzzy_score("as", "a list"));
+    }
+}
@@ -0,0 +1,302 @@
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use std::any::Any;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::Component;
+use crate::{
+    action::{Action, MenuItem},
+    app::{AppState, Mode},
+    config::Config,
+    core::session::{SessionInfo, auto_title},
+    locale::t,
+};
+
+/// Sidebar listing saved sessions, with the current conversation highlighted.
+#[derive(Default)]
+pub struct SessionSidebar {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    state: Option<AppState>,
+    list_state: ListState,
+    is_focused: bool,
+    resume_focus: bool,      // Whether to take focus back once a popup menu closes
+    editing: Option<String>, // New title while renaming the selected session
+    show_archived: bool,
+}
+
+impl SessionSidebar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sessions to list; the current conversation comes first if it has not been saved yet.
+    fn rows(&self) -> Vec<SessionInfo> {
+        let Some(state) = &self.state else {
+            return Vec::new();
+        };
+        let mut rows = state.sessions.clone();
+        if !rows
+            .iter()
+            .any(|session| session.id == state.conversation.id)
+        {
+            rows.insert(
+                0,
+                SessionInfo {
+                    id: state.conversation.id.clone(),
+                    title: state
+                        .conversation
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| auto_title(&state.conversation.messages)),
+                    updated_at: 0,
+                    archived: false,
+                },
+            );
+        }
+        rows.retain(|session| {
+            !session.archived || self.show_archived || session.id == state.conversation.id
+        });
+        rows
+    }
+
+    fn selected_row(&self) -> Option<SessionInfo> {
+        self.list_state
+            .selected()
+            .and_then(|index| self.rows().into_iter().nth(index))
+    }
+
+    /// Run a command bound in `Mode::Sidebar`; anything else is passed on.
+    fn perform(&mut self, action: Action) -> Option<Action> {
+        match action {
+            Action::SelectPrevious => self.list_state.select_previous(),
+            Action::SelectNext => self.list_state.select_next(),
+            Action::SelectFirst => self.list_state.select_first(),
+            Action::SelectLast => self.list_state.select_last(),
+            Action::Confirm => return self.selected_row().map(|row| Action::OpenSession(row.id)),
+            Action::RenameSelected => self.editing = self.selected_row().map(|row| row.title),
+            Action::ArchiveSelected => {
+                return self
+                    .selected_row()
+                    .map(|row| Action::ArchiveSession(row.id, !row.archived));
+            }
+            Action::ToggleArchived => {
+                self.show_archived = !self.show_archived;
+                // Keep the selection in range now that the list changed length
+                let count = self.rows().len();
+                if self
+                    .list_state
+                    .selected()
+                    .is_some_and(|index| index >= count)
+                {
+                    self.list_state.select(count.checked_sub(1));
+                }
+            }
+            Action::DeleteSelected => {
+                return self.selected_row().map(|row| {
+                    Action::ShowMenu(
+                        format!("Delete \"{}\" permanently?", row.title),
+                        vec![
+                            MenuItem {
+                                label: "Delete".to_string(),
+                                action: Action::DeleteSession(row.id),
+                            },
+                            MenuItem {
+                                label: "Cancel".to_string(),
+                                action: Action::HideMenu,
+                            },
+                        ],
+                    )
+                });
+            }
+            Action::MergeSelected => {
+                return self.selected_row().map(|row| {
+                    Action::ShowMenu(
+                        format!("Merge this chat into \"{}\"?", row.title),
+                        vec![
+                            MenuItem {
+                                label: "Merge".to_string(),
+                                action: Action::MergeSession(row.id),
+                            },
+                            MenuItem {
+                                label: "Cancel".to_string(),
+                                action: Action::HideMenu,
+                            },
+                        ],
+                    )
+                });
+            }
+            action => return Some(action),
+        }
+        Some(Action::Render)
+    }
+
+    fn handle_rename_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let title = self.editing.as_mut()?;
+        match key.code {
+            KeyCode::Char(c) => title.push(c),
+            KeyCode::Backspace => {
+                title.pop();
+            }
+            KeyCode::Enter => {
+                let title = self.editing.take().unwrap_or_default();
+                let title = title.trim();
+                if !title.is_empty()
+                    && let Some(row) = self.selected_row()
+                {
+                    return Some(Action::RenameSession(row.id, title.to_string()));
+                }
+            }
+            KeyCode::Esc => self.editing = None,
+            _ => {}
+        }
+        // Swallow everything while editing so global keybindings don't fire
+        Some(Action::Render)
+    }
+}
+
+impl Component for SessionSidebar {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn register_state_handler(&mut self, state: AppState) -> Result<()> {
+        self.state = Some(state);
+        let count = self.rows().len();
+        match self.list_state.selected() {
+            _ if count == 0 => self.list_state.select(None),
+            Some(index) if index >= count => self.list_state.select(Some(count - 1)),
+            None => self.list_state.select_first(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if !self.is_focused {
+            return Ok(None);
+        }
+        if self.editing.is_some() {
+            return Ok(self.handle_rename_key(key));
+        }
+
+        Ok(self
+            .config
+            .keybindings
+            .action(Mode::Sidebar, key)
+            .and_then(|action| self.perform(action)))
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::FocusSidebar => {
+                self.is_focused = true;
+                return Ok(Some(Action::Render));
+            }
+            Action::FocusInput
+            | Action::FocusChat
+            | Action::ShowDialog(_)
+            | Action::ShowSystemPromptDialog
+            | Action::ShowStats
+            | Action::ShareConversation
+            | Action::ShowApiKeyDialog
+            | Action::ShowCommitMessage(_)
+            | Action::EditMessage(_) => {
+                self.is_focused = false;
+                self.editing = None;
+            }
+            Action::ShowMenu(..)
+            | Action::ShowSessionFinder
+            | Action::ShowModelPicker
+            | Action::AskOtherModel(_)
+            | Action::ShowToolApproval(_)
+            | Action::ShowTemplateForm(..)
+            | Action::ShowVersionDiff(_) => {
+                self.resume_focus = self.is_focused;
+                self.is_focused = false;
+            }
+            Action::HideMenu
+            | Action::HideSessionFinder
+            | Action::HideModelPicker
+            | Action::HideToolApproval
+            | Action::HideTemplateForm
+            | Action::HideVersionDiff => {
+                self.is_focused = self.resume_focus;
+                self.resume_focus = false;
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        let Some(state) = &self.state else {
+            return Ok(());
+        };
+        if !state.show_sidebar {
+            return Ok(());
+        }
+
+        let hint = if self.editing.is_some() {
+            t("Enter: save | Esc: cancel")
+        } else if self.is_focused {
+            t("Enter: open | r: rename | a: archive | d: delete")
+        } else {
+            t("")
+        };
+        let border_color = if self.is_focused {
+            self.config.ui.theme.theme().focus
+        } else {
+            Color::White
+        };
+        let block = Block::bordered()
+            .title(if self.show_archived {
+                t("Sessions (all)")
+            } else {
+                t("Sessions")
+            })
+            .title_bottom(hint)
+            .border_style(Style::default().fg(border_color));
+
+        let icons = self.config.ui.icons();
+        let selected = self.list_state.selected();
+        let items: Vec<ListItem> = self
+            .rows()
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let title = match &self.editing {
+                    Some(title) if selected == Some(index) => format!("{title}▏"),
+                    _ => row.title,
+                };
+                if row.id == state.conversation.id {
+                    ListItem::new(format!("{}{title}", icons.current_session))
+                        .style(Style::default().fg(Color::Cyan))
+                } else if row.archived {
+                    ListItem::new(format!("{}{title}", icons.session))
+                        .style(Style::default().fg(Color::DarkGray))
+                } else {
+                    ListItem::new(format!("{}{title}", icons.session))
+                }
+            })
+            .collect();
+
+        let mut list = List::new(items).block(block);
+        if self.is_focused {
+            list = list.highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use std::any::Any;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Component, is_text_key, pasted_line};
+use crate::{
+    action::Action,
+    app::Mode,
+    config::Config,
+    core::templates::TemplateField,
+    locale::{t, tf},
+};
+
+/// A modal form for the placeholders of a prompt template, one line per field.
+#[derive(Default)]
+pub struct TemplateForm {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    /// The template being filled in, `None` while hidden.
+    template: Option<String>,
+    fields: Vec<TemplateField>,
+    selected: usize,
+}
+
+impl TemplateForm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.fields.len().max(1);
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self
+            .selected
+            .checked_sub(1)
+            .unwrap_or(self.fields.len().saturating_sub(1));
+    }
+
+    /// Close the form and fill the template with the values entered.
+    fn submit(&mut self, template: String) -> Result<Option<Action>> {
+        // Close first so focus is back in the input when the text arrives
+        if let Some(tx) = &self.command_tx {
+            tx.send(Action::HideTemplateForm)?;
+        }
+        Ok(Some(Action::FillTemplate(
+            template,
+            std::mem::take(&mut self.fields),
+        )))
+    }
+}
+
+impl Component for TemplateForm {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn handle_paste_event(&mut self, text: String) -> Result<Option<Action>> {
+        if self.template.is_none() {
+            return Ok(None);
+        }
+        if let Some(field) = self.fields.get_mut(self.selected) {
+            field.value.push_str(&pasted_line(&text));
+        }
+        Ok(Some(Action::Render))
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        let Some(template) = self.template.clone() else {
+            return Ok(None);
+        };
+
+        if let KeyCode::Char(c) = key.code
+            && is_text_key(key)
+        {
+            if let Some(field) = self.fields.get_mut(self.selected) {
+                field.value.push(c);
+            }
+            return Ok(Some(Action::Render));
+        }
+        match key.code {
+            KeyCode::Tab => self.select_next(),
+            KeyCode::BackTab => self.select_previous(),
+            KeyCode::Backspace => {
+                if let Some(field) = self.fields.get_mut(self.selected) {
+                    field.value.pop();
+                }
+            }
+            _ => match self.config.keybindings.action(Mode::Menu, key) {
+                Some(Action::Cancel) => return Ok(Some(Action::HideTemplateForm)),
+                Some(Action::SelectPrevious) => self.select_previous(),
+                Some(Action::SelectNext) => self.select_next(),
+                // Enter goes through the fields, and inserts the text after the last one
+                Some(Action::Confirm) if self.selected + 1 < self.fields.len() => {
+                    self.select_next()
+                }
+                Some(Action::Confirm) => return self.submit(template),
+                _ => {}
+            },
+        }
+        // The form is modal, swallow everything else
+        Ok(Some(Action::Render))
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::ShowTemplateForm(template, fields) => {
+                self.template = Some(template);
+                self.fields = fields;
+                self.selected = 0;
+                Ok(Some(Action::Render))
+            }
+            Action::HideTemplateForm => {
+                self.template = None;
+                Ok(Some(Action::Render))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        let Some(template) = &self.template else {
+            return Ok(());
+        };
+
+        let width = area.width.min(60);
+        let height = area.height.min(self.fields.len() as u16 + 2);
+        let popup_area = Rect {
+            x: (area.width.saturating_sub(width)) / 2,
+            y: (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup_area);
+
+        let block = Block::bordered()
+            .border_style(Style::default().fg(Color::Blue))
+            .style(Style::default().bg(Color::Black))
+            .title(tf("Fill in {template}", &[("template", &template)]))
+            .title_bottom(t("Enter: next/insert | Tab: next | Esc: cancel"));
+        let inner = block.inner(popup_area);
+        frame.render_widget(block, popup_area);
+
+        let label_width = self
+            .fields
+            .iter()
+            .map(|field| field.name.chars().count())
+            .max()
+            .unwrap_or_default();
+        let lines: Vec<Line> = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let label = format!("{:>label_width$}: ", field.name);
+                if index == self.selected {
+                    Line::from(vec![
+                        Span::styled(label, Style::default().fg(Color::Yellow)),
+                        Span::styled(
+                            format!("{}▏", field.value),
+                            Style::default().fg(Color::Yellow),
+                        ),
+                    ])
+                } else {
+                    Line::from(vec![
+                        Span::styled(label, Style::default().fg(Color::DarkGray)),
+                        Span::styled(field.value.clone(), Style::default().fg(Color::Gray)),
+                    ])
+                }
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), inner);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::components::testing::render;
+
+    fn field(name: &str, value: &str) -> TemplateField {
+        TemplateField {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_draw() -> Result<()> {
+        let mut form = TemplateForm::new();
+        form.update(Action::ShowTemplateForm(
+            "translate".to_string(),
+            vec![field("text", "hi"), field("language", "German")],
+        ))?;
+        form.select_next();
+        assert_snapshot!(render(&mut form, 50, 6));
+        Ok(())
+    }
+}
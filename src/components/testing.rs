@@ -0,0 +1,48 @@
+//! Helpers for snapshot tests of component rendering.
+
+use ratatui::{Terminal, backend::TestBackend};
+
+use super::Component;
+use crate::{
+    app::AppState,
+    core::{ChatMessage, Conversation},
+};
+
+/// Draw a component into an in-memory terminal of the given size.
+///
+/// The returned backend displays as the rendered text, for use with `insta::assert_snapshot!`.
+pub fn render(component: &mut dyn Component, width: u16, height: u16) -> TestBackend {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal
+        .draw(|frame| component.draw(frame, frame.area()).unwrap())
+        .unwrap();
+    terminal.backend().clone()
+}
+
+/// App state holding a conversation of `(role, content)` messages.
+pub fn state(messages: &[(&str, &str)]) -> AppState {
+    AppState {
+        conversation: Conversation {
+            model: "openai/gpt-4o".to_string(),
+            messages: messages
+                .iter()
+                .map(|(role, content)| ChatMessage::new(role, *content))
+                .collect(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// A short conversation with a reply long enough to wrap in narrow terminals.
+pub fn conversation() -> AppState {
+    state(&[
+        ("user", "What is a borrow checker?"),
+        (
+            "AI",
+            "The borrow checker is the part of the Rust compiler that makes sure references never \
+             outlive the data they point to.",
+        ),
+        ("user", "Thanks!"),
+    ])
+}
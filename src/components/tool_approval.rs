@@ -0,0 +1,222 @@
+use color_eyre::Result;
+use crossterm::event::KeyEvent;
+use ratatui::{prelude::*, widgets::*};
+use std::any::Any;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::Component;
+use crate::{
+    action::Action,
+    app::Mode,
+    config::Config,
+    core::{conversation::ToolCall, tools},
+    locale::{t, tf},
+};
+
+/// A modal popup asking whether a tool call the model made may run, showing its arguments, or
+/// the diff for a file write.
+#[derive(Default)]
+pub struct ToolApproval {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    call: Option<ToolCall>,
+    /// Read when the popup opens, not on every draw.
+    preview: String,
+    list_state: ListState,
+}
+
+impl ToolApproval {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The choices, each with the action it sends.
+    fn options(call: &ToolCall) -> Vec<(String, Action)> {
+        let mut options = vec![(
+            "Allow once".to_string(),
+            Action::ApproveToolCall(call.clone(), false),
+        )];
+        if !tools::confirms_each_call(&call.name) {
+            options.push((
+                format!("Always allow {} for this session", call.name),
+                Action::ApproveToolCall(call.clone(), true),
+            ));
+        }
+        options.push(("Deny".to_string(), Action::DenyToolCall(call.clone())));
+        options
+    }
+
+    /// Close the popup and answer with `action`.
+    fn answer(&mut self, action: Action) -> Result<Option<Action>> {
+        self.call = None;
+        // Close first so focus is restored before the call runs
+        if let Some(tx) = &self.command_tx {
+            tx.send(Action::HideToolApproval)?;
+        }
+        Ok(Some(action))
+    }
+}
+
+/// A preview line, with diff lines colored.
+fn preview_line(line: &str) -> Line<'_> {
+    let color = if line.starts_with("+++") || line.starts_with("---") {
+        Color::White
+    } else if line.starts_with('+') {
+        Color::Green
+    } else if line.starts_with('-') {
+        Color::Red
+    } else if line.starts_with("@@") {
+        Color::Cyan
+    } else {
+        Color::Gray
+    };
+    Line::styled(line, Style::default().fg(color))
+}
+
+impl Component for ToolApproval {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        let Some(call) = &self.call else {
+            return Ok(None);
+        };
+
+        match self.config.keybindings.action(Mode::Menu, key) {
+            Some(Action::Cancel) => {
+                let action = Action::DenyToolCall(call.clone());
+                return self.answer(action);
+            }
+            Some(Action::SelectPrevious) => self.list_state.select_previous(),
+            Some(Action::SelectNext) => self.list_state.select_next(),
+            Some(Action::SelectFirst) => self.list_state.select_first(),
+            Some(Action::SelectLast) => self.list_state.select_last(),
+            Some(Action::Confirm) => {
+                let index = self.list_state.selected().unwrap_or_default();
+                if let Some((_, action)) = Self::options(call).into_iter().nth(index) {
+                    return self.answer(action);
+                }
+            }
+            _ => {}
+        }
+        // The popup is modal, swallow everything else
+        Ok(Some(Action::Render))
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::ShowToolApproval(call) => {
+                self.preview = self.config.tools.preview(&call);
+                self.call = Some(call);
+                self.list_state = ListState::default();
+                self.list_state.select_first();
+                Ok(Some(Action::Render))
+            }
+            Action::HideToolApproval => {
+                self.call = None;
+                Ok(Some(Action::Render))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        let Some(call) = &self.call else {
+            return Ok(());
+        };
+
+        let options = Self::options(call);
+        let width = area
+            .width
+            .min(if call.name == "write_file" { 90 } else { 70 });
+        let inner_width = width.saturating_sub(2).max(1) as usize;
+        let arguments_height: usize = self
+            .preview
+            .lines()
+            .map(|line| line.chars().count().div_ceil(inner_width).max(1))
+            .sum();
+        // Arguments, a blank line and the options inside the border
+        let height = area
+            .height
+            .min((arguments_height + options.len() + 3) as u16);
+        let popup_area = Rect {
+            x: (area.width.saturating_sub(width)) / 2,
+            y: (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup_area);
+
+        let block = Block::bordered()
+            .border_style(Style::default().fg(Color::Magenta))
+            .style(Style::default().bg(Color::Black))
+            .title(tf("Run tool {name}?", &[("name", &call.name)]))
+            .title_bottom(t("Enter: select | Esc: deny"));
+        let inner = block.inner(popup_area);
+        frame.render_widget(block, popup_area);
+
+        let [arguments_area, options_area] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(options.len() as u16 + 1),
+        ])
+        .areas(inner);
+        let lines: Vec<Line> = self.preview.lines().map(preview_line).collect();
+        frame.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: false }),
+            arguments_area,
+        );
+        let items: Vec<ListItem> = options
+            .into_iter()
+            .map(|(label, _)| ListItem::new(label))
+            .collect();
+        let list = List::new(items)
+            .block(Block::new().borders(Borders::TOP))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, options_area, &mut self.list_state);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::components::testing::render;
+
+    #[test]
+    fn test_draw() -> Result<()> {
+        let mut approval = ToolApproval::new();
+        approval.update(Action::ShowToolApproval(ToolCall {
+            id: "call_1".to_string(),
+            name: "shell".to_string(),
+            arguments: r#"{"command": "ls -la"}"#.to_string(),
+        }))?;
+        assert_snapshot!(render(&mut approval, 50, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_write_file() -> Result<()> {
+        let mut approval = ToolApproval::new();
+        approval.update(Action::ShowToolApproval(ToolCall {
+            id: "call_1".to_string(),
+            name: "write_file".to_string(),
+            arguments: r#"{"path": "does-not-exist.txt", "content": "hello\nworld\n"}"#.to_string(),
+        }))?;
+        assert_snapshot!(render(&mut approval, 50, 12));
+        Ok(())
+    }
+}
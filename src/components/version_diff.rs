@@ -0,0 +1,274 @@
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use similar::{ChangeTag, TextDiff};
+use std::any::Any;
+
+use super::Component;
+use crate::{
+    action::Action,
+    app::{AppState, Mode},
+    config::Config,
+    core::MessageVersion,
+};
+
+/// A modal popup comparing the versions of a regenerated reply, unified or side by side.
+#[derive(Default)]
+pub struct VersionDiff {
+    config: Config,
+    state: Option<AppState>,
+    /// Every version of the reply being compared; empty while hidden.
+    versions: Vec<MessageVersion>,
+    /// The newer of the two versions compared; the one before it is the older.
+    newer: usize,
+    side_by_side: bool,
+    scroll: u16,
+}
+
+impl VersionDiff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn show(&mut self, index: usize) {
+        let Some(message) = self
+            .state
+            .as_ref()
+            .and_then(|state| state.conversation.messages.get(index))
+            .filter(|message| !message.versions.is_empty())
+        else {
+            return;
+        };
+        self.versions = message.all_versions();
+        // Start with how the version shown differs from the one before it
+        self.newer = message.version.max(1);
+        self.scroll = 0;
+    }
+
+    /// Compare the pair of versions `step` further on, staying within the versions.
+    fn step(&mut self, step: isize) {
+        let newer = self.newer.saturating_add_signed(step);
+        if (1..self.versions.len()).contains(&newer) {
+            self.newer = newer;
+            self.scroll = 0;
+        }
+    }
+
+    fn label(&self, index: usize) -> String {
+        match &self.versions[index].metadata.model {
+            Some(model) => format!("v{} · {model}", index + 1),
+            None => format!("v{}", index + 1),
+        }
+    }
+}
+
+/// A changed line as runs of text, each marked whether it is the part that changed.
+type Change = (ChangeTag, Vec<(bool, String)>);
+
+/// The lines of `new` compared with `old`, with the changed words within changed lines marked.
+fn changes(old: &str, new: &str) -> Vec<Change> {
+    let diff = TextDiff::from_lines(old, new);
+    diff.ops()
+        .iter()
+        .flat_map(|op| diff.iter_inline_changes(op))
+        .map(|change| {
+            let runs = change
+                .iter_strings_lossy()
+                .map(|(emphasized, text)| (emphasized, text.trim_end_matches('\n').to_string()))
+                .collect();
+            (change.tag(), runs)
+        })
+        .collect()
+}
+
+/// A line of the diff, removed text in red and added text in green, the changed words
+/// highlighted.
+fn change_line(tag: ChangeTag, runs: &[(bool, String)], prefix: Option<&str>) -> Line<'static> {
+    let color = match tag {
+        ChangeTag::Delete => Color::Red,
+        ChangeTag::Insert => Color::Green,
+        ChangeTag::Equal => Color::Gray,
+    };
+    let mut spans: Vec<Span> = prefix
+        .map(|prefix| Span::styled(prefix.to_string(), Style::default().fg(color)))
+        .into_iter()
+        .collect();
+    spans.extend(runs.iter().map(|(emphasized, text)| {
+        let style = if *emphasized {
+            Style::default().fg(Color::Black).bg(color)
+        } else {
+            Style::default().fg(color)
+        };
+        Span::styled(text.clone(), style)
+    }));
+    Line::from(spans)
+}
+
+impl Component for VersionDiff {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn register_state_handler(&mut self, state: AppState) -> Result<()> {
+        self.state = Some(state);
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.versions.is_empty() {
+            return Ok(None);
+        }
+
+        match key.code {
+            KeyCode::Tab => self.side_by_side = !self.side_by_side,
+            KeyCode::Left | KeyCode::Char('h') => self.step(-1),
+            KeyCode::Right | KeyCode::Char('l') => self.step(1),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+            _ => match self.config.keybindings.action(Mode::Menu, key) {
+                Some(Action::Cancel | Action::Confirm) => return Ok(Some(Action::HideVersionDiff)),
+                Some(Action::SelectPrevious) => self.scroll = self.scroll.saturating_sub(1),
+                Some(Action::SelectNext) => self.scroll = self.scroll.saturating_add(1),
+                _ => {}
+            },
+        }
+        // The popup is modal, swallow everything else
+        Ok(Some(Action::Render))
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::ShowVersionDiff(index) => {
+                self.show(index);
+                Ok(Some(Action::Render))
+            }
+            Action::HideVersionDiff => {
+                self.versions.clear();
+                Ok(Some(Action::Render))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if self.versions.len() < 2 {
+            return Ok(());
+        }
+
+        let width = area.width.saturating_sub(4).min(120);
+        let height = area.height.saturating_sub(2);
+        let popup_area = Rect {
+            x: (area.width.saturating_sub(width)) / 2,
+            y: (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup_area);
+
+        let older = self.newer - 1;
+        let (older_label, newer_label) = (self.label(older), self.label(self.newer));
+        let layout = if self.side_by_side {
+            "Tab: unified"
+        } else {
+            "Tab: side by side"
+        };
+        let block = Block::bordered()
+            .border_style(Style::default().fg(Color::Blue))
+            .style(Style::default().bg(Color::Black))
+            .title(format!(
+                "Versions {older_label} → {newer_label} ({} in all)",
+                self.versions.len()
+            ))
+            .title_bottom(format!(
+                "←/→: versions | {layout} | ↑/↓: scroll | Esc: close"
+            ));
+        let inner = block.inner(popup_area);
+        frame.render_widget(block, popup_area);
+
+        let changes = changes(
+            &self.versions[older].content,
+            &self.versions[self.newer].content,
+        );
+        let paragraph = |lines: Vec<Line<'static>>| {
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll, 0))
+        };
+        if self.side_by_side {
+            let [left, right] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(inner);
+            let column = |hidden: ChangeTag, label: String| {
+                let mut lines = vec![Line::styled(
+                    label,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )];
+                lines.extend(
+                    changes
+                        .iter()
+                        .filter(|(tag, _)| *tag != hidden)
+                        .map(|(tag, runs)| change_line(*tag, runs, None)),
+                );
+                lines
+            };
+            frame.render_widget(
+                paragraph(column(ChangeTag::Insert, older_label))
+                    .block(Block::new().borders(Borders::RIGHT)),
+                left,
+            );
+            frame.render_widget(paragraph(column(ChangeTag::Delete, newer_label)), right);
+        } else {
+            let lines = changes
+                .iter()
+                .map(|(tag, runs)| {
+                    let prefix = match tag {
+                        ChangeTag::Delete => "- ",
+                        ChangeTag::Insert => "+ ",
+                        ChangeTag::Equal => "  ",
+                    };
+                    change_line(*tag, runs, Some(prefix))
+                })
+                .collect();
+            frame.render_widget(paragraph(lines), inner);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::{
+        components::testing::{render, state},
+        core::MessageMetadata,
+    };
+
+    #[test]
+    fn test_draw() -> Result<()> {
+        let mut state = state(&[("user", "Name a fruit"), ("AI", "A pear.\nIt is sweet.")]);
+        let reply = &mut state.conversation.messages[1];
+        reply.versions = vec![MessageVersion {
+            content: "An apple.\nIt is sweet.".to_string(),
+            metadata: MessageMetadata {
+                model: Some("mock-1".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+        reply.version = 1;
+        let mut diff = VersionDiff::new();
+        diff.register_state_handler(state)?;
+        diff.update(Action::ShowVersionDiff(1))?;
+        assert_snapshot!("unified", render(&mut diff, 60, 8));
+
+        diff.side_by_side = true;
+        assert_snapshot!("side_by_side", render(&mut diff, 60, 8));
+        Ok(())
+    }
+}
@@ -7,11 +7,34 @@ use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use derive_deref::{Deref, DerefMut};
 use directories::ProjectDirs;
 use lazy_static::lazy_static;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use ratatui::style::{Color, Modifier, Style};
 use serde::{Deserialize, de::Deserializer};
-use tracing::error;
-
-use crate::{action::Action, app::Mode};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error};
+
+use crate::{
+    action::Action,
+    app::Mode,
+    core::{
+        Settings,
+        attachments::AttachmentsConfig,
+        budget::BudgetConfig,
+        health::HealthConfig,
+        metrics::MetricsConfig,
+        notes::NotesConfig,
+        personas::Persona,
+        provider::{ChatConfig, MockConfig, ModelParams, ProviderKind},
+        rag::RagConfig,
+        share::ShareConfig,
+        summary::CompactionConfig,
+        tools::ToolsConfig,
+        transcript::TranscriptConfig,
+        workflow::Workflow,
+    },
+    icons::{self, Icons},
+    theme::ThemeName,
+};
 
 const CONFIG: &str = include_str!("../.config/config.json5");
 
@@ -23,6 +46,82 @@ pub struct AppConfig {
     pub config_dir: PathBuf,
 }
 
+impl AppConfig {
+    pub fn sessions_dir(&self) -> PathBuf {
+        self.data_dir.join("sessions")
+    }
+
+    pub fn drafts_dir(&self) -> PathBuf {
+        self.data_dir.join("drafts")
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.data_dir.join("cache")
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        self.data_dir.join("logs")
+    }
+
+    /// Markdown prompt templates, whose frontmatter can set up the session too.
+    pub fn templates_dir(&self) -> PathBuf {
+        self.config_dir.join("templates")
+    }
+
+    /// Personas and character cards as JSON files, picked with `/persona`.
+    pub fn personas_dir(&self) -> PathBuf {
+        self.config_dir.join("personas")
+    }
+
+    /// Translations of the UI that override or add to the built-in ones.
+    pub fn locales_dir(&self) -> PathBuf {
+        self.config_dir.join("locales")
+    }
+
+    /// The values last filled into each prompt template.
+    pub fn template_values_file(&self) -> PathBuf {
+        self.data_dir.join("template_values.json")
+    }
+
+    /// Favorite and recently used models.
+    pub fn models_file(&self) -> PathBuf {
+        self.data_dir.join("models.json")
+    }
+
+    /// Tokens and cost of every reply, for usage reports and the spending budget.
+    pub fn usage_file(&self) -> PathBuf {
+        self.data_dir.join("usage.jsonl")
+    }
+
+    /// API keys entered in the app, readable only by the user.
+    pub fn keys_file(&self) -> PathBuf {
+        self.data_dir.join("keys.json")
+    }
+
+    /// Request counts and latency for `lazychat stats`, if metrics are enabled.
+    pub fn metrics_file(&self) -> PathBuf {
+        self.data_dir.join("metrics.jsonl")
+    }
+
+    /// Embedded chunks of the directory last indexed with `/index`.
+    pub fn index_file(&self) -> PathBuf {
+        self.data_dir.join("index.json")
+    }
+
+    /// Create the data directory and its subdirectories if they don't exist yet.
+    pub fn create_dirs(&self) -> Result<()> {
+        for dir in [
+            self.sessions_dir(),
+            self.drafts_dir(),
+            self.cache_dir(),
+            self.log_dir(),
+        ] {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct Config {
     #[serde(default, flatten)]
@@ -31,6 +130,161 @@ pub struct Config {
     pub keybindings: KeyBindings,
     #[serde(default)]
     pub styles: Styles,
+    #[serde(default)]
+    pub audio: AudioConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub chat: ChatConfig,
+    /// Parameters for specific models, e.g. `models."anthropic/claude-3.5-sonnet".max_tokens`.
+    #[serde(default)]
+    pub models: HashMap<String, ModelParams>,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    /// Prompts picked with `/template`, by name; `{{name}}` or `{{name:default}}` placeholders
+    /// are asked for before the text is inserted.
+    #[serde(default)]
+    pub templates: HashMap<String, String>,
+    /// Personas to start a session as with `/persona`, by name.
+    #[serde(default)]
+    pub personas: HashMap<String, Persona>,
+    /// Prompts sent at the press of a key, by key, e.g. `"<F2>": "Explain {{clipboard}}"`;
+    /// `{{clipboard}}` and `{{selection}}` are replaced with the clipboard text and the selected
+    /// message.
+    #[serde(default)]
+    pub quick_prompts: HashMap<String, String>,
+    /// Abbreviations expanded in the input on Tab or space, e.g. `";sig": "Thanks,\nAda"`.
+    #[serde(default)]
+    pub snippets: HashMap<String, String>,
+    /// Multi-step prompts run with `/workflow <name>`, by name.
+    #[serde(default)]
+    pub workflows: HashMap<String, Workflow>,
+    #[serde(default)]
+    pub transcript: TranscriptConfig,
+    /// Opt-in local request metrics for `lazychat stats`.
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Where `/share` publishes the conversation.
+    #[serde(default)]
+    pub share: ShareConfig,
+    /// Where `/note` saves the conversation, e.g. the daily notes of an Obsidian vault.
+    #[serde(default)]
+    pub notes: NotesConfig,
+    /// Limits of `/diff` and other attachments.
+    #[serde(default)]
+    pub attachments: AttachmentsConfig,
+    /// Embeddings and retrieval for questions about a directory indexed with `/index`.
+    #[serde(default)]
+    pub rag: RagConfig,
+    /// When long conversations are replaced by a summary of their beginning.
+    #[serde(default)]
+    pub compaction: CompactionConfig,
+    #[serde(default)]
+    pub mock: MockConfig,
+    /// Command line settings, kept so that reloading the config file doesn't drop them.
+    #[serde(skip)]
+    pub overrides: Overrides,
+}
+
+/// Settings given on the command line, which take precedence over the config file.
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    pub data_dir: Option<PathBuf>,
+    pub provider: Option<ProviderKind>,
+}
+
+/// Display settings.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Show message numbers in the chat window gutter.
+    pub show_message_numbers: bool,
+    /// Messages longer than this many lines are shown collapsed; 0 shows everything.
+    pub collapse_after_lines: usize,
+    /// Number the lines of the input and editor dialogs and show the cursor's `line:col`.
+    pub line_numbers: bool,
+    /// Edits of the input and editor dialogs that can be undone.
+    pub undo_history: usize,
+    /// Clearing a draft of more lines than this asks to press the key again; 0 never asks.
+    pub confirm_clear_lines: usize,
+    /// Pasting more characters than this asks whether to attach the text collapsed instead of
+    /// typing it into the draft; 0 never asks.
+    pub confirm_paste_chars: usize,
+    /// Show a static indicator instead of the spinner and redraw only when something changes.
+    pub reduced_motion: bool,
+    /// Milliseconds to gather streamed text for before the transcript is updated with it, so
+    /// fast models don't cause an update per token; 0 updates for every chunk.
+    pub stream_flush_ms: u64,
+    /// Built-in color theme: `default`, `high-contrast` or `deuteranopia`.
+    pub theme: ThemeName,
+    /// Language of the UI like `de`, or the one of `LC_ALL` or `LANG` if unset.
+    pub locale: Option<String>,
+    /// Nerd Font icons for roles, sessions and status items instead of ASCII markers; detected
+    /// from the installed fonts if unset.
+    pub use_icons: Option<bool>,
+    /// Print the conversation as Markdown once the TUI is closed, as `--print-on-exit` does.
+    pub print_on_exit: bool,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            show_message_numbers: false,
+            collapse_after_lines: 30,
+            line_numbers: false,
+            undo_history: 500,
+            confirm_clear_lines: 3,
+            confirm_paste_chars: 10_000,
+            reduced_motion: false,
+            stream_flush_ms: 50,
+            theme: ThemeName::Default,
+            locale: None,
+            use_icons: None,
+            print_on_exit: false,
+        }
+    }
+}
+
+impl UiConfig {
+    pub fn icons(&self) -> &'static Icons {
+        if self.use_icons == Some(true) {
+            &icons::NERD_FONT
+        } else {
+            &icons::ASCII
+        }
+    }
+}
+
+/// Settings for push-to-talk voice input.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Command used to record from the microphone; `{file}` is replaced with the output path.
+    pub record_command: Vec<String>,
+    /// OpenAI-compatible transcription endpoint (OpenAI Whisper or a whisper.cpp server).
+    pub transcription_url: String,
+    pub model: String,
+    /// Environment variable holding the API key, if the endpoint requires one.
+    pub api_key_env: Option<String>,
+    pub language: Option<String>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            record_command: ["sox", "-q", "-d", "-c", "1", "-r", "16000", "{file}"]
+                .map(String::from)
+                .to_vec(),
+            transcription_url: "https://api.openai.com/v1/audio/transcriptions".to_string(),
+            model: "whisper-1".to_string(),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            language: None,
+        }
+    }
 }
 
 lazy_static! {
@@ -47,12 +301,20 @@ lazy_static! {
 
 impl Config {
     pub fn new() -> Result<Self, config::ConfigError> {
+        Self::load(&Overrides::default())
+    }
+
+    /// Load the configuration, with command line `overrides` taking precedence over the config
+    /// file.
+    pub fn load(overrides: &Overrides) -> Result<Self, config::ConfigError> {
         let default_config: Config = json5::from_str(CONFIG).unwrap();
-        let data_dir = get_data_dir();
         let config_dir = get_config_dir();
         let mut builder = config::Config::builder()
-            .set_default("data_dir", data_dir.to_str().unwrap())?
+            .set_default("data_dir", get_data_dir().to_str().unwrap())?
             .set_default("config_dir", config_dir.to_str().unwrap())?;
+        if let Some(data_dir) = &overrides.data_dir {
+            builder = builder.set_override("data_dir", data_dir.to_str().unwrap())?;
+        }
 
         let config_files = [
             ("config.json5", config::FileFormat::Json5),
@@ -76,7 +338,23 @@ impl Config {
         }
 
         let mut cfg: Self = builder.build()?.try_deserialize()?;
-
+        if let Some(provider) = overrides.provider {
+            cfg.chat.provider = provider;
+        }
+        cfg.overrides = overrides.clone();
+        cfg.ui.use_icons.get_or_insert_with(icons::detect);
+        cfg.rag.models_dir = cfg.config.cache_dir().join("models");
+
+        // Quick prompts go first, so that they win over default bindings of the same key
+        let home_bindings = cfg.keybindings.entry(Mode::Home).or_default();
+        for (keys, prompt) in &cfg.quick_prompts {
+            let keys = parse_key_sequence(keys)
+                .map_err(|err| config::ConfigError::Message(format!("quick_prompts: {err}")))?
+                .into_iter()
+                .map(normalize_key)
+                .collect();
+            home_bindings.insert(keys, Action::QuickPrompt(prompt.clone()));
+        }
         for (mode, default_bindings) in default_config.keybindings.iter() {
             let user_bindings = cfg.keybindings.entry(*mode).or_default();
             for (key, cmd) in default_bindings.iter() {
@@ -94,6 +372,49 @@ impl Config {
 
         Ok(cfg)
     }
+
+    /// The settings of the chat engine.
+    pub fn engine_settings(&self) -> Settings {
+        Settings {
+            chat: self.chat.clone(),
+            models: self.models.clone(),
+            mock: self.mock.clone(),
+            transcript: self.transcript.clone(),
+            sessions_dir: self.config.sessions_dir(),
+            log_dir: self.config.log_dir(),
+            models_file: self.config.models_file(),
+            budget: self.budget.clone(),
+            usage_file: self.config.usage_file(),
+            metrics: self.metrics.clone(),
+            metrics_file: self.config.metrics_file(),
+            keys_file: self.config.keys_file(),
+            tools: self.tools.clone(),
+            workflows: self.workflows.clone(),
+            rag: self.rag.clone(),
+            index_file: self.config.index_file(),
+        }
+    }
+}
+
+/// Watch the config directory and send `Action::ConfigReloaded` whenever a config file changes.
+///
+/// The returned watcher stops watching when dropped.
+pub fn watch(action_tx: UnboundedSender<Action>) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        let is_config_file = event.paths.iter().any(|path| {
+            path.file_stem()
+                .is_some_and(|stem| stem.eq_ignore_ascii_case("config"))
+        });
+        if is_config_file && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            debug!("Config changed: {:?}", event.paths);
+            let _ = action_tx.send(Action::ConfigReloaded);
+        }
+    })?;
+    watcher.watch(&get_config_dir(), RecursiveMode::NonRecursive)?;
+    Ok(watcher)
 }
 
 pub fn get_data_dir() -> PathBuf {
@@ -123,6 +444,107 @@ fn project_directory() -> Option<ProjectDirs> {
 #[derive(Clone, Debug, Default, Deref, DerefMut)]
 pub struct KeyBindings(pub HashMap<Mode, HashMap<Vec<KeyEvent>, Action>>);
 
+impl KeyBindings {
+    /// The action bound to a single key in `mode`, if any.
+    pub fn action(&self, mode: Mode, key: KeyEvent) -> Option<Action> {
+        self.get(&mode)?.get(&vec![normalize_key(key)]).cloned()
+    }
+}
+
+impl Config {
+    /// Keybindings that override a default or a global binding, or can never fire, as
+    /// human-readable warnings.
+    pub fn keybinding_diagnostics(&self) -> Vec<String> {
+        let defaults: Config = json5::from_str(CONFIG).unwrap();
+        let mut diagnostics = Vec::new();
+
+        for (mode, bindings) in self.keybindings.iter() {
+            let default_bindings = defaults.keybindings.get(mode);
+            for (keys, action) in bindings {
+                let keys_str = key_sequence_to_string(keys);
+                if let Some(default) = default_bindings.and_then(|defaults| defaults.get(keys))
+                    && default != action
+                {
+                    diagnostics.push(format!(
+                        "{mode:?}: {keys_str} runs {action} instead of the default {default}"
+                    ));
+                }
+                if *mode != Mode::Home && keys.len() > 1 {
+                    diagnostics.push(format!(
+                        "{mode:?}: {keys_str} never fires, key sequences only work in Home"
+                    ));
+                }
+                if matches!(mode, Mode::Input | Mode::Dialog)
+                    && keys.first().is_some_and(|key| {
+                        matches!(key.code, KeyCode::Char(_))
+                            && !key
+                                .modifiers
+                                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                    })
+                {
+                    diagnostics.push(format!(
+                        "{mode:?}: {keys_str} never fires, letters are typed as text"
+                    ));
+                }
+                // The defaults shadow some global keys on purpose, e.g. <q> closes a menu
+                let default_global = defaults
+                    .keybindings
+                    .get(&Mode::Home)
+                    .and_then(|home| home.get(keys));
+                if *mode != Mode::Home
+                    && let Some(global) = self
+                        .keybindings
+                        .get(&Mode::Home)
+                        .and_then(|home| home.get(keys))
+                    && global != action
+                    && (default_bindings.and_then(|defaults| defaults.get(keys)) != Some(action)
+                        || default_global != Some(global))
+                {
+                    diagnostics.push(format!(
+                        "{mode:?}: {keys_str} runs {action} instead of the global {global} from Home"
+                    ));
+                }
+                if let Some(prefix) = (1..keys.len())
+                    .map(|len| &keys[..len])
+                    .find(|prefix| bindings.contains_key(*prefix))
+                {
+                    diagnostics.push(format!(
+                        "{mode:?}: {keys_str} never fires, {} is bound on its own",
+                        key_sequence_to_string(prefix)
+                    ));
+                }
+            }
+
+            // Overriding every key of a default action leaves no way to run it
+            for action in default_bindings
+                .into_iter()
+                .flat_map(|defaults| defaults.values())
+            {
+                if !bindings.values().any(|bound| bound == action) {
+                    let message = format!("{mode:?}: {action} is no longer bound to any key");
+                    if !diagnostics.contains(&message) {
+                        diagnostics.push(message);
+                    }
+                }
+            }
+        }
+
+        diagnostics.sort();
+        diagnostics
+    }
+}
+
+/// Drop Shift from character keys since the character itself is already shifted.
+///
+/// Terminals disagree on whether e.g. `G` or `#` is reported with the Shift modifier, so keys are
+/// compared without it.
+pub fn normalize_key(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(_) => KeyEvent::new(key.code, key.modifiers - KeyModifiers::SHIFT),
+        _ => KeyEvent::new(key.code, key.modifiers),
+    }
+}
+
 impl<'de> Deserialize<'de> for KeyBindings {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -130,16 +552,28 @@ impl<'de> Deserialize<'de> for KeyBindings {
     {
         let parsed_map = HashMap::<Mode, HashMap<String, Action>>::deserialize(deserializer)?;
 
-        let keybindings = parsed_map
-            .into_iter()
-            .map(|(mode, inner_map)| {
-                let converted_inner_map = inner_map
+        let mut keybindings = HashMap::new();
+        for (mode, inner_map) in parsed_map {
+            let mut converted_inner_map = HashMap::new();
+            let mut spellings: HashMap<Vec<KeyEvent>, String> = HashMap::new();
+            for (key_str, cmd) in inner_map {
+                let keys: Vec<KeyEvent> = parse_key_sequence(&key_str)
+                    .map_err(|err| {
+                        serde::de::Error::custom(format!("keybindings.{mode:?}: {err}"))
+                    })?
                     .into_iter()
-                    .map(|(key_str, cmd)| (parse_key_sequence(&key_str).unwrap(), cmd))
+                    .map(normalize_key)
                     .collect();
-                (mode, converted_inner_map)
-            })
-            .collect();
+                // e.g. `<Ctrl-a>` and `<ctrl-A>` are the same key, so one would silently win
+                if let Some(other) = spellings.insert(keys.clone(), key_str.clone()) {
+                    return Err(serde::de::Error::custom(format!(
+                        "keybindings.{mode:?}: `{other}` and `{key_str}` are the same key"
+                    )));
+                }
+                converted_inner_map.insert(keys, cmd);
+            }
+            keybindings.insert(mode, converted_inner_map);
+        }
 
         Ok(KeyBindings(keybindings))
     }
@@ -148,6 +582,11 @@ impl<'de> Deserialize<'de> for KeyBindings {
 fn parse_key_event(raw: &str) -> Result<KeyEvent, String> {
     let raw_lower = raw.to_ascii_lowercase();
     let (remaining, modifiers) = extract_modifiers(&raw_lower);
+    // Unmodified characters keep their case so that e.g. `<G>` and `<g>` can be bound separately
+    let remaining = match &raw[raw.len() - remaining.len()..] {
+        c if modifiers.is_empty() && c.chars().count() == 1 => c,
+        _ => remaining,
+    };
     parse_key_code_with_modifiers(remaining, modifiers)
 }
 
@@ -213,8 +652,11 @@ fn parse_key_code_with_modifiers(
         "space" => KeyCode::Char(' '),
         "hyphen" => KeyCode::Char('-'),
         "minus" => KeyCode::Char('-'),
+        // `<` and `>` delimit keys, so they go by name
+        "lt" => KeyCode::Char('<'),
+        "gt" => KeyCode::Char('>'),
         "tab" => KeyCode::Tab,
-        c if c.len() == 1 => {
+        c if c.chars().count() == 1 => {
             let mut c = c.chars().next().unwrap();
             if modifiers.contains(KeyModifiers::SHIFT) {
                 c = c.to_ascii_uppercase();
@@ -248,6 +690,8 @@ pub fn key_event_to_string(key_event: &KeyEvent) -> String {
             &char
         }
         KeyCode::Char(' ') => "space",
+        KeyCode::Char('<') => "lt",
+        KeyCode::Char('>') => "gt",
         KeyCode::Char(c) => {
             char = c.to_string();
             &char
@@ -289,6 +733,12 @@ pub fn key_event_to_string(key_event: &KeyEvent) -> String {
     key
 }
 
+pub fn key_sequence_to_string(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .map(|key| format!("<{}>", key_event_to_string(key)))
+        .collect()
+}
+
 pub fn parse_key_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
     if raw.chars().filter(|c| *c == '>').count() != raw.chars().filter(|c| *c == '<').count() {
         return Err(format!("Unable to parse `{raw}`"));
@@ -357,6 +807,22 @@ pub fn parse_style(line: &str) -> Style {
     style
 }
 
+/// Why `parse_style` would ignore part of `line`, if it would.
+pub fn style_problem(line: &str) -> Option<String> {
+    let (foreground, background) =
+        line.split_at(line.to_lowercase().find("on ").unwrap_or(line.len()));
+    [foreground.to_string(), background.replace("on ", "")]
+        .iter()
+        .map(|part| process_color_string(part).0)
+        .map(|color| color.trim().to_string())
+        .find(|color| {
+            !color.is_empty()
+                && !["bold", "underline", "inverse"].contains(&color.as_str())
+                && parse_color(color).is_none()
+        })
+        .map(|color| format!("unknown color `{color}`"))
+}
+
 fn process_color_string(color_str: &str) -> (String, Modifier) {
     let color = color_str
         .replace("grey", "gray")
@@ -511,6 +977,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_model_params() -> Result<()> {
+        let source = r#"{
+            "chat": { "temperature": 0.7, "max_tokens": 1024 },
+            "models": { "anthropic/claude-3.5-sonnet": { "temperature": 0.3 } },
+        }"#;
+        let c: Config = config::Config::builder()
+            .add_source(config::File::from_str(source, config::FileFormat::Json5))
+            .build()?
+            .try_deserialize()?;
+        let params = ModelParams::for_model("anthropic/claude-3.5-sonnet", &c.chat, &c.models);
+        assert_eq!(params.temperature, Some(0.3));
+        assert_eq!(params.max_tokens, Some(1024));
+        let params = ModelParams::for_model("openai/gpt-4o", &c.chat, &c.models);
+        assert_eq!(params.temperature, Some(0.7));
+        Ok(())
+    }
+
     #[test]
     fn test_simple_keys() {
         assert_eq!(
@@ -527,6 +1011,11 @@ mod tests {
             parse_key_event("esc").unwrap(),
             KeyEvent::new(KeyCode::Esc, KeyModifiers::empty())
         );
+
+        assert_eq!(
+            parse_key_sequence("<lt>").unwrap(),
+            [KeyEvent::new(KeyCode::Char('<'), KeyModifiers::empty())]
+        );
     }
 
     #[test]
@@ -580,6 +1069,46 @@ mod tests {
         assert!(parse_key_event("ctrl-invalid-key").is_err());
     }
 
+    #[test]
+    fn test_shifted_characters() {
+        assert_eq!(
+            parse_key_event("G").unwrap(),
+            KeyEvent::new(KeyCode::Char('G'), KeyModifiers::empty())
+        );
+        assert_eq!(
+            normalize_key(parse_key_event("shift-g").unwrap()),
+            normalize_key(KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            normalize_key(KeyEvent::new(KeyCode::Char('#'), KeyModifiers::SHIFT)),
+            parse_key_event("#").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_keybinding_diagnostics() -> Result<()> {
+        let mut c = Config::new()?;
+        assert_eq!(c.keybinding_diagnostics(), Vec::<String>::new());
+
+        let chat = c.keybindings.get_mut(&Mode::Chat).unwrap();
+        chat.insert(parse_key_sequence("<j>").unwrap(), Action::ScrollDown);
+        chat.insert(parse_key_sequence("<Down>").unwrap(), Action::ScrollDown);
+        chat.insert(parse_key_sequence("<g><g>").unwrap(), Action::SelectFirst);
+        chat.insert(parse_key_sequence("<ctrl-n>").unwrap(), Action::SelectLast);
+        assert_eq!(
+            c.keybinding_diagnostics(),
+            vec![
+                "Chat: <ctrl-n> runs SelectLast instead of the global NewChat from Home",
+                "Chat: <down> runs ScrollDown instead of the default SelectNext",
+                "Chat: <g><g> never fires, <g> is bound on its own",
+                "Chat: <g><g> never fires, key sequences only work in Home",
+                "Chat: <j> runs ScrollDown instead of the default SelectNext",
+                "Chat: SelectNext is no longer bound to any key",
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_case_insensitivity() {
         assert_eq!(
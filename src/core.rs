@@ -0,0 +1,41 @@
+//! The chat engine: conversations, provider calls and sessions.
+//!
+//! Nothing in here knows about the terminal, so the TUI and the headless `--prompt` mode share it
+//! and it can be tested with plain tokio tests.
+
+pub mod attachments;
+pub mod budget;
+pub mod commit;
+pub mod conversation;
+pub mod engine;
+pub mod health;
+pub mod keys;
+pub mod length;
+pub mod metrics;
+pub mod middleware;
+pub mod models;
+pub mod notes;
+pub mod openapi;
+pub mod personas;
+pub mod prose;
+pub mod provider;
+pub mod rag;
+pub mod rate_limit;
+pub mod roundtable;
+pub mod session;
+pub mod share;
+pub mod stream;
+pub mod summary;
+pub mod templates;
+pub mod todos;
+pub mod tools;
+pub mod transcript;
+pub mod translate;
+pub mod usage;
+pub mod walk;
+pub mod workflow;
+
+pub use conversation::{
+    ChatMessage, Conversation, Delta, MessageMetadata, MessageVersion, summarize,
+};
+pub use engine::{Completion, Engine, Settings};
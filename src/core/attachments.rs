@@ -0,0 +1,348 @@
+//! Text attached to the next message, like the output of `git diff` for `/diff`.
+
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::core::walk::walk;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AttachmentsConfig {
+    /// Longer attachments are cut off at a line boundary, so that one large diff doesn't use up
+    /// the context window.
+    pub max_bytes: usize,
+    /// How many levels of directories `/tree` shows.
+    pub tree_depth: usize,
+    /// Entries `/tree` lists at most, so that a huge directory stays readable.
+    pub tree_max_entries: usize,
+    /// Offer newly copied text as an attachment from the start, as `/clipboard` does.
+    pub watch_clipboard: bool,
+    /// How often the clipboard is checked while watching it.
+    pub clipboard_poll_ms: u64,
+}
+
+impl Default for AttachmentsConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 50_000,
+            tree_depth: 3,
+            tree_max_entries: 500,
+            watch_clipboard: false,
+            clipboard_poll_ms: 500,
+        }
+    }
+}
+
+/// Text sent along with the next message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// What the text is, e.g. `git diff HEAD`.
+    pub label: String,
+    /// Markdown code block language.
+    pub language: String,
+    pub text: String,
+    /// Lines left out to stay within the size limit.
+    pub omitted_lines: usize,
+    /// Shown as a placeholder line in the transcript until the message is expanded.
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+impl Attachment {
+    /// An attachment of `text`, cut off after the last whole line within `max_bytes`.
+    pub fn new(label: &str, language: &str, text: &str, max_bytes: usize) -> Self {
+        let mut kept = 0;
+        for line in text.split_inclusive('\n') {
+            if kept + line.len() > max_bytes {
+                break;
+            }
+            kept += line.len();
+        }
+        Self {
+            label: label.to_string(),
+            language: language.to_string(),
+            text: text[..kept].trim_end().to_string(),
+            omitted_lines: text[kept..].lines().count(),
+            collapsed: false,
+        }
+    }
+
+    /// A large paste, sent in full but shown collapsed.
+    pub fn pasted(text: &str) -> Self {
+        Self {
+            label: "Pasted text".to_string(),
+            language: String::new(),
+            text: text.trim_end().to_string(),
+            omitted_lines: 0,
+            collapsed: true,
+        }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.text.lines().count()
+    }
+
+    /// The attachment as it is added to the message.
+    fn render(&self) -> String {
+        let mut text = format!("{}:\n```{}\n{}\n```", self.label, self.language, self.text);
+        if self.omitted_lines > 0 {
+            text.push_str(&format!("\n({} more lines left out)", self.omitted_lines));
+        }
+        text
+    }
+}
+
+/// `text` followed by the attachments, and where in it the ones shown collapsed are.
+pub fn attach(text: &str, attachments: &[Attachment]) -> (String, Vec<Range<usize>>) {
+    let mut text = text.to_string();
+    let mut collapsed = Vec::new();
+    for attachment in attachments {
+        text.push_str("\n\n");
+        let start = text.len();
+        text.push_str(&attachment.render());
+        if attachment.collapsed {
+            collapsed.push(start..text.len());
+        }
+    }
+    (text, collapsed)
+}
+
+/// The changes in the git repository at `dir` since `reference`, or all uncommitted changes,
+/// staged or not.
+pub async fn git_diff(
+    dir: &Path,
+    reference: Option<&str>,
+    config: &AttachmentsConfig,
+) -> Result<Attachment> {
+    let reference = reference.unwrap_or("HEAD");
+    // git would take it for an option, e.g. `--output=<file>`
+    if reference.starts_with('-') {
+        return Err(eyre!("`{reference}` is not a git reference"));
+    }
+    run_git_diff(dir, &[reference], config)
+        .await?
+        .ok_or_else(|| eyre!("No changes since {reference}"))
+}
+
+/// The changes staged for the next commit in the git repository at `dir`.
+pub async fn staged_diff(dir: &Path, config: &AttachmentsConfig) -> Result<Attachment> {
+    run_git_diff(dir, &["--cached"], config)
+        .await?
+        .ok_or_else(|| eyre!("Nothing is staged, `git add` the changes to commit first"))
+}
+
+/// `git diff` with `args`, or `None` if there are no changes.
+async fn run_git_diff(
+    dir: &Path,
+    args: &[&str],
+    config: &AttachmentsConfig,
+) -> Result<Option<Attachment>> {
+    let label = format!("git diff {}", args.join(" "));
+    let output = tokio::process::Command::new("git")
+        .arg("diff")
+        .args(args)
+        .arg("--")
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|err| eyre!("Failed to run git: {err}"))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{label} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let diff = String::from_utf8_lossy(&output.stdout);
+    if diff.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Attachment::new(
+        &label,
+        "diff",
+        &diff,
+        config.max_bytes,
+    )))
+}
+
+/// The files and directories under `dir` drawn as a tree, leaving out the ones [`walk`] skips.
+pub fn tree(dir: &Path, config: &AttachmentsConfig) -> Result<Attachment> {
+    if !dir.is_dir() {
+        return Err(eyre!("{} is not a directory", dir.display()));
+    }
+    // Walked depth first with siblings sorted by name
+    let mut entries: Vec<(usize, PathBuf, bool)> = Vec::new();
+    let mut left_out = 0;
+    // Unreadable entries are left out like ignored ones
+    for entry in walk(dir, Some(config.tree_depth))?.flatten() {
+        if entry.depth() == 0 {
+            continue;
+        }
+        if entries.len() == config.tree_max_entries {
+            left_out += 1;
+            continue;
+        }
+        let is_dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+        entries.push((entry.depth(), entry.file_name().into(), is_dir));
+    }
+
+    // An entry is the last of its siblings if no entry at its depth follows before the walk
+    // goes back up to its parent's depth
+    let mut is_last = vec![false; entries.len()];
+    let mut followed_at: Vec<bool> = Vec::new();
+    for (index, (depth, _, _)) in entries.iter().enumerate().rev() {
+        followed_at.resize(depth + 1, false);
+        is_last[index] = !followed_at[*depth];
+        followed_at[*depth] = true;
+    }
+
+    let name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| dir.display().to_string());
+    let mut text = format!("{name}/\n");
+    // Whether the ancestor at each depth was the last of its siblings
+    let mut ancestors_last: Vec<bool> = Vec::new();
+    for ((depth, name, is_dir), last) in entries.iter().zip(is_last) {
+        ancestors_last.truncate(depth - 1);
+        for ancestor_last in &ancestors_last {
+            text.push_str(if *ancestor_last { "    " } else { "│   " });
+        }
+        text.push_str(if last { "└── " } else { "├── " });
+        text.push_str(&name.to_string_lossy());
+        if *is_dir {
+            text.push('/');
+        }
+        text.push('\n');
+        ancestors_last.push(last);
+    }
+    if left_out > 0 {
+        text.push_str(&format!("({left_out} more entries left out)\n"));
+    }
+    Ok(Attachment::new(
+        &format!("Directory tree of {}", dir.display()),
+        "",
+        &text,
+        config.max_bytes,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_attach_truncated() {
+        let attachment = Attachment::new("notes", "", "one\ntwo\nthree\n", 9);
+        assert_eq!(attachment.text, "one\ntwo");
+        assert_eq!(attachment.omitted_lines, 1);
+        assert_eq!(
+            attach("Summarize", &[attachment]),
+            (
+                "Summarize\n\nnotes:\n```\none\ntwo\n```\n(1 more lines left out)".to_string(),
+                vec![]
+            )
+        );
+        let (text, collapsed) = attach("Look", &[Attachment::pasted("a\nb\n")]);
+        assert_eq!(text, "Look\n\nPasted text:\n```\na\nb\n```");
+        assert_eq!(collapsed.len(), 1);
+        assert_eq!(&text[collapsed[0].clone()], "Pasted text:\n```\na\nb\n```");
+    }
+
+    #[test]
+    fn test_tree() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-tree-{}", new_session_id()));
+        for path in ["src/core", "target/debug", ".git"] {
+            std::fs::create_dir_all(dir.join(path))?;
+        }
+        for file in [
+            "src/main.rs",
+            "src/core/engine.rs",
+            "README.md",
+            "target/debug/app",
+        ] {
+            std::fs::write(dir.join(file), "")?;
+        }
+        std::fs::write(dir.join(".gitignore"), "target/\n")?;
+        let config = AttachmentsConfig::default();
+        let tree = tree(&dir, &config)?;
+        let name = dir.file_name().unwrap().to_string_lossy();
+        assert_eq!(
+            tree.text,
+            format!(
+                "{name}/\n\
+                 ├── README.md\n\
+                 └── src/\n    \
+                     ├── core/\n    \
+                     │   └── engine.rs\n    \
+                     └── main.rs"
+            )
+        );
+
+        let shallow = AttachmentsConfig {
+            tree_depth: 1,
+            tree_max_entries: 1,
+            ..config
+        };
+        assert_eq!(
+            super::tree(&dir, &shallow)?.text,
+            format!("{name}/\n└── README.md\n(1 more entries left out)")
+        );
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_git_diff() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-diff-{}", new_session_id()));
+        std::fs::create_dir_all(&dir)?;
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(&dir)
+                .output()
+        };
+        git(&["init", "-q"])?;
+        git(&["config", "user.email", "test@example.com"])?;
+        git(&["config", "user.name", "Test"])?;
+        std::fs::write(dir.join("a.txt"), "old\n")?;
+        git(&["add", "."])?;
+        git(&["commit", "-qm", "first"])?;
+
+        let config = AttachmentsConfig::default();
+        assert!(git_diff(&dir, None, &config).await.is_err());
+        std::fs::write(dir.join("a.txt"), "new\n")?;
+        let attachment = git_diff(&dir, None, &config).await?;
+        assert_eq!(attachment.label, "git diff HEAD");
+        assert!(
+            attachment.text.ends_with("-old\n+new"),
+            "{}",
+            attachment.text
+        );
+        assert!(git_diff(&dir, Some("nope"), &config).await.is_err());
+        let output = dir.join("out.txt");
+        let option = format!("--output={}", output.display());
+        assert!(git_diff(&dir, Some(&option), &config).await.is_err());
+        assert!(!output.exists());
+        assert!(staged_diff(&dir, &config).await.is_err());
+        git(&["add", "."])?;
+        assert_eq!(staged_diff(&dir, &config).await?.text, attachment.text);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use serde::Deserialize;
+
+/// Spending limits in dollars; days and months are counted in UTC.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    pub daily: Limits,
+    pub monthly: Limits,
+}
+
+/// A limit that only warns and one that blocks sending; either may be left out.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    pub soft: Option<f64>,
+    pub hard: Option<f64>,
+}
+
+/// Money spent so far in the current day and month.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Spending {
+    pub today: f64,
+    pub month: f64,
+}
+
+/// How spending compares to the budget, with a message saying which limit was reached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetStatus {
+    Within,
+    /// Over a soft limit: sending still works, but the user should know.
+    Soft(String),
+    /// Over a hard limit: nothing is sent unless the user overrides it.
+    Hard(String),
+}
+
+impl BudgetConfig {
+    pub fn check(&self, spending: Spending) -> BudgetStatus {
+        let periods = [
+            ("today", spending.today, &self.daily),
+            ("this month", spending.month, &self.monthly),
+        ];
+        for (period, spent, limits) in periods {
+            if let Some(hard) = limits.hard
+                && spent >= hard
+            {
+                return BudgetStatus::Hard(format!(
+                    "Spent ${spent:.2} {period}, over the hard limit of ${hard:.2}"
+                ));
+            }
+        }
+        for (period, spent, limits) in periods {
+            if let Some(soft) = limits.soft
+                && spent >= soft
+            {
+                return BudgetStatus::Soft(format!(
+                    "Spent ${spent:.2} {period}, over the soft limit of ${soft:.2}"
+                ));
+            }
+        }
+        BudgetStatus::Within
+    }
+
+    /// Like [`BudgetConfig::check`], but also at a hard limit if a request estimated to cost
+    /// `estimate` would cross it.
+    pub fn check_request(&self, spending: Spending, estimate: f64) -> BudgetStatus {
+        let status = self.check(spending);
+        if matches!(status, BudgetStatus::Hard(_)) {
+            return status;
+        }
+        let periods = [
+            ("today", spending.today, &self.daily),
+            ("this month", spending.month, &self.monthly),
+        ];
+        for (period, spent, limits) in periods {
+            if let Some(hard) = limits.hard
+                && spent + estimate >= hard
+            {
+                return BudgetStatus::Hard(format!(
+                    "Spent ${spent:.2} {period}, and the request costs about ${estimate:.2}, \
+                     over the hard limit of ${hard:.2}"
+                ));
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let budget = BudgetConfig {
+            daily: Limits {
+                soft: Some(0.5),
+                hard: Some(1.0),
+            },
+            monthly: Limits {
+                soft: None,
+                hard: Some(10.0),
+            },
+        };
+        let spending = Spending {
+            today: 0.75,
+            month: 5.0,
+        };
+        assert_eq!(
+            budget.check(spending),
+            BudgetStatus::Soft("Spent $0.75 today, over the soft limit of $0.50".to_string())
+        );
+        assert_eq!(
+            budget.check(Spending {
+                today: 0.0,
+                month: 10.0
+            }),
+            BudgetStatus::Hard(
+                "Spent $10.00 this month, over the hard limit of $10.00".to_string()
+            )
+        );
+        assert_eq!(
+            BudgetConfig::default().check(spending),
+            BudgetStatus::Within
+        );
+        assert_eq!(
+            budget.check_request(spending, 0.1),
+            BudgetStatus::Soft("Spent $0.75 today, over the soft limit of $0.50".to_string())
+        );
+        assert_eq!(
+            budget.check_request(spending, 0.3),
+            BudgetStatus::Hard(
+                "Spent $0.75 today, and the request costs about $0.30, over the hard limit of $1.00"
+                    .to_string()
+            )
+        );
+    }
+}
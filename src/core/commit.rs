@@ -0,0 +1,117 @@
+//! Commit messages written by the model from the staged changes, for `lazychat commit` and
+//! `/commit`.
+
+use std::{path::Path, process::Stdio};
+
+use color_eyre::{Result, eyre::eyre};
+use tokio::io::AsyncWriteExt;
+
+use super::attachments::{self, Attachment};
+
+pub const SYSTEM_PROMPT: &str = "You write git commit messages in the Conventional Commits \
+format: a `type(scope): summary` line of at most 72 characters in the imperative mood, then a \
+blank line and a short body saying what changed and why, wrapped at 72 columns. Leave the body \
+out for trivial changes. Answer with the commit message only.";
+
+/// The question asking for the message of `diff`.
+pub fn question(diff: &Attachment) -> String {
+    attachments::attach(
+        "Write the commit message for these staged changes.",
+        std::slice::from_ref(diff),
+    )
+    .0
+}
+
+/// The message in the model's reply, without a code fence around it.
+pub fn message(reply: &str) -> String {
+    let reply = reply.trim();
+    match reply.strip_prefix("```") {
+        Some(fenced) => fenced
+            .split_once('\n')
+            .map_or("", |(_, rest)| rest)
+            .trim_end()
+            .trim_end_matches("```")
+            .trim()
+            .to_string(),
+        None => reply.to_string(),
+    }
+}
+
+/// Commit the staged changes in the repository at `dir` with `message`; returns what git
+/// printed, e.g. `[main 1a2b3c4] feat: add x`.
+pub async fn commit(dir: &Path, message: &str) -> Result<String> {
+    let mut child = tokio::process::Command::new("git")
+        .args(["commit", "--file", "-"])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| eyre!("Failed to run git: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        return Err(eyre!(
+            "git commit failed: {}",
+            if stderr.trim().is_empty() {
+                stdout.trim()
+            } else {
+                stderr.trim()
+            }
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_message() {
+        assert_eq!(message("feat: add x\n"), "feat: add x");
+        assert_eq!(
+            message("```text\nfix(ui): wrap titles\n\nLong ones were cut.\n```"),
+            "fix(ui): wrap titles\n\nLong ones were cut."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-commit-{}", new_session_id()));
+        std::fs::create_dir_all(&dir)?;
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(&dir)
+                .output()
+        };
+        git(&["init", "-q", "-b", "main"])?;
+        git(&["config", "user.email", "test@example.com"])?;
+        git(&["config", "user.name", "Test"])?;
+        assert!(commit(&dir, "feat: nothing").await.is_err());
+
+        std::fs::write(dir.join("a.txt"), "a\n")?;
+        git(&["add", "."])?;
+        let summary = commit(&dir, "feat: add a\n\nThe first file.").await?;
+        assert!(summary.starts_with("[main (root-commit) "), "{summary}");
+        let log = git(&["log", "--format=%B"])?;
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout).trim(),
+            "feat: add a\n\nThe first file."
+        );
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,850 @@
+use std::{
+    collections::HashSet,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{
+    attachments::Attachment,
+    length::ResponseLength,
+    provider::{ChatConfig, ModelParams},
+    session::new_session_id,
+    translate::Translation,
+};
+use crate::tokens;
+
+/// Anthropic allows at most four `cache_control` breakpoints per request.
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    /// Thinking the model did before answering, kept out of the prompt.
+    #[serde(default)]
+    pub reasoning: String,
+    #[serde(default)]
+    pub metadata: MessageMetadata,
+    /// Pinned messages are listed in the quick-jump menu and kept in the prompt.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Vim-style marks set on this message; each mark belongs to at most one message.
+    #[serde(default)]
+    pub marks: Vec<char>,
+    /// Functions the model asked to call in this reply.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// On `tool` messages: the call this is the result of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// On `tool` messages cut down for the model: the whole output, shown only to the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_output: Option<String>,
+    /// Another model's answer to the question before it, kept for comparison but left out of
+    /// the prompt.
+    #[serde(default)]
+    pub alternative: bool,
+    /// The other answers to the same question, from regenerating the reply, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<MessageVersion>,
+    /// Where the answer shown, and sent as context, comes among the versions.
+    #[serde(default)]
+    pub version: usize,
+    /// Parts of the content shown as a placeholder line until the message is expanded, like
+    /// large pastes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collapsed: Vec<Range<usize>>,
+}
+
+/// One answer of a reply that was regenerated.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageVersion {
+    pub content: String,
+    #[serde(default)]
+    pub reasoning: String,
+    #[serde(default)]
+    pub metadata: MessageMetadata,
+}
+
+/// A function call requested by the model.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// JSON object, exactly as the model wrote it.
+    pub arguments: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Every answer of a regenerated reply, oldest first, the one shown among them.
+    pub fn all_versions(&self) -> Vec<MessageVersion> {
+        let mut versions = self.versions.clone();
+        let shown = MessageVersion {
+            content: self.content.clone(),
+            reasoning: self.reasoning.clone(),
+            metadata: self.metadata.clone(),
+        };
+        versions.insert(self.version.min(versions.len()), shown);
+        versions
+    }
+
+    /// Show another version of the reply, which is also what the model sees from now on.
+    ///
+    /// Returns whether there is such a version.
+    pub fn select_version(&mut self, version: usize) -> bool {
+        let mut versions = self.all_versions();
+        if version >= versions.len() {
+            return false;
+        }
+        let shown = versions.remove(version);
+        self.content = shown.content;
+        self.reasoning = shown.reasoning;
+        self.metadata = shown.metadata;
+        self.versions = versions;
+        self.version = version;
+        true
+    }
+}
+
+/// Response details reported by the API, recorded on assistant messages.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageMetadata {
+    /// The model that wrote the reply, as reported by the provider.
+    pub model: Option<String>,
+    /// The persona the model played.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    pub prompt_tokens: Option<u64>,
+    /// Prompt tokens read from the provider's prompt cache.
+    pub cached_tokens: Option<u64>,
+    /// Prompt tokens written to the prompt cache, i.e. cache misses.
+    pub cache_write_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    /// Cost in credits (USD) as reported by OpenRouter.
+    pub cost: Option<f64>,
+    pub latency_ms: Option<u64>,
+    /// Time from sending the request to the first streamed token.
+    pub first_token_ms: Option<u64>,
+    /// Why generation stopped: `stop`, `length` (hit `max_tokens`), `content_filter`, ...
+    pub finish_reason: Option<String>,
+    /// Generation speed once the first token arrived.
+    pub tokens_per_second: Option<f64>,
+}
+
+impl MessageMetadata {
+    /// Whether the reply was cut off by the `max_tokens` limit.
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
+
+    /// Add up the usage of a reply and its continuation; the finish reason and speed are the
+    /// continuation's.
+    fn merge(&mut self, next: MessageMetadata) {
+        fn add<T: std::ops::Add<Output = T>>(total: Option<T>, next: Option<T>) -> Option<T> {
+            match (total, next) {
+                (Some(total), Some(next)) => Some(total + next),
+                (total, next) => total.or(next),
+            }
+        }
+        // A continuation keeps the attribution of the reply it continues
+        self.model = self.model.take().or(next.model);
+        self.prompt_tokens = add(self.prompt_tokens, next.prompt_tokens);
+        self.cached_tokens = add(self.cached_tokens, next.cached_tokens);
+        self.cache_write_tokens = add(self.cache_write_tokens, next.cache_write_tokens);
+        self.completion_tokens = add(self.completion_tokens, next.completion_tokens);
+        self.cost = add(self.cost, next.cost);
+        self.latency_ms = add(self.latency_ms, next.latency_ms);
+        self.first_token_ms = self.first_token_ms.or(next.first_token_ms);
+        self.finish_reason = next.finish_reason;
+        self.tokens_per_second = next.tokens_per_second;
+    }
+}
+
+/// The conversation being chatted in and the settings it is sent with.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    /// Id of the session the conversation is saved as.
+    pub id: String,
+    /// Title set by the user; sessions are titled after their first message otherwise.
+    pub title: Option<String>,
+    /// What the session is about, e.g. a summary saved with `/summarize save`.
+    pub description: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub model: String,
+    pub system_prompt: String,
+    /// The persona the model plays, shown instead of `AI` on its replies.
+    pub persona: Option<String>,
+    /// Whether a reply is being waited for.
+    pub is_loading: bool,
+    /// The reply received so far while it is streamed.
+    #[serde(skip)]
+    pub partial: Option<PartialReply>,
+    /// Messages waiting to be sent once the provider can be reached, oldest first.
+    pub queued: Vec<String>,
+    /// Preset for how long replies should be.
+    pub length: ResponseLength,
+    /// Messages are translated instead of answered, see `/translate`.
+    pub translation: Option<Translation>,
+    /// Overrides the configured temperature, e.g. as set by a prompt template.
+    pub temperature: Option<f64>,
+    /// Tools the user allowed to run without asking for the rest of the session.
+    #[serde(skip)]
+    pub allowed_tools: HashSet<String>,
+    /// Agent mode: tool calls run without asking, up to a step budget per user message.
+    #[serde(skip)]
+    pub agent: bool,
+    /// Tool calls run in agent mode since the user's last message.
+    #[serde(skip)]
+    pub agent_steps: usize,
+    /// Text added to the next message the user sends, e.g. with `/diff`.
+    #[serde(skip)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A piece of a streamed reply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Delta {
+    Reasoning(String),
+    Content(String),
+}
+
+/// A reply that is still being streamed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialReply {
+    pub reasoning: String,
+    pub content: String,
+    /// Estimated from the text, the API only reports usage at the end.
+    pub tokens: usize,
+    pub started: Instant,
+    first_token: Option<Instant>,
+    /// When the rate limiter lets the request go out, if it has to wait.
+    pub throttled_until: Option<Instant>,
+    /// Set once the provider refused the request with 429; `throttled_until` is then when it
+    /// is sent again.
+    pub rate_limited: bool,
+    /// Whether the reply continues the last message, a reply cut off by `max_tokens`.
+    pub continues: bool,
+    /// Where an alternative answer goes, right after the reply it is compared with.
+    pub alternative_at: Option<usize>,
+    /// The versions of the reply being regenerated, which the new one is added to.
+    pub versions: Vec<MessageVersion>,
+}
+
+impl PartialReply {
+    pub fn new() -> Self {
+        Self {
+            reasoning: String::new(),
+            content: String::new(),
+            tokens: 0,
+            started: Instant::now(),
+            first_token: None,
+            throttled_until: None,
+            rate_limited: false,
+            continues: false,
+            alternative_at: None,
+            versions: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, delta: &Delta, tokens: usize) {
+        self.first_token.get_or_insert_with(Instant::now);
+        match delta {
+            Delta::Reasoning(text) => self.reasoning.push_str(text),
+            Delta::Content(text) => self.content.push_str(text),
+        }
+        self.tokens += tokens;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reasoning.is_empty() && self.content.is_empty()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Wait for the provider's rate limit to reset before the request is sent again.
+    pub fn retry_after(&mut self, wait: Duration) {
+        self.rate_limited = true;
+        self.throttled_until = Some(Instant::now() + wait);
+    }
+
+    /// How long the request is still held back by a rate limit.
+    pub fn throttled_for(&self) -> Option<Duration> {
+        self.throttled_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|wait| !wait.is_zero())
+    }
+
+    /// Generation speed so far, measured from the first token.
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let seconds = self.first_token?.elapsed().as_secs_f64();
+        (seconds > 0.0).then(|| self.tokens as f64 / seconds)
+    }
+}
+
+impl Default for PartialReply {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Conversation {
+    pub fn new(model: &str) -> Self {
+        Self {
+            id: new_session_id(),
+            model: model.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Replace the messages, starting a new session; model, system prompt and persona carry over.
+    pub fn restart(&mut self, messages: Vec<ChatMessage>) {
+        self.id = new_session_id();
+        self.title = None;
+        self.description = None;
+        self.messages = messages;
+        self.queued.clear();
+        self.allowed_tools.clear();
+        self.agent_steps = 0;
+    }
+
+    pub fn push(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+    }
+
+    /// Add a reply, appending it to the last message if it continues that.
+    pub fn push_reply(&mut self, message: ChatMessage) {
+        self.is_loading = false;
+        let partial = self.partial.take();
+        if let Some(index) = partial.as_ref().and_then(|partial| partial.alternative_at) {
+            let index = index.min(self.messages.len());
+            self.messages.insert(
+                index,
+                ChatMessage {
+                    alternative: true,
+                    ..message
+                },
+            );
+            return;
+        }
+        let (continues, versions) = partial
+            .map(|partial| (partial.continues, partial.versions))
+            .unwrap_or_default();
+        match self.messages.last_mut() {
+            Some(last) if continues => {
+                last.content.push_str(&message.content);
+                last.reasoning.push_str(&message.reasoning);
+                last.metadata.merge(message.metadata);
+            }
+            _ => self.push(ChatMessage {
+                version: versions.len(),
+                versions,
+                ..message
+            }),
+        }
+    }
+
+    /// Whether the last message is a reply cut off by `max_tokens`, which can be continued.
+    pub fn can_continue(&self) -> bool {
+        !self.is_loading
+            && self.messages.last().is_some_and(|message| {
+                message.role != "user" && !message.alternative && message.metadata.is_truncated()
+            })
+    }
+
+    /// Record a failed request as a system message.
+    pub fn push_error(&mut self, err: &str) {
+        self.is_loading = false;
+        self.partial = None;
+        self.push(ChatMessage::new("system", format!("Error: {err}")));
+    }
+
+    /// Take back the question of a request that never reached the provider, to send it again
+    /// before anything else queued.
+    pub fn requeue_unanswered(&mut self) {
+        if let Some(text) = self.take_back_unanswered() {
+            self.queued.insert(0, text);
+        }
+    }
+
+    /// Stop waiting for the reply, dropping anything streamed, and remove the question it
+    /// answers. Returns the question's text, if the last message was one.
+    pub fn take_back_unanswered(&mut self) -> Option<String> {
+        self.is_loading = false;
+        self.partial = None;
+        if !self.awaits_reply() {
+            return None;
+        }
+        self.messages.pop().map(|message| message.content)
+    }
+
+    /// Stop waiting for the reply, keeping the text streamed so far as a reply whose finish
+    /// reason is `cancelled`. Returns whether there was a reply in progress.
+    pub fn cancel_reply(&mut self) -> bool {
+        if !self.is_loading {
+            return false;
+        }
+        match self.partial.as_ref().filter(|partial| !partial.is_empty()) {
+            Some(partial) => {
+                let mut message = ChatMessage::new("AI", partial.content.clone());
+                message.reasoning = partial.reasoning.clone();
+                message.metadata = MessageMetadata {
+                    latency_ms: Some(partial.started.elapsed().as_millis() as u64),
+                    finish_reason: Some("cancelled".to_string()),
+                    ..Default::default()
+                };
+                self.push_reply(message);
+            }
+            None => {
+                self.is_loading = false;
+                self.partial = None;
+            }
+        }
+        true
+    }
+
+    /// Calls of the last reply that have no result yet, in the order the model made them.
+    pub fn pending_tool_calls(&self) -> Vec<&ToolCall> {
+        let Some(reply) = self
+            .messages
+            .iter()
+            .rposition(|message| message.role != "tool")
+        else {
+            return Vec::new();
+        };
+        let answered: Vec<&str> = self.messages[reply + 1..]
+            .iter()
+            .filter_map(|message| message.tool_call_id.as_deref())
+            .collect();
+        self.messages[reply]
+            .tool_calls
+            .iter()
+            .filter(|call| !answered.contains(&call.id.as_str()))
+            .collect()
+    }
+
+    /// Add the output of a call as a `tool` message.
+    pub fn push_tool_result(&mut self, call: &ToolCall, output: impl Into<String>) {
+        let mut message = ChatMessage::new("tool", output);
+        message.tool_call_id = Some(call.id.clone());
+        self.push(message);
+    }
+
+    /// The call a `tool` message answers, looked up in the replies before it.
+    pub fn tool_call(&self, message: &ChatMessage) -> Option<&ToolCall> {
+        let id = message.tool_call_id.as_ref()?;
+        self.messages
+            .iter()
+            .flat_map(|message| &message.tool_calls)
+            .find(|call| &call.id == id)
+    }
+
+    /// Whether the last message is a question waiting for an answer.
+    pub fn awaits_reply(&self) -> bool {
+        self.messages
+            .last()
+            .is_some_and(|message| message.role == "user")
+    }
+
+    pub fn toggle_pin(&mut self, index: usize) {
+        if let Some(message) = self.messages.get_mut(index) {
+            message.pinned = !message.pinned;
+        }
+    }
+
+    /// Set a mark on a message, moving it off any other message.
+    pub fn set_mark(&mut self, mark: char, index: usize) {
+        for message in self.messages.iter_mut() {
+            message.marks.retain(|m| *m != mark);
+        }
+        if let Some(message) = self.messages.get_mut(index) {
+            message.marks.push(mark);
+            message.marks.sort_unstable();
+        }
+    }
+
+    pub fn update_message(&mut self, index: usize, content: &str) {
+        if let Some(message) = self.messages.get_mut(index) {
+            message.content = content.to_string();
+            message.collapsed.clear();
+        }
+    }
+
+    pub fn delete_message(&mut self, index: usize) {
+        if index < self.messages.len() {
+            self.messages.remove(index);
+        }
+    }
+
+    /// Drop the messages after the one to regenerate.
+    ///
+    /// Regenerating a question asks it again, regenerating an answer replaces it. Returns whether
+    /// a question is left to be answered.
+    pub fn truncate_for_regenerate(&mut self, index: usize) -> bool {
+        let Some(message) = self.messages.get(index) else {
+            return false;
+        };
+        let keep = if message.role == "user" {
+            index + 1
+        } else {
+            index
+        };
+        self.messages.truncate(keep);
+        self.awaits_reply()
+    }
+
+    /// The earlier versions of the reply regenerated from `index`: every version of the reply it
+    /// replaces.
+    pub fn versions_for_regenerate(&self, index: usize) -> Vec<MessageVersion> {
+        let Some(message) = self.messages.get(index) else {
+            return Vec::new();
+        };
+        let replaced = if message.role == "user" {
+            self.messages.get(index + 1)
+        } else {
+            Some(message)
+        };
+        let Some(replaced) = replaced.filter(|reply| reply.role == "AI" && !reply.alternative)
+        else {
+            return Vec::new();
+        };
+        replaced.all_versions()
+    }
+
+    /// The messages up to and including the given one.
+    pub fn fork(&self, index: usize) -> Vec<ChatMessage> {
+        let end = (index + 1).min(self.messages.len());
+        self.messages[..end].to_vec()
+    }
+
+    /// The chat completion request for the conversation.
+    pub fn request_body(&self, config: &ChatConfig, params: &ModelParams) -> serde_json::Value {
+        // With prompt caching, the system prompt and the first long messages become cache
+        // breakpoints; everything up to a breakpoint is cached
+        let cache = &config.prompt_cache;
+        let mut breakpoints = if cache.enabled {
+            MAX_CACHE_BREAKPOINTS
+        } else {
+            0
+        };
+        let mut content = |text: &str| {
+            // A token is at least a byte, so short texts are skipped without tokenizing them
+            if breakpoints > 0
+                && text.len() >= cache.min_tokens
+                && tokens::count_tokens(&self.model, text) >= cache.min_tokens
+            {
+                breakpoints -= 1;
+                json!([{ "type": "text", "text": text, "cache_control": { "type": "ephemeral" } }])
+            } else {
+                json!(text)
+            }
+        };
+
+        // Prepare messages with optional system prompt
+        let mut messages = Vec::new();
+
+        // Add system prompt if it exists and is not empty, with the length preset's instruction;
+        // translating replaces both
+        let preset = config.lengths.get(self.length);
+        let system_prompt = match &self.translation {
+            Some(translation) => translation.system_prompt(),
+            None => [self.system_prompt.as_str(), preset.instruction.as_str()]
+                .into_iter()
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        };
+        if !system_prompt.is_empty() {
+            messages.push(json!({
+                "role": "system",
+                "content": content(&system_prompt)
+            }));
+        }
+
+        // Add chat history, without the answers kept only for comparison
+        let history = self.messages.iter().filter(|msg| !msg.alternative);
+        messages.extend(history.map(|msg| {
+            // At a roundtable the other personas' replies are put to the one answering
+            let mut message = match &msg.metadata.persona {
+                Some(persona) if msg.role == "AI" && Some(persona) != self.persona.as_ref() => {
+                    json!({
+                        "role": "user",
+                        "content": content(&format!("{persona}: {}", msg.content))
+                    })
+                }
+                _ => json!({
+                    "role": msg.role,
+                    "content": content(&msg.content)
+                }),
+            };
+            if !msg.tool_calls.is_empty() {
+                message["tool_calls"] = msg
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": { "name": call.name, "arguments": call.arguments },
+                        })
+                    })
+                    .collect();
+            }
+            if let Some(id) = &msg.tool_call_id {
+                message["tool_call_id"] = json!(id);
+            }
+            message
+        }));
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true,
+            "usage": { "include": true }
+        });
+        if let Some(temperature) = params.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = preset.max_tokens.or(params.max_tokens) {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        body
+    }
+}
+
+/// Shorten text to its first line, truncated to `max_chars` characters.
+pub fn summarize(text: &str, max_chars: usize) -> String {
+    let first_line = text.lines().next().unwrap_or_default();
+    if first_line.chars().count() > max_chars {
+        let truncated: String = first_line.chars().take(max_chars).collect();
+        format!("{truncated}…")
+    } else {
+        first_line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_truncate_for_regenerate() {
+        let mut conversation = Conversation::new("mock");
+        for (role, content) in [("user", "a"), ("AI", "b"), ("user", "c"), ("AI", "d")] {
+            conversation.push(ChatMessage::new(role, content));
+        }
+
+        assert!(conversation.truncate_for_regenerate(3));
+        assert_eq!(conversation.messages.len(), 3);
+        assert!(conversation.truncate_for_regenerate(0));
+        assert_eq!(conversation.messages.len(), 1);
+        assert!(!conversation.truncate_for_regenerate(5));
+    }
+
+    #[test]
+    fn test_select_version() {
+        let mut reply = ChatMessage::new("AI", "third");
+        reply.versions = vec![
+            MessageVersion {
+                content: "first".to_string(),
+                ..Default::default()
+            },
+            MessageVersion {
+                content: "second".to_string(),
+                ..Default::default()
+            },
+        ];
+        reply.version = 2;
+        let contents = |reply: &ChatMessage| {
+            reply
+                .all_versions()
+                .into_iter()
+                .map(|version| version.content)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents(&reply), ["first", "second", "third"]);
+
+        assert!(reply.select_version(0));
+        assert_eq!((reply.content.as_str(), reply.version), ("first", 0));
+        assert_eq!(contents(&reply), ["first", "second", "third"]);
+        assert!(!reply.select_version(3));
+
+        // The version shown is what the model sees
+        let mut conversation = Conversation::new("mock");
+        conversation.push(ChatMessage::new("user", "q"));
+        conversation.push(reply);
+        let body = conversation.request_body(&ChatConfig::default(), &ModelParams::default());
+        assert_eq!(body["messages"][1]["content"], "first");
+    }
+
+    #[test]
+    fn test_cancel_reply_keeps_partial_output() {
+        let mut conversation = Conversation::new("mock");
+        conversation.push(ChatMessage::new("user", "a"));
+        assert!(!conversation.cancel_reply());
+
+        conversation.is_loading = true;
+        let mut partial = PartialReply::new();
+        partial.push(&Delta::Content("Half an ans".to_string()), 3);
+        conversation.partial = Some(partial);
+        assert!(conversation.cancel_reply());
+        assert!(!conversation.is_loading);
+        assert_eq!(conversation.partial, None);
+        let reply = &conversation.messages[1];
+        assert_eq!(reply.content, "Half an ans");
+        assert_eq!(reply.metadata.finish_reason.as_deref(), Some("cancelled"));
+
+        // Nothing streamed yet: nothing to keep, the question stays unanswered
+        conversation.push(ChatMessage::new("user", "b"));
+        conversation.is_loading = true;
+        conversation.partial = Some(PartialReply::new());
+        assert!(conversation.cancel_reply());
+        assert_eq!(conversation.messages.len(), 3);
+    }
+
+    #[test]
+    fn test_take_back_unanswered() {
+        let mut conversation = Conversation::new("mock");
+        conversation.push(ChatMessage::new("user", "a"));
+        conversation.push(ChatMessage::new("AI", "b"));
+        assert_eq!(conversation.take_back_unanswered(), None);
+        assert_eq!(conversation.messages.len(), 2);
+
+        conversation.push(ChatMessage::new("user", "c"));
+        conversation.is_loading = true;
+        conversation.partial = Some(PartialReply::new());
+        assert_eq!(conversation.take_back_unanswered().as_deref(), Some("c"));
+        assert_eq!(conversation.messages.len(), 2);
+        assert!(!conversation.is_loading);
+    }
+
+    #[test]
+    fn test_push_reply_continues_truncated_reply() {
+        let mut conversation = Conversation::new("mock");
+        conversation.push(ChatMessage::new("user", "a"));
+        let mut reply = ChatMessage::new("AI", "The first half, ");
+        reply.metadata = MessageMetadata {
+            completion_tokens: Some(4),
+            cost: Some(0.5),
+            finish_reason: Some("length".to_string()),
+            ..Default::default()
+        };
+        conversation.push(reply);
+        assert!(conversation.can_continue());
+
+        conversation.is_loading = true;
+        let mut partial = PartialReply::new();
+        partial.continues = true;
+        conversation.partial = Some(partial);
+        let mut rest = ChatMessage::new("AI", "the second half.");
+        rest.metadata = MessageMetadata {
+            completion_tokens: Some(4),
+            cost: Some(0.25),
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        };
+        conversation.push_reply(rest);
+
+        assert_eq!(conversation.messages.len(), 2);
+        let reply = &conversation.messages[1];
+        assert_eq!(reply.content, "The first half, the second half.");
+        assert_eq!(reply.metadata.completion_tokens, Some(8));
+        assert_eq!(reply.metadata.cost, Some(0.75));
+        assert_eq!(reply.metadata.finish_reason.as_deref(), Some("stop"));
+        assert!(!conversation.can_continue());
+    }
+
+    #[test]
+    fn test_pending_tool_calls() {
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            name: "shell".to_string(),
+            arguments: "{}".to_string(),
+        };
+        let mut conversation = Conversation::new("mock");
+        conversation.push(ChatMessage::new("user", "a"));
+        assert!(conversation.pending_tool_calls().is_empty());
+
+        let mut reply = ChatMessage::new("AI", "");
+        reply.tool_calls = vec![call("1"), call("2")];
+        conversation.push(reply);
+        assert_eq!(conversation.pending_tool_calls().len(), 2);
+        conversation.push_tool_result(&call("1"), "done");
+        assert_eq!(conversation.pending_tool_calls(), vec![&call("2")]);
+        assert_eq!(
+            conversation.tool_call(&conversation.messages[2]),
+            Some(&call("1"))
+        );
+        conversation.push_tool_result(&call("2"), "done");
+        assert!(conversation.pending_tool_calls().is_empty());
+    }
+
+    #[test]
+    fn test_request_body_marks_long_content_for_caching() {
+        let mut conversation = Conversation::new("anthropic/claude-3.5-sonnet");
+        conversation.system_prompt = "word ".repeat(10);
+        conversation.push(ChatMessage::new("user", "short"));
+        let mut config = ChatConfig::default();
+        config.prompt_cache.min_tokens = 5;
+
+        let params = ModelParams::default();
+        let body = conversation.request_body(&config, &params);
+        assert_eq!(body["messages"][0]["content"], json!("word ".repeat(10)));
+
+        config.prompt_cache.enabled = true;
+        let body = conversation.request_body(&config, &params);
+        assert_eq!(
+            body["messages"][0]["content"][0]["cache_control"],
+            json!({ "type": "ephemeral" })
+        );
+        assert_eq!(body["messages"][1]["content"], json!("short"));
+    }
+
+    #[test]
+    fn test_request_body_applies_length_preset() {
+        let mut conversation = Conversation::new("mock");
+        conversation.push(ChatMessage::new("user", "hi"));
+        let config = ChatConfig::default();
+        let params = ModelParams {
+            max_tokens: Some(1024),
+            ..Default::default()
+        };
+        let body = conversation.request_body(&config, &params);
+        assert_eq!(body["messages"][0]["role"], json!("user"));
+        assert_eq!(body["max_tokens"], json!(1024));
+
+        conversation.system_prompt = "Be nice.".to_string();
+        conversation.length = ResponseLength::Brief;
+        let body = conversation.request_body(&config, &params);
+        assert_eq!(
+            body["messages"][0]["content"],
+            json!("Be nice.\n\nKeep the answer brief: a few sentences, no preamble.")
+        );
+        assert_eq!(body["max_tokens"], json!(400));
+
+        conversation.translation = Translation::parse("German");
+        let body = conversation.request_body(&config, &params);
+        assert!(
+            body["messages"][0]["content"]
+                .as_str()
+                .is_some_and(|prompt| prompt.starts_with("You are a translator"))
+        );
+    }
+}
@@ -0,0 +1,1424 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde_json::Value;
+use tracing::error;
+
+use super::{
+    attachments,
+    budget::{BudgetConfig, BudgetStatus},
+    conversation::{ChatMessage, Conversation, Delta, PartialReply, ToolCall},
+    keys::{ApiKey, KeyStore},
+    metrics::{MetricsConfig, MetricsLog, Outcome},
+    middleware::{Middleware, MiddlewareChain},
+    models::ModelHistory,
+    personas::Persona,
+    provider::{ChatConfig, MockConfig, ModelParams, Provider, RateLimited},
+    rag::{IndexCache, RagConfig},
+    rate_limit::RateLimiter,
+    roundtable::Roundtable,
+    session::{self, Session, SessionInfo, SessionStore},
+    summary::{self, CompactionConfig},
+    todos,
+    tools::{self, ToolPolicy, ToolsConfig},
+    transcript::{TranscriptConfig, TranscriptLog},
+    usage::UsageLog,
+    workflow::{Workflow, WorkflowRun},
+};
+use crate::tokens;
+
+/// The parts of the config the engine needs.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub chat: ChatConfig,
+    /// Parameters for specific models, taking precedence over `chat`.
+    pub models: HashMap<String, ModelParams>,
+    pub mock: MockConfig,
+    pub transcript: TranscriptConfig,
+    pub sessions_dir: PathBuf,
+    pub log_dir: PathBuf,
+    pub models_file: PathBuf,
+    pub budget: BudgetConfig,
+    pub usage_file: PathBuf,
+    pub metrics: MetricsConfig,
+    pub metrics_file: PathBuf,
+    /// API keys entered in the app.
+    pub keys_file: PathBuf,
+    pub tools: ToolsConfig,
+    pub workflows: HashMap<String, Workflow>,
+    pub rag: RagConfig,
+    /// Chunks of the directory indexed with `/index`, added to questions if it exists.
+    pub index_file: PathBuf,
+}
+
+/// Drives a conversation: sends it to the provider and saves it as a session.
+///
+/// Requests are split into [`Engine::begin_completion`] and [`Completion::run`] so that callers
+/// decide where to wait for the reply, e.g. the TUI in a background task.
+pub struct Engine {
+    pub conversation: Conversation,
+    settings: Settings,
+    sessions: SessionStore,
+    /// Body of the last chat completion request, for `/curl`; shared with the request while it
+    /// adds indexed excerpts.
+    last_request: Arc<Mutex<Option<Value>>>,
+    /// The index of `/index`, loaded on the first question that uses it.
+    index: IndexCache,
+    model_history: ModelHistory,
+    usage: UsageLog,
+    rate_limiter: RateLimiter,
+    workflow: Option<WorkflowRun>,
+    roundtable: Option<Roundtable>,
+    /// The settings of the last chat left for a persona, to go back to once the persona's chat is
+    /// left too.
+    before_persona: Option<ChatSettings>,
+    middleware: MiddlewareChain,
+}
+
+/// What a persona changes about a conversation besides the persona itself.
+#[derive(Debug, Clone)]
+struct ChatSettings {
+    system_prompt: String,
+    model: String,
+    temperature: Option<f64>,
+}
+
+impl ChatSettings {
+    fn of(conversation: &Conversation) -> Self {
+        Self {
+            system_prompt: conversation.system_prompt.clone(),
+            model: conversation.model.clone(),
+            temperature: conversation.temperature,
+        }
+    }
+
+    fn apply(self, conversation: &mut Conversation) {
+        conversation.system_prompt = self.system_prompt;
+        conversation.model = self.model;
+        conversation.temperature = self.temperature;
+    }
+}
+
+/// A chat completion request that has been prepared but not sent yet.
+pub struct Completion {
+    provider: Provider,
+    body: Value,
+    middleware: MiddlewareChain,
+    metrics: Option<MetricsLog>,
+    /// Set if indexed chunks are added to the question, which the middleware runs after.
+    retrieval: Option<Retrieval>,
+    /// Time to hold the request back to stay within the rate limits.
+    wait: Duration,
+    /// What the prompt costs in dollars, counted locally, if the model's price is known.
+    estimated_cost: Option<f64>,
+    on_retry: Option<Box<dyn FnMut(Duration) + Send>>,
+}
+
+/// What a request needs to add indexed chunks to its question.
+struct Retrieval {
+    config: RagConfig,
+    index_file: PathBuf,
+    index: IndexCache,
+    /// Where the request is recorded once complete, for `/curl`.
+    last_request: Arc<Mutex<Option<Value>>>,
+}
+
+/// What `prompt_tokens` cost at the model's prompt price, if it is known.
+fn estimate_cost(params: &ModelParams, prompt_tokens: usize) -> Option<f64> {
+    params
+        .prompt_price
+        .map(|price| prompt_tokens as f64 * price / 1_000_000.0)
+}
+
+/// How often a request refused with 429 is sent again before giving up.
+const MAX_RETRIES: usize = 5;
+
+impl Completion {
+    /// Call `on_retry` with the wait whenever the provider rate limits the request and it is
+    /// about to be sent again.
+    pub fn on_retry(mut self, on_retry: impl FnMut(Duration) + Send + 'static) -> Self {
+        self.on_retry = Some(Box::new(on_retry));
+        self
+    }
+
+    fn record(&self, retry: bool, result: &Result<ChatMessage>) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        let outcome = match result {
+            Ok(reply) => Outcome::Replied {
+                latency_ms: reply.metadata.latency_ms,
+                first_token_ms: reply.metadata.first_token_ms,
+            },
+            Err(err) if err.downcast_ref::<RateLimited>().is_some() => Outcome::RateLimited,
+            Err(_) => Outcome::Failed,
+        };
+        if let Err(err) = metrics.record(session::now(), retry, outcome) {
+            error!("Failed to record metrics: {err}");
+        }
+    }
+
+    /// Send the request and wait for the reply, passing the text to `on_delta` as it streams in.
+    ///
+    /// Requests the provider refuses with 429 are sent again once its limit allows.
+    pub async fn run(mut self, mut on_delta: impl FnMut(Delta) + Send) -> Result<ChatMessage> {
+        tokio::time::sleep(self.wait).await;
+        if let Some(retrieval) = self.retrieval.take() {
+            // Without the excerpts the question can still be answered, so only log failures
+            let result = match retrieval.index.load(&retrieval.index_file).await {
+                Ok(Some(index)) => index.augment(&retrieval.config, &mut self.body).await,
+                Ok(None) => Ok(()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                error!("Failed to add indexed excerpts: {err}");
+            }
+            // So that e.g. redaction sees the excerpts, and `/curl` copies what is sent
+            self.middleware.on_request(&mut self.body);
+            *retrieval
+                .last_request
+                .lock()
+                .unwrap_or_else(|err| err.into_inner()) = Some(self.body.clone());
+        }
+        let mut retries = 0;
+        loop {
+            let result = self.provider.complete(&self.body, &mut on_delta).await;
+            self.record(retries > 0, &result);
+            let retry_after = match &result {
+                Err(err) if retries < MAX_RETRIES => err
+                    .downcast_ref::<RateLimited>()
+                    .map(|limited| limited.retry_after),
+                _ => None,
+            };
+            let Some(wait) = retry_after else {
+                return result.map(|mut reply| {
+                    self.middleware.on_response(&mut reply);
+                    reply
+                });
+            };
+            retries += 1;
+            if let Some(on_retry) = &mut self.on_retry {
+                on_retry(wait);
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Engine {
+    pub fn new(settings: Settings) -> Self {
+        let mut conversation = Conversation::new(&settings.chat.model);
+        conversation.system_prompt = settings.chat.system_prompt.clone();
+        Self {
+            conversation,
+            sessions: SessionStore::new(settings.sessions_dir.clone()),
+            model_history: ModelHistory::load(&settings.models_file),
+            usage: UsageLog::new(settings.usage_file.clone()),
+            rate_limiter: RateLimiter::default(),
+            workflow: None,
+            roundtable: None,
+            before_persona: None,
+            settings,
+            last_request: Arc::default(),
+            index: IndexCache::default(),
+            middleware: MiddlewareChain::default(),
+        }
+    }
+
+    /// Run `middleware` on every request from now on, after the middleware added before it.
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
+        self.middleware.push(middleware);
+    }
+
+    /// Use new settings, switching the conversation to the configured model if that changed.
+    pub fn set_settings(&mut self, settings: Settings) {
+        if settings.chat.model != self.settings.chat.model {
+            self.conversation.model = settings.chat.model.clone();
+        }
+        // Follow a new default unless the prompt of the conversation was changed
+        if settings.chat.system_prompt != self.settings.chat.system_prompt
+            && self.conversation.system_prompt == self.settings.chat.system_prompt
+        {
+            self.conversation.system_prompt = settings.chat.system_prompt.clone();
+        }
+        if settings.index_file != self.settings.index_file {
+            self.index.clear();
+        }
+        self.settings = settings;
+    }
+
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
+    }
+
+    pub fn last_request(&self) -> Option<Value> {
+        self.last_request
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// The cached index, to update when the directory is indexed again or the index removed.
+    pub fn index(&self) -> &IndexCache {
+        &self.index
+    }
+
+    pub fn model_history(&self) -> &ModelHistory {
+        &self.model_history
+    }
+
+    /// The provider chat completions and model lists come from.
+    pub fn provider(&self) -> Provider {
+        let transcript =
+            TranscriptLog::new(&self.settings.transcript, self.settings.log_dir.clone());
+        let api_key = self
+            .keys()
+            .get(self.settings.chat.provider.name())
+            .unwrap_or_else(|err| {
+                error!("Failed to read the stored API keys: {err}");
+                None
+            });
+        Provider::new(
+            &self.settings.chat,
+            &self.settings.mock,
+            transcript,
+            api_key,
+        )
+    }
+
+    fn keys(&self) -> KeyStore {
+        KeyStore::new(self.settings.keys_file.clone())
+    }
+
+    /// Use `key` for the configured provider from the next request on, instead of the
+    /// environment variable.
+    pub fn set_api_key(&self, key: ApiKey) -> Result<()> {
+        self.keys().set(self.settings.chat.provider.name(), key)
+    }
+
+    /// Use another model for the rest of the conversation.
+    pub fn set_model(&mut self, model: &str) {
+        self.conversation.model = model.to_string();
+        self.record_model_use();
+    }
+
+    /// Star or unstar a model; returns whether it is a favorite now.
+    pub fn toggle_favorite(&mut self, model: &str) -> Result<bool> {
+        let favorite = self.model_history.toggle_favorite(model);
+        self.model_history.save(&self.settings.models_file)?;
+        Ok(favorite)
+    }
+
+    /// Compare what was spent today and this month with the configured budget.
+    pub fn budget_status(&self) -> BudgetStatus {
+        match self.usage.spending(session::now()) {
+            Ok(spending) => self.settings.budget.check(spending),
+            Err(err) => {
+                error!("Failed to read the usage log: {err}");
+                BudgetStatus::Within
+            }
+        }
+    }
+
+    /// Like [`Engine::budget_status`], but also at a hard limit if `completion` would cross it.
+    pub fn request_budget_status(&self, completion: &Completion) -> BudgetStatus {
+        match self.usage.spending(session::now()) {
+            Ok(spending) => self
+                .settings
+                .budget
+                .check_request(spending, completion.estimated_cost.unwrap_or_default()),
+            Err(err) => {
+                error!("Failed to read the usage log: {err}");
+                BudgetStatus::Within
+            }
+        }
+    }
+
+    fn record_model_use(&mut self) {
+        if self.model_history.record_use(&self.conversation.model)
+            && let Err(err) = self.model_history.save(&self.settings.models_file)
+        {
+            error!("Failed to save recent models: {err}");
+        }
+    }
+
+    /// Add a question to the conversation and prepare the request for its answer.
+    pub fn send(&mut self, text: &str) -> Completion {
+        let attachments = std::mem::take(&mut self.conversation.attachments);
+        let (text, collapsed) = attachments::attach(text, &attachments);
+        let mut message = ChatMessage::new("user", text);
+        message.collapsed = collapsed;
+        self.conversation.push(message);
+        self.conversation.agent_steps = 0;
+        if let Some(roundtable) = &mut self.roundtable {
+            roundtable.restart();
+        }
+        self.begin_completion()
+    }
+
+    /// Prepare the request for the next reply and mark the conversation as waiting for it.
+    pub fn begin_completion(&mut self) -> Completion {
+        if let Some(roundtable) = &self.roundtable {
+            roundtable.seat(&mut self.conversation);
+        }
+        self.record_model_use();
+        self.conversation.is_loading = true;
+        self.conversation.partial = Some(PartialReply::new());
+        let params = ModelParams {
+            temperature: self.conversation.temperature,
+            ..Default::default()
+        }
+        .or(ModelParams::for_model(
+            &self.conversation.model,
+            &self.settings.chat,
+            &self.settings.models,
+        ));
+        let mut body = self.conversation.request_body(&self.settings.chat, &params);
+        if self.settings.tools.enabled {
+            body["tools"] = self.settings.tools.definitions();
+        }
+        let retrieval = self.settings.index_file.exists().then(|| Retrieval {
+            config: self.settings.rag.clone(),
+            index_file: self.settings.index_file.clone(),
+            index: self.index.clone(),
+            last_request: self.last_request.clone(),
+        });
+        // With excerpts to add, the middleware runs once they are added
+        if retrieval.is_none() {
+            self.middleware.on_request(&mut body);
+            *self
+                .last_request
+                .lock()
+                .unwrap_or_else(|err| err.into_inner()) = Some(body.clone());
+        }
+        let conversation = &self.conversation;
+        let prompt_tokens = tokens::count_prompt_tokens(
+            &conversation.model,
+            &conversation.system_prompt,
+            &conversation.messages,
+        );
+        let now = Instant::now();
+        let wait =
+            self.rate_limiter
+                .reserve(&self.settings.chat.rate_limit, prompt_tokens as u64, now);
+        if let Some(partial) = &mut self.conversation.partial {
+            partial.throttled_until = (!wait.is_zero()).then(|| now + wait);
+        }
+        Completion {
+            provider: self.provider(),
+            body,
+            middleware: self.middleware.clone(),
+            metrics: MetricsLog::new(&self.settings.metrics, self.settings.metrics_file.clone()),
+            retrieval,
+            wait,
+            estimated_cost: estimate_cost(&params, prompt_tokens),
+            on_retry: None,
+        }
+    }
+
+    /// Add streamed text to the reply in progress.
+    pub fn receive_delta(&mut self, delta: &Delta) {
+        let (Delta::Reasoning(text) | Delta::Content(text)) = delta;
+        let tokens = tokens::count_tokens(&self.conversation.model, text);
+        if let Some(partial) = &mut self.conversation.partial {
+            partial.push(delta, tokens);
+        }
+    }
+
+    /// Add the reply to a request started with [`Engine::begin_completion`].
+    pub fn receive(&mut self, mut message: ChatMessage) {
+        self.record_usage(&message);
+        if message.role == "AI" {
+            message.metadata.persona = self.conversation.persona.clone();
+        }
+        self.conversation.push_reply(message);
+    }
+
+    /// Count the tokens of a reply against the rate limits and the budget.
+    pub fn record_usage(&mut self, message: &ChatMessage) {
+        if let Some(tokens) = message.metadata.completion_tokens {
+            self.rate_limiter.add(tokens, Instant::now());
+        }
+        // The model may have been switched while the reply was streamed
+        let model = message
+            .metadata
+            .model
+            .as_deref()
+            .unwrap_or(&self.conversation.model);
+        if let Err(err) = self.usage.record(session::now(), model, &message.metadata) {
+            error!("Failed to record usage: {err}");
+        }
+    }
+
+    /// Prepare a request outside the conversation, e.g. for a commit message: one question with
+    /// its own system prompt, sent to the conversation's model without tools or indexed
+    /// excerpts. Pass the reply to [`Engine::record_usage`].
+    pub fn ask_aside(&mut self, system_prompt: &str, question: &str) -> Completion {
+        let mut conversation = Conversation::new(&self.conversation.model);
+        conversation.system_prompt = system_prompt.to_string();
+        conversation.push(ChatMessage::new("user", question));
+        let params = ModelParams::for_model(
+            &conversation.model,
+            &self.settings.chat,
+            &self.settings.models,
+        );
+        let mut body = conversation.request_body(&self.settings.chat, &params);
+        self.middleware.on_request(&mut body);
+        let prompt_tokens = tokens::count_prompt_tokens(
+            &conversation.model,
+            &conversation.system_prompt,
+            &conversation.messages,
+        );
+        let wait = self.rate_limiter.reserve(
+            &self.settings.chat.rate_limit,
+            prompt_tokens as u64,
+            Instant::now(),
+        );
+        Completion {
+            provider: self.provider(),
+            body,
+            middleware: self.middleware.clone(),
+            metrics: MetricsLog::new(&self.settings.metrics, self.settings.metrics_file.clone()),
+            retrieval: None,
+            wait,
+            estimated_cost: estimate_cost(&params, prompt_tokens),
+            on_retry: None,
+        }
+    }
+
+    /// Prepare a request for a summary of the first `messages` messages of the conversation, see
+    /// [`Engine::ask_aside`].
+    pub fn summarize(&mut self, messages: usize) -> Completion {
+        let messages =
+            &self.conversation.messages[..messages.min(self.conversation.messages.len())];
+        self.ask_aside(summary::SYSTEM_PROMPT, &summary::question(messages))
+    }
+
+    /// Prepare a request for the action items of the conversation, see [`Engine::ask_aside`].
+    pub fn extract_todos(&mut self) -> Completion {
+        let question = todos::question(&self.conversation.messages);
+        self.ask_aside(todos::SYSTEM_PROMPT, &question)
+    }
+
+    /// Whether the prompt has reached `config.auto_at_tokens` and there are messages to compact,
+    /// once the reply and its tool calls are done.
+    pub fn compaction_due(&self, config: &CompactionConfig) -> bool {
+        let conversation = &self.conversation;
+        let Some(limit) = config.auto_at_tokens else {
+            return false;
+        };
+        if conversation.is_loading || !conversation.pending_tool_calls().is_empty() {
+            return false;
+        }
+        summary::compactable(&conversation.messages, config.keep_messages) > 0
+            && tokens::count_prompt_tokens(
+                &conversation.model,
+                &conversation.system_prompt,
+                &conversation.messages,
+            ) >= limit
+    }
+
+    /// Start the workflow `name` from the config and prepare the request for its first step.
+    pub fn start_workflow(&mut self, name: &str, input: &str) -> Result<Completion> {
+        let workflow = self
+            .settings
+            .workflows
+            .get(name)
+            .cloned()
+            .ok_or_else(|| eyre!("No workflow named {name}"))?;
+        self.workflow = Some(WorkflowRun::new(
+            name,
+            workflow,
+            input,
+            &self.conversation.model,
+        )?);
+        self.send_workflow_step()
+            .ok_or_else(|| eyre!("Workflow {name} has no steps"))
+    }
+
+    /// The workflow being run, if any.
+    pub fn workflow(&self) -> Option<&WorkflowRun> {
+        self.workflow.as_ref()
+    }
+
+    /// Take the reply to the workflow's last step and prepare the request for the next one.
+    ///
+    /// Returns `None` once the workflow is done, or if the step wasn't answered.
+    pub fn advance_workflow(&mut self) -> Option<Completion> {
+        let reply = self
+            .conversation
+            .messages
+            .last()
+            .filter(|message| message.role == "AI")
+            .map(|message| message.content.clone());
+        let run = self.workflow.as_mut()?;
+        let Some(reply) = reply else {
+            self.stop_workflow();
+            return None;
+        };
+        run.record(&reply);
+        let completion = self.send_workflow_step();
+        if completion.is_none() {
+            self.stop_workflow();
+        }
+        completion
+    }
+
+    /// Have `personas` answer every message in turn from now on.
+    pub fn start_roundtable(&mut self, personas: Vec<Persona>) -> Result<()> {
+        let roundtable = Roundtable::new(personas, &self.conversation)?;
+        self.stop_roundtable();
+        self.roundtable = Some(roundtable);
+        Ok(())
+    }
+
+    /// Prepare the request for the next persona's answer once the last one has answered.
+    ///
+    /// Returns `None` once everyone has answered, or if the last persona didn't.
+    pub fn advance_roundtable(&mut self) -> Option<Completion> {
+        let roundtable = self.roundtable.as_mut()?;
+        let answered = self.conversation.messages.last().is_some_and(|message| {
+            message.role == "AI"
+                && message.metadata.persona.as_ref() == Some(&roundtable.speaker().name)
+        });
+        if !answered || !roundtable.pass() {
+            return None;
+        }
+        Some(self.begin_completion())
+    }
+
+    /// End the roundtable, giving the conversation its own persona and settings back.
+    pub fn stop_roundtable(&mut self) -> Option<Roundtable> {
+        let roundtable = self.roundtable.take()?;
+        roundtable.restore(&mut self.conversation);
+        Some(roundtable)
+    }
+
+    /// End the workflow, switching back to the model it started with.
+    pub fn stop_workflow(&mut self) -> Option<WorkflowRun> {
+        let run = self.workflow.take()?;
+        self.conversation.model = run.model.clone();
+        Some(run)
+    }
+
+    fn send_workflow_step(&mut self) -> Option<Completion> {
+        let run = self.workflow.as_ref()?;
+        let step = run.next_step()?;
+        self.conversation.model = step.model.unwrap_or_else(|| run.model.clone());
+        Some(self.send(&step.prompt))
+    }
+
+    /// Ask for an answer and wait for it, passing the text to `on_delta` as it streams in.
+    ///
+    /// `on_retry` is told how long to wait when the provider rate limits the request.
+    pub async fn complete(
+        &mut self,
+        on_retry: impl FnMut(Duration) + Send + 'static,
+        on_delta: impl FnMut(Delta) + Send,
+    ) -> Result<ChatMessage> {
+        let result = self
+            .begin_completion()
+            .on_retry(on_retry)
+            .run(on_delta)
+            .await;
+        match &result {
+            Ok(message) => self.receive(message.clone()),
+            Err(err) => self.conversation.push_error(&err.to_string()),
+        }
+        result
+    }
+
+    /// Drop the given message and everything after it and ask again.
+    ///
+    /// Returns `None` while a reply is pending or when there is no question left to answer.
+    pub fn regenerate(&mut self, index: usize) -> Option<Completion> {
+        if self.conversation.is_loading {
+            return None;
+        }
+        let versions = self.conversation.versions_for_regenerate(index);
+        if !self.conversation.truncate_for_regenerate(index) {
+            return None;
+        }
+        let completion = self.begin_completion();
+        if let Some(partial) = &mut self.conversation.partial {
+            partial.versions = versions;
+        }
+        Some(completion)
+    }
+
+    /// Put the question the reply at `index` answers to `model` as well; its answer is inserted
+    /// right after that reply, for comparison.
+    ///
+    /// The conversation keeps its model. Returns `None` unless `index` is a reply and no other
+    /// reply is awaited.
+    pub fn ask_model(&mut self, index: usize, model: &str) -> Option<Completion> {
+        let conversation = &self.conversation;
+        if conversation.is_loading
+            || conversation
+                .messages
+                .get(index)
+                .is_none_or(|message| message.role != "AI")
+        {
+            return None;
+        }
+        // Build the request from the history before the reply, as the other model
+        let messages = self.conversation.messages.clone();
+        let current = std::mem::replace(&mut self.conversation.model, model.to_string());
+        self.conversation.messages.truncate(index);
+        let completion = self.begin_completion();
+        self.conversation.messages = messages;
+        self.conversation.model = current;
+        if let Some(partial) = &mut self.conversation.partial {
+            partial.alternative_at = Some(index + 1);
+        }
+        Some(completion)
+    }
+
+    /// Ask the model to go on with a reply cut off by `max_tokens`; the continuation is appended
+    /// to it.
+    ///
+    /// The cut off reply is sent as the last message, which the model picks up where it ended.
+    /// Returns `None` unless the last message is such a reply.
+    pub fn continue_reply(&mut self) -> Option<Completion> {
+        if !self.conversation.can_continue() {
+            return None;
+        }
+        let completion = self.begin_completion();
+        if let Some(partial) = &mut self.conversation.partial {
+            partial.continues = true;
+        }
+        Some(completion)
+    }
+
+    /// Whether a call of `tool` runs without asking, taking agent mode and the session's
+    /// allowances into account.
+    pub fn tool_policy(&self, tool: &str) -> ToolPolicy {
+        let conversation = &self.conversation;
+        match self.settings.tools.policy(tool) {
+            // Writes always show their diff first
+            ToolPolicy::Auto if tools::confirms_each_call(tool) => ToolPolicy::Ask,
+            ToolPolicy::Ask
+                if (conversation.agent || conversation.allowed_tools.contains(tool))
+                    && !tools::confirms_each_call(tool) =>
+            {
+                ToolPolicy::Auto
+            }
+            policy => policy,
+        }
+    }
+
+    /// Whether agent mode has used up its steps for the current message.
+    pub fn agent_budget_spent(&self) -> bool {
+        let budget = self.settings.tools.agent_steps;
+        self.conversation.agent && budget > 0 && self.conversation.agent_steps >= budget
+    }
+
+    /// Add the output of a call, cut to the configured size; the whole output is kept for the
+    /// user but not sent.
+    pub fn push_tool_output(&mut self, call: &ToolCall, output: String) {
+        match self.settings.tools.truncate_output(&output) {
+            Some(truncated) => {
+                self.conversation.push_tool_result(call, truncated);
+                if let Some(message) = self.conversation.messages.last_mut() {
+                    message.full_output = Some(output);
+                }
+            }
+            None => self.conversation.push_tool_result(call, output),
+        }
+    }
+
+    /// Send the oldest queued message, unless a reply is still pending.
+    pub fn send_queued(&mut self) -> Option<Completion> {
+        if self.conversation.is_loading
+            || self.conversation.queued.is_empty()
+            || !self.conversation.pending_tool_calls().is_empty()
+        {
+            return None;
+        }
+        let text = self.conversation.queued.remove(0);
+        Some(self.send(&text))
+    }
+
+    /// Save the conversation as a session; empty conversations are not saved.
+    pub fn save(&self) -> Result<()> {
+        let conversation = &self.conversation;
+        if conversation.messages.is_empty() && conversation.queued.is_empty() {
+            return Ok(());
+        }
+        let mut session = Session::new(conversation.id.clone(), conversation.messages.clone());
+        session.queued = conversation.queued.clone();
+        if let Some(title) = &conversation.title {
+            session.title = title.clone();
+        }
+        session.description = conversation.description.clone();
+        session.system_prompt = Some(conversation.system_prompt.clone());
+        session.persona = conversation.persona.clone();
+        session.model = Some(conversation.model.clone());
+        session.temperature = conversation.temperature;
+        // Not the settings of whoever is answering at the roundtable
+        if let Some(roundtable) = &self.roundtable {
+            session.system_prompt = Some(roundtable.system_prompt.clone());
+            session.persona = roundtable.persona.clone();
+            session.model = Some(roundtable.model.clone());
+            session.temperature = roundtable.temperature;
+        }
+        self.sessions.save(&session)
+    }
+
+    /// Save the conversation and replace it with a new one with the same system prompt.
+    pub fn start_conversation(&mut self, messages: Vec<ChatMessage>) -> Result<()> {
+        self.stop_roundtable();
+        self.save()?;
+        self.conversation.restart(messages);
+        Ok(())
+    }
+
+    /// Save the conversation and start an empty one with the same system prompt, model and
+    /// temperature, or the ones from before the persona after a persona's chat.
+    pub fn new_chat(&mut self) -> Result<()> {
+        self.start_conversation(Vec::new())?;
+        self.leave_persona();
+        Ok(())
+    }
+
+    /// Drop the persona of the conversation, going back to the settings of the chat it was
+    /// started from, or the configured ones for a persona's session opened from disk.
+    fn leave_persona(&mut self) {
+        if self.conversation.persona.take().is_none() {
+            return;
+        }
+        let settings = self.before_persona.clone().unwrap_or_else(|| ChatSettings {
+            system_prompt: self.settings.chat.system_prompt.clone(),
+            model: self.settings.chat.model.clone(),
+            temperature: None,
+        });
+        settings.apply(&mut self.conversation);
+    }
+
+    /// Save the conversation and start one as `persona`, opening with its greeting.
+    pub fn start_persona(&mut self, persona: &Persona) -> Result<()> {
+        self.new_chat()?;
+        self.before_persona = Some(ChatSettings::of(&self.conversation));
+        self.conversation.persona = Some(persona.name.clone());
+        self.conversation.system_prompt = persona.system_prompt.clone();
+        if let Some(model) = &persona.model {
+            self.set_model(model);
+        }
+        self.conversation.temperature = persona.temperature;
+        if let Some(greeting) = &persona.greeting {
+            self.conversation
+                .push(ChatMessage::new("AI", greeting.clone()));
+        }
+        Ok(())
+    }
+
+    /// Save the conversation and switch to a stored session.
+    pub fn open_session(&mut self, id: &str) -> Result<()> {
+        if id == self.conversation.id {
+            return Ok(());
+        }
+        self.stop_roundtable();
+        self.save()?;
+        let session = self.sessions.load(id)?;
+        if self.conversation.persona.is_none() {
+            self.before_persona = Some(ChatSettings::of(&self.conversation));
+        }
+        self.conversation.id = session.id;
+        self.conversation.title = Some(session.title);
+        self.conversation.description = session.description;
+        self.conversation.system_prompt = session
+            .system_prompt
+            .unwrap_or_else(|| self.settings.chat.system_prompt.clone());
+        self.conversation.persona = session.persona;
+        self.conversation.model = session
+            .model
+            .unwrap_or_else(|| self.settings.chat.model.clone());
+        self.conversation.temperature = session.temperature;
+        self.conversation.messages = session.messages;
+        self.conversation.queued = session.queued;
+        self.conversation.allowed_tools.clear();
+        self.conversation.agent_steps = 0;
+        Ok(())
+    }
+
+    pub fn rename_session(&mut self, id: &str, title: &str) -> Result<()> {
+        if id == self.conversation.id {
+            self.conversation.title = Some(title.to_string());
+        }
+        if self.sessions.exists(id) {
+            self.sessions.rename(id, title)?;
+        }
+        Ok(())
+    }
+
+    pub fn archive_session(&mut self, id: &str, archived: bool) -> Result<()> {
+        // The current conversation may not have been written out yet
+        if id == self.conversation.id {
+            self.save()?;
+        }
+        if self.sessions.exists(id) {
+            self.sessions.set_archived(id, archived)?;
+        }
+        Ok(())
+    }
+
+    /// Append the conversation to the session `target`, after a marker, and continue there.
+    ///
+    /// The conversation's own session is deleted.
+    pub fn merge_into(&mut self, target: &str) -> Result<()> {
+        if target == self.conversation.id {
+            return Err(eyre!("A chat can't be merged into itself"));
+        }
+        if self.conversation.is_loading {
+            return Err(eyre!("Wait for the reply before merging"));
+        }
+        let mut session = self.sessions.load(target)?;
+        let messages = std::mem::take(&mut self.conversation.messages);
+        if !messages.is_empty() {
+            let title = self
+                .conversation
+                .title
+                .clone()
+                .unwrap_or_else(|| session::auto_title(&messages));
+            session.messages.push(ChatMessage::new(
+                "system",
+                format!("── Merged from \"{title}\" ──"),
+            ));
+            session.messages.extend(messages);
+        }
+        session.queued.append(&mut self.conversation.queued);
+        self.sessions.save(&session)?;
+        let source = self.conversation.id.clone();
+        if self.sessions.exists(&source) {
+            self.sessions.delete(&source)?;
+        }
+        // Nothing is left to save, so this only switches over
+        self.open_session(target)
+    }
+
+    /// Delete a session, starting over if it is the current conversation.
+    pub fn delete_session(&mut self, id: &str) -> Result<()> {
+        if id == self.conversation.id {
+            self.conversation.restart(Vec::new());
+            self.leave_persona();
+        }
+        if self.sessions.exists(id) {
+            self.sessions.delete(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        self.sessions.list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::{
+        provider::ProviderKind,
+        rag::{EmbeddingProvider, Index},
+        session::new_session_id,
+        workflow::WorkflowStep,
+    };
+
+    fn engine(dir: &std::path::Path) -> Engine {
+        Engine::new(Settings {
+            chat: ChatConfig {
+                provider: ProviderKind::Mock,
+                model: "mock-1".to_string(),
+                ..Default::default()
+            },
+            mock: MockConfig {
+                response: "{model} heard: {prompt}".to_string(),
+                reasoning: String::new(),
+                latency_ms: 0,
+                word_delay_ms: 0,
+            },
+            sessions_dir: dir.join("sessions"),
+            log_dir: dir.join("logs"),
+            models_file: dir.join("models.json"),
+            usage_file: dir.join("usage.jsonl"),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_workflow() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        let step = |model: Option<&str>, prompt: &str| WorkflowStep {
+            name: None,
+            model: model.map(str::to_string),
+            prompt: prompt.to_string(),
+        };
+        engine.settings.workflows.insert(
+            "echo".to_string(),
+            Workflow {
+                description: String::new(),
+                steps: vec![
+                    step(Some("mock-2"), "first {{input}}"),
+                    step(None, "second {{previous}}"),
+                ],
+            },
+        );
+        assert!(engine.start_workflow("missing", "").is_err());
+
+        let completion = engine.start_workflow("echo", "go")?;
+        assert_eq!(engine.conversation.model, "mock-2");
+        engine.receive(completion.run(|_| {}).await?);
+        let completion = engine.advance_workflow().expect("a second step");
+        assert_eq!(engine.conversation.model, "mock-1");
+        engine.receive(completion.run(|_| {}).await?);
+        assert!(engine.advance_workflow().is_none());
+        assert_eq!(engine.workflow(), None);
+
+        let prompts: Vec<&str> = engine
+            .conversation
+            .messages
+            .iter()
+            .filter(|message| message.role == "user")
+            .map(|message| message.content.as_str())
+            .collect();
+        assert_eq!(prompts, ["first go", "second mock-2 heard: first go"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_agent_mode() {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.settings.tools.agent_steps = 2;
+        assert_eq!(engine.tool_policy("shell"), ToolPolicy::Ask);
+
+        engine.conversation.agent = true;
+        assert_eq!(engine.tool_policy("shell"), ToolPolicy::Auto);
+        // Writes still show their diff
+        assert_eq!(engine.tool_policy("write_file"), ToolPolicy::Ask);
+        engine
+            .settings
+            .tools
+            .policies
+            .insert("write_file".to_string(), ToolPolicy::Auto);
+        assert_eq!(engine.tool_policy("write_file"), ToolPolicy::Ask);
+
+        engine.conversation.agent_steps = 2;
+        assert!(engine.agent_budget_spent());
+        // A new message starts a new budget
+        engine.send("again");
+        assert!(!engine.agent_budget_spent());
+    }
+
+    #[tokio::test]
+    async fn test_switch_model() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        let reply = engine.send("one").run(|_| {}).await?;
+        engine.receive(reply);
+        engine.set_model("mock-2");
+        let reply = engine.send("two").run(|_| {}).await?;
+        engine.receive(reply);
+
+        let models: Vec<Option<&str>> = engine
+            .conversation
+            .messages
+            .iter()
+            .filter(|message| message.role == "AI")
+            .map(|message| message.metadata.model.as_deref())
+            .collect();
+        assert_eq!(models, [Some("mock-1"), Some("mock-2")]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_middleware() -> Result<()> {
+        struct Redact;
+        impl Middleware for Redact {
+            fn name(&self) -> &str {
+                "redact"
+            }
+            fn on_request(&self, body: &mut Value) {
+                for message in body["messages"].as_array_mut().into_iter().flatten() {
+                    if let Some(content) = message["content"].as_str() {
+                        message["content"] = content.replace("hunter2", "[redacted]").into();
+                    }
+                }
+            }
+            fn on_response(&self, reply: &mut ChatMessage) {
+                reply.content = reply.content.replace("[redacted]", "hunter2");
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.add_middleware(Redact);
+        let reply = engine.send("my password is hunter2").run(|_| {}).await?;
+        let sent = engine.last_request().unwrap()["messages"].to_string();
+        assert!(sent.contains("[redacted]") && !sent.contains("hunter2"));
+        assert_eq!(reply.content, "mock-1 heard: my password is hunter2");
+        engine.receive(reply);
+
+        // Indexed excerpts are added before the middleware runs
+        let project = dir.join("project");
+        std::fs::create_dir_all(&project)?;
+        std::fs::write(project.join("secrets.txt"), "the password is hunter2\n")?;
+        engine.settings.rag.embeddings = EmbeddingProvider::Mock;
+        engine.settings.index_file = dir.join("index.json");
+        let index = Index::build(&engine.settings.rag, &project, |_, _| {}).await?;
+        engine.index().store(index, &dir.join("index.json")).await?;
+        engine.send("Where is the password?").run(|_| {}).await?;
+        let sent = engine.last_request().unwrap()["messages"].to_string();
+        assert!(sent.contains("secrets.txt") && !sent.contains("hunter2"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ask_aside() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.conversation.push(ChatMessage::new("user", "Hi"));
+        let reply = engine
+            .ask_aside("Answer in one word.", "Name a color")
+            .run(|_| {})
+            .await?;
+        assert_eq!(reply.content, "mock-1 heard: Name a color");
+        assert_eq!(engine.conversation.messages.len(), 1);
+        assert!(!engine.conversation.is_loading);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summarize() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        for text in ["one", "two", "three"] {
+            let reply = engine.send(text).run(|_| {}).await?;
+            engine.receive(reply);
+        }
+        let mut config = CompactionConfig::default();
+        assert!(!engine.compaction_due(&config));
+        config.auto_at_tokens = Some(20);
+        assert!(engine.compaction_due(&config));
+
+        let reply = engine.summarize(2).run(|_| {}).await?;
+        assert!(
+            reply.content.contains("mock-1 heard: one"),
+            "{}",
+            reply.content
+        );
+        assert!(!reply.content.contains("two"), "{}", reply.content);
+        assert_eq!(engine.conversation.messages.len(), 6);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ask_model() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        for text in ["one", "two"] {
+            let reply = engine.send(text).run(|_| {}).await?;
+            engine.receive(reply);
+        }
+        assert!(engine.ask_model(0, "mock-2").is_none());
+
+        let completion = engine.ask_model(1, "mock-2").expect("a reply to ask again");
+        assert_eq!(engine.conversation.model, "mock-1");
+        engine.receive(completion.run(|_| {}).await?);
+        let messages = &engine.conversation.messages;
+        assert_eq!(messages[2].content, "mock-2 heard: one");
+        assert!(messages[2].alternative);
+        assert_eq!(messages[3].content, "two");
+
+        // The alternative is left out of the next prompt
+        engine.send("three");
+        let sent = engine.last_request().unwrap()["messages"]
+            .as_array()
+            .unwrap()
+            .len();
+        assert_eq!(sent, 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_into() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        let reply = engine.send("old question").run(|_| {}).await?;
+        engine.receive(reply);
+        let old = engine.conversation.id.clone();
+        engine.start_conversation(Vec::new())?;
+        let reply = engine.send("follow-up").run(|_| {}).await?;
+        engine.receive(reply);
+        engine.save()?;
+        let new = engine.conversation.id.clone();
+
+        assert!(engine.merge_into(&new).is_err());
+        engine.merge_into(&old)?;
+        assert_eq!(engine.conversation.id, old);
+        let contents: Vec<&str> = engine
+            .conversation
+            .messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "old question",
+                "mock-1 heard: old question",
+                "── Merged from \"follow-up\" ──",
+                "follow-up",
+                "mock-1 heard: follow-up",
+            ]
+        );
+        let sessions = engine.list_sessions()?;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, old);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_system_prompt_per_session() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.set_settings(Settings {
+            chat: ChatConfig {
+                system_prompt: "Be brief.".to_string(),
+                ..engine.settings.chat.clone()
+            },
+            ..engine.settings.clone()
+        });
+        assert_eq!(engine.conversation.system_prompt, "Be brief.");
+
+        engine.conversation.system_prompt = "You are a pirate.".to_string();
+        let reply = engine.send("ahoy").run(|_| {}).await?;
+        engine.receive(reply);
+        let pirate = engine.conversation.id.clone();
+        // New chats keep the prompt, only sessions saved without one use the default
+        engine.new_chat()?;
+        assert_eq!(engine.conversation.system_prompt, "You are a pirate.");
+        engine.conversation.system_prompt = "Be brief.".to_string();
+        let reply = engine.send("hi").run(|_| {}).await?;
+        engine.receive(reply);
+        let brief = engine.conversation.id.clone();
+
+        engine.open_session(&pirate)?;
+        assert_eq!(engine.conversation.system_prompt, "You are a pirate.");
+        // Forks keep the prompt of the conversation they came from
+        engine.start_conversation(engine.conversation.messages.clone())?;
+        assert_eq!(engine.conversation.system_prompt, "You are a pirate.");
+        engine.open_session(&brief)?;
+        assert_eq!(engine.conversation.system_prompt, "Be brief.");
+
+        // A model picked for the chat carries over, unlike the one of a persona
+        engine.set_model("mock-3");
+        engine.conversation.temperature = Some(0.2);
+        engine.new_chat()?;
+        assert_eq!(engine.conversation.model, "mock-3");
+        engine.start_persona(&Persona {
+            name: "Ada".to_string(),
+            system_prompt: "You are Ada.".to_string(),
+            greeting: Some("Welcome.".to_string()),
+            model: Some("mock-2".to_string()),
+            temperature: Some(0.9),
+        })?;
+        let ada = engine.conversation.id.clone();
+        assert_eq!(engine.conversation.messages[0].content, "Welcome.");
+        assert_eq!(engine.conversation.model, "mock-2");
+        engine.new_chat()?;
+        assert_eq!(engine.conversation.persona, None);
+        assert_eq!(engine.conversation.system_prompt, "Be brief.");
+        assert_eq!(engine.conversation.model, "mock-3");
+        assert_eq!(engine.conversation.temperature, Some(0.2));
+        engine.open_session(&ada)?;
+        assert_eq!(engine.conversation.persona.as_deref(), Some("Ada"));
+        assert_eq!(engine.conversation.system_prompt, "You are Ada.");
+        assert_eq!(engine.conversation.model, "mock-2");
+        assert_eq!(engine.conversation.temperature, Some(0.9));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_roundtable() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        let persona = |name: &str, model: Option<&str>| Persona {
+            name: name.to_string(),
+            system_prompt: format!("You are {name}."),
+            model: model.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(engine.start_roundtable(vec![persona("Ada", None)]).is_err());
+        engine.start_roundtable(vec![
+            persona("Ada", None),
+            persona("Critic", Some("mock-2")),
+        ])?;
+        let reply = engine.send("Tabs or spaces?").run(|_| {}).await?;
+        engine.receive(reply);
+        let completion = engine
+            .advance_roundtable()
+            .expect("the critic answers next");
+        // The critic sees Ada's answer as something said to it
+        let request = engine.last_request().unwrap()["messages"].clone();
+        assert_eq!(request[0]["content"], "You are Critic.");
+        assert_eq!(request[2]["role"], "user");
+        assert_eq!(request[2]["content"], "Ada: mock-1 heard: Tabs or spaces?");
+        engine.receive(completion.run(|_| {}).await?);
+        assert!(engine.advance_roundtable().is_none());
+
+        let labels: Vec<_> = engine
+            .conversation
+            .messages
+            .iter()
+            .map(|message| message.metadata.persona.as_deref())
+            .collect();
+        assert_eq!(labels, [None, Some("Ada"), Some("Critic")]);
+        assert_eq!(
+            engine.conversation.messages[2].content,
+            "mock-2 heard: Ada: mock-1 heard: Tabs or spaces?"
+        );
+        engine.stop_roundtable();
+        assert_eq!(engine.conversation.model, "mock-1");
+        assert_eq!(engine.conversation.persona, None);
+        assert_eq!(engine.conversation.system_prompt, "");
+        std::fs::remove_dir_all(dir).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversation_temperature() {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.settings.chat.temperature = Some(0.7);
+        engine.send("hot");
+        assert_eq!(engine.last_request().unwrap()["temperature"], 0.7);
+
+        // Set by a template, it wins over the config
+        engine.conversation.is_loading = false;
+        engine.conversation.temperature = Some(0.0);
+        engine.send("cold");
+        assert_eq!(engine.last_request().unwrap()["temperature"], 0.0);
+    }
+
+    #[test]
+    fn test_request_budget_status() {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.settings.budget.daily.hard = Some(1.0);
+        let completion = engine.send("hello");
+        assert_eq!(
+            engine.request_budget_status(&completion),
+            BudgetStatus::Within
+        );
+
+        // At a dollar per token, the prompt alone crosses the limit
+        let model = engine.conversation.model.clone();
+        engine.settings.models.insert(
+            model,
+            ModelParams {
+                prompt_price: Some(1_000_000.0),
+                ..Default::default()
+            },
+        );
+        engine.conversation.is_loading = false;
+        let completion = engine.send("hello again");
+        assert!(matches!(
+            engine.request_budget_status(&completion),
+            BudgetStatus::Hard(reason) if reason.contains("the request costs about")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_regenerate_and_save() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+
+        let mut deltas = Vec::new();
+        let reply = engine.send("hello").run(|delta| deltas.push(delta)).await?;
+        assert!(engine.conversation.is_loading);
+        for delta in &deltas {
+            engine.receive_delta(delta);
+        }
+        assert_eq!(
+            engine
+                .conversation
+                .partial
+                .as_ref()
+                .map(|partial| partial.content.as_str()),
+            Some("mock-1 heard: hello")
+        );
+        engine.receive(reply);
+        assert_eq!(engine.conversation.partial, None);
+        assert!(!engine.conversation.is_loading);
+        assert_eq!(
+            engine.conversation.messages[1].content,
+            "mock-1 heard: hello"
+        );
+
+        let completion = engine.regenerate(1).expect("the question is asked again");
+        assert_eq!(engine.conversation.messages.len(), 1);
+        engine.receive(completion.run(|_| {}).await?);
+        assert_eq!(engine.conversation.messages.len(), 2);
+        assert_eq!(engine.model_history().recent, vec!["mock-1"]);
+        // The answer it replaced is kept as an earlier version
+        let reply = &engine.conversation.messages[1];
+        assert_eq!((reply.versions.len(), reply.version), (1, 1));
+        assert_eq!(reply.versions[0].content, "mock-1 heard: hello");
+
+        let id = engine.conversation.id.clone();
+        engine.start_conversation(Vec::new())?;
+        assert!(engine.conversation.messages.is_empty());
+        assert_eq!(engine.list_sessions()?[0].title, "hello");
+
+        engine.open_session(&id)?;
+        assert_eq!(engine.conversation.messages.len(), 2);
+        engine.delete_session(&id)?;
+        assert_eq!(engine.list_sessions()?, Vec::new());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_queued_in_order() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-engine-{}", new_session_id()));
+        let mut engine = engine(&dir);
+        engine.conversation.queued = vec!["first".to_string(), "second".to_string()];
+
+        let completion = engine.send_queued().expect("the oldest message is sent");
+        assert!(
+            engine.send_queued().is_none(),
+            "waits for the pending reply"
+        );
+        // The request failed without reaching the provider
+        engine.conversation.requeue_unanswered();
+        assert_eq!(engine.conversation.queued, vec!["first", "second"]);
+        assert!(engine.conversation.messages.is_empty());
+        drop(completion);
+
+        for _ in 0..2 {
+            let completion = engine.send_queued().expect("a message is queued");
+            engine.receive(completion.run(|_| {}).await?);
+        }
+        let questions: Vec<&str> = engine
+            .conversation
+            .messages
+            .iter()
+            .filter(|message| message.role == "user")
+            .map(|message| message.content.as_str())
+            .collect();
+        assert_eq!(questions, vec!["first", "second"]);
+        assert!(engine.send_queued().is_none());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::Report;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use super::provider::Provider;
+
+/// Settings for checking whether the provider can be reached.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Seconds between checks; 0 turns them off.
+    pub interval_secs: u64,
+    /// Answers slower than this count as degraded.
+    pub slow_ms: u64,
+    /// Checks that take longer than this count as offline.
+    pub timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            slow_ms: 2000,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// How the connection to the provider looks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+pub enum Health {
+    /// Not checked yet.
+    #[default]
+    Unknown,
+    Online,
+    /// Reachable, but slow or failing requests.
+    Degraded,
+    Offline,
+}
+
+impl Health {
+    /// Judge a ping by its HTTP status and how long it took.
+    pub fn from_ping(status: u16, elapsed: Duration, config: &HealthConfig) -> Self {
+        if status >= 500 || elapsed > Duration::from_millis(config.slow_ms) {
+            Self::Degraded
+        } else {
+            Self::Online
+        }
+    }
+
+    /// Judge a failed request: no connection at all is worse than an error from the provider.
+    pub fn from_error(err: &Report) -> Self {
+        let unreachable = err
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|err| err.is_connect() || err.is_timeout());
+        if unreachable {
+            Self::Offline
+        } else {
+            Self::Degraded
+        }
+    }
+}
+
+/// Ping the provider once.
+pub async fn check(provider: &Provider, config: &HealthConfig) -> Health {
+    let started = Instant::now();
+    match provider
+        .ping(Duration::from_millis(config.timeout_ms))
+        .await
+    {
+        Ok(status) => Health::from_ping(status, started.elapsed(), config),
+        Err(err) => Health::from_error(&err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_from_ping() {
+        let config = HealthConfig::default();
+        let fast = Duration::from_millis(100);
+        assert_eq!(Health::from_ping(200, fast, &config), Health::Online);
+        // Any answer means the provider is reachable, even one refusing the method
+        assert_eq!(Health::from_ping(405, fast, &config), Health::Online);
+        assert_eq!(Health::from_ping(503, fast, &config), Health::Degraded);
+        assert_eq!(
+            Health::from_ping(200, Duration::from_secs(3), &config),
+            Health::Degraded
+        );
+    }
+}
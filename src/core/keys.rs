@@ -0,0 +1,109 @@
+//! API keys entered in the app, kept in the data directory so that they survive restarts and take
+//! precedence over the environment.
+
+use std::{collections::BTreeMap, fmt, io::Write, path::PathBuf};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// An API key, shown only by its last characters when debug-printed, e.g. in the action log.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    pub fn new(key: &str) -> Self {
+        Self(key.trim().to_string())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// The key with everything but its last four characters hidden.
+    pub fn masked(&self) -> String {
+        let chars: Vec<char> = self.0.chars().collect();
+        let shown: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+        format!("••••{shown}")
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ApiKey({})", self.masked())
+    }
+}
+
+/// The stored keys, by provider, in a JSON file only the user can read.
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    path: PathBuf,
+}
+
+impl KeyStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, ApiKey>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(&self.path)?)?)
+    }
+
+    pub fn get(&self, provider: &str) -> Result<Option<ApiKey>> {
+        Ok(self.load()?.remove(provider))
+    }
+
+    /// Store `key` for `provider`, replacing the one it had.
+    pub fn set(&self, provider: &str, key: ApiKey) -> Result<()> {
+        let mut keys = self.load()?;
+        keys.insert(provider.to_string(), key);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&self.path)?;
+        // The mode only applies to new files, so tighten one made by hand before writing to it
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        file.write_all(serde_json::to_string_pretty(&keys)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_store_and_mask() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-keys-{}", new_session_id()));
+        let store = KeyStore::new(dir.join("keys.json"));
+        assert_eq!(store.get("openrouter")?, None);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("keys.json"), "{}")?;
+
+        store.set("openrouter", ApiKey::new(" sk-or-old1 "))?;
+        store.set("openrouter", ApiKey::new("sk-or-new2"))?;
+        let key = store.get("openrouter")?.unwrap();
+        assert_eq!(key.expose(), "sk-or-new2");
+        assert_eq!(format!("{key:?}"), "ApiKey(••••new2)");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join("keys.json"))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
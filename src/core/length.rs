@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+/// How long replies should be, switched per conversation.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Display, EnumString, Serialize, Deserialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseLength {
+    Brief,
+    #[default]
+    Normal,
+    Detailed,
+}
+
+impl ResponseLength {
+    /// The next preset, wrapping around, for a key that cycles through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Brief => Self::Normal,
+            Self::Normal => Self::Detailed,
+            Self::Detailed => Self::Brief,
+        }
+    }
+}
+
+/// What a length preset changes about the request.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LengthPreset {
+    /// Replaces the configured `max_tokens`, if set.
+    pub max_tokens: Option<u64>,
+    /// Appended to the system prompt; nothing if empty.
+    pub instruction: String,
+}
+
+/// The presets for each [`ResponseLength`].
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LengthPresets {
+    pub brief: LengthPreset,
+    pub normal: LengthPreset,
+    pub detailed: LengthPreset,
+}
+
+impl Default for LengthPresets {
+    fn default() -> Self {
+        Self {
+            brief: LengthPreset {
+                max_tokens: Some(400),
+                instruction: "Keep the answer brief: a few sentences, no preamble.".to_string(),
+            },
+            normal: LengthPreset::default(),
+            detailed: LengthPreset {
+                max_tokens: Some(8192),
+                instruction: "Answer in detail, with explanations and examples where they help."
+                    .to_string(),
+            },
+        }
+    }
+}
+
+impl LengthPresets {
+    pub fn get(&self, length: ResponseLength) -> &LengthPreset {
+        match length {
+            ResponseLength::Brief => &self.brief,
+            ResponseLength::Normal => &self.normal,
+            ResponseLength::Detailed => &self.detailed,
+        }
+    }
+}
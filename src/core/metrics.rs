@@ -0,0 +1,278 @@
+//! Opt-in counters of requests, errors and latency, kept in a local file for `lazychat stats`.
+//!
+//! Only outcomes and timings are recorded, never messages or model names, and nothing is sent
+//! anywhere.
+
+use std::{io::Write, path::PathBuf};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::usage::{SECONDS_PER_DAY, civil_date};
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+}
+
+/// Upper bounds of the latency histogram buckets in milliseconds; slower requests go into a last
+/// bucket.
+const BUCKETS_MS: [u64; 7] = [250, 500, 1000, 2000, 5000, 10_000, 30_000];
+const BAR_WIDTH: u64 = 30;
+
+/// How many requests took how long.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Histogram {
+    /// One count per bucket of [`BUCKETS_MS`] and one for the slower ones.
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn add(&mut self, ms: u64) {
+        self.counts.resize(BUCKETS_MS.len() + 1, 0);
+        let bucket = BUCKETS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let max = self.counts.iter().copied().max().unwrap_or_default().max(1);
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(bucket, &count)| {
+                let label = match BUCKETS_MS.get(bucket) {
+                    Some(bound) => format!("≤ {}", duration(*bound)),
+                    None => format!("> {}", duration(BUCKETS_MS[BUCKETS_MS.len() - 1])),
+                };
+                let bar = "█".repeat((count * BAR_WIDTH).div_ceil(max) as usize);
+                format!("  {label:>8} {count:>7} {bar}")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+fn duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{ms} ms")
+    } else {
+        format!("{} s", ms / 1000)
+    }
+}
+
+/// Everything recorded so far.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metrics {
+    /// Unix timestamp in seconds of the first request recorded.
+    pub since: u64,
+    pub requests: u64,
+    /// Requests sent again after the provider refused them, not counted as requests.
+    pub retries: u64,
+    pub errors: u64,
+    /// Attempts refused with 429.
+    pub rate_limited: u64,
+    /// Time until the whole reply arrived.
+    pub latency: Histogram,
+    /// Time until the first token arrived.
+    pub first_token: Histogram,
+}
+
+impl Metrics {
+    fn add(&mut self, record: &MetricsRecord) {
+        if self.requests + self.retries == 0 {
+            self.since = record.timestamp;
+        }
+        if record.retry {
+            self.retries += 1;
+        } else {
+            self.requests += 1;
+        }
+        match record.outcome {
+            Outcome::Replied {
+                latency_ms,
+                first_token_ms,
+            } => {
+                if let Some(ms) = latency_ms {
+                    self.latency.add(ms);
+                }
+                if let Some(ms) = first_token_ms {
+                    self.first_token.add(ms);
+                }
+            }
+            Outcome::RateLimited => self.rate_limited += 1,
+            Outcome::Failed => self.errors += 1,
+        }
+    }
+
+    /// Render the metrics as text for `lazychat stats`.
+    pub fn lines(&self) -> Vec<String> {
+        let attempts = self.requests + self.retries;
+        let share = |count: u64| count as f64 * 100.0 / attempts.max(1) as f64;
+        let (year, month, day) = civil_date(self.since / SECONDS_PER_DAY);
+        let mut lines = vec![
+            format!("Since {year:04}-{month:02}-{day:02}"),
+            String::new(),
+            format!("Requests      {:>7}", self.requests),
+            format!("Retries       {:>7}", self.retries),
+            format!(
+                "Errors        {:>7} ({:.1}%)",
+                self.errors,
+                share(self.errors)
+            ),
+            format!(
+                "Rate limited  {:>7} ({:.1}%)",
+                self.rate_limited,
+                share(self.rate_limited)
+            ),
+        ];
+        for (title, histogram) in [
+            ("Latency", &self.latency),
+            ("Time to first token", &self.first_token),
+        ] {
+            if histogram.counts.iter().any(|&count| count > 0) {
+                lines.push(String::new());
+                lines.push(title.to_string());
+                lines.extend(histogram.lines());
+            }
+        }
+        lines
+    }
+}
+
+/// How a request ended, as far as the metrics are concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Replied {
+        latency_ms: Option<u64>,
+        first_token_ms: Option<u64>,
+    },
+    RateLimited,
+    Failed,
+}
+
+/// One line of the metrics file.
+#[derive(Debug, Serialize, Deserialize)]
+struct MetricsRecord {
+    timestamp: u64,
+    /// Whether the request was sent again after the provider refused it.
+    #[serde(default)]
+    retry: bool,
+    outcome: Outcome,
+}
+
+/// Appends the outcome of every request to a JSON lines file, so that concurrent requests don't
+/// overwrite each other's counts.
+#[derive(Debug, Clone)]
+pub struct MetricsLog {
+    path: PathBuf,
+}
+
+impl MetricsLog {
+    /// A log at `path`, or `None` if metrics are disabled.
+    pub fn new(config: &MetricsConfig, path: PathBuf) -> Option<Self> {
+        config.enabled.then_some(Self { path })
+    }
+
+    /// The metrics at `path` added up, empty if nothing was recorded yet.
+    pub fn load(path: &std::path::Path) -> Result<Metrics> {
+        let mut metrics = Metrics::default();
+        if !path.exists() {
+            return Ok(metrics);
+        }
+        std::fs::read_to_string(path)?
+            .lines()
+            // A line cut short by a crash shouldn't lose the rest
+            .filter_map(|line| serde_json::from_str::<MetricsRecord>(line).ok())
+            .for_each(|record| metrics.add(&record));
+        Ok(metrics)
+    }
+
+    /// Record how an attempt at a request ended; `retry` if it was sent again after a refusal.
+    pub fn record(&self, now: u64, retry: bool, outcome: Outcome) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let record = MetricsRecord {
+            timestamp: now,
+            retry,
+            outcome,
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_record_and_lines() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-metrics-{}", new_session_id()));
+        let path = dir.join("metrics.json");
+        assert!(MetricsLog::new(&MetricsConfig::default(), path.clone()).is_none());
+
+        let log = MetricsLog::new(&MetricsConfig { enabled: true }, path.clone()).unwrap();
+        for latency_ms in [300, 400, 1500, 40_000] {
+            log.record(
+                100,
+                false,
+                Outcome::Replied {
+                    latency_ms: Some(latency_ms),
+                    first_token_ms: None,
+                },
+            )?;
+        }
+        log.record(200, false, Outcome::RateLimited)?;
+        log.record(
+            200,
+            true,
+            Outcome::Replied {
+                latency_ms: None,
+                first_token_ms: None,
+            },
+        )?;
+        log.record(200, false, Outcome::Failed)?;
+
+        let metrics = MetricsLog::load(&path)?;
+        assert_eq!(metrics.since, 100);
+        assert_eq!(
+            metrics.lines(),
+            [
+                "Since 1970-01-01",
+                "",
+                "Requests            6",
+                "Retries             1",
+                "Errors              1 (14.3%)",
+                "Rate limited        1 (14.3%)",
+                "",
+                "Latency",
+                "  ≤ 250 ms       0",
+                "  ≤ 500 ms       2 ██████████████████████████████",
+                "     ≤ 1 s       0",
+                "     ≤ 2 s       1 ███████████████",
+                "     ≤ 5 s       0",
+                "    ≤ 10 s       0",
+                "    ≤ 30 s       0",
+                "    > 30 s       1 ███████████████",
+            ]
+        );
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
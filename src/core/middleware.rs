@@ -0,0 +1,124 @@
+//! Hooks that see every request before it is sent and every reply before it is kept.
+//!
+//! Features that need to change what goes to or comes back from the provider, like redaction,
+//! templating or logging, register a [`Middleware`] with [`Engine::add_middleware`] instead of
+//! special-casing the places requests are made.
+//!
+//! [`Engine::add_middleware`]: super::Engine::add_middleware
+
+use std::{fmt, sync::Arc};
+
+use serde_json::Value;
+use tracing::debug;
+
+use super::ChatMessage;
+
+pub trait Middleware: Send + Sync {
+    /// Shown in logs.
+    fn name(&self) -> &str;
+
+    /// Change the chat completion request body before it is sent.
+    fn on_request(&self, _body: &mut Value) {}
+
+    /// Change the reply before it is added to the conversation. The text streamed while it
+    /// arrives is shown as the provider sent it.
+    fn on_response(&self, _reply: &mut ChatMessage) {}
+}
+
+/// Middleware run in the order it was added for requests and in reverse order for replies, so
+/// that each one sees replies as the request it changed would get them.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain(Vec<Arc<dyn Middleware>>);
+
+impl MiddlewareChain {
+    pub fn push(&mut self, middleware: impl Middleware + 'static) {
+        self.0.push(Arc::new(middleware));
+    }
+
+    pub fn on_request(&self, body: &mut Value) {
+        for middleware in &self.0 {
+            middleware.on_request(body);
+        }
+    }
+
+    pub fn on_response(&self, reply: &mut ChatMessage) {
+        for middleware in self.0.iter().rev() {
+            middleware.on_response(reply);
+        }
+    }
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|middleware| middleware.name()))
+            .finish()
+    }
+}
+
+/// Logs the size of every request and reply at debug level, e.g. with `/log lazychat=debug`.
+pub struct Logging;
+
+impl Middleware for Logging {
+    fn name(&self) -> &str {
+        "logging"
+    }
+
+    fn on_request(&self, body: &mut Value) {
+        let messages = body["messages"].as_array().map_or(0, Vec::len);
+        debug!("Sending {messages} messages to {}", body["model"]);
+    }
+
+    fn on_response(&self, reply: &mut ChatMessage) {
+        debug!(
+            "Received {} characters and {} tool calls from {}",
+            reply.content.chars().count(),
+            reply.tool_calls.len(),
+            reply.metadata.model.as_deref().unwrap_or("the provider")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Appends its tag to the request's `tags` and to the reply.
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn on_request(&self, body: &mut Value) {
+            body["tags"]
+                .as_array_mut()
+                .expect("tags")
+                .push(self.0.into());
+        }
+
+        fn on_response(&self, reply: &mut ChatMessage) {
+            reply.content.push_str(self.0);
+        }
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let mut chain = MiddlewareChain::default();
+        chain.push(Tag("a"));
+        chain.push(Tag("b"));
+        assert_eq!(format!("{chain:?}"), r#"["a", "b"]"#);
+
+        let mut body = json!({ "tags": [] });
+        chain.on_request(&mut body);
+        assert_eq!(body["tags"], json!(["a", "b"]));
+
+        let mut reply = ChatMessage::new("AI", "");
+        chain.on_response(&mut reply);
+        assert_eq!(reply.content, "ba");
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// How many recently used models are remembered.
+const MAX_RECENT: usize = 8;
+
+/// Starred and recently used models, saved in the data directory.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelHistory {
+    pub favorites: Vec<String>,
+    /// Most recently used first.
+    pub recent: Vec<String>,
+}
+
+impl ModelHistory {
+    /// Read the history from `path`, starting empty if it is missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        let Ok(json) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|err| {
+            error!("Failed to read {}: {err}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Move `model` to the front of the recent models; returns whether anything changed.
+    pub fn record_use(&mut self, model: &str) -> bool {
+        if self.recent.first().is_some_and(|recent| recent == model) {
+            return false;
+        }
+        self.recent.retain(|recent| recent != model);
+        self.recent.insert(0, model.to_string());
+        self.recent.truncate(MAX_RECENT);
+        true
+    }
+
+    /// Star or unstar `model`; returns whether it is a favorite now.
+    pub fn toggle_favorite(&mut self, model: &str) -> bool {
+        if self.is_favorite(model) {
+            self.favorites.retain(|favorite| favorite != model);
+            false
+        } else {
+            self.favorites.push(model.to_string());
+            true
+        }
+    }
+
+    pub fn is_favorite(&self, model: &str) -> bool {
+        self.favorites.iter().any(|favorite| favorite == model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_record_use() {
+        let mut history = ModelHistory::default();
+        for model in ["a", "b", "c"] {
+            assert!(history.record_use(model));
+        }
+        assert!(!history.record_use("c"));
+        assert!(history.record_use("a"));
+        assert_eq!(history.recent, vec!["a", "c", "b"]);
+
+        for index in 0..MAX_RECENT {
+            history.record_use(&format!("model-{index}"));
+        }
+        assert_eq!(history.recent.len(), MAX_RECENT);
+        assert_eq!(history.recent[0], format!("model-{}", MAX_RECENT - 1));
+    }
+
+    #[test]
+    fn test_toggle_favorite() {
+        let mut history = ModelHistory::default();
+        assert!(history.toggle_favorite("a"));
+        assert!(history.is_favorite("a"));
+        assert!(!history.toggle_favorite("a"));
+        assert_eq!(history.favorites, Vec::<String>::new());
+    }
+}
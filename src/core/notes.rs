@@ -0,0 +1,229 @@
+//! Conversations saved with `/note` to a notes directory, e.g. an Obsidian vault, as Markdown
+//! with YAML frontmatter.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+
+use super::{
+    Conversation, session, share,
+    usage::{self, SECONDS_PER_DAY},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct NotesConfig {
+    /// The directory notes are saved to; `/note` asks for it to be set until it is.
+    pub dir: Option<String>,
+    /// Append to the note of the day, `YYYY-MM-DD.md`, instead of writing a note per
+    /// conversation.
+    pub daily: bool,
+    /// Tags in the frontmatter of new notes.
+    pub tags: Vec<String>,
+}
+
+impl Default for NotesConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            daily: true,
+            tags: vec!["ai".to_string(), "lazychat".to_string()],
+        }
+    }
+}
+
+/// Today's date as `YYYY-MM-DD` in the local time zone, so that notes written late in the
+/// evening go to the day they were written on.
+pub fn today() -> String {
+    let now = session::now();
+    let local = now.saturating_add_signed(utc_offset(now));
+    let (year, month, day) = usage::civil_date(local / SECONDS_PER_DAY);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+/// Seconds the local time zone is ahead of UTC at `time`, 0 where that can't be told.
+#[cfg(unix)]
+fn utc_offset(time: u64) -> i64 {
+    let time = time as libc::time_t;
+    // SAFETY: a zeroed `tm` is valid, and `localtime_r` only writes to it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(not(unix))]
+fn utc_offset(_time: u64) -> i64 {
+    0
+}
+
+/// Save the conversation, or the `summary` of it, to a note in `dir` and return its path.
+///
+/// A daily note gets a section per conversation and lists the models of all of them in its
+/// frontmatter.
+pub fn save(
+    config: &NotesConfig,
+    dir: &Path,
+    date: &str,
+    conversation: &Conversation,
+    summary: Option<&str>,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .map_err(|err| eyre!("Failed to create {}: {err}", dir.display()))?;
+    let title = conversation
+        .title
+        .clone()
+        .unwrap_or_else(|| session::auto_title(&conversation.messages));
+    let model = &conversation.model;
+    let tags = config.tags.join(", ");
+    let (path, text) = if config.daily {
+        let path = dir.join(format!("{date}.md"));
+        let existing = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                format!("---\ndate: {date}\nmodels: []\ntags: [{tags}]\n---\n")
+            }
+            Err(err) => return Err(eyre!("Failed to read {}: {err}", path.display())),
+        };
+        let mut text = add_model(&existing, model);
+        text.push_str(&format!("\n## {title}\n\nModel: `{model}`\n"));
+        text.push_str(&body(conversation, summary, "###"));
+        (path, text)
+    } else {
+        let mut text =
+            format!("---\ndate: {date}\nmodel: {model}\ntags: [{tags}]\n---\n\n# {title}\n");
+        text.push_str(&body(conversation, summary, "##"));
+        return write_new(dir, &format!("{date} {}", file_name(&title)), &text);
+    };
+    std::fs::write(&path, text)
+        .map_err(|err| eyre!("Failed to write {}: {err}", path.display()))?;
+    Ok(path)
+}
+
+/// Write `text` to a new note `name.md` in `dir`, or `name (2).md` and so on if there is one
+/// already, e.g. of another conversation with the same title.
+fn write_new(dir: &Path, name: &str, text: &str) -> Result<PathBuf> {
+    for number in 1.. {
+        let path = match number {
+            1 => dir.join(format!("{name}.md")),
+            number => dir.join(format!("{name} ({number}).md")),
+        };
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path);
+        match file {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())
+                    .map_err(|err| eyre!("Failed to write {}: {err}", path.display()))?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(eyre!("Failed to write {}: {err}", path.display())),
+        }
+    }
+    unreachable!("there is always a free number")
+}
+
+fn body(conversation: &Conversation, summary: Option<&str>, heading: &str) -> String {
+    match summary {
+        Some(summary) => format!("\n{}\n", summary.trim_end()),
+        None => share::messages_markdown(conversation, heading),
+    }
+}
+
+/// `note` with `model` added to the `models: [...]` list of its frontmatter, if not there yet.
+fn add_model(note: &str, model: &str) -> String {
+    let Some(frontmatter) = note
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+        .map(|(frontmatter, _)| frontmatter)
+    else {
+        return note.to_string();
+    };
+    let mut lines = Vec::new();
+    for line in frontmatter.lines() {
+        match line
+            .strip_prefix("models: [")
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            Some(models) if !models.split(", ").any(|name| name == model) => {
+                let separator = if models.is_empty() { "" } else { ", " };
+                lines.push(format!("models: [{models}{separator}{model}]"));
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    format!(
+        "---\n{}{}",
+        lines.join("\n"),
+        &note[4 + frontmatter.len()..]
+    )
+}
+
+/// `title` without the characters file systems or note apps don't allow in names.
+fn file_name(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '-',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::{ChatMessage, session::new_session_id};
+
+    #[test]
+    fn test_save() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-notes-{}", new_session_id()));
+        let config = NotesConfig {
+            dir: None,
+            daily: true,
+            tags: vec!["ai".to_string()],
+        };
+        let mut conversation = Conversation::new("mock-1");
+        conversation.push(ChatMessage::new("user", "Hi"));
+        conversation.push(ChatMessage::new("AI", "Hello!"));
+        let path = save(&config, &dir, "2026-10-18", &conversation, None)?;
+        conversation.model = "mock-2".to_string();
+        conversation.title = Some("Greetings".to_string());
+        save(&config, &dir, "2026-10-18", &conversation, Some("Said hi."))?;
+        assert_eq!(path, dir.join("2026-10-18.md"));
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "---\ndate: 2026-10-18\nmodels: [mock-1, mock-2]\ntags: [ai]\n---\n\n## Hi\n\n\
+             Model: `mock-1`\n\n### User\n\nHi\n\n### Assistant\n\nHello!\n\n## Greetings\n\n\
+             Model: `mock-2`\n\nSaid hi.\n"
+        );
+
+        let config = NotesConfig {
+            daily: false,
+            ..config
+        };
+        conversation.title = Some("Hi: again?".to_string());
+        let path = save(&config, &dir, "2026-10-18", &conversation, None)?;
+        assert_eq!(path, dir.join("2026-10-18 Hi- again-.md"));
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "---\ndate: 2026-10-18\nmodel: mock-2\ntags: [ai]\n---\n\n# Hi: again?\n\n\
+             ## User\n\nHi\n\n## Assistant\n\nHello!\n"
+        );
+        // Another conversation of the same title doesn't overwrite it
+        let path = save(&config, &dir, "2026-10-18", &conversation, Some("Said hi."))?;
+        assert_eq!(path, dir.join("2026-10-18 Hi- again- (2).md"));
+        assert_eq!(today().len(), "2026-10-18".len());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+//! Turn the endpoints of an OpenAPI 3 spec into custom tools.
+
+use std::collections::{BTreeMap, HashMap};
+
+use color_eyre::{Result, eyre::eyre};
+use serde_json::{Map, Value, json};
+use yaml_rust2::Yaml;
+
+use super::tools::{CustomTool, HttpTool};
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// How deep `$ref`s are followed; deeper or recursive schemas are left as plain objects.
+const MAX_REF_DEPTH: usize = 8;
+
+/// Parse a spec written in JSON or YAML.
+pub fn parse(text: &str) -> Result<Value> {
+    if let Ok(spec) = serde_json::from_str(text) {
+        return Ok(spec);
+    }
+    let documents = yaml_rust2::YamlLoader::load_from_str(text)?;
+    let document = documents
+        .first()
+        .ok_or_else(|| eyre!("The spec is empty"))?;
+    Ok(yaml_to_json(document))
+}
+
+fn yaml_to_json(yaml: &Yaml) -> Value {
+    match yaml {
+        Yaml::Real(real) => real
+            .parse::<f64>()
+            .map(|real| json!(real))
+            .unwrap_or_else(|_| json!(real)),
+        Yaml::Integer(integer) => json!(integer),
+        Yaml::String(text) => json!(text),
+        Yaml::Boolean(boolean) => json!(boolean),
+        Yaml::Array(items) => items.iter().map(yaml_to_json).collect(),
+        Yaml::Hash(hash) => hash
+            .iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    Yaml::String(key) => key.clone(),
+                    // Status codes and the like
+                    key => yaml_to_json(key).to_string(),
+                };
+                (key, yaml_to_json(value))
+            })
+            .collect(),
+        _ => Value::Null,
+    }
+}
+
+/// One tool per operation, named after its `operationId` or its method and path.
+///
+/// `base_url` replaces the spec's first server and `headers` are sent with every request.
+pub fn import(
+    spec: &Value,
+    base_url: Option<&str>,
+    headers: &HashMap<String, String>,
+) -> Result<BTreeMap<String, CustomTool>> {
+    let base_url = base_url
+        .or_else(|| spec["servers"][0]["url"].as_str())
+        .ok_or_else(|| eyre!("The spec has no servers, pass a base URL"))?
+        .trim_end_matches('/');
+    let paths = spec["paths"]
+        .as_object()
+        .ok_or_else(|| eyre!("The spec has no paths"))?;
+
+    let mut tools = BTreeMap::new();
+    for (path, item) in paths {
+        let shared = item["parameters"].as_array().cloned().unwrap_or_default();
+        for method in METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+            let name = operation["operationId"]
+                .as_str()
+                .map(tool_name)
+                .unwrap_or_else(|| tool_name(&format!("{method}_{path}")));
+
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            let mut query = HashMap::new();
+            let parameters = shared
+                .iter()
+                .chain(operation["parameters"].as_array().into_iter().flatten());
+            for parameter in parameters {
+                let parameter = resolve(spec, parameter, 0);
+                let Some(parameter_name) = parameter["name"].as_str() else {
+                    continue;
+                };
+                match parameter["in"].as_str() {
+                    Some("path") => {}
+                    Some("query") => {
+                        query.insert(parameter_name.to_string(), format!("{{{parameter_name}}}"));
+                    }
+                    // Headers and cookies are for auth, which `headers` covers
+                    _ => continue,
+                }
+                let mut schema = parameter
+                    .get("schema")
+                    .cloned()
+                    .unwrap_or_else(|| json!({ "type": "string" }));
+                if let Some(description) = parameter.get("description") {
+                    schema["description"] = description.clone();
+                }
+                properties.insert(parameter_name.to_string(), schema);
+                if parameter["required"].as_bool() == Some(true) || parameter["in"] == "path" {
+                    required.push(parameter_name.to_string());
+                }
+            }
+
+            let body = resolve(spec, &operation["requestBody"], 0);
+            let body_schema = &body["content"]["application/json"]["schema"];
+            if !body_schema.is_null() {
+                properties.insert("body".to_string(), body_schema.clone());
+                if body["required"].as_bool() == Some(true) {
+                    required.push("body".to_string());
+                }
+            }
+
+            let description = operation["summary"]
+                .as_str()
+                .or_else(|| operation["description"].as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{} {path}", method.to_uppercase()));
+            let tool = CustomTool {
+                description,
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                })),
+                command: None,
+                http: Some(HttpTool {
+                    url: format!("{base_url}{path}"),
+                    method: method.to_uppercase(),
+                    query,
+                    headers: headers.clone(),
+                    body: (!body_schema.is_null()).then(|| "{body}".to_string()),
+                }),
+            };
+            tools.insert(name, tool);
+        }
+    }
+    Ok(tools)
+}
+
+/// Inline the local `$ref`s in `value`.
+fn resolve(spec: &Value, value: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                let target = reference
+                    .strip_prefix('#')
+                    .and_then(|pointer| spec.pointer(pointer));
+                return match target {
+                    Some(target) if depth < MAX_REF_DEPTH => resolve(spec, target, depth + 1),
+                    _ => json!({ "type": "object" }),
+                };
+            }
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), resolve(spec, value, depth)))
+                .collect()
+        }
+        Value::Array(values) => values
+            .iter()
+            .map(|value| resolve(spec, value, depth))
+            .collect(),
+        value => value.clone(),
+    }
+}
+
+/// Tool names may only use letters, digits, `_` and `-`, up to 64 of them.
+fn tool_name(raw: &str) -> String {
+    raw.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .take(64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const SPEC: &str = r##"
+openapi: 3.0.0
+servers:
+  - url: https://api.example.com/v1/
+paths:
+  /pets/{petId}:
+    parameters:
+      - name: petId
+        in: path
+        schema: { type: integer }
+    get:
+      operationId: getPet
+      summary: Get a pet
+    delete: {}
+  /pets:
+    get:
+      operationId: listPets
+      parameters:
+        - $ref: "#/components/parameters/limit"
+    post:
+      operationId: createPet
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema: { $ref: "#/components/schemas/Pet" }
+components:
+  parameters:
+    limit:
+      name: limit
+      in: query
+      description: How many to return
+      schema: { type: integer }
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name: { type: string }
+"##;
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let spec = parse(SPEC)?;
+        let headers = HashMap::from([(
+            "Authorization".to_string(),
+            "Bearer ${PETS_TOKEN}".to_string(),
+        )]);
+        let tools = import(&spec, None, &headers)?;
+        assert_eq!(
+            tools.keys().collect::<Vec<_>>(),
+            ["createPet", "delete_pets_petId", "getPet", "listPets"]
+        );
+
+        let get = &tools["getPet"];
+        assert_eq!(get.description, "Get a pet");
+        assert_eq!(
+            get.parameters,
+            Some(json!({
+                "type": "object",
+                "properties": { "petId": { "type": "integer" } },
+                "required": ["petId"],
+            }))
+        );
+        let http = get.http.as_ref().unwrap();
+        assert_eq!(http.url, "https://api.example.com/v1/pets/{petId}");
+        assert_eq!(http.headers, headers);
+
+        let list = tools["listPets"].http.as_ref().unwrap();
+        assert_eq!(
+            list.query,
+            HashMap::from([("limit".to_string(), "{limit}".to_string())])
+        );
+
+        let create = &tools["createPet"];
+        assert_eq!(
+            create.parameters.as_ref().unwrap()["properties"]["body"],
+            json!({ "type": "object", "properties": { "name": { "type": "string" } } })
+        );
+        assert_eq!(create.http.as_ref().unwrap().method, "POST");
+        assert_eq!(
+            create.http.as_ref().unwrap().body.as_deref(),
+            Some("{body}")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_without_servers() {
+        let spec = json!({ "paths": {} });
+        assert!(import(&spec, None, &HashMap::new()).is_err());
+        assert!(import(&spec, Some("http://localhost"), &HashMap::new()).is_ok());
+    }
+}
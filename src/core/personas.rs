@@ -0,0 +1,184 @@
+//! Personas to start a session as with `/persona`: from the config, or JSON files in the personas
+//! directory, which can also be character cards exported from SillyTavern and similar apps.
+
+use std::{collections::HashMap, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+/// Who the model plays in a session: its system prompt, first message and model settings.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Persona {
+    /// Taken from the config key or the file name unless set.
+    pub name: String,
+    pub system_prompt: String,
+    /// The persona's first message in a new session.
+    pub greeting: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+}
+
+impl Persona {
+    /// Read a persona file: either a persona like in the config or a character card, version 1
+    /// or a later one with the fields under `data`.
+    pub fn parse(name: &str, source: &str) -> Result<Self> {
+        let value: Value =
+            json5::from_str(source).map_err(|err| eyre!("Invalid persona {name}: {err}"))?;
+        let card = if value.get("spec").is_some() {
+            &value["data"]
+        } else {
+            &value
+        };
+        if ["first_mes", "description", "personality"]
+            .iter()
+            .any(|field| card.get(field).is_some())
+        {
+            return Ok(Self::from_card(name, card));
+        }
+        let mut persona: Self =
+            serde_json::from_value(value).map_err(|err| eyre!("Invalid persona {name}: {err}"))?;
+        if persona.name.is_empty() {
+            persona.name = name.to_string();
+        }
+        Ok(persona)
+    }
+
+    /// The persona of a character card, its description, personality, scenario and example
+    /// dialogue making up the system prompt.
+    fn from_card(name: &str, card: &Value) -> Self {
+        let field = |key: &str| {
+            card[key]
+                .as_str()
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+        };
+        let name = field("name").unwrap_or(name).to_string();
+        let mut parts = Vec::new();
+        // Cards may build on the app's default prompt, which there is none of here
+        if let Some(prompt) = field("system_prompt")
+            .map(|prompt| prompt.replace("{{original}}", "").trim().to_string())
+            .filter(|prompt| !prompt.is_empty())
+        {
+            parts.push(prompt);
+        }
+        if let Some(description) = field("description") {
+            parts.push(description.to_string());
+        }
+        if let Some(personality) = field("personality") {
+            parts.push(format!("{{{{char}}}}'s personality: {personality}"));
+        }
+        if let Some(scenario) = field("scenario") {
+            parts.push(format!("Scenario: {scenario}"));
+        }
+        if let Some(examples) = field("mes_example") {
+            parts.push(format!("Example dialogue:\n{examples}"));
+        }
+        let fill = |text: &str| {
+            text.replace("{{char}}", &name)
+                .replace("<BOT>", &name)
+                .replace("{{user}}", "User")
+                .replace("<USER>", "User")
+        };
+        Self {
+            system_prompt: fill(&parts.join("\n\n")),
+            greeting: field("first_mes").map(fill),
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// The name with the model, for the persona picker.
+    pub fn label(&self) -> String {
+        match &self.model {
+            Some(model) => format!("{} · {model}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The personas from the config and the `.json` or `.json5` files in `dir`, sorted by name; a
+/// file replaces a config entry of the same name.
+pub fn load_all(config: &HashMap<String, Persona>, dir: &Path) -> Vec<Persona> {
+    let mut personas: HashMap<String, Persona> = config
+        .iter()
+        .map(|(name, persona)| {
+            let mut persona = persona.clone();
+            if persona.name.is_empty() {
+                persona.name = name.clone();
+            }
+            (persona.name.clone(), persona)
+        })
+        .collect();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != "json" && extension != "json5")
+        {
+            continue;
+        }
+        let Some(name) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        let persona = std::fs::read_to_string(&path)
+            .map_err(Into::into)
+            .and_then(|source| Persona::parse(&name, &source));
+        match persona {
+            Ok(persona) => {
+                personas.insert(persona.name.clone(), persona);
+            }
+            Err(err) => error!("Failed to read persona {}: {err}", path.display()),
+        }
+    }
+    let mut personas: Vec<Persona> = personas.into_values().collect();
+    personas.sort_by(|a, b| a.name.cmp(&b.name));
+    personas
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let source = r#"{
+  "spec": "chara_card_v2",
+  "data": {
+    "name": "Ada",
+    "description": "{{char}} is a Victorian mathematician.",
+    "personality": "curious, precise",
+    "scenario": "{{user}} visits {{char}}'s study.",
+    "first_mes": "Welcome, {{user}}. Shall we compute something?",
+    "mes_example": "",
+    "system_prompt": "{{original}}"
+  }
+}"#;
+        assert_eq!(
+            Persona::parse("ada-card", source)?,
+            Persona {
+                name: "Ada".to_string(),
+                system_prompt: "Ada is a Victorian mathematician.\n\n\
+                                Ada's personality: curious, precise\n\n\
+                                Scenario: User visits Ada's study."
+                    .to_string(),
+                greeting: Some("Welcome, User. Shall we compute something?".to_string()),
+                model: None,
+                temperature: None,
+            }
+        );
+
+        let source = "{ system_prompt: 'You review code.', model: 'openai/gpt-4o' }";
+        let persona = Persona::parse("reviewer", source)?;
+        assert_eq!(persona.name, "reviewer");
+        assert_eq!(persona.label(), "reviewer · openai/gpt-4o");
+        Ok(())
+    }
+}
@@ -0,0 +1,733 @@
+use std::{
+    collections::HashMap,
+    env,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+use super::{
+    ChatMessage, Delta, MessageMetadata, conversation::ToolCall, keys::ApiKey,
+    length::LengthPresets, rate_limit::RateLimitConfig, transcript::TranscriptLog,
+};
+
+pub const API_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+/// Every model OpenRouter offers; listing them needs no API key.
+pub const MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+pub const API_KEY_ENV: &str = "OPENROUTER_API_KEY";
+/// Answers with the limits of the key it is called with, without spending anything.
+pub const KEY_URL: &str = "https://openrouter.ai/api/v1/key";
+
+/// Model used for chat completions.
+pub const DEFAULT_MODEL: &str = "mistralai/mistral-nemo";
+
+/// Model and sampling parameters for chat completions.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    pub provider: ProviderKind,
+    pub model: String,
+    /// The system prompt new sessions start with.
+    pub system_prompt: String,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub prompt_cache: PromptCacheConfig,
+    pub rate_limit: RateLimitConfig,
+    /// What the brief, normal and detailed reply lengths change.
+    pub lengths: LengthPresets,
+}
+
+impl ChatConfig {
+    /// The sampling parameters set for all models.
+    pub fn params(&self) -> ModelParams {
+        ModelParams {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+            prompt_price: None,
+        }
+    }
+}
+
+/// Sampling parameters; unset ones are left to the provider.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ModelParams {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u64>,
+    /// Dollars per million prompt tokens, to estimate what a request costs before it is sent.
+    pub prompt_price: Option<f64>,
+}
+
+impl ModelParams {
+    /// These parameters, with the unset ones taken from `fallback`.
+    pub fn or(self, fallback: ModelParams) -> Self {
+        Self {
+            temperature: self.temperature.or(fallback.temperature),
+            top_p: self.top_p.or(fallback.top_p),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            prompt_price: self.prompt_price.or(fallback.prompt_price),
+        }
+    }
+
+    /// The parameters for `model`: its entry in `models`, then the `chat` defaults.
+    pub fn for_model(model: &str, chat: &ChatConfig, models: &HashMap<String, Self>) -> Self {
+        models
+            .get(model)
+            .cloned()
+            .unwrap_or_default()
+            .or(chat.params())
+    }
+}
+
+/// Prompt caching (Anthropic `cache_control`, passed through by OpenRouter).
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PromptCacheConfig {
+    pub enabled: bool,
+    /// Only the system prompt and messages at least this long are marked for caching.
+    pub min_tokens: usize,
+}
+
+impl Default for PromptCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_tokens: 1024,
+        }
+    }
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            model: DEFAULT_MODEL.to_string(),
+            system_prompt: String::new(),
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            prompt_cache: PromptCacheConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            lengths: LengthPresets::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    #[value(name = "openrouter")]
+    OpenRouter,
+    Mock,
+}
+
+impl ProviderKind {
+    /// How the provider is named in the config, and which of the stored API keys it uses.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OpenRouter => "openrouter",
+            Self::Mock => "mock",
+        }
+    }
+}
+
+/// Replies of the mock provider.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MockConfig {
+    /// Reply template; `{prompt}`, `{model}` and `{count}` (number of messages) are filled in.
+    pub response: String,
+    /// Reasoning streamed before the reply, filled in like `response`; none if empty.
+    pub reasoning: String,
+    /// Delay before replying, plus a delay per word of the reply to simulate streaming.
+    pub latency_ms: u64,
+    pub word_delay_ms: u64,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            response: "You said: {prompt}".to_string(),
+            reasoning: String::new(),
+            latency_ms: 300,
+            word_delay_ms: 30,
+        }
+    }
+}
+
+/// Where chat completions come from.
+#[derive(Debug, Clone)]
+pub enum Provider {
+    OpenRouter {
+        transcript: Option<TranscriptLog>,
+        /// A key entered in the app, used instead of the environment variable.
+        api_key: Option<ApiKey>,
+    },
+    /// Canned replies for offline demos and tests.
+    Mock(MockConfig),
+}
+
+impl Provider {
+    pub fn new(
+        config: &ChatConfig,
+        mock: &MockConfig,
+        transcript: Option<TranscriptLog>,
+        api_key: Option<ApiKey>,
+    ) -> Self {
+        match config.provider {
+            ProviderKind::OpenRouter => Self::OpenRouter {
+                transcript,
+                api_key,
+            },
+            ProviderKind::Mock => Self::Mock(mock.clone()),
+        }
+    }
+
+    /// Send a chat completion request and return the assistant's reply.
+    ///
+    /// The reply is streamed: `on_delta` is called with each piece of reasoning and answer as it
+    /// arrives.
+    pub async fn complete(
+        &self,
+        body: &Value,
+        on_delta: &mut (dyn FnMut(Delta) + Send),
+    ) -> Result<ChatMessage> {
+        match self {
+            Self::OpenRouter {
+                transcript,
+                api_key,
+            } => {
+                let api_key = match api_key {
+                    Some(api_key) => api_key.expose().to_string(),
+                    None => env::var(API_KEY_ENV)
+                        .map_err(|_| eyre!("{API_KEY_ENV} environment variable not set"))?,
+                };
+                open_router(body, &api_key, transcript.as_ref(), on_delta).await
+            }
+            Self::Mock(config) => mock(config, body, on_delta).await,
+        }
+    }
+
+    /// Check that the provider accepts `key`, without spending tokens.
+    pub async fn check_key(&self, key: &ApiKey) -> Result<()> {
+        match self {
+            Self::OpenRouter { .. } => {
+                let response = reqwest::Client::new()
+                    .get(KEY_URL)
+                    .bearer_auth(key.expose())
+                    .send()
+                    .await?;
+                let status = response.status();
+                if status.is_success() {
+                    return Ok(());
+                }
+                let text = response.text().await.unwrap_or_default();
+                let body: Value = serde_json::from_str(&text).unwrap_or_default();
+                Err(eyre!(
+                    "OpenRouter rejected the key ({status}): {}",
+                    body["error"]["message"].as_str().unwrap_or(&text)
+                ))
+            }
+            Self::Mock(_) => Ok(()),
+        }
+    }
+
+    /// Check that the provider answers at all, without spending tokens; returns the HTTP status.
+    pub async fn ping(&self, timeout: Duration) -> Result<u16> {
+        match self {
+            Self::OpenRouter { .. } => {
+                let response = reqwest::Client::builder()
+                    .timeout(timeout)
+                    .build()?
+                    .head(MODELS_URL)
+                    .send()
+                    .await?;
+                Ok(response.status().as_u16())
+            }
+            Self::Mock(_) => Ok(200),
+        }
+    }
+
+    /// Ids of the models that can be chosen, sorted by name.
+    pub async fn models(&self) -> Result<Vec<String>> {
+        match self {
+            Self::OpenRouter { .. } => {
+                let text = reqwest::get(MODELS_URL)
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                let response: Value = serde_json::from_str(&text)?;
+                let mut ids: Vec<String> = response["data"]
+                    .as_array()
+                    .ok_or_else(|| eyre!("Unexpected model list: {response}"))?
+                    .iter()
+                    .filter_map(|model| model["id"].as_str().map(str::to_string))
+                    .collect();
+                ids.sort();
+                Ok(ids)
+            }
+            // The mock answers as whichever model is asked for
+            Self::Mock(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+async fn open_router(
+    body: &Value,
+    api_key: &str,
+    transcript: Option<&TranscriptLog>,
+    on_delta: &mut (dyn FnMut(Delta) + Send),
+) -> Result<ChatMessage> {
+    let client = reqwest::Client::new();
+
+    let started = Instant::now();
+    // Everything received, for the transcript and error messages
+    let mut raw = String::new();
+    let mut status = None;
+    let result = async {
+        let mut response = client
+            .post(API_URL)
+            .header("Content-Type", "application/json")
+            .bearer_auth(api_key)
+            .body(body.to_string())
+            .send()
+            .await?;
+        status = Some(response.status());
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let header = |name: &str| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let (retry_after, reset) = (header("retry-after"), header("x-ratelimit-reset"));
+            raw = response.text().await?;
+            let wait = retry_after
+                .as_deref()
+                .and_then(parse_retry_after)
+                .or_else(|| {
+                    // OpenRouter puts the upstream limit's headers into the error body
+                    let body: Value = serde_json::from_str(&raw).unwrap_or_default();
+                    let reset = reset.or_else(|| {
+                        body["error"]["metadata"]["headers"]["X-RateLimit-Reset"]
+                            .as_str()
+                            .map(str::to_string)
+                    })?;
+                    parse_rate_limit_reset(&reset, SystemTime::now())
+                })
+                .unwrap_or(DEFAULT_RETRY_AFTER);
+            return Err(RateLimited {
+                retry_after: wait,
+                message: raw.clone(),
+            }
+            .into());
+        }
+        if !response.status().is_success() {
+            raw = response.text().await?;
+            return Err(eyre!(
+                "Unexpected response ({}): {raw}",
+                response_status(status)
+            ));
+        }
+
+        let mut lines = SseLines::default();
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        let mut tool_calls = Vec::new();
+        let mut first_token = None;
+        let mut finish_reason = None;
+        let mut model = None;
+        let mut usage = Value::Null;
+        while let Some(chunk) = response.chunk().await? {
+            raw.push_str(&String::from_utf8_lossy(&chunk));
+            for data in lines.push(&chunk) {
+                if data == "[DONE]" {
+                    continue;
+                }
+                let event: Value = serde_json::from_str(&data)?;
+                if let Some(err) = event.get("error") {
+                    return Err(eyre!(
+                        "{}",
+                        err["message"].as_str().unwrap_or("stream error")
+                    ));
+                }
+                if model.is_none() {
+                    model = event["model"].as_str().map(str::to_string);
+                }
+                let choice = &event["choices"][0];
+                if let Some(delta) = choice["delta"]["reasoning"].as_str()
+                    && !delta.is_empty()
+                {
+                    first_token.get_or_insert_with(Instant::now);
+                    reasoning.push_str(delta);
+                    on_delta(Delta::Reasoning(delta.to_string()));
+                }
+                if let Some(delta) = choice["delta"]["content"].as_str()
+                    && !delta.is_empty()
+                {
+                    first_token.get_or_insert_with(Instant::now);
+                    content.push_str(delta);
+                    on_delta(Delta::Content(delta.to_string()));
+                }
+                if let Some(deltas) = choice["delta"]["tool_calls"].as_array() {
+                    first_token.get_or_insert_with(Instant::now);
+                    push_tool_call_deltas(&mut tool_calls, deltas);
+                }
+                if let Some(reason) = choice["finish_reason"].as_str() {
+                    finish_reason = Some(reason.to_string());
+                }
+                if usage.is_null() && event["usage"].is_object() {
+                    usage = event["usage"].clone();
+                }
+            }
+        }
+        if content.is_empty() && tool_calls.is_empty() && finish_reason.is_none() {
+            return Err(eyre!(
+                "Unexpected response ({}): {raw}",
+                response_status(status)
+            ));
+        }
+        let completion_tokens = usage["completion_tokens"].as_u64();
+        Ok(ChatMessage {
+            role: "AI".to_string(),
+            content,
+            reasoning,
+            tool_calls,
+            metadata: MessageMetadata {
+                // Routers like `openrouter/auto` answer with the model they picked
+                model: model.or_else(|| body["model"].as_str().map(str::to_string)),
+                prompt_tokens: usage["prompt_tokens"].as_u64(),
+                cached_tokens: usage["prompt_tokens_details"]["cached_tokens"].as_u64(),
+                cache_write_tokens: usage["prompt_tokens_details"]["cache_write_tokens"].as_u64(),
+                completion_tokens,
+                cost: usage["cost"].as_f64(),
+                latency_ms: Some(started.elapsed().as_millis() as u64),
+                first_token_ms: first_token
+                    .map(|first_token| first_token.duration_since(started).as_millis() as u64),
+                finish_reason,
+                tokens_per_second: tokens_per_second(completion_tokens, first_token),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+    .await;
+    if let Some(transcript) = transcript {
+        let text = match &result {
+            Err(err) if raw.is_empty() => err.to_string(),
+            _ => raw.clone(),
+        };
+        let status = status.map(|status| status.as_u16());
+        let latency_ms = started.elapsed().as_millis() as u64;
+        if let Err(err) = transcript.record(API_URL, api_key, body, status, &text, latency_ms) {
+            error!("Failed to write transcript: {err}");
+        }
+    }
+    result
+}
+
+/// Add streamed `tool_calls` deltas to the calls received so far.
+///
+/// The first delta of a call carries its id and name, the following ones pieces of the arguments;
+/// `index` says which call a delta belongs to.
+fn push_tool_call_deltas(calls: &mut Vec<ToolCall>, deltas: &[Value]) {
+    for delta in deltas {
+        let index = delta["index"]
+            .as_u64()
+            .map_or(calls.len(), |index| index as usize);
+        if index >= calls.len() {
+            calls.resize_with(index + 1, ToolCall::default);
+        }
+        let call = &mut calls[index];
+        if let Some(id) = delta["id"].as_str() {
+            call.id = id.to_string();
+        }
+        if let Some(name) = delta["function"]["name"].as_str() {
+            call.name.push_str(name);
+        }
+        if let Some(arguments) = delta["function"]["arguments"].as_str() {
+            call.arguments.push_str(arguments);
+        }
+    }
+}
+
+/// How long to wait after a 429 that doesn't say.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// The provider refused the request with 429 Too Many Requests.
+#[derive(Debug)]
+pub struct RateLimited {
+    /// When the provider accepts requests again.
+    pub retry_after: Duration,
+    pub message: String,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rate limited, retry in {}s: {}",
+            self.retry_after.as_secs(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Parse a `Retry-After` header given in seconds.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Parse an `X-RateLimit-Reset` value, a Unix timestamp in milliseconds (or seconds), into the
+/// time left until then.
+fn parse_rate_limit_reset(value: &str, now: SystemTime) -> Option<Duration> {
+    let reset: u64 = value.trim().parse().ok()?;
+    // Seconds since the epoch are 10 digits until the year 2286
+    let reset = if reset < 10_000_000_000 {
+        UNIX_EPOCH + Duration::from_secs(reset)
+    } else {
+        UNIX_EPOCH + Duration::from_millis(reset)
+    };
+    Some(reset.duration_since(now).unwrap_or_default())
+}
+
+fn response_status(status: Option<reqwest::StatusCode>) -> String {
+    status.map(|status| status.to_string()).unwrap_or_default()
+}
+
+/// Generation speed, measured from the first token so that queueing and prompt processing
+/// don't count.
+fn tokens_per_second(tokens: Option<u64>, first_token: Option<Instant>) -> Option<f64> {
+    let (tokens, seconds) = (tokens?, first_token?.elapsed().as_secs_f64());
+    (seconds > 0.0).then(|| tokens as f64 / seconds)
+}
+
+/// Splits a server-sent event stream into the payloads of its `data:` lines.
+#[derive(Default)]
+struct SseLines {
+    buffer: Vec<u8>,
+}
+
+impl SseLines {
+    /// Add a chunk of the stream and return the payloads of the lines it completed.
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut payloads = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            // Comments (`: OPENROUTER PROCESSING`) and event fields other than data are skipped
+            if let Some(payload) = String::from_utf8_lossy(&line)
+                .trim_end()
+                .strip_prefix("data:")
+            {
+                payloads.push(payload.trim_start().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+/// Reply with the configured templates word by word, like a stream.
+async fn mock(
+    config: &MockConfig,
+    body: &Value,
+    on_delta: &mut (dyn FnMut(Delta) + Send),
+) -> Result<ChatMessage> {
+    let started = Instant::now();
+    let mut content = fill_template(&config.response, body);
+    // Count a word as a token, so that small `max_tokens` limits cut replies off
+    let mut finish_reason = "stop";
+    if let Some(max_tokens) = body["max_tokens"].as_u64()
+        && content.split_whitespace().count() as u64 > max_tokens
+    {
+        content = content
+            .split_whitespace()
+            .take(max_tokens as usize)
+            .collect::<Vec<_>>()
+            .join(" ");
+        finish_reason = "length";
+    }
+    let reasoning = fill_template(&config.reasoning, body);
+    let words = (reasoning.split_whitespace().count() + content.split_whitespace().count()) as u64;
+    tokio::time::sleep(Duration::from_millis(config.latency_ms)).await;
+    let first_token = Instant::now();
+    for word in reasoning.split_inclusive(' ') {
+        tokio::time::sleep(Duration::from_millis(config.word_delay_ms)).await;
+        on_delta(Delta::Reasoning(word.to_string()));
+    }
+    for word in content.split_inclusive(' ') {
+        tokio::time::sleep(Duration::from_millis(config.word_delay_ms)).await;
+        on_delta(Delta::Content(word.to_string()));
+    }
+    Ok(ChatMessage {
+        role: "AI".to_string(),
+        content,
+        reasoning,
+        metadata: MessageMetadata {
+            model: body["model"].as_str().map(str::to_string),
+            completion_tokens: Some(words),
+            cost: Some(0.0),
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            first_token_ms: Some(first_token.duration_since(started).as_millis() as u64),
+            finish_reason: Some(finish_reason.to_string()),
+            tokens_per_second: tokens_per_second(Some(words), Some(first_token)),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+}
+
+fn fill_template(template: &str, body: &Value) -> String {
+    let messages = body["messages"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let prompt = messages
+        .iter()
+        .rev()
+        .find(|message| message["role"] == "user")
+        .and_then(|message| message["content"].as_str())
+        .unwrap_or_default();
+    template
+        .replace("{prompt}", prompt)
+        .replace("{model}", body["model"].as_str().unwrap_or_default())
+        .replace("{count}", &messages.len().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(parse_retry_after(" 12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            parse_rate_limit_reset("1700000030000", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_rate_limit_reset("1700000005", now),
+            Some(Duration::from_secs(5))
+        );
+        // A reset in the past means the request can go out right away
+        assert_eq!(
+            parse_rate_limit_reset("1699999990000", now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_push_tool_call_deltas() {
+        let mut calls = Vec::new();
+        let chunks = [
+            json!([{ "index": 0, "id": "call_1", "function": { "name": "search", "arguments": "" } }]),
+            json!([{ "index": 0, "function": { "arguments": "{\"q\": " } }]),
+            json!([
+                { "index": 0, "function": { "arguments": "\"rust\"}" } },
+                { "index": 1, "id": "call_2", "function": { "name": "time", "arguments": "{}" } },
+            ]),
+        ];
+        for chunk in &chunks {
+            push_tool_call_deltas(&mut calls, chunk.as_array().unwrap());
+        }
+        assert_eq!(
+            calls,
+            vec![
+                ToolCall {
+                    id: "call_1".to_string(),
+                    name: "search".to_string(),
+                    arguments: r#"{"q": "rust"}"#.to_string(),
+                },
+                ToolCall {
+                    id: "call_2".to_string(),
+                    name: "time".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_reply() -> Result<()> {
+        let config = MockConfig {
+            response: "{model} heard: {prompt}".to_string(),
+            reasoning: "Hmm, {count} messages".to_string(),
+            latency_ms: 0,
+            word_delay_ms: 0,
+        };
+        let body = json!({
+            "model": "mock-1",
+            "messages": [
+                { "role": "user", "content": "first" },
+                { "role": "AI", "content": "ok" },
+                { "role": "user", "content": "second" },
+            ],
+        });
+        let mut deltas = Vec::new();
+        let reply = Provider::Mock(config)
+            .complete(&body, &mut |delta| deltas.push(delta))
+            .await?;
+        assert_eq!(
+            deltas,
+            [
+                Delta::Reasoning("Hmm, ".to_string()),
+                Delta::Reasoning("3 ".to_string()),
+                Delta::Reasoning("messages".to_string()),
+                Delta::Content("mock-1 ".to_string()),
+                Delta::Content("heard: ".to_string()),
+                Delta::Content("second".to_string()),
+            ]
+        );
+        assert_eq!(reply.content, "mock-1 heard: second");
+        assert_eq!(reply.reasoning, "Hmm, 3 messages");
+        assert_eq!(reply.metadata.completion_tokens, Some(6));
+        assert_eq!(reply.metadata.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(reply.metadata.model.as_deref(), Some("mock-1"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_reply_hits_max_tokens() -> Result<()> {
+        let config = MockConfig {
+            response: "one two three four".to_string(),
+            reasoning: String::new(),
+            latency_ms: 0,
+            word_delay_ms: 0,
+        };
+        let body = json!({ "model": "mock-1", "messages": [], "max_tokens": 2 });
+        let reply = Provider::Mock(config).complete(&body, &mut |_| {}).await?;
+        assert_eq!(reply.content, "one two");
+        assert!(reply.metadata.is_truncated());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sse_lines() {
+        let mut lines = SseLines::default();
+        assert_eq!(
+            lines.push(b": OPENROUTER PROCESSING\n\ndata: {\"a\""),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            lines.push(b": 1}\r\n\ndata: [DONE]\n"),
+            ["{\"a\": 1}", "[DONE]"]
+        );
+    }
+}
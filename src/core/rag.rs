@@ -0,0 +1,516 @@
+//! Questions about a local directory: `/index <dir>` splits its text files into chunks and
+//! embeds them, and each question is then sent along with the chunks closest to it.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::core::walk::walk;
+
+/// Size of the vectors of the mock embeddings.
+const MOCK_DIMENSIONS: usize = 256;
+
+/// Where the vectors come from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingProvider {
+    /// An OpenAI-compatible `/embeddings` endpoint, e.g. OpenAI or a local Ollama server.
+    #[default]
+    Api,
+    /// A fastembed ONNX model run on this machine, so that nothing indexed leaves it. Needs a
+    /// build with the `local-embeddings` feature.
+    Local,
+    /// Hashed words instead of a model, for offline demos and tests.
+    Mock,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RagConfig {
+    pub embeddings: EmbeddingProvider,
+    pub url: String,
+    pub model: String,
+    /// Environment variable holding the API key, if the endpoint requires one.
+    pub api_key_env: Option<String>,
+    /// The fastembed model for `local`, by its Hugging Face name.
+    pub local_model: String,
+    /// Where local models are downloaded to, in the cache directory.
+    #[serde(skip)]
+    pub models_dir: PathBuf,
+    /// How many chunks are sent with each question.
+    pub top_k: usize,
+    pub chunk_lines: usize,
+    /// Larger files are skipped, they are mostly generated.
+    pub max_file_bytes: u64,
+    /// Indexing stops with an error beyond this, so that a wrong directory doesn't get expensive.
+    pub max_chunks: usize,
+    /// Chunks embedded per request.
+    pub batch_size: usize,
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self {
+            embeddings: EmbeddingProvider::Api,
+            url: "https://api.openai.com/v1/embeddings".to_string(),
+            model: "text-embedding-3-small".to_string(),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            local_model: "Xenova/bge-small-en-v1.5".to_string(),
+            models_dir: PathBuf::new(),
+            top_k: 5,
+            chunk_lines: 40,
+            max_file_bytes: 200_000,
+            max_chunks: 5000,
+            batch_size: 64,
+        }
+    }
+}
+
+impl RagConfig {
+    /// The embedding model, as recorded in the index.
+    fn embedder(&self) -> String {
+        match self.embeddings {
+            EmbeddingProvider::Api => self.model.clone(),
+            EmbeddingProvider::Local => self.local_model.clone(),
+            EmbeddingProvider::Mock => "mock".to_string(),
+        }
+    }
+}
+
+/// The index of `/index`, read from its file once and shared with the requests that use it.
+#[derive(Clone, Debug, Default)]
+pub struct IndexCache(Arc<Mutex<Option<Arc<Index>>>>);
+
+impl IndexCache {
+    /// The index at `path`, if one was built, read off the async runtime the first time.
+    pub async fn load(&self, path: &Path) -> Result<Option<Arc<Index>>> {
+        if let Some(index) = self.get() {
+            return Ok(Some(index));
+        }
+        let path = path.to_path_buf();
+        let index = tokio::task::spawn_blocking(move || Index::load(&path))
+            .await??
+            .map(Arc::new);
+        self.set(index.clone());
+        Ok(index)
+    }
+
+    /// Save `index` to `path` and use it from now on.
+    pub async fn store(&self, index: Index, path: &Path) -> Result<Arc<Index>> {
+        let index = Arc::new(index);
+        let path = path.to_path_buf();
+        let saved = index.clone();
+        tokio::task::spawn_blocking(move || saved.save(&path)).await??;
+        self.set(Some(index.clone()));
+        Ok(index)
+    }
+
+    /// Forget the index, e.g. once its file is removed.
+    pub fn clear(&self) {
+        self.set(None);
+    }
+
+    fn get(&self) -> Option<Arc<Index>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    fn set(&self, index: Option<Arc<Index>>) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) = index;
+    }
+}
+
+/// Consecutive lines of an indexed file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Relative to the indexed directory.
+    pub path: PathBuf,
+    /// One-based and inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    /// Normalized, so that the dot product is the cosine similarity.
+    pub vector: Vec<f32>,
+}
+
+impl Chunk {
+    /// What is embedded: the text with its path, which often says what it is about.
+    fn document(&self) -> String {
+        format!("{}\n{}", self.path.display(), self.text)
+    }
+}
+
+/// The embedded chunks of one directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    pub root: PathBuf,
+    pub embedder: String,
+    pub files: usize,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Index {
+    /// Split the text files under `root` into chunks and embed them, calling `on_progress` with
+    /// the number of chunks embedded so far and the total.
+    pub async fn build(
+        config: &RagConfig,
+        root: &Path,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<Self> {
+        let (root, files, mut chunks) = {
+            let config = config.clone();
+            let root = root.to_path_buf();
+            tokio::task::spawn_blocking(move || read_chunks(&config, &root)).await??
+        };
+        let total = chunks.len();
+        for start in (0..total).step_by(config.batch_size.max(1)) {
+            let end = (start + config.batch_size.max(1)).min(total);
+            let documents: Vec<String> = chunks[start..end].iter().map(Chunk::document).collect();
+            let vectors = embed(config, &documents).await?;
+            for (chunk, vector) in chunks[start..end].iter_mut().zip(vectors) {
+                chunk.vector = vector;
+            }
+            on_progress(end, total);
+        }
+        Ok(Self {
+            root,
+            embedder: config.embedder(),
+            files,
+            chunks,
+        })
+    }
+
+    /// The index at `path`, if one was built.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The `k` chunks most similar to `query`, best first.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<&Chunk> {
+        let mut scored: Vec<(f32, &Chunk)> = self
+            .chunks
+            .iter()
+            .map(|chunk| (dot(&chunk.vector, query), chunk))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(k).map(|(_, chunk)| chunk).collect()
+    }
+
+    /// Add the chunks closest to the question at the end of the request `body` in a system
+    /// message before it. Requests that don't end with a question, e.g. after a tool call, are
+    /// left alone.
+    pub async fn augment(&self, config: &RagConfig, body: &mut Value) -> Result<()> {
+        if self.embedder != config.embedder() {
+            return Err(eyre!(
+                "The index was built with {}, not {}; run /index again",
+                self.embedder,
+                config.embedder()
+            ));
+        }
+        let Some(messages) = body["messages"].as_array_mut() else {
+            return Ok(());
+        };
+        let question = match messages.last() {
+            Some(last) if last["role"] == "user" => last["content"].as_str().unwrap_or_default(),
+            _ => return Ok(()),
+        };
+        if question.trim().is_empty() {
+            return Ok(());
+        }
+        let query = embed(config, &[question.to_string()]).await?.remove(0);
+        let mut context = format!(
+            "Excerpts from {} that may help to answer the next question:\n",
+            self.root.display()
+        );
+        for chunk in self.search(&query, config.top_k) {
+            context.push_str(&format!(
+                "\n{}:{}-{}\n```\n{}\n```\n",
+                chunk.path.display(),
+                chunk.start_line,
+                chunk.end_line,
+                chunk.text.trim_end()
+            ));
+        }
+        messages.insert(
+            messages.len() - 1,
+            json!({ "role": "system", "content": context }),
+        );
+        Ok(())
+    }
+}
+
+/// The files under `dir` that [`walk`] doesn't skip, sorted, leaving out large ones.
+/// The canonical `root`, how many files under it are indexed and their chunks, not embedded yet.
+fn read_chunks(config: &RagConfig, root: &Path) -> Result<(PathBuf, usize, Vec<Chunk>)> {
+    let root = root.canonicalize()?;
+    let files = collect_files(&root, config.max_file_bytes)?;
+    let mut chunks = Vec::new();
+    for path in &files {
+        // Binary files don't read as UTF-8
+        let Ok(text) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        let relative = path.strip_prefix(&root).unwrap_or(path);
+        chunks.extend(split(relative, &text, config.chunk_lines.max(1)));
+        if chunks.len() > config.max_chunks {
+            return Err(eyre!(
+                "{} has more than {} chunks, index a subdirectory or raise rag.max_chunks",
+                root.display(),
+                config.max_chunks
+            ));
+        }
+    }
+    Ok((root, files.len(), chunks))
+}
+
+fn collect_files(dir: &Path, max_bytes: u64) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in walk(dir, None)? {
+        let entry = entry?;
+        if entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+            && entry.metadata()?.len() <= max_bytes
+        {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// `text` in chunks of `lines` lines, without vectors yet.
+fn split(path: &Path, text: &str, lines: usize) -> Vec<Chunk> {
+    let all: Vec<&str> = text.lines().collect();
+    all.chunks(lines)
+        .enumerate()
+        .filter(|(_, chunk)| chunk.iter().any(|line| !line.trim().is_empty()))
+        .map(|(index, chunk)| Chunk {
+            path: path.to_path_buf(),
+            start_line: index * lines + 1,
+            end_line: index * lines + chunk.len(),
+            text: chunk.join("\n"),
+            vector: Vec::new(),
+        })
+        .collect()
+}
+
+/// One normalized vector per text.
+async fn embed(config: &RagConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let vectors = match config.embeddings {
+        EmbeddingProvider::Api => embed_api(config, texts).await?,
+        EmbeddingProvider::Local => embed_local(config, texts).await?,
+        EmbeddingProvider::Mock => texts.iter().map(|text| embed_mock(text)).collect(),
+    };
+    Ok(vectors.into_iter().map(normalize).collect())
+}
+
+async fn embed_api(config: &RagConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let mut request = reqwest::Client::new()
+        .post(&config.url)
+        .header("Content-Type", "application/json")
+        .body(json!({ "model": config.model, "input": texts }).to_string());
+    if let Some(name) = &config.api_key_env {
+        let key = env::var(name).map_err(|_| eyre!("{name} environment variable not set"))?;
+        request = request.bearer_auth(key);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        return Err(eyre!("Embedding failed ({status}): {text}"));
+    }
+    let response: Value = serde_json::from_str(&text)?;
+    let mut data = response["data"]
+        .as_array()
+        .cloned()
+        .ok_or_else(|| eyre!("Unexpected embeddings response: {text}"))?;
+    data.sort_by_key(|item| item["index"].as_u64());
+    let vectors: Vec<Vec<f32>> = data
+        .iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|value| value.as_f64().map(|value| value as f32))
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+    if vectors.len() != texts.len() {
+        return Err(eyre!(
+            "Asked for {} embeddings, got {}",
+            texts.len(),
+            vectors.len()
+        ));
+    }
+    Ok(vectors)
+}
+
+/// Run the local model, downloading it the first time.
+#[cfg(feature = "local-embeddings")]
+async fn embed_local(config: &RagConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    use std::sync::Mutex;
+
+    use fastembed::{TextEmbedding, TextInitOptions};
+
+    /// Loading a model takes a while, so the last one is kept for the questions that follow.
+    static LOADED: Mutex<Option<(String, TextEmbedding)>> = Mutex::new(None);
+
+    let name = config.local_model.clone();
+    let models_dir = config.models_dir.clone();
+    let texts = texts.to_vec();
+    tokio::task::spawn_blocking(move || {
+        let mut loaded = LOADED
+            .lock()
+            .map_err(|_| eyre!("The embedding model crashed"))?;
+        if loaded.as_ref().is_none_or(|(loaded, _)| *loaded != name) {
+            let model = TextEmbedding::list_supported_models()
+                .into_iter()
+                .find(|info| info.model_code.eq_ignore_ascii_case(&name))
+                .ok_or_else(|| {
+                    eyre!("Unknown local embedding model {name}, e.g. Xenova/bge-small-en-v1.5")
+                })?
+                .model;
+            let options = TextInitOptions::new(model)
+                .with_cache_dir(models_dir)
+                .with_show_download_progress(false);
+            let embedding = TextEmbedding::try_new(options)
+                .map_err(|err| eyre!("Failed to load {name}: {err}"))?;
+            *loaded = Some((name, embedding));
+        }
+        let (_, embedding) = loaded.as_mut().expect("loaded above");
+        embedding
+            .embed(&texts, None)
+            .map_err(|err| eyre!("Embedding failed: {err}"))
+    })
+    .await?
+}
+
+#[cfg(not(feature = "local-embeddings"))]
+async fn embed_local(_config: &RagConfig, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    Err(eyre!(
+        "This build can't embed locally, install lazychat with `--features local-embeddings`"
+    ))
+}
+
+/// Counts of the words of `text`, each hashed to one of the dimensions.
+fn embed_mock(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0; MOCK_DIMENSIONS];
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        // FNV-1a
+        let hash = word
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf29ce484222325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+            });
+        vector[(hash % MOCK_DIMENSIONS as u64) as usize] += 1.0;
+    }
+    vector
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let length = dot(&vector, &vector).sqrt();
+    if length > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= length);
+    }
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[tokio::test]
+    async fn test_index_and_augment() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-rag-{}", new_session_id()));
+        std::fs::create_dir_all(dir.join("src"))?;
+        std::fs::create_dir_all(dir.join(".git"))?;
+        std::fs::write(
+            dir.join("src/parser.rs"),
+            "fn parse_tokens() {}\n\nfn lexer() {}\n",
+        )?;
+        std::fs::write(dir.join("README.md"), "Bakes sourdough bread\n")?;
+        std::fs::write(dir.join(".git/config"), "parse parse parse\n")?;
+        let config = RagConfig {
+            embeddings: EmbeddingProvider::Mock,
+            chunk_lines: 2,
+            top_k: 1,
+            ..Default::default()
+        };
+
+        let mut progress = Vec::new();
+        let index = Index::build(&config, &dir, |done, total| progress.push((done, total))).await?;
+        assert_eq!(index.files, 2);
+        assert_eq!(progress, [(3, 3)]);
+        let chunks: Vec<_> = index
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.path.clone(), chunk.start_line, chunk.end_line))
+            .collect();
+        assert_eq!(
+            chunks,
+            [
+                (PathBuf::from("README.md"), 1, 1),
+                (PathBuf::from("src/parser.rs"), 1, 2),
+                (PathBuf::from("src/parser.rs"), 3, 3),
+            ]
+        );
+
+        let path = dir.join("index.json");
+        IndexCache::default().store(index, &path).await?;
+        let index = IndexCache::default().load(&path).await?.unwrap();
+        let mut body = json!({ "messages": [
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Where is the lexer?" },
+        ] });
+        index.augment(&config, &mut body).await?;
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["role"], "system");
+        let context = messages[1]["content"].as_str().unwrap();
+        assert!(
+            context.contains("src/parser.rs:3-3\n```\nfn lexer() {}\n```"),
+            "{context}"
+        );
+        assert_eq!(messages[2]["content"], "Where is the lexer?");
+
+        let other = RagConfig {
+            model: "other".to_string(),
+            embeddings: EmbeddingProvider::Api,
+            ..config
+        };
+        assert!(index.augment(&other, &mut body).await.is_err());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
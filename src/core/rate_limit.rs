@@ -0,0 +1,130 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+/// Limits are enforced over a sliding window of this length.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Client-side limits that keep rapid-fire use below the provider's rate limits.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: Option<usize>,
+    /// Prompt and completion tokens; prompts are estimated before they are sent.
+    pub tokens_per_minute: Option<u64>,
+}
+
+/// Remembers recent requests and decides how long the next one has to wait.
+///
+/// Clones share their history, so every request of the engine counts against the same limits.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    history: Arc<Mutex<History>>,
+}
+
+/// When requests were (or are booked to be) sent and when tokens were used, oldest first.
+#[derive(Debug, Default)]
+struct History {
+    requests: Vec<Instant>,
+    tokens: Vec<(Instant, u64)>,
+}
+
+impl RateLimiter {
+    /// Book a request of `tokens` at the earliest time the limits allow and return how long to
+    /// wait until then.
+    pub fn reserve(&self, config: &RateLimitConfig, tokens: u64, now: Instant) -> Duration {
+        let mut history = self.history.lock().unwrap_or_else(|err| err.into_inner());
+        history.requests.retain(|at| *at + WINDOW > now);
+        history.tokens.retain(|(at, _)| *at + WINDOW > now);
+        let mut start = now;
+        let requests = &history.requests;
+        if let Some(limit) = config.requests_per_minute.filter(|limit| *limit > 0)
+            && requests.len() >= limit
+        {
+            start = start.max(requests[requests.len() - limit] + WINDOW);
+        }
+        if let Some(limit) = config.tokens_per_minute {
+            // Wait for the oldest tokens to leave the window until the new ones fit; a request
+            // larger than the limit goes out once the window is empty
+            let mut total = history.tokens.iter().map(|(_, used)| used).sum::<u64>() + tokens;
+            for (at, used) in &history.tokens {
+                if total <= limit {
+                    break;
+                }
+                total -= used;
+                start = start.max(*at + WINDOW);
+            }
+        }
+        history.requests.push(start);
+        history.requests.sort();
+        history.add(start, tokens);
+        start - now
+    }
+
+    /// Count tokens that only became known later, e.g. those of the completion.
+    pub fn add(&self, tokens: u64, now: Instant) {
+        let mut history = self.history.lock().unwrap_or_else(|err| err.into_inner());
+        history.add(now, tokens);
+    }
+}
+
+impl History {
+    fn add(&mut self, at: Instant, tokens: u64) {
+        self.tokens.push((at, tokens));
+        self.tokens.sort_by_key(|(at, _)| *at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_requests_per_minute() {
+        let config = RateLimitConfig {
+            requests_per_minute: Some(2),
+            tokens_per_minute: None,
+        };
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(&config, 0, now), Duration::ZERO);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(&config, 0, later), Duration::ZERO);
+        // The third request waits for the first to leave the window
+        assert_eq!(limiter.reserve(&config, 0, later), Duration::from_secs(50));
+        // And the fourth for the second, even though the third hasn't been sent yet
+        assert_eq!(limiter.reserve(&config, 0, later), Duration::from_secs(60));
+        // Tokens used by replies are not requests
+        limiter.add(100, later);
+        assert_eq!(limiter.reserve(&config, 0, later), Duration::from_secs(110));
+    }
+
+    #[test]
+    fn test_tokens_per_minute() {
+        let config = RateLimitConfig {
+            requests_per_minute: None,
+            tokens_per_minute: Some(1000),
+        };
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(&config, 600, now), Duration::ZERO);
+        limiter.add(300, now + Duration::from_secs(5));
+        let later = now + Duration::from_secs(20);
+        assert_eq!(limiter.reserve(&config, 100, later), Duration::ZERO);
+        // 1000 tokens are in the window, the next request waits until the first 600 expire
+        assert_eq!(
+            limiter.reserve(&config, 100, later),
+            Duration::from_secs(40)
+        );
+        // Too large to ever fit: sent once everything booked before it has expired
+        assert_eq!(
+            limiter.reserve(&config, 5000, later),
+            Duration::from_secs(100)
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Roundtables started with `/roundtable`: several personas answering every message in turn,
+//! e.g. to have them debate an idea. Experimental.
+
+use color_eyre::{Result, eyre::eyre};
+
+use super::{Conversation, personas::Persona};
+
+/// The personas taking turns, and the settings of the conversation to go back to afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roundtable {
+    pub personas: Vec<Persona>,
+    /// The persona answering next, or answering now while a reply streams in.
+    turn: usize,
+    /// The conversation's own settings, which are saved with it meanwhile.
+    pub model: String,
+    pub temperature: Option<f64>,
+    pub system_prompt: String,
+    pub persona: Option<String>,
+}
+
+impl Roundtable {
+    pub fn new(personas: Vec<Persona>, conversation: &Conversation) -> Result<Self> {
+        if personas.len() < 2 {
+            return Err(eyre!("A roundtable needs at least two personas"));
+        }
+        for (index, persona) in personas.iter().enumerate() {
+            if personas[..index]
+                .iter()
+                .any(|other| other.name == persona.name)
+            {
+                return Err(eyre!("{} can only take one seat", persona.name));
+            }
+        }
+        Ok(Self {
+            personas,
+            turn: 0,
+            model: conversation.model.clone(),
+            temperature: conversation.temperature,
+            system_prompt: conversation.system_prompt.clone(),
+            persona: conversation.persona.clone(),
+        })
+    }
+
+    pub fn speaker(&self) -> &Persona {
+        &self.personas[self.turn]
+    }
+
+    /// Let the persona whose turn it is speak in the conversation.
+    pub fn seat(&self, conversation: &mut Conversation) {
+        let speaker = self.speaker();
+        conversation.persona = Some(speaker.name.clone());
+        conversation.system_prompt = speaker.system_prompt.clone();
+        conversation.model = speaker.model.clone().unwrap_or_else(|| self.model.clone());
+        conversation.temperature = speaker.temperature.or(self.temperature);
+    }
+
+    /// Move on to the next persona; `false` once everyone has answered and the user is next.
+    pub fn pass(&mut self) -> bool {
+        self.turn = (self.turn + 1) % self.personas.len();
+        self.turn > 0
+    }
+
+    /// Start the next round with the first persona.
+    pub fn restart(&mut self) {
+        self.turn = 0;
+    }
+
+    /// Give the conversation its own settings back.
+    pub fn restore(&self, conversation: &mut Conversation) {
+        conversation.persona = self.persona.clone();
+        conversation.system_prompt = self.system_prompt.clone();
+        conversation.model = self.model.clone();
+        conversation.temperature = self.temperature;
+    }
+
+    /// The names of the personas, in the order they answer.
+    pub fn names(&self) -> String {
+        self.personas
+            .iter()
+            .map(|persona| persona.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::conversation::{ChatMessage, summarize};
+
+/// A saved conversation.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub title: String,
+    /// Unix timestamps in seconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Archived sessions are hidden from the sidebar unless asked for.
+    #[serde(default)]
+    pub archived: bool,
+    /// What the session is about, e.g. a summary saved with `/summarize save`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Unset for sessions saved before the prompt was, which use the configured default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    /// Unset for sessions saved before the model was, which use the configured default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Overrides the configured temperature, e.g. as set by a persona.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    pub messages: Vec<ChatMessage>,
+    /// Messages typed while offline that haven't been sent yet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queued: Vec<String>,
+}
+
+impl Session {
+    pub fn new(id: String, messages: Vec<ChatMessage>) -> Self {
+        let now = now();
+        Self {
+            id,
+            title: auto_title(&messages),
+            created_at: now,
+            updated_at: now,
+            archived: false,
+            description: None,
+            system_prompt: None,
+            persona: None,
+            model: None,
+            temperature: None,
+            messages,
+            queued: Vec::new(),
+        }
+    }
+}
+
+/// The parts of a session shown in the session sidebar.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub title: String,
+    pub updated_at: u64,
+    pub archived: bool,
+}
+
+impl From<&Session> for SessionInfo {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id.clone(),
+            title: session.title.clone(),
+            updated_at: session.updated_at,
+            archived: session.archived,
+        }
+    }
+}
+
+/// Generate a new, time-ordered session id.
+pub fn new_session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{nanos:x}")
+}
+
+/// Derive a title from the first user message of a conversation.
+pub fn auto_title(messages: &[ChatMessage]) -> String {
+    let Some(first_line) = messages
+        .iter()
+        .find(|message| message.role == "user")
+        .and_then(|message| message.content.lines().find(|line| !line.trim().is_empty()))
+    else {
+        return "Untitled".to_string();
+    };
+    summarize(first_line.trim(), 40)
+}
+
+/// The current Unix timestamp in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Stores sessions as one JSON file per session in a directory.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    /// Save a session, keeping the creation time and archive flag of any stored copy.
+    pub fn save(&self, session: &Session) -> Result<()> {
+        let mut session = session.clone();
+        if let Ok(existing) = self.load(&session.id) {
+            session.created_at = existing.created_at;
+            session.archived = existing.archived;
+        }
+        session.updated_at = now();
+        self.write(&session)
+    }
+
+    fn write(&self, session: &Session) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(
+            self.path(&session.id),
+            serde_json::to_string_pretty(session)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: &str) -> Result<Session> {
+        let contents = std::fs::read_to_string(self.path(id))?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn exists(&self, id: &str) -> bool {
+        self.path(id).exists()
+    }
+
+    /// Load every stored session, most recently updated first.
+    pub fn load_all(&self) -> Result<Vec<Session>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut sessions = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                match std::fs::read_to_string(&path)
+                    .map_err(color_eyre::eyre::Error::from)
+                    .and_then(|contents| Ok(serde_json::from_str::<Session>(&contents)?))
+                {
+                    Ok(session) => sessions.push(session),
+                    Err(err) => warn!("Skipping unreadable session {}: {err}", path.display()),
+                }
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+        Ok(sessions)
+    }
+
+    pub fn list(&self) -> Result<Vec<SessionInfo>> {
+        Ok(self.load_all()?.iter().map(SessionInfo::from).collect())
+    }
+
+    pub fn rename(&self, id: &str, title: &str) -> Result<()> {
+        let mut session = self.load(id)?;
+        session.title = title.to_string();
+        self.save(&session)
+    }
+
+    pub fn set_archived(&self, id: &str, archived: bool) -> Result<()> {
+        let mut session = self.load(id)?;
+        session.archived = archived;
+        self.write(&session)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        std::fs::remove_file(self.path(id))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_archive_survives_save_and_delete_removes() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-sessions-{}", new_session_id()));
+        let store = SessionStore::new(dir.clone());
+        let session = Session::new("a".to_string(), Vec::new());
+        store.save(&session)?;
+
+        store.set_archived("a", true)?;
+        store.save(&session)?;
+        assert!(store.load("a")?.archived);
+
+        store.delete("a")?;
+        assert_eq!(store.list()?, Vec::new());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
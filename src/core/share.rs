@@ -0,0 +1,278 @@
+//! Publishing a conversation as Markdown to a gist or paste service.
+
+use std::{process::Stdio, time::Duration};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use strum::Display;
+use tokio::io::AsyncWriteExt;
+
+use super::{Conversation, session};
+
+const GISTS_URL: &str = "https://api.github.com/gists";
+const FILE_NAME: &str = "conversation.md";
+const USER_AGENT: &str = concat!("lazychat/", env!("CARGO_PKG_VERSION"));
+/// How long an upload may take before it is given up.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where `/share` uploads the transcript.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, Deserialize)]
+pub enum ShareService {
+    /// A GitHub gist, secret unless `public` is set.
+    #[default]
+    #[serde(rename = "gist")]
+    #[strum(serialize = "GitHub gist")]
+    Gist,
+    /// A 0x0.st style paste host, taking a multipart `file` upload and answering with the URL.
+    #[serde(rename = "0x0")]
+    #[strum(serialize = "0x0")]
+    ZeroXZero,
+    /// A command reading the transcript on stdin and printing the URL.
+    #[serde(rename = "command")]
+    #[strum(serialize = "command")]
+    Command,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ShareConfig {
+    pub service: ShareService,
+    /// Whether gists are listed publicly.
+    pub public: bool,
+    /// Environment variable holding a GitHub token with the `gist` scope.
+    pub token_env: String,
+    /// The paste host for `0x0`.
+    pub url: String,
+    /// For `command`, e.g. `["pastebinit", "-f", "markdown"]`.
+    pub command: Vec<String>,
+}
+
+impl Default for ShareConfig {
+    fn default() -> Self {
+        Self {
+            service: ShareService::Gist,
+            public: false,
+            token_env: "GITHUB_TOKEN".to_string(),
+            url: "https://0x0.st".to_string(),
+            command: Vec::new(),
+        }
+    }
+}
+
+/// The conversation as a Markdown transcript, as it is shared.
+pub fn markdown(conversation: &Conversation) -> String {
+    let title = conversation
+        .title
+        .clone()
+        .unwrap_or_else(|| session::auto_title(&conversation.messages));
+    let mut text = format!("# {title}\n\nModel: `{}`\n", conversation.model);
+    if !conversation.system_prompt.is_empty() {
+        text.push_str("\n**System prompt**\n\n");
+        for line in conversation.system_prompt.lines() {
+            text.push_str(&format!("> {line}\n"));
+        }
+    }
+    text.push_str(&messages_markdown(conversation, "##"));
+    text
+}
+
+/// The messages of the conversation in Markdown, each under a `heading` like `##`.
+pub fn messages_markdown(conversation: &Conversation, heading: &str) -> String {
+    let mut text = String::new();
+    for message in &conversation.messages {
+        match message.role.as_str() {
+            "user" => text.push_str(&format!("\n{heading} User\n\n")),
+            "tool" => {
+                let output = message.full_output.as_deref().unwrap_or(&message.content);
+                text.push_str(&format!("\n{heading} Tool result\n\n```\n{output}\n```\n"));
+                continue;
+            }
+            "system" => {
+                text.push_str(&format!("\n*{}*\n", message.content));
+                continue;
+            }
+            _ => {
+                text.push_str(&format!("\n{heading} Assistant"));
+                if let Some(model) = &message.metadata.model {
+                    text.push_str(&format!(" · {model}"));
+                }
+                if message.alternative {
+                    text.push_str(" (alternative)");
+                }
+                text.push_str("\n\n");
+            }
+        }
+        if !message.content.is_empty() {
+            text.push_str(message.content.trim_end());
+            text.push('\n');
+        }
+        for call in &message.tool_calls {
+            text.push_str(&format!("\n`⚙ {}({})`\n", call.name, call.arguments));
+        }
+    }
+    text
+}
+
+/// Upload `text` to the configured service and return the URL it can be read at.
+pub async fn publish(config: &ShareConfig, title: &str, text: &str) -> Result<String> {
+    match config.service {
+        ShareService::Gist => {
+            let token = std::env::var(&config.token_env)
+                .map_err(|_| eyre!("{} environment variable not set", config.token_env))?;
+            let body = json!({
+                "description": title,
+                "public": config.public,
+                "files": { FILE_NAME: { "content": text } },
+            });
+            let response = reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()?
+                .post(GISTS_URL)
+                .bearer_auth(token)
+                .header("Accept", "application/vnd.github+json")
+                .header("User-Agent", USER_AGENT)
+                .header("Content-Type", "application/json")
+                .body(body.to_string())
+                .send()
+                .await?;
+            let status = response.status();
+            let response_text = response.text().await?;
+            if !status.is_success() {
+                return Err(eyre!(
+                    "Creating the gist failed ({status}): {response_text}"
+                ));
+            }
+            let gist: Value = serde_json::from_str(&response_text)?;
+            gist["html_url"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| eyre!("The gist response has no URL: {response_text}"))
+        }
+        ShareService::ZeroXZero => {
+            let file = reqwest::multipart::Part::text(text.to_string())
+                .file_name(FILE_NAME)
+                .mime_str("text/markdown")?;
+            let form = reqwest::multipart::Form::new().part("file", file);
+            let response = reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()?
+                .post(&config.url)
+                .header("User-Agent", USER_AGENT)
+                .multipart(form)
+                .send()
+                .await?;
+            let status = response.status();
+            let response_text = response.text().await?;
+            if !status.is_success() {
+                return Err(eyre!("Uploading failed ({status}): {response_text}"));
+            }
+            Ok(response_text.trim().to_string())
+        }
+        ShareService::Command => {
+            let (program, args) = config
+                .command
+                .split_first()
+                .ok_or_else(|| eyre!("Set `share.command` to share with a command"))?;
+            let mut child = tokio::process::Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            // Written while the output is read, so that a command answering before it has read
+            // everything can't leave both sides waiting on a full pipe
+            let stdin = child.stdin.take();
+            let write = async move {
+                match stdin {
+                    // Dropped once written, which closes it
+                    Some(mut stdin) => stdin.write_all(text.as_bytes()).await,
+                    None => Ok(()),
+                }
+            };
+            let (written, output) = tokio::join!(write, child.wait_with_output());
+            let output = output?;
+            if !output.status.success() {
+                return Err(eyre!(
+                    "{program} failed ({}): {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            // A command may print the URL without reading everything, which is fine once it
+            // succeeded
+            match written {
+                Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(err.into()),
+                _ => {}
+            }
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+                .ok_or_else(|| eyre!("{program} printed no URL"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::ChatMessage;
+
+    #[test]
+    fn test_markdown() {
+        let mut conversation = Conversation::new("mock-1");
+        conversation.system_prompt = "Be brief.".to_string();
+        conversation.push(ChatMessage::new("user", "Hi"));
+        let mut reply = ChatMessage::new("AI", "Hello!\n");
+        reply.metadata.model = Some("mock-1".to_string());
+        conversation.push(reply);
+        assert_eq!(
+            markdown(&conversation),
+            "# Hi\n\nModel: `mock-1`\n\n**System prompt**\n\n> Be brief.\n\n## User\n\nHi\n\n\
+             ## Assistant · mock-1\n\nHello!\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_with_command() -> Result<()> {
+        let config = ShareConfig {
+            service: ShareService::Command,
+            command: [
+                "sh",
+                "-c",
+                "wc -l >/dev/null; echo https://paste.example/abc",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            ..Default::default()
+        };
+        let url = publish(&config, "Hi", "# Hi\n").await?;
+        assert_eq!(url, "https://paste.example/abc");
+
+        // Echoing more than a pipe holds while the input is still being written
+        let config = ShareConfig {
+            command: ["sh", "-c", "echo https://paste.example/def; cat"]
+                .map(str::to_string)
+                .to_vec(),
+            ..config
+        };
+        let text = "line\n".repeat(100_000);
+        let url = publish(&config, "Hi", &text).await?;
+        assert_eq!(url, "https://paste.example/def");
+
+        // Not reading the input at all
+        let config = ShareConfig {
+            command: ["sh", "-c", "echo https://paste.example/ghi"]
+                .map(str::to_string)
+                .to_vec(),
+            ..config
+        };
+        let url = publish(&config, "Hi", &text).await?;
+        assert_eq!(url, "https://paste.example/ghi");
+        Ok(())
+    }
+}
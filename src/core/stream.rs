@@ -0,0 +1,88 @@
+//! Streamed replies passed on in batches, so that fast models don't update the UI for every
+//! token.
+
+use std::time::Duration;
+
+use tokio::{
+    sync::mpsc::UnboundedReceiver,
+    time::{Instant, MissedTickBehavior},
+};
+
+use super::Delta;
+
+/// Pass the deltas from `rx` on to `forward` at most every `interval`, the ones of each kind in
+/// between joined into one, until `rx` is closed; every delta right away for a zero interval.
+pub async fn coalesce(
+    mut rx: UnboundedReceiver<Delta>,
+    interval: Duration,
+    mut forward: impl FnMut(Delta),
+) {
+    if interval.is_zero() {
+        while let Some(delta) = rx.recv().await {
+            forward(delta);
+        }
+        return;
+    }
+    let mut pending: Vec<Delta> = Vec::new();
+    let mut timer = tokio::time::interval_at(Instant::now() + interval, interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            delta = rx.recv() => match delta {
+                Some(delta) => join(&mut pending, delta),
+                None => break,
+            },
+            _ = timer.tick() => pending.drain(..).for_each(&mut forward),
+        }
+    }
+    pending.into_iter().for_each(forward);
+}
+
+/// Add `delta` to the last pending one if it is of the same kind, so reasoning and content keep
+/// their order.
+fn join(pending: &mut Vec<Delta>, delta: Delta) {
+    match (pending.last_mut(), delta) {
+        (Some(Delta::Reasoning(text)), Delta::Reasoning(more))
+        | (Some(Delta::Content(text)), Delta::Content(more)) => text.push_str(&more),
+        (_, delta) => pending.push(delta),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_coalesce() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for delta in [
+            Delta::Reasoning("Hm".to_string()),
+            Delta::Reasoning("m.".to_string()),
+            Delta::Content("Hel".to_string()),
+            Delta::Content("lo".to_string()),
+        ] {
+            tx.send(delta).unwrap();
+        }
+        drop(tx);
+        let mut forwarded = Vec::new();
+        coalesce(rx, Duration::from_secs(60), |delta| forwarded.push(delta)).await;
+        assert_eq!(
+            forwarded,
+            [
+                Delta::Reasoning("Hmm.".to_string()),
+                Delta::Content("Hello".to_string())
+            ]
+        );
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(Delta::Content("a".to_string())).unwrap();
+        tx.send(Delta::Content("b".to_string())).unwrap();
+        drop(tx);
+        let mut forwarded = Vec::new();
+        coalesce(rx, Duration::ZERO, |delta| forwarded.push(delta)).await;
+        assert_eq!(forwarded.len(), 2);
+    }
+}
@@ -0,0 +1,236 @@
+//! Summaries of the conversation written by the model, for `/summarize` and for compacting long
+//! conversations into a summary of their beginning.
+
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::ChatMessage;
+
+pub const SYSTEM_PROMPT: &str = "You summarize conversations between a user and an assistant. \
+Keep the goal, the decisions made, facts and code that later questions may build on, and what \
+is still open; leave out greetings and dead ends. Write a few short paragraphs or a list, without \
+a heading, in the language of the conversation.";
+
+/// What happens with a summary once the model has written it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SummaryUse {
+    /// Show it in a dialog.
+    Show,
+    /// Save it as the description of the session.
+    Describe,
+    /// Replace the summarized messages with it.
+    Compact,
+    /// Save it to the notes directory instead of the transcript.
+    Note,
+}
+
+/// A summary being written: what it is for and what it covers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummaryRequest {
+    pub target: SummaryUse,
+    /// Id of the conversation summarized, which may have been switched by the time the reply
+    /// arrives.
+    pub conversation: String,
+    /// How many messages from the start are summarized.
+    pub messages: usize,
+    /// [`fingerprint`] of those messages, to tell whether they changed by the time the reply
+    /// arrives.
+    pub fingerprint: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CompactionConfig {
+    /// Compact the conversation once its prompt reaches this many tokens; off if unset.
+    pub auto_at_tokens: Option<usize>,
+    /// Latest messages kept as they are when compacting.
+    pub keep_messages: usize,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            auto_at_tokens: None,
+            keep_messages: 4,
+        }
+    }
+}
+
+/// The question asking for a summary of `messages`.
+pub fn question(messages: &[ChatMessage]) -> String {
+    format!("Summarize this conversation.\n\n{}", transcript(messages))
+}
+
+/// `messages` as plain text in a `<conversation>` tag, for questions about the conversation.
+pub fn transcript(messages: &[ChatMessage]) -> String {
+    let lines: Vec<String> = messages
+        .iter()
+        .filter(|message| !message.alternative)
+        .map(|message| format!("{}: {}", message.role, message.content.trim()))
+        .collect();
+    format!("<conversation>\n{}\n</conversation>", lines.join("\n\n"))
+}
+
+/// How many messages from the start to compact so that at least `keep` stay as they are.
+///
+/// The kept messages start with a user message, so tool results stay with the call they answer.
+pub fn compactable(messages: &[ChatMessage], keep: usize) -> usize {
+    let mut split = messages.len().saturating_sub(keep);
+    // Keeping none, everything is compacted
+    while split > 0
+        && messages
+            .get(split)
+            .is_some_and(|message| message.role != "user")
+    {
+        split -= 1;
+    }
+    split
+}
+
+/// A hash of the roles and contents of `messages`.
+pub fn fingerprint(messages: &[ChatMessage]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for message in messages {
+        message.role.hash(&mut hasher);
+        message.content.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Replace the first `count` messages with `summary`, keeping the pinned ones among them.
+///
+/// A pinned tool call keeps its results, and a pinned result the call with all of its results,
+/// since providers refuse calls and results that don't answer each other.
+pub fn compact(messages: &mut Vec<ChatMessage>, count: usize, summary: &str) {
+    let count = count.min(messages.len());
+    let call_ids = |message: &ChatMessage| -> Vec<String> {
+        let calls = message.tool_calls.iter().map(|call| call.id.clone());
+        calls.chain(message.tool_call_id.clone()).collect()
+    };
+    let mut kept_calls: HashSet<String> = messages[..count]
+        .iter()
+        .filter(|message| message.pinned)
+        .flat_map(call_ids)
+        .collect();
+    // The calls answered by a pinned result, with their other results
+    let partners: Vec<String> = messages[..count]
+        .iter()
+        .filter(|message| {
+            message
+                .tool_calls
+                .iter()
+                .any(|call| kept_calls.contains(&call.id))
+        })
+        .flat_map(call_ids)
+        .collect();
+    kept_calls.extend(partners);
+    let pinned: Vec<ChatMessage> = messages
+        .drain(..count)
+        .filter(|message| {
+            message.pinned || call_ids(message).iter().any(|id| kept_calls.contains(id))
+        })
+        .collect();
+    let summary = ChatMessage::new(
+        "system",
+        format!("Summary of the conversation so far:\n\n{}", summary.trim()),
+    );
+    messages.splice(0..0, std::iter::once(summary).chain(pinned));
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::conversation::ToolCall;
+
+    #[test]
+    fn test_compact() {
+        let mut messages: Vec<ChatMessage> = [
+            ("user", "one"),
+            ("assistant", "1"),
+            ("user", "two"),
+            ("assistant", "calling"),
+            ("tool", "2"),
+            ("assistant", "2"),
+        ]
+        .into_iter()
+        .map(|(role, content)| ChatMessage::new(role, content))
+        .collect();
+        messages[1].pinned = true;
+        assert_eq!(compactable(&messages, 2), 2);
+        assert_eq!(compactable(&messages, 6), 0);
+        assert_eq!(compactable(&messages, 0), 6);
+        assert_eq!(compactable(&[], 0), 0);
+        assert_ne!(fingerprint(&messages[..2]), fingerprint(&messages[1..3]));
+        assert!(question(&messages[..2]).contains("user: one\n\nassistant: 1"));
+
+        compact(&mut messages, 2, "Counted to one.\n");
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            [
+                "Summary of the conversation so far:\n\nCounted to one.",
+                "1",
+                "two",
+                "calling",
+                "2",
+                "2"
+            ]
+        );
+        assert_eq!(messages[0].role, "system");
+    }
+
+    #[test]
+    fn test_compact_pinned_tool_call() {
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            name: "read_file".to_string(),
+            arguments: "{}".to_string(),
+        };
+        let result = |id: &str, content: &str| ChatMessage {
+            tool_call_id: Some(id.to_string()),
+            ..ChatMessage::new("tool", content)
+        };
+        let mut messages = vec![
+            ChatMessage::new("user", "read a and b"),
+            ChatMessage {
+                tool_calls: vec![call("a"), call("b")],
+                ..ChatMessage::new("AI", "")
+            },
+            result("a", "A"),
+            result("b", "B"),
+            ChatMessage::new("AI", "done"),
+            ChatMessage::new("user", "read c"),
+            ChatMessage {
+                tool_calls: vec![call("c")],
+                ..ChatMessage::new("AI", "")
+            },
+            result("c", "C"),
+            ChatMessage::new("AI", "done again"),
+            ChatMessage::new("user", "thanks"),
+        ];
+        // A pinned result keeps its call and the call's other result
+        messages[3].pinned = true;
+        // A pinned call keeps its result
+        messages[6].pinned = true;
+        compact(&mut messages, 9, "Read files.");
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            [
+                "Summary of the conversation so far:\n\nRead files.",
+                "",
+                "A",
+                "B",
+                "",
+                "C",
+                "thanks"
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Prompt templates from the config, with `{{name}}` or `{{name:default}}` placeholders.
+
+use std::{collections::HashMap, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// A prompt template: text from the config, or a Markdown file whose frontmatter also sets up the
+/// session it is used in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub text: String,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub system_prompt: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl PromptTemplate {
+    /// Read a template file: optional YAML frontmatter between `---` lines, then the text.
+    pub fn parse(name: &str, source: &str) -> Result<Self> {
+        let mut template = Self {
+            name: name.to_string(),
+            text: source.to_string(),
+            ..Default::default()
+        };
+        let Some((frontmatter, text)) = source.strip_prefix("---\n").and_then(|rest| {
+            rest.split_once("\n---\n")
+                .or_else(|| rest.split_once("\n---"))
+        }) else {
+            return Ok(template);
+        };
+        template.text = text.trim_start_matches('\n').to_string();
+        let documents = YamlLoader::load_from_str(frontmatter)?;
+        let Some(meta) = documents.first() else {
+            return Ok(template);
+        };
+        if !matches!(meta, Yaml::Hash(_)) {
+            return Err(eyre!("The frontmatter of {name} is not a mapping"));
+        }
+        template.model = meta["model"].as_str().map(str::to_string);
+        template.temperature = meta["temperature"]
+            .as_f64()
+            .or_else(|| meta["temperature"].as_i64().map(|value| value as f64));
+        template.system_prompt = meta["system_prompt"].as_str().map(str::to_string);
+        template.tags = match &meta["tags"] {
+            Yaml::Array(tags) => tags
+                .iter()
+                .filter_map(|tag| tag.as_str().map(str::to_string))
+                .collect(),
+            Yaml::String(tags) => tags
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(template)
+    }
+
+    /// The name with the tags and model, for the template picker.
+    pub fn label(&self) -> String {
+        let mut label = self.name.clone();
+        if !self.tags.is_empty() {
+            label.push_str(&format!(" [{}]", self.tags.join(", ")));
+        }
+        if let Some(model) = &self.model {
+            label.push_str(&format!(" · {model}"));
+        }
+        label
+    }
+}
+
+/// The templates from the config and the `.md` files in `dir`, sorted by name; a file replaces
+/// a config entry of the same name.
+pub fn load_all(config: &HashMap<String, String>, dir: &Path) -> Vec<PromptTemplate> {
+    let mut templates: HashMap<String, PromptTemplate> = config
+        .iter()
+        .map(|(name, text)| {
+            let template = PromptTemplate {
+                name: name.clone(),
+                text: text.clone(),
+                ..Default::default()
+            };
+            (name.clone(), template)
+        })
+        .collect();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "md") {
+            continue;
+        }
+        let Some(name) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        let template = std::fs::read_to_string(&path)
+            .map_err(Into::into)
+            .and_then(|source| PromptTemplate::parse(&name, &source));
+        match template {
+            Ok(template) => {
+                templates.insert(name, template);
+            }
+            Err(err) => error!("Failed to read template {}: {err}", path.display()),
+        }
+    }
+    let mut templates: Vec<PromptTemplate> = templates.into_values().collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+/// A placeholder of a template and the value it is filled with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub name: String,
+    pub value: String,
+}
+
+/// The `{{...}}` placeholders in `text`: each one's whole text, name and default.
+fn placeholders(text: &str) -> Vec<(&str, &str, &str)> {
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{")
+        && let Some(end) = rest[start..].find("}}")
+    {
+        let placeholder = &rest[start..start + end + 2];
+        let inner = &placeholder[2..placeholder.len() - 2];
+        let (name, default) = inner.split_once(':').unwrap_or((inner, ""));
+        found.push((placeholder, name.trim(), default.trim()));
+        rest = &rest[start + end + 2..];
+    }
+    found
+}
+
+/// The fields to fill in, in order of appearance, with their defaults.
+pub fn fields(text: &str) -> Vec<TemplateField> {
+    let mut fields: Vec<TemplateField> = Vec::new();
+    for (_, name, default) in placeholders(text) {
+        match fields.iter_mut().find(|field| field.name == name) {
+            // The first default given for a name wins
+            Some(field) if field.value.is_empty() => field.value = default.to_string(),
+            Some(_) => {}
+            None => fields.push(TemplateField {
+                name: name.to_string(),
+                value: default.to_string(),
+            }),
+        }
+    }
+    fields
+}
+
+/// Replace every placeholder with the value of its field.
+pub fn fill(text: &str, fields: &[TemplateField]) -> String {
+    let mut filled = text.to_string();
+    for (placeholder, name, _) in placeholders(text) {
+        if let Some(field) = fields.iter().find(|field| field.name == name) {
+            filled = filled.replacen(placeholder, &field.value, 1);
+        }
+    }
+    filled
+}
+
+/// The values last used for each template's fields, saved in the data directory.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateValues(HashMap<String, HashMap<String, String>>);
+
+impl TemplateValues {
+    /// Read the values from `path`, starting empty if it is missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        let Ok(json) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|err| {
+            error!("Failed to read {}: {err}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The fields of `template`, prefilled with the values used last time, or the defaults.
+    pub fn fields(&self, template: &str, text: &str) -> Vec<TemplateField> {
+        let mut fields = fields(text);
+        if let Some(values) = self.0.get(template) {
+            for field in &mut fields {
+                if let Some(value) = values.get(&field.name) {
+                    field.value = value.clone();
+                }
+            }
+        }
+        fields
+    }
+
+    pub fn remember(&mut self, template: &str, fields: &[TemplateField]) {
+        let values = self.0.entry(template.to_string()).or_default();
+        for field in fields {
+            values.insert(field.name.clone(), field.value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn field(name: &str, value: &str) -> TemplateField {
+        TemplateField {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fields_and_fill() {
+        let text = "Translate {{text}} from {{from:English}} to {{ to : German }}, {{from}} again";
+        assert_eq!(
+            fields(text),
+            [
+                field("text", ""),
+                field("from", "English"),
+                field("to", "German")
+            ]
+        );
+        assert_eq!(
+            fill(
+                text,
+                &[
+                    field("text", "hello"),
+                    field("from", "English"),
+                    field("to", "French")
+                ]
+            ),
+            "Translate hello from English to French, English again"
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter() -> Result<()> {
+        let source = "---\nmodel: openai/gpt-4o\ntemperature: 0\nsystem_prompt: You review code.\ntags: [code, review]\n---\n\nReview this {{language:Rust}} code\n";
+        let template = PromptTemplate::parse("review", source)?;
+        assert_eq!(
+            template,
+            PromptTemplate {
+                name: "review".to_string(),
+                text: "Review this {{language:Rust}} code\n".to_string(),
+                model: Some("openai/gpt-4o".to_string()),
+                temperature: Some(0.0),
+                system_prompt: Some("You review code.".to_string()),
+                tags: vec!["code".to_string(), "review".to_string()],
+            }
+        );
+        assert_eq!(template.label(), "review [code, review] · openai/gpt-4o");
+
+        // Without frontmatter the whole file is the text
+        let plain = PromptTemplate::parse("plain", "Just text\n---\nmore")?;
+        assert_eq!(plain.text, "Just text\n---\nmore");
+        assert_eq!(plain.model, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remembered_values() {
+        let mut values = TemplateValues::default();
+        let text = "Review this {{language:Rust}} code for {{focus}}";
+        values.remember("review", &[field("language", "Go"), field("focus", "bugs")]);
+        assert_eq!(
+            values.fields("review", text),
+            [field("language", "Go"), field("focus", "bugs")]
+        );
+        assert_eq!(
+            values.fields("other", text),
+            [field("language", "Rust"), field("focus", "")]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Action items the model extracts from the conversation with `/todos`, as a Markdown checklist.
+
+use std::{io::Write, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+
+use super::{ChatMessage, summary};
+
+pub const SYSTEM_PROMPT: &str = "You extract action items from conversations between a user and \
+an assistant: tasks someone agreed or decided to do, open questions to follow up on and next \
+steps. Answer with a Markdown checklist, one `- [ ] ` item per line, each short and actionable \
+on its own, with no other text. Answer `None` if there are no action items.";
+
+/// The question asking for the action items of `messages`.
+pub fn question(messages: &[ChatMessage]) -> String {
+    format!(
+        "List the action items of this conversation.\n\n{}",
+        summary::transcript(messages)
+    )
+}
+
+/// The items of the model's reply as `- [ ] ` lines, whatever list markers it used.
+pub fn checklist(reply: &str) -> Vec<String> {
+    reply
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let item = line
+                .strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .or_else(|| {
+                    let (number, rest) = line.split_once(". ")?;
+                    number.chars().all(|c| c.is_ascii_digit()).then_some(rest)
+                })?;
+            let item = ["[ ]", "[x]", "[X]"]
+                .iter()
+                .find_map(|checkbox| item.strip_prefix(checkbox))
+                .unwrap_or(item)
+                .trim();
+            (!item.is_empty()).then(|| format!("- [ ] {item}"))
+        })
+        .collect()
+}
+
+/// Append `items` under a `title` heading to the Markdown file at `path`, creating it if needed.
+pub fn append(path: &Path, title: &str, items: &[String]) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| eyre!("Failed to open {}: {err}", path.display()))?;
+    let separator = if file.metadata()?.len() > 0 { "\n" } else { "" };
+    write!(file, "{separator}## {title}\n\n{}\n", items.join("\n"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_checklist() -> Result<()> {
+        let reply = "Here you go:\n\n- [ ] Write the RFC\n* [x] Ask Sam\n2. Book a room\n-\nNone";
+        let items = checklist(reply);
+        assert_eq!(
+            items,
+            ["- [ ] Write the RFC", "- [ ] Ask Sam", "- [ ] Book a room"]
+        );
+        assert!(checklist("None").is_empty());
+
+        let path = std::env::temp_dir().join(format!("lazychat-todos-{}.md", new_session_id()));
+        append(&path, "Planning", &items[..1])?;
+        append(&path, "Later", &items[1..2])?;
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "## Planning\n\n- [ ] Write the RFC\n\n## Later\n\n- [ ] Ask Sam\n"
+        );
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
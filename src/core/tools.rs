@@ -0,0 +1,835 @@
+//! Functions the model can call, and the policy deciding which calls run without asking.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use strum::Display;
+
+use super::conversation::ToolCall;
+
+/// Which tools the model is offered and which calls need the user's approval.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ToolsConfig {
+    /// Offer the tools to the model; off by default since they run on this machine.
+    pub enabled: bool,
+    /// Policy for tools not listed in `policies`.
+    pub default_policy: ToolPolicy,
+    /// Policy per tool name, e.g. `{ "shell": "ask" }`.
+    pub policies: HashMap<String, ToolPolicy>,
+    /// Outputs longer than this are cut before they are sent to the model; 0 for no limit.
+    pub max_output_bytes: usize,
+    pub max_output_lines: usize,
+    /// Directory the file tools are confined to and the shell runs in; defaults to the
+    /// directory lazychat was started from.
+    pub workspace: Option<PathBuf>,
+    pub shell: ShellConfig,
+    /// Tools defined in the config, by name; a custom tool replaces a built-in of the same name.
+    pub custom: HashMap<String, CustomTool>,
+    /// Tool calls agent mode may make per user message before it is stopped; 0 for no limit.
+    pub agent_steps: usize,
+}
+
+/// A tool defined in the config, run as a shell command or an HTTP request.
+///
+/// `{name}` in its templates is replaced by the argument `name`: shell-quoted in `command`,
+/// percent-encoded in `url`, as JSON in `body` and as is in `query` and headers.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomTool {
+    pub description: String,
+    /// JSON schema of the arguments.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    /// Run with the `shell` tool's limits, but not its allow and deny lists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpTool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpTool {
+    pub url: String,
+    pub method: String,
+    /// Query parameters; one that is just a `{name}` is left out when the argument is missing.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub query: HashMap<String, String>,
+    /// `${NAME}` in header values is read from the environment, so keys stay out of the config.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl Default for HttpTool {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            method: "GET".to_string(),
+            query: HashMap::new(),
+            headers: HashMap::new(),
+            body: None,
+        }
+    }
+}
+
+/// Limits on what the `shell` tool may run.
+///
+/// The command lists are checked against the program names in the command line, which keeps
+/// the model from wandering off but is no security boundary: anything allowed can run anything.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ShellConfig {
+    /// Commands are killed after this many seconds; 0 for no limit.
+    pub timeout_secs: u64,
+    /// CPU time limit in seconds; 0 for no limit.
+    pub max_cpu_secs: u64,
+    /// Address space limit in MiB; 0 for no limit.
+    pub max_memory_mb: u64,
+    /// If not empty, only these programs may run.
+    pub allow: Vec<String>,
+    /// These programs may never run.
+    pub deny: Vec<String>,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            max_cpu_secs: 0,
+            max_memory_mb: 0,
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+}
+
+impl ShellConfig {
+    /// Refuse a command line that runs a denied program, or one that isn't allowed.
+    pub fn check(&self, command: &str) -> Result<()> {
+        for program in programs(command) {
+            if self.deny.contains(&program) {
+                return Err(eyre!("`{program}` is denied by the shell tool's config"));
+            }
+            if !self.allow.is_empty() && !self.allow.contains(&program) {
+                return Err(eyre!("`{program}` is not in the shell tool's allow list"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The programs a command line runs: the first word of every command in pipelines, lists and
+/// substitutions, without environment assignments or directories.
+fn programs(command: &str) -> Vec<String> {
+    const SEPARATORS: &[&str] = &["&&", "||", "$(", ";", "|", "&", "\n", "`", "(", ")"];
+    let mut command = command.to_string();
+    for separator in SEPARATORS {
+        command = command.replace(separator, "\n");
+    }
+    command
+        .lines()
+        .filter_map(|segment| {
+            segment
+                .split_whitespace()
+                .map(|word| word.trim_matches(['"', '\'']))
+                .find(|word| !word.contains('=') && !word.is_empty())
+        })
+        .map(|word| word.rsplit('/').next().unwrap_or(word).to_string())
+        .collect()
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_policy: ToolPolicy::default(),
+            policies: HashMap::new(),
+            max_output_bytes: 16 * 1024,
+            max_output_lines: 200,
+            workspace: None,
+            shell: ShellConfig::default(),
+            custom: HashMap::new(),
+            agent_steps: 10,
+        }
+    }
+}
+
+/// What happens when the model calls a tool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ToolPolicy {
+    /// Run it right away.
+    Auto,
+    /// Ask every time, unless allowed for the rest of the session or in agent mode. Writes are
+    /// always confirmed.
+    #[default]
+    Ask,
+    /// Never run it; the model is told the call was denied.
+    Deny,
+}
+
+/// A tool offered to the model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments.
+    pub parameters: Value,
+}
+
+/// Tools whose calls are confirmed one by one, even under `auto` or after "always allow".
+const CONFIRM_EACH_CALL: &[&str] = &["write_file"];
+
+/// Whether every call of `tool` needs its own confirmation unless it is denied.
+pub fn confirms_each_call(tool: &str) -> bool {
+    CONFIRM_EACH_CALL.contains(&tool)
+}
+
+impl ToolsConfig {
+    pub fn policy(&self, name: &str) -> ToolPolicy {
+        self.policies
+            .get(name)
+            .copied()
+            .unwrap_or(self.default_policy)
+    }
+
+    /// The tools offered to the model; denied ones are left out.
+    pub fn tools(&self) -> Vec<Tool> {
+        let mut custom: Vec<Tool> = self
+            .custom
+            .iter()
+            .map(|(name, tool)| Tool {
+                name: name.clone(),
+                description: tool.description.clone(),
+                parameters: tool
+                    .parameters
+                    .clone()
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+            })
+            .collect();
+        custom.sort_by(|a, b| a.name.cmp(&b.name));
+        builtin()
+            .into_iter()
+            .filter(|tool| !self.custom.contains_key(&tool.name))
+            .chain(custom)
+            .filter(|tool| self.policy(&tool.name) != ToolPolicy::Deny)
+            .collect()
+    }
+
+    /// Cut `output` down to the size limits, keeping its start and saying how much is missing.
+    ///
+    /// Returns `None` if it fits.
+    pub fn truncate_output(&self, output: &str) -> Option<String> {
+        let mut end = output.len();
+        if self.max_output_lines > 0
+            && let Some((index, _)) = output.match_indices('\n').nth(self.max_output_lines - 1)
+        {
+            end = index;
+        }
+        if self.max_output_bytes > 0 && end > self.max_output_bytes {
+            end = self.max_output_bytes;
+            while !output.is_char_boundary(end) {
+                end -= 1;
+            }
+        }
+        let rest = &output[end..];
+        if rest.trim().is_empty() {
+            return None;
+        }
+        let lines = rest.trim_start_matches('\n').lines().count();
+        Some(format!(
+            "{}\n[Output truncated: {lines} more {} ({} bytes) not shown]",
+            &output[..end],
+            if lines == 1 { "line" } else { "lines" },
+            rest.len()
+        ))
+    }
+
+    /// The workspace root as an absolute path.
+    pub fn workspace(&self) -> Result<PathBuf> {
+        let root = match &self.workspace {
+            Some(root) => root.clone(),
+            None => std::env::current_dir()?,
+        };
+        Ok(root.canonicalize()?)
+    }
+
+    /// Resolve a path the model passed against the workspace, refusing anything outside it.
+    ///
+    /// The path doesn't need to exist, so that new files can be written.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let root = self.workspace()?;
+        let path = Path::new(path);
+        if path.components().any(|part| part == Component::ParentDir) {
+            return Err(eyre!("`..` is not allowed in paths: {}", path.display()));
+        }
+        let full = root.join(path);
+        // Symlinks could still lead out, so check where the deepest existing part really is;
+        // a symlink to nothing can't be checked and is refused
+        let existing = full
+            .ancestors()
+            .find(|ancestor| ancestor.symlink_metadata().is_ok())
+            .unwrap_or(&root)
+            .canonicalize()
+            .map_err(|_| eyre!("{} leads to a path that doesn't exist", path.display()))?;
+        if !existing.starts_with(&root) {
+            return Err(eyre!("{} is outside the workspace", path.display()));
+        }
+        Ok(full)
+    }
+
+    /// What the user is shown when asked about a call: a diff for writes, the arguments
+    /// otherwise.
+    pub fn preview(&self, call: &ToolCall) -> String {
+        let arguments = serde_json::from_str::<Value>(&call.arguments).ok();
+        if call.name == "write_file"
+            && let Some(arguments) = &arguments
+            && let (Some(path), Some(content)) =
+                (arguments["path"].as_str(), arguments["content"].as_str())
+        {
+            return match self.resolve(path) {
+                Ok(full) => {
+                    let old = std::fs::read_to_string(&full).unwrap_or_default();
+                    let diff = similar::TextDiff::from_lines(old.as_str(), content)
+                        .unified_diff()
+                        .header(path, path)
+                        .to_string();
+                    if diff.is_empty() {
+                        format!("{path} is unchanged")
+                    } else {
+                        diff
+                    }
+                }
+                Err(err) => format!("{err}"),
+            };
+        }
+        arguments
+            .and_then(|arguments| serde_json::to_string_pretty(&arguments).ok())
+            .unwrap_or_else(|| call.arguments.clone())
+    }
+
+    /// The `tools` array of a chat completion request.
+    pub fn definitions(&self) -> Value {
+        self.tools()
+            .into_iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+fn builtin() -> Vec<Tool> {
+    vec![
+        Tool {
+            name: "shell".to_string(),
+            description: "Run a shell command in the workspace and return its output.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "command": { "type": "string", "description": "The command line to run" },
+                },
+                "required": ["command"],
+            }),
+        },
+        Tool {
+            name: "read_file".to_string(),
+            description: "Read a text file in the workspace.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the workspace" },
+                },
+                "required": ["path"],
+            }),
+        },
+        Tool {
+            name: "write_file".to_string(),
+            description: "Create or overwrite a text file in the workspace with the given content."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the workspace" },
+                    "content": { "type": "string", "description": "The whole new content" },
+                },
+                "required": ["path", "content"],
+            }),
+        },
+        Tool {
+            name: "list_dir".to_string(),
+            description: "List a directory in the workspace; directories end with `/`.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path relative to the workspace, `.` for its root",
+                    },
+                },
+            }),
+        },
+    ]
+}
+
+/// Run a call and return the output to send back to the model.
+pub async fn run(call: &ToolCall, config: &ToolsConfig) -> Result<String> {
+    let arguments: Value = serde_json::from_str(&call.arguments)
+        .map_err(|err| eyre!("Invalid arguments for {}: {err}", call.name))?;
+    let argument = |name: &str| {
+        arguments[name]
+            .as_str()
+            .ok_or_else(|| eyre!("Missing `{name}` argument"))
+    };
+    if let Some(tool) = config.custom.get(&call.name) {
+        return run_custom(tool, &arguments, config).await;
+    }
+    match call.name.as_str() {
+        "shell" => shell(argument("command")?, &config.workspace()?, &config.shell).await,
+        "read_file" => Ok(tokio::fs::read_to_string(config.resolve(argument("path")?)?).await?),
+        "write_file" => {
+            let path = config.resolve(argument("path")?)?;
+            let content = argument("content")?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, content).await?;
+            Ok(format!(
+                "Wrote {} bytes to {}",
+                content.len(),
+                argument("path")?
+            ))
+        }
+        "list_dir" => list_dir(&config.resolve(argument("path").unwrap_or("."))?).await,
+        name => Err(eyre!("Unknown tool: {name}")),
+    }
+}
+
+async fn list_dir(path: &Path) -> Result<String> {
+    let mut entries = tokio::fs::read_dir(path).await?;
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let mut name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type().await?.is_dir() {
+            name.push('/');
+        }
+        names.push(name);
+    }
+    names.sort();
+    Ok(names.join("\n"))
+}
+
+async fn run_custom(tool: &CustomTool, arguments: &Value, config: &ToolsConfig) -> Result<String> {
+    if let Some(command) = &tool.command {
+        let command = fill(command, arguments, |value| shell_quote(&text(value)));
+        return spawn_shell(&command, &config.workspace()?, &config.shell).await;
+    }
+    let Some(http) = &tool.http else {
+        return Err(eyre!("The tool has neither a `command` nor `http`"));
+    };
+    let url = fill(&http.url, arguments, |value| percent_encode(&text(value)));
+    let method = reqwest::Method::from_bytes(http.method.to_uppercase().as_bytes())?;
+    let mut request = reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()?
+        .request(method, url);
+    for (name, template) in &http.query {
+        let missing = template
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+            .is_some_and(|argument| arguments[argument].is_null());
+        if !missing {
+            request = request.query(&[(name, fill(template, arguments, text))]);
+        }
+    }
+    for (name, value) in &http.headers {
+        request = request.header(name, expand_env(&fill(value, arguments, text)));
+    }
+    if let Some(body) = &http.body {
+        request = request
+            .header("Content-Type", "application/json")
+            .body(fill(body, arguments, Value::to_string));
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    let mut output = response.text().await?;
+    if !status.is_success() {
+        output.push_str(&format!("\n(HTTP status {status})"));
+    }
+    Ok(output)
+}
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Replace each `{name}` in `template` with the argument `name`, passed through `encode`;
+/// other braces, as in a JSON body, are left alone.
+fn fill(template: &str, arguments: &Value, encode: impl Fn(&Value) -> String) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let name = rest[1..]
+            .find('}')
+            .map(|end| &rest[1..end + 1])
+            .filter(|name| {
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            });
+        match name {
+            Some(name) => {
+                filled.push_str(&encode(&arguments[name]));
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// An argument as plain text; missing ones are empty.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Replace each `${NAME}` with the environment variable `NAME`, empty if it isn't set.
+fn expand_env(text: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${")
+        && let Some(end) = rest[start..].find('}')
+    {
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&std::env::var(&rest[start + 2..start + end]).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+async fn shell(command: &str, dir: &Path, config: &ShellConfig) -> Result<String> {
+    config.check(command)?;
+    spawn_shell(command, dir, config).await
+}
+
+async fn spawn_shell(command: &str, dir: &Path, config: &ShellConfig) -> Result<String> {
+    let mut process = tokio::process::Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        // Never let a command read from or draw over the TUI
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    {
+        let (cpu, memory) = (config.max_cpu_secs, config.max_memory_mb);
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            process.pre_exec(move || {
+                // A group of its own, so a timeout kills whatever the command started too
+                libc::setsid();
+                if cpu > 0 && libc::setrlimit(libc::RLIMIT_CPU, &rlimit(cpu)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let bytes = memory * 1024 * 1024;
+                if memory > 0 && libc::setrlimit(libc::RLIMIT_AS, &rlimit(bytes)) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    let child = process.spawn()?;
+    let pid = child.id();
+    let output = child.wait_with_output();
+    let output = if config.timeout_secs > 0 {
+        match tokio::time::timeout(Duration::from_secs(config.timeout_secs), output).await {
+            Ok(output) => output?,
+            Err(_) => {
+                #[cfg(unix)]
+                if let Some(pid) = pid {
+                    unsafe {
+                        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+                    }
+                }
+                return Err(eyre!(
+                    "Killed after the {}s timeout: {command}",
+                    config.timeout_secs
+                ));
+            }
+        }
+    } else {
+        output.await?
+    };
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        text.push_str(&format!("\n({})", output.status));
+    }
+    Ok(text)
+}
+
+#[cfg(unix)]
+fn rlimit(value: u64) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let config = ToolsConfig {
+            default_policy: ToolPolicy::Deny,
+            policies: HashMap::from([("shell".to_string(), ToolPolicy::Auto)]),
+            ..Default::default()
+        };
+        assert_eq!(config.policy("shell"), ToolPolicy::Auto);
+        assert_eq!(config.policy("read_file"), ToolPolicy::Deny);
+        assert_eq!(config.definitions()[0]["function"]["name"], "shell");
+        assert_eq!(config.definitions().as_array().map(Vec::len), Some(1));
+
+        let denied = ToolsConfig {
+            default_policy: ToolPolicy::Deny,
+            ..Default::default()
+        };
+        assert_eq!(denied.definitions(), json!([]));
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let config = ToolsConfig {
+            max_output_bytes: 10,
+            max_output_lines: 2,
+            ..Default::default()
+        };
+        assert_eq!(config.truncate_output("one\ntwo\n"), None);
+        assert_eq!(
+            config.truncate_output("one\ntwo\nthree\nfour"),
+            Some("one\ntwo\n[Output truncated: 2 more lines (11 bytes) not shown]".to_string())
+        );
+        // Cut at a character boundary
+        assert_eq!(
+            config.truncate_output("aäääää"),
+            Some("aääää\n[Output truncated: 1 more line (2 bytes) not shown]".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_shell() -> Result<()> {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "shell".to_string(),
+            arguments: r#"{"command": "echo hello; exit 3"}"#.to_string(),
+        };
+        let output = run(&call, &ToolsConfig::default()).await?;
+        assert!(output.starts_with("hello\n"), "{output}");
+        assert!(output.contains("exit status: 3"), "{output}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_shell_check() {
+        assert_eq!(
+            programs("FOO=1 ls -la | grep x && /usr/bin/rm -rf y; echo $(date)"),
+            ["ls", "grep", "rm", "echo", "date"]
+        );
+        let config = ShellConfig {
+            deny: vec!["rm".to_string()],
+            ..Default::default()
+        };
+        assert!(config.check("ls -la").is_ok());
+        assert!(config.check("ls && rm -rf /").is_err());
+
+        let config = ShellConfig {
+            allow: vec!["ls".to_string(), "grep".to_string()],
+            ..Default::default()
+        };
+        assert!(config.check("ls | grep foo").is_ok());
+        assert!(config.check("ls | xargs cat").is_err());
+    }
+
+    #[test]
+    fn test_fill() {
+        let arguments = json!({ "city": "São Paulo", "days": 3, "note": "it's" });
+        assert_eq!(
+            fill("https://x.test/{city}?days={days}", &arguments, |value| {
+                percent_encode(&text(value))
+            }),
+            "https://x.test/S%C3%A3o%20Paulo?days=3"
+        );
+        assert_eq!(
+            fill("echo {note} {missing}", &arguments, |value| shell_quote(
+                &text(value)
+            )),
+            r"echo 'it'\''s' ''"
+        );
+        assert_eq!(
+            fill(
+                r#"{"q": {city}, "n": {days}}"#,
+                &arguments,
+                Value::to_string
+            ),
+            r#"{"q": "São Paulo", "n": 3}"#
+        );
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let path = std::env::var("PATH").unwrap_or_default();
+        assert_eq!(
+            expand_env("Bearer ${PATH}${LAZYCHAT_UNSET_VARIABLE}"),
+            format!("Bearer {path}")
+        );
+        assert_eq!(expand_env("no ${ end"), "no ${ end");
+    }
+
+    #[tokio::test]
+    async fn test_run_custom() -> Result<()> {
+        let config = ToolsConfig {
+            custom: HashMap::from([(
+                "greet".to_string(),
+                CustomTool {
+                    description: "Say hello".to_string(),
+                    command: Some("echo hello {name}".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config
+                .definitions()
+                .as_array()
+                .and_then(|tools| tools.last())
+                .map(|tool| tool["function"]["name"].clone()),
+            Some(json!("greet"))
+        );
+        let call = call("greet", json!({ "name": "you; rm -rf /" }));
+        assert_eq!(run(&call, &config).await?, "hello you; rm -rf /\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shell_timeout() {
+        let config = ToolsConfig {
+            shell: ShellConfig {
+                timeout_secs: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let call = call("shell", json!({ "command": "sleep 5" }));
+        let err = run(&call, &config).await.unwrap_err();
+        assert!(err.to_string().contains("timeout"), "{err}");
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_tools() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-tools-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src"))?;
+        std::fs::write(dir.join("notes.txt"), "one\ntwo\n")?;
+        let config = ToolsConfig {
+            workspace: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        assert!(config.resolve("../etc/passwd").is_err());
+        assert!(config.resolve("/etc/passwd").is_err());
+        assert_eq!(
+            config.resolve("src/new.rs")?,
+            dir.canonicalize()?.join("src/new.rs")
+        );
+
+        let read = call("read_file", json!({ "path": "notes.txt" }));
+        assert_eq!(run(&read, &config).await?, "one\ntwo\n");
+
+        let write = call(
+            "write_file",
+            json!({ "path": "notes.txt", "content": "one\nthree\n" }),
+        );
+        assert_eq!(
+            config.preview(&write),
+            "--- notes.txt\n+++ notes.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+three\n"
+        );
+        assert_eq!(run(&write, &config).await?, "Wrote 10 bytes to notes.txt");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("notes.txt"))?,
+            "one\nthree\n"
+        );
+
+        let list = call("list_dir", json!({}));
+        assert_eq!(run(&list, &config).await?, "notes.txt\nsrc/");
+
+        #[cfg(unix)]
+        {
+            let outside = std::env::temp_dir().join(format!("lazychat-out-{}", std::process::id()));
+            std::os::unix::fs::symlink(&outside, dir.join("link"))?;
+            assert!(config.resolve("link").is_err());
+            assert!(config.resolve("link/new.rs").is_err());
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// Logging of full API requests and responses to `logs/transcript.jsonl`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TranscriptConfig {
+    pub enabled: bool,
+    /// Start a new file once the current one would grow past this size.
+    pub max_bytes: u64,
+    /// Number of rotated files to keep next to the current one.
+    pub max_files: usize,
+}
+
+impl Default for TranscriptConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 3,
+        }
+    }
+}
+
+const FILE_NAME: &str = "transcript.jsonl";
+const REDACTED: &str = "[REDACTED]";
+
+/// Appends full API requests and responses to a size-rotated JSON Lines file.
+#[derive(Debug, Clone)]
+pub struct TranscriptLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl TranscriptLog {
+    /// A log in `dir`, or `None` if transcripts are disabled.
+    pub fn new(config: &TranscriptConfig, dir: PathBuf) -> Option<Self> {
+        config.enabled.then_some(Self {
+            dir,
+            max_bytes: config.max_bytes,
+            max_files: config.max_files,
+        })
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(FILE_NAME),
+            index => self.dir.join(format!("transcript.{index}.jsonl")),
+        }
+    }
+
+    /// Record one exchange; `api_key` is scrubbed from everything that is written.
+    pub fn record(
+        &self,
+        url: &str,
+        api_key: &str,
+        request: &Value,
+        status: Option<u16>,
+        response: &str,
+        latency_ms: u64,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let response = serde_json::from_str(response).unwrap_or_else(|_| json!(response));
+        let entry = json!({
+            "timestamp": timestamp,
+            "url": url,
+            "headers": { "Authorization": format!("Bearer {REDACTED}") },
+            "request": request,
+            "status": status,
+            "response": response,
+            "latency_ms": latency_ms,
+        });
+        let mut line = entry.to_string();
+        if !api_key.is_empty() {
+            line = line.replace(api_key, REDACTED);
+        }
+        line.push('\n');
+
+        fs::create_dir_all(&self.dir)?;
+        self.rotate_if_needed(line.len() as u64)?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Shift `transcript.jsonl` to `transcript.1.jsonl` and so on when it would grow too large.
+    fn rotate_if_needed(&self, incoming: u64) -> Result<()> {
+        let size = fs::metadata(self.path(0)).map_or(0, |metadata| metadata.len());
+        if size == 0 || size + incoming <= self.max_bytes {
+            return Ok(());
+        }
+        if self.max_files == 0 {
+            fs::remove_file(self.path(0))?;
+            return Ok(());
+        }
+        for index in (0..self.max_files).rev() {
+            let from = self.path(index);
+            if from.exists() {
+                fs::rename(from, self.path(index + 1))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `curl` command that replays a request, reading the key from `$key_env` instead of
+/// embedding it.
+pub fn curl_command(url: &str, key_env: &str, body: &Value) -> String {
+    let body = serde_json::to_string_pretty(body).unwrap_or_default();
+    // Single-quote the body for the shell; a quote inside becomes '\''
+    let body = body.replace('\'', r"'\''");
+    format!(
+        "curl {url} \\\n  -H 'Content-Type: application/json' \\\n  -H \"Authorization: Bearer ${key_env}\" \\\n  -d '{body}'"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_curl_command() {
+        let body = json!({ "content": "it's" });
+        assert_eq!(
+            curl_command("https://x", "KEY", &body),
+            "curl https://x \\\n  -H 'Content-Type: application/json' \\\n  -H \"Authorization: Bearer $KEY\" \\\n  -d '{\n  \"content\": \"it'\\''s\"\n}'"
+        );
+    }
+
+    #[test]
+    fn test_redacts_key_and_rotates() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-transcript-{}", std::process::id()));
+        let log = TranscriptLog {
+            dir: dir.clone(),
+            max_bytes: 200,
+            max_files: 1,
+        };
+        let request = json!({ "model": "m" });
+        log.record(
+            "https://x",
+            "sk-secret",
+            &request,
+            Some(401),
+            "bad key sk-secret",
+            1,
+        )?;
+        log.record("https://x", "sk-secret", &request, Some(200), "{}", 1)?;
+        log.record("https://x", "sk-secret", &request, Some(200), "{}", 1)?;
+
+        let current = fs::read_to_string(log.path(0))?;
+        let rotated = fs::read_to_string(log.path(1))?;
+        assert!(!current.contains("sk-secret") && !rotated.contains("sk-secret"));
+        assert_eq!(current.lines().count(), 1);
+        assert!(!log.path(2).exists());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
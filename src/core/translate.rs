@@ -0,0 +1,80 @@
+//! Translation mode, `/translate`: messages sent are translated by the model instead of answered.
+
+use serde::{Deserialize, Serialize};
+
+/// The languages translated between.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Translation {
+    /// Detected by the model if unset.
+    pub source: Option<String>,
+    pub target: String,
+}
+
+impl Translation {
+    /// The languages of `/translate` arguments: a target like `German`, or a source and a
+    /// target separated by `->`, `→` or `>`, like `en -> de`.
+    pub fn parse(args: &str) -> Option<Self> {
+        let (source, target) = match args
+            .split_once("->")
+            .or_else(|| args.split_once('→'))
+            .or_else(|| args.split_once('>'))
+        {
+            Some((source, target)) => (Some(source.trim()), target.trim()),
+            None => (None, args.trim()),
+        };
+        (!target.is_empty()).then(|| Self {
+            source: source
+                .filter(|source| !source.is_empty())
+                .map(str::to_string),
+            target: target.to_string(),
+        })
+    }
+
+    /// The system prompt the messages are sent with instead of the conversation's.
+    pub fn system_prompt(&self) -> String {
+        let from = self
+            .source
+            .as_ref()
+            .map(|source| format!("from {source} "))
+            .unwrap_or_default();
+        format!(
+            "You are a translator. Translate every message of the user {from}into {}. Answer \
+             with the translation only: keep the meaning, tone and Markdown formatting, leave \
+             code, names and URLs as they are, and don't answer or comment on the content.",
+            self.target
+        )
+    }
+
+    /// Source and target, for the status bar.
+    pub fn label(&self) -> String {
+        format!(
+            "{} → {}",
+            self.source.as_deref().unwrap_or("auto"),
+            self.target
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let translation = Translation::parse("Brazilian Portuguese").unwrap();
+        assert_eq!(translation.source, None);
+        assert_eq!(translation.label(), "auto → Brazilian Portuguese");
+        assert_eq!(
+            Translation::parse("en -> de").map(|translation| translation.label()),
+            Some("en → de".to_string())
+        );
+        assert_eq!(
+            Translation::parse("ja→en").map(|translation| translation.label()),
+            Some("ja → en".to_string())
+        );
+        assert_eq!(Translation::parse(" "), None);
+        assert_eq!(Translation::parse("en ->"), None);
+    }
+}
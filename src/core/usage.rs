@@ -0,0 +1,407 @@
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{MessageMetadata, budget::Spending};
+
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const SPARKLINE: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Tokens and cost of one reply, a line of the usage log.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageRecord {
+    /// Unix timestamp in seconds.
+    pub timestamp: u64,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+    pub latency_ms: Option<u64>,
+    pub first_token_ms: Option<u64>,
+}
+
+/// Usage added up over some replies.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Totals {
+    pub replies: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+}
+
+impl Totals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.replies += 1;
+        self.prompt_tokens += record.prompt_tokens;
+        self.completion_tokens += record.completion_tokens;
+        self.cost += record.cost;
+    }
+}
+
+/// Usage per day and per model over the last days.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UsageSummary {
+    /// One entry per day, oldest first, including days without usage.
+    pub days: Vec<(String, Totals)>,
+    pub models: BTreeMap<String, Totals>,
+}
+
+impl UsageSummary {
+    pub fn total(&self) -> Totals {
+        let mut total = Totals::default();
+        for (_, day) in &self.days {
+            total.replies += day.replies;
+            total.prompt_tokens += day.prompt_tokens;
+            total.completion_tokens += day.completion_tokens;
+            total.cost += day.cost;
+        }
+        total
+    }
+
+    /// Render the summary as text for `lazychat usage` and the usage dialog.
+    pub fn lines(&self) -> Vec<String> {
+        let row = |label: &str, totals: &Totals| {
+            format!(
+                "{label:<28} {:>7} {:>10} {:>10} {:>9}",
+                totals.replies,
+                totals.prompt_tokens,
+                totals.completion_tokens,
+                format!("${:.4}", totals.cost)
+            )
+        };
+        let header = |label: &str| {
+            format!(
+                "{label:<28} {:>7} {:>10} {:>10} {:>9}",
+                "Replies", "Tokens in", "Tokens out", "Cost"
+            )
+        };
+        let mut lines = vec![
+            format!("Last {} days (UTC)", self.days.len()),
+            format!("Cost per day: {}", self.sparkline()),
+            String::new(),
+            header("Date"),
+        ];
+        // Newest first, skipping the quiet days that the sparkline already shows
+        for (date, totals) in self.days.iter().rev() {
+            if totals.replies > 0 {
+                lines.push(row(date, totals));
+            }
+        }
+        lines.push(row("Total", &self.total()));
+        if !self.models.is_empty() {
+            lines.push(String::new());
+            lines.push(header("Model"));
+            for (model, totals) in &self.models {
+                lines.push(row(model, totals));
+            }
+        }
+        lines
+    }
+
+    fn sparkline(&self) -> String {
+        let max = self
+            .days
+            .iter()
+            .map(|(_, totals)| totals.cost)
+            .fold(0.0, f64::max);
+        self.days
+            .iter()
+            .map(|(_, totals)| {
+                if max <= 0.0 {
+                    return SPARKLINE[0];
+                }
+                let level = (totals.cost / max * (SPARKLINE.len() - 1) as f64).round();
+                SPARKLINE[level as usize]
+            })
+            .collect()
+    }
+}
+
+/// The 50th, 90th and 99th percentile of some durations in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles, or `None` without samples.
+    fn of(mut samples: Vec<u64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = |percent: usize| samples[(samples.len() * percent).div_ceil(100).max(1) - 1];
+        Some(Self {
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+        })
+    }
+}
+
+/// How quickly a model answers, over every recorded reply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub model: String,
+    pub replies: usize,
+    /// Time to the first streamed token, what interactive use feels like.
+    pub first_token: Option<Percentiles>,
+    /// Time to the complete reply.
+    pub total: Option<Percentiles>,
+}
+
+/// Render latency statistics as a table for the model info dialog.
+pub fn latency_lines(stats: &[LatencyStats]) -> Vec<String> {
+    let seconds = |percentiles: Option<Percentiles>| {
+        percentiles.map_or("-".to_string(), |p| {
+            format!(
+                "{:.1}/{:.1}/{:.1}s",
+                p.p50 as f64 / 1000.0,
+                p.p90 as f64 / 1000.0,
+                p.p99 as f64 / 1000.0
+            )
+        })
+    };
+    let mut lines = vec![format!(
+        "{:<28} {:>7} {:>16} {:>16}",
+        "Model", "Replies", "First token", "Total"
+    )];
+    lines.extend(stats.iter().map(|stats| {
+        format!(
+            "{:<28} {:>7} {:>16} {:>16}",
+            stats.model,
+            stats.replies,
+            seconds(stats.first_token),
+            seconds(stats.total)
+        )
+    }));
+    lines
+}
+
+/// Appends the tokens and cost of every reply to a JSON lines file, so that usage survives
+/// restarts and deleted sessions.
+#[derive(Debug, Clone)]
+pub struct UsageLog {
+    path: PathBuf,
+}
+
+impl UsageLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn record(&self, timestamp: u64, model: &str, metadata: &MessageMetadata) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let record = UsageRecord {
+            timestamp,
+            model: model.to_string(),
+            prompt_tokens: metadata.prompt_tokens.unwrap_or_default(),
+            completion_tokens: metadata.completion_tokens.unwrap_or_default(),
+            cost: metadata.cost.unwrap_or_default(),
+            latency_ms: metadata.latency_ms,
+            first_token_ms: metadata.first_token_ms,
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        Ok(())
+    }
+
+    fn records(&self) -> Result<Vec<UsageRecord>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(std::fs::read_to_string(&self.path)?
+            .lines()
+            // A line cut short by a crash shouldn't lose the rest
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Latency of every model that replied, fastest first token first.
+    pub fn latency(&self) -> Result<Vec<LatencyStats>> {
+        let mut samples: BTreeMap<String, (Vec<u64>, Vec<u64>)> = BTreeMap::new();
+        for record in self.records()? {
+            let (first_token, total) = samples.entry(record.model).or_default();
+            first_token.extend(record.first_token_ms);
+            total.extend(record.latency_ms);
+        }
+        let mut stats: Vec<LatencyStats> = samples
+            .into_iter()
+            .map(|(model, (first_token, total))| LatencyStats {
+                model,
+                replies: total.len(),
+                first_token: Percentiles::of(first_token),
+                total: Percentiles::of(total),
+            })
+            .collect();
+        // Models without timings go last
+        stats.sort_by_key(|stats| stats.first_token.map_or(u64::MAX, |p| p.p50));
+        Ok(stats)
+    }
+
+    /// Add up what was spent on the day and in the month of `now`.
+    pub fn spending(&self, now: u64) -> Result<Spending> {
+        let today = now / SECONDS_PER_DAY;
+        let (year, month, _) = civil_date(today);
+        let mut spending = Spending::default();
+        for record in self.records()? {
+            let day = record.timestamp / SECONDS_PER_DAY;
+            if day == today {
+                spending.today += record.cost;
+            }
+            let (record_year, record_month, _) = civil_date(day);
+            if (record_year, record_month) == (year, month) {
+                spending.month += record.cost;
+            }
+        }
+        Ok(spending)
+    }
+
+    /// Usage of the `days` days up to and including the day of `now`.
+    pub fn summary(&self, now: u64, days: u64) -> Result<UsageSummary> {
+        let today = now / SECONDS_PER_DAY;
+        let first = (today + 1).saturating_sub(days);
+        let mut summary = UsageSummary {
+            days: (first..=today)
+                .map(|day| {
+                    let (year, month, day) = civil_date(day);
+                    (format!("{year}-{month:02}-{day:02}"), Totals::default())
+                })
+                .collect(),
+            models: BTreeMap::new(),
+        };
+        for record in self.records()? {
+            let day = record.timestamp / SECONDS_PER_DAY;
+            if !(first..=today).contains(&day) {
+                continue;
+            }
+            summary.days[(day - first) as usize].1.add(&record);
+            summary
+                .models
+                .entry(record.model.clone())
+                .or_default()
+                .add(&record);
+        }
+        Ok(summary)
+    }
+}
+
+/// Year, month and day of the given number of days since the Unix epoch.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+pub fn civil_date(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    /// 2024-03-01 12:00 UTC
+    const NOW: u64 = 19_783 * SECONDS_PER_DAY + 12 * 60 * 60;
+
+    /// Metadata of a reply whose first token took `tokens` milliseconds.
+    fn metadata(tokens: u64, cost: f64) -> MessageMetadata {
+        MessageMetadata {
+            prompt_tokens: Some(tokens),
+            completion_tokens: Some(tokens),
+            cost: Some(cost),
+            latency_ms: Some(tokens * 10),
+            first_token_ms: Some(tokens),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        assert_eq!(Percentiles::of(Vec::new()), None);
+        assert_eq!(
+            Percentiles::of((1..=100).rev().collect()),
+            Some(Percentiles {
+                p50: 50,
+                p90: 90,
+                p99: 99
+            })
+        );
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(19_782), (2024, 2, 29));
+        assert_eq!(civil_date(19_783), (2024, 3, 1));
+    }
+
+    #[test]
+    fn test_spending_and_summary() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-usage-{}", new_session_id()));
+        let log = UsageLog::new(dir.join("usage.jsonl"));
+        log.record(NOW - SECONDS_PER_DAY, "a", &metadata(10, 1.0))?; // February
+        log.record(NOW - 60, "a", &metadata(20, 0.5))?;
+        log.record(NOW, "b", &metadata(30, 0.25))?;
+
+        assert_eq!(
+            log.spending(NOW)?,
+            Spending {
+                today: 0.75,
+                month: 0.75
+            }
+        );
+        assert_eq!(
+            log.spending(NOW + SECONDS_PER_DAY)?,
+            Spending {
+                today: 0.0,
+                month: 0.75
+            }
+        );
+
+        let summary = log.summary(NOW, 3)?;
+        let dates: Vec<&str> = summary.days.iter().map(|(date, _)| date.as_str()).collect();
+        assert_eq!(dates, vec!["2024-02-28", "2024-02-29", "2024-03-01"]);
+        assert_eq!(summary.days[1].1.cost, 1.0);
+        assert_eq!(summary.days[2].1.replies, 2);
+        assert_eq!(summary.models["a"].prompt_tokens, 30);
+        assert_eq!(summary.total().cost, 1.75);
+        assert_eq!(summary.sparkline(), "▁█▆");
+
+        let latency = log.latency()?;
+        assert_eq!(latency[0].model, "a");
+        assert_eq!(latency[1].model, "b");
+        assert_eq!(
+            latency[0].first_token,
+            Some(Percentiles {
+                p50: 10,
+                p90: 20,
+                p99: 20
+            })
+        );
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Walking a directory for `/index` and `/tree`, leaving out what shouldn't be sent to a model.
+//!
+//! Hidden files, anything matched by a `.gitignore` or `.lazychatignore` (same syntax) and the
+//! [`EXCLUDED`] build output, lockfiles and keys are skipped.
+
+use std::path::Path;
+
+use color_eyre::Result;
+use ignore::{Walk, WalkBuilder, overrides::OverrideBuilder};
+
+/// Ignore file for what should stay out of lazychat only, next to `.gitignore`.
+pub const IGNORE_FILE: &str = ".lazychatignore";
+
+/// Always left out, even in directories that aren't git repositories.
+const EXCLUDED: [&str; 14] = [
+    "target/",
+    "node_modules/",
+    "dist/",
+    "build/",
+    "*.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "id_rsa*",
+    "id_ecdsa*",
+    "id_ed25519*",
+];
+
+/// The entries under `dir`, depth first with siblings sorted by name, at most `max_depth` levels
+/// deep.
+pub fn walk(dir: &Path, max_depth: Option<usize>) -> Result<Walk> {
+    let mut overrides = OverrideBuilder::new(dir);
+    for glob in EXCLUDED {
+        overrides.add(&format!("!{glob}"))?;
+    }
+    Ok(WalkBuilder::new(dir)
+        .max_depth(max_depth)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .overrides(overrides.build()?)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_walk() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lazychat-walk-{}", new_session_id()));
+        std::fs::create_dir_all(dir.join("src"))?;
+        std::fs::create_dir_all(dir.join("target"))?;
+        for file in [
+            "src/main.rs",
+            "src/generated.rs",
+            "notes.txt",
+            "secrets.toml",
+            "Cargo.lock",
+            "server.pem",
+            ".env",
+            "target/app",
+        ] {
+            std::fs::write(dir.join(file), "")?;
+        }
+        std::fs::write(dir.join(".gitignore"), "generated.rs\n")?;
+        std::fs::write(dir.join(IGNORE_FILE), "secrets.toml\n")?;
+
+        let paths: Vec<String> = walk(&dir, None)?
+            .flatten()
+            .filter(|entry| entry.depth() > 0)
+            .map(|entry| {
+                let path = entry.path().strip_prefix(&dir).unwrap_or(entry.path());
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+        assert_eq!(paths, ["notes.txt", "src", "src/main.rs"]);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! Workflows: prompts sent one after the other, each able to use the replies before it.
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+
+/// A named list of steps from the config, run with `/workflow <name> <input>`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Workflow {
+    pub description: String,
+    pub steps: Vec<WorkflowStep>,
+}
+
+/// One prompt of a workflow.
+///
+/// `{{input}}` in the prompt is replaced by the text the workflow was started with,
+/// `{{previous}}` by the reply to the step before and `{{<name>}}` by the reply to an earlier
+/// step of that name.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WorkflowStep {
+    /// Defaults to `step1`, `step2` and so on.
+    pub name: Option<String>,
+    /// Model for this step only; the conversation's model otherwise.
+    pub model: Option<String>,
+    pub prompt: String,
+}
+
+impl Workflow {
+    fn step_name(&self, index: usize) -> String {
+        self.steps[index]
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("step{}", index + 1))
+    }
+
+    /// Check that every placeholder refers to the input or an earlier step.
+    pub fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(eyre!("The workflow has no steps"));
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            for placeholder in placeholders(&step.prompt) {
+                let known = placeholder == "input"
+                    || (placeholder == "previous" && index > 0)
+                    || (0..index).any(|earlier| self.step_name(earlier) == placeholder);
+                if !known {
+                    return Err(eyre!(
+                        "Step {} refers to {{{{{placeholder}}}}}, which no earlier step defines",
+                        index + 1
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The names inside `{{...}}` in `text`.
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{")
+        && let Some(end) = rest[start..].find("}}")
+    {
+        names.push(rest[start + 2..start + end].trim());
+        rest = &rest[start + end + 2..];
+    }
+    names
+}
+
+/// A workflow being run in the conversation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkflowRun {
+    pub name: String,
+    workflow: Workflow,
+    input: String,
+    /// Replies to the steps sent so far, by step name.
+    outputs: Vec<(String, String)>,
+    /// The conversation's model, restored when the run ends.
+    pub model: String,
+}
+
+impl WorkflowRun {
+    pub fn new(name: &str, workflow: Workflow, input: &str, model: &str) -> Result<Self> {
+        workflow
+            .validate()
+            .map_err(|err| eyre!("Workflow {name}: {err}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            workflow,
+            input: input.to_string(),
+            outputs: Vec::new(),
+            model: model.to_string(),
+        })
+    }
+
+    /// The number of the step in progress, from 1, and the number of steps.
+    pub fn progress(&self) -> (usize, usize) {
+        let total = self.workflow.steps.len();
+        ((self.outputs.len() + 1).min(total), total)
+    }
+
+    /// The next step, with its placeholders filled in.
+    pub fn next_step(&self) -> Option<WorkflowStep> {
+        let step = self.workflow.steps.get(self.outputs.len())?;
+        Some(WorkflowStep {
+            prompt: self.fill(&step.prompt),
+            ..step.clone()
+        })
+    }
+
+    /// Keep the reply to the step that was sent last.
+    pub fn record(&mut self, output: &str) {
+        let name = self.workflow.step_name(self.outputs.len());
+        self.outputs.push((name, output.to_string()));
+    }
+
+    fn fill(&self, template: &str) -> String {
+        let mut filled = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{")
+            && let Some(end) = rest[start..].find("}}")
+        {
+            filled.push_str(&rest[..start]);
+            let placeholder = &rest[start..start + end + 2];
+            let value = match placeholder[2..placeholder.len() - 2].trim() {
+                "input" => Some(self.input.as_str()),
+                "previous" => self.outputs.last().map(|(_, output)| output.as_str()),
+                name => self
+                    .outputs
+                    .iter()
+                    .find(|(step, _)| step == name)
+                    .map(|(_, output)| output.as_str()),
+            };
+            filled.push_str(value.unwrap_or(placeholder));
+            rest = &rest[start + end + 2..];
+        }
+        filled.push_str(rest);
+        filled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn step(name: Option<&str>, prompt: &str) -> WorkflowStep {
+        WorkflowStep {
+            name: name.map(str::to_string),
+            model: None,
+            prompt: prompt.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_run() -> Result<()> {
+        let workflow = Workflow {
+            description: String::new(),
+            steps: vec![
+                step(Some("draft"), "Write a haiku about {{input}}"),
+                step(None, "Critique this: {{previous}}"),
+                step(None, "Rewrite {{ draft }} using {{step2}}"),
+            ],
+        };
+        let mut run = WorkflowRun::new("haiku", workflow, "rain", "mock-1")?;
+        assert_eq!(run.progress(), (1, 3));
+        assert_eq!(
+            run.next_step().map(|step| step.prompt),
+            Some("Write a haiku about rain".to_string())
+        );
+        run.record("Drops on the window");
+        assert_eq!(
+            run.next_step().map(|step| step.prompt),
+            Some("Critique this: Drops on the window".to_string())
+        );
+        assert_eq!(run.progress(), (2, 3));
+        run.record("Too short");
+        assert_eq!(
+            run.next_step().map(|step| step.prompt),
+            Some("Rewrite Drops on the window using Too short".to_string())
+        );
+        run.record("Done");
+        assert_eq!(run.next_step(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let workflow = |steps| Workflow {
+            description: String::new(),
+            steps,
+        };
+        assert!(workflow(vec![]).validate().is_err());
+        assert!(
+            workflow(vec![step(None, "{{previous}}")])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            workflow(vec![step(None, "{{input}}"), step(None, "{{ step1 }}")])
+                .validate()
+                .is_ok()
+        );
+        let err = workflow(vec![step(None, "{{step2}}"), step(None, "x")])
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Step 1 refers to {{step2}}, which no earlier step defines"
+        );
+    }
+}
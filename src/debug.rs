@@ -0,0 +1,99 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::action::Action;
+
+/// Frame, tick and action metrics shown in the debug overlay.
+#[derive(Debug, Default)]
+pub struct DebugMetrics {
+    frames: VecDeque<Instant>,
+    ticks: VecDeque<Instant>,
+    queue_depth: usize,
+    max_queue_depth: usize,
+    last_action: Option<String>,
+    frame_time: Duration,
+    draw_times: Vec<(&'static str, Duration)>,
+}
+
+/// Keep only the events of the last second so their count is a rate.
+fn record_event(events: &mut VecDeque<Instant>, now: Instant) {
+    events.push_back(now);
+    while events
+        .front()
+        .is_some_and(|first| now.duration_since(*first) > Duration::from_secs(1))
+    {
+        events.pop_front();
+    }
+}
+
+impl DebugMetrics {
+    pub fn record_tick(&mut self) {
+        record_event(&mut self.ticks, Instant::now());
+    }
+
+    pub fn record_queue_depth(&mut self, depth: usize) {
+        self.queue_depth = depth;
+        self.max_queue_depth = self.max_queue_depth.max(depth);
+    }
+
+    pub fn record_action(&mut self, action: &Action) {
+        if !matches!(action, Action::Tick | Action::Render) {
+            self.last_action = Some(action.to_string());
+        }
+    }
+
+    /// Record a rendered frame with the time each component took to draw.
+    pub fn record_frame(&mut self, started: Instant, draw_times: Vec<(&'static str, Duration)>) {
+        self.frame_time = started.elapsed();
+        self.draw_times = draw_times;
+        record_event(&mut self.frames, Instant::now());
+    }
+
+    pub fn draw(&self, frame: &mut Frame, area: Rect, tick_rate: f64, frame_rate: f64) {
+        let mut lines = vec![
+            format!("FPS: {} (target {frame_rate})", self.frames.len()),
+            format!("Ticks/s: {} (target {tick_rate})", self.ticks.len()),
+            format!(
+                "Action queue: {} (max {})",
+                self.queue_depth, self.max_queue_depth
+            ),
+            format!(
+                "Last action: {}",
+                self.last_action.as_deref().unwrap_or("-")
+            ),
+            format!("Frame: {:.2?}", self.frame_time),
+        ];
+        lines.extend(
+            self.draw_times
+                .iter()
+                .map(|(name, time)| format!("  {name}: {time:.2?}")),
+        );
+
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count() as u16 + 2)
+            .max()
+            .unwrap_or_default()
+            .min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let overlay_area = Rect {
+            x: area.right().saturating_sub(width),
+            y: area.y,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, overlay_area);
+        frame.render_widget(
+            Paragraph::new(lines.join("\n")).block(
+                Block::bordered()
+                    .title("Debug")
+                    .border_style(Style::default().fg(Color::Magenta)),
+            ),
+            overlay_area,
+        );
+    }
+}
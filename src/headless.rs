@@ -0,0 +1,153 @@
+//! `lazychat --prompt`: one question and answer without the TUI.
+
+use std::{io::Write, time::Duration};
+
+use color_eyre::{Result, eyre::eyre};
+
+use crate::core::{
+    ChatMessage, Delta, Engine, Settings,
+    attachments::{self, AttachmentsConfig},
+    budget::BudgetStatus,
+    commit, session,
+};
+
+/// Ask a single question, stream the answer to stdout and save the exchange as a session.
+///
+/// Reasoning goes to stderr, so that only the answer ends up in pipes.
+pub async fn run(settings: Settings, prompt: &str) -> Result<()> {
+    let mut engine = Engine::new(settings);
+    check_budget(&engine)?;
+    engine.conversation.push(ChatMessage::new("user", prompt));
+    let reply = engine.complete(report_retry, printer(true)).await;
+    engine.save()?;
+    reply?;
+    println!();
+    Ok(())
+}
+
+/// Run a workflow and save it as a session.
+///
+/// Only the reply to the last step goes to stdout; the steps before it are shown on stderr.
+pub async fn run_workflow(settings: Settings, name: &str, input: &str) -> Result<()> {
+    let mut engine = Engine::new(settings);
+    check_budget(&engine)?;
+    let mut completion = Some(engine.start_workflow(name, input)?);
+    while let Some(next) = completion {
+        let (step, total) = engine.workflow().map_or((1, 1), |run| run.progress());
+        eprintln!(
+            "[{name}: step {step} of {total}, {}]",
+            engine.conversation.model
+        );
+        let reply = next
+            .on_retry(report_retry)
+            .run(printer(step == total))
+            .await;
+        match reply {
+            Ok(reply) => engine.receive(reply),
+            Err(err) => {
+                engine.conversation.push_error(&err.to_string());
+                engine.stop_workflow();
+                engine.save()?;
+                return Err(err);
+            }
+        }
+        if step == total {
+            println!();
+        } else {
+            eprintln!("\n");
+        }
+        completion = engine.advance_workflow();
+    }
+    engine.save()
+}
+
+/// Write a commit message for the staged changes and commit with it once it was edited in git's
+/// editor, or only print it.
+pub async fn commit(
+    settings: Settings,
+    attachments_config: &AttachmentsConfig,
+    print_only: bool,
+) -> Result<()> {
+    let dir = std::env::current_dir()?;
+    let diff = attachments::staged_diff(&dir, attachments_config).await?;
+    if diff.omitted_lines > 0 {
+        eprintln!(
+            "The diff is too long, {} lines are left out",
+            diff.omitted_lines
+        );
+    }
+    let mut engine = Engine::new(settings);
+    check_budget(&engine)?;
+    eprintln!(
+        "[writing a commit message with {}]",
+        engine.conversation.model
+    );
+    let reply = engine
+        .ask_aside(commit::SYSTEM_PROMPT, &commit::question(&diff))
+        .on_retry(report_retry)
+        .run(|_| {})
+        .await?;
+    engine.record_usage(&reply);
+    let message = commit::message(&reply.content);
+    if print_only {
+        println!("{message}");
+        return Ok(());
+    }
+    // git opens its editor on the message; emptying it aborts the commit
+    let file =
+        std::env::temp_dir().join(format!("lazychat-commit-{}.txt", session::new_session_id()));
+    std::fs::write(&file, &message)?;
+    let status = tokio::process::Command::new("git")
+        .args(["commit", "--edit", "--file"])
+        .arg(&file)
+        .status()
+        .await;
+    let _ = std::fs::remove_file(&file);
+    if !status?.success() {
+        return Err(eyre!("Nothing was committed"));
+    }
+    Ok(())
+}
+
+fn check_budget(engine: &Engine) -> Result<()> {
+    match engine.budget_status() {
+        BudgetStatus::Hard(reason) => return Err(eyre!("{reason}, not sending")),
+        BudgetStatus::Soft(reason) => eprintln!("{reason}"),
+        BudgetStatus::Within => {}
+    }
+    Ok(())
+}
+
+fn report_retry(wait: Duration) {
+    eprintln!("Rate limited, retrying in {}s", wait.as_secs());
+}
+
+/// Print a streamed reply: the answer to stdout, or stderr unless `to_stdout`, and the reasoning
+/// to stderr.
+fn printer(to_stdout: bool) -> impl FnMut(Delta) + Send {
+    let mut reasoning = false;
+    move |delta| {
+        let _ = match delta {
+            Delta::Reasoning(text) => {
+                reasoning = true;
+                write_now(&mut std::io::stderr(), &text)
+            }
+            Delta::Content(text) => {
+                // Separate the answer from the reasoning before it
+                if std::mem::take(&mut reasoning) {
+                    let _ = write_now(&mut std::io::stderr(), "\n\n");
+                }
+                if to_stdout {
+                    write_now(&mut std::io::stdout(), &text)
+                } else {
+                    write_now(&mut std::io::stderr(), &text)
+                }
+            }
+        };
+    }
+}
+
+fn write_now(out: &mut impl Write, text: &str) -> std::io::Result<()> {
+    out.write_all(text.as_bytes())?;
+    out.flush()
+}
@@ -0,0 +1,75 @@
+//! Nerd Font icons for roles, sessions and status items, or plain ASCII markers for fonts
+//! without them, chosen with `ui.use_icons`.
+
+use std::{
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+/// The markers drawn in front of roles, sessions and status items.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Icons {
+    pub user: &'static str,
+    pub assistant: &'static str,
+    pub tool: &'static str,
+    pub system: &'static str,
+    /// The open session in the sidebar.
+    pub current_session: &'static str,
+    pub session: &'static str,
+    /// The connection indicator, colored by its health.
+    pub health: &'static str,
+    pub recording: &'static str,
+    pub agent: &'static str,
+}
+
+pub const NERD_FONT: Icons = Icons {
+    user: "\u{f007} ",
+    assistant: "\u{f06a9} ",
+    tool: "\u{f0ad} ",
+    system: "\u{f013} ",
+    current_session: "\u{f075} ",
+    session: "\u{f0e5} ",
+    health: "\u{f1eb}",
+    recording: "\u{f130}",
+    agent: "\u{f013}",
+};
+
+/// Roles need no marker as their name is shown anyway.
+pub const ASCII: Icons = Icons {
+    user: "",
+    assistant: "",
+    tool: "",
+    system: "",
+    current_session: "* ",
+    session: "  ",
+    health: "*",
+    recording: "*",
+    agent: "@",
+};
+
+impl Icons {
+    /// The marker of a message of `role`.
+    pub fn role(&self, role: &str) -> &'static str {
+        match role {
+            "user" => self.user,
+            "tool" => self.tool,
+            "system" => self.system,
+            _ => self.assistant,
+        }
+    }
+}
+
+/// Whether a Nerd Font is installed, as far as `fc-list` knows; `false` if it can't be asked.
+///
+/// Only asked once, so that reloading the config doesn't run `fc-list` again.
+pub fn detect() -> bool {
+    static DETECTED: OnceLock<bool> = OnceLock::new();
+    *DETECTED.get_or_init(|| {
+        Command::new("fc-list")
+            .args([":", "family"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("Nerd Font"))
+    })
+}
@@ -0,0 +1,118 @@
+//! Translations of the UI, chosen with `ui.locale` or the `LANG` of the environment.
+//!
+//! Strings are looked up by their English text, so an untranslated string shows in English.
+//! Translations are JSON5 files named after the language, e.g. `de.json5`, mapping the English
+//! text to the translated one. Placeholders like `{count}` are filled in after the lookup.
+
+use std::{borrow::Cow, collections::HashMap, path::Path, sync::OnceLock};
+
+use color_eyre::{Result, eyre::eyre};
+
+/// Translations shipped with lazychat, by language.
+const BUILT_IN: &[(&str, &str)] = &[("de", include_str!("../.config/locales/de.json5"))];
+
+static STRINGS: OnceLock<Strings> = OnceLock::new();
+
+/// The translated strings of one language.
+#[derive(Debug, Default)]
+pub struct Strings(HashMap<String, String>);
+
+impl Strings {
+    /// The strings of `language`: the built-in ones, overridden by those in `dir`.
+    pub fn load(language: &str, dir: &Path) -> Result<Self> {
+        let mut strings = HashMap::new();
+        if let Some((_, text)) = BUILT_IN.iter().find(|(name, _)| *name == language) {
+            strings.extend(parse(text)?);
+        }
+        let path = dir.join(format!("{language}.json5"));
+        if path.exists() {
+            let text = std::fs::read_to_string(&path)?;
+            strings.extend(parse(&text).map_err(|err| eyre!("{}: {err}", path.display()))?);
+        }
+        Ok(Self(strings))
+    }
+
+    fn get<'a>(&'a self, text: &'a str) -> &'a str {
+        self.0.get(text).map_or(text, String::as_str)
+    }
+}
+
+fn parse(text: &str) -> Result<HashMap<String, String>> {
+    json5::from_str(text).map_err(|err| eyre!("Invalid translations: {err}"))
+}
+
+/// The language of `locale` like `de_DE.UTF-8`, or of the environment's `LC_ALL` or `LANG`.
+pub fn language(locale: Option<&str>) -> String {
+    let locale = locale.map(str::to_string).or_else(|| {
+        ["LC_ALL", "LANG"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    });
+    let locale = locale.unwrap_or_default();
+    let language = locale
+        .split(['_', '.', '-', '@'])
+        .next()
+        .unwrap_or_default();
+    match language {
+        "" | "C" | "POSIX" => "en".to_string(),
+        language => language.to_lowercase(),
+    }
+}
+
+/// Use the translations of `language` from now on; only the first call has an effect.
+pub fn init(language: &str, dir: &Path) -> Result<()> {
+    let strings = if language == "en" {
+        Strings::default()
+    } else {
+        Strings::load(language, dir)?
+    };
+    let _ = STRINGS.set(strings);
+    Ok(())
+}
+
+/// `text` in the language of the UI.
+pub fn t(text: &'static str) -> Cow<'static, str> {
+    match STRINGS.get().map(|strings| strings.get(text)) {
+        Some(translated) if translated != text => Cow::Owned(translated.to_string()),
+        _ => Cow::Borrowed(text),
+    }
+}
+
+/// `text` in the language of the UI with its `{name}` placeholders replaced by `args`.
+pub fn tf(text: &'static str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+    fill(&t(text), args)
+}
+
+fn fill(text: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+    args.iter().fold(text.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), &value.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::session::new_session_id;
+
+    #[test]
+    fn test_strings() -> Result<()> {
+        assert_eq!(language(Some("de_DE.UTF-8")), "de");
+        assert_eq!(language(Some("C")), "en");
+
+        let dir = std::env::temp_dir().join(format!("lazychat-locales-{}", new_session_id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("de.json5"), r#"{ "Input": "Eingabe!" }"#)?;
+        let strings = Strings::load("de", &dir)?;
+        assert_eq!(strings.get("Input"), "Eingabe!");
+        assert_eq!(strings.get("Chat Window"), "Chat");
+        assert_eq!(strings.get("Not translated"), "Not translated");
+        assert_eq!(
+            fill(strings.get("Find session ({count})"), &[("count", &3)]),
+            "Sitzung suchen (3)"
+        );
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
+use std::{path::Path, sync::OnceLock};
+
 use color_eyre::Result;
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+use tracing_subscriber::{EnvFilter, Registry, fmt, prelude::*, reload};
 
 use crate::config;
 
@@ -9,9 +11,11 @@ lazy_static::lazy_static! {
     pub static ref LOG_FILE: String = format!("{}.log", env!("CARGO_PKG_NAME"));
 }
 
-pub fn init() -> Result<()> {
-    let directory = config::get_data_dir();
-    std::fs::create_dir_all(directory.clone())?;
+/// Handle for swapping the log filter while running.
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+pub fn init(directory: &Path) -> Result<()> {
+    std::fs::create_dir_all(directory)?;
     let log_path = directory.join(LOG_FILE.clone());
     let log_file = std::fs::File::create(log_path)?;
     let env_filter = EnvFilter::builder().with_default_directive(tracing::Level::INFO.into());
@@ -21,6 +25,8 @@ pub fn init() -> Result<()> {
     let env_filter = env_filter
         .try_from_env()
         .or_else(|_| env_filter.with_env_var(LOG_ENV.clone()).from_env())?;
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    let _ = FILTER_HANDLE.set(handle);
     let file_subscriber = fmt::layer()
         .with_file(true)
         .with_line_number(true)
@@ -34,3 +40,13 @@ pub fn init() -> Result<()> {
         .try_init()?;
     Ok(())
 }
+
+/// Replace the log filter, e.g. `debug` or `info,lazychat::app=trace`.
+pub fn set_filter(directives: &str) -> Result<()> {
+    let filter = EnvFilter::builder().parse(directives)?;
+    if let Some(handle) = FILTER_HANDLE.get() {
+        handle.reload(filter)?;
+    }
+    tracing::info!("Log filter set to `{directives}`");
+    Ok(())
+}
@@ -1,26 +1,140 @@
 use clap::Parser;
-use cli::Cli;
-use color_eyre::Result;
+use cli::{Cli, Command};
+use color_eyre::{Result, eyre::eyre};
 
-use crate::app::App;
+use crate::{
+    app::App,
+    config::{Config, Overrides, get_config_dir},
+    core::{metrics::MetricsLog, openapi, session, usage::UsageLog},
+};
 
 mod action;
 mod app;
+mod audio;
 mod cli;
+mod clipboard;
+mod commands;
 mod components;
 mod config;
+mod core;
+mod debug;
 mod errors;
+mod headless;
+mod icons;
+mod locale;
 mod logging;
+mod schema;
+mod stats;
+mod theme;
+mod tokens;
 mod tui;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv::dotenv().ok(); // Load .env file if it exists
     crate::errors::init()?;
-    crate::logging::init()?;
 
     let args = Cli::parse();
-    let mut app = App::new(args.tick_rate, args.frame_rate)?;
+    let config = Config::load(&Overrides {
+        data_dir: args.data_dir.clone(),
+        provider: args.provider,
+    })
+    .inspect_err(|_| {
+        for problem in schema::check_dir(&get_config_dir()) {
+            eprintln!("{problem}");
+        }
+    })?;
+    if args.check_keys {
+        let diagnostics = config.keybinding_diagnostics();
+        if diagnostics.is_empty() {
+            println!("No keybinding conflicts found");
+            return Ok(());
+        }
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+        std::process::exit(1);
+    }
+    match args.command {
+        Some(Command::Usage { days }) => {
+            let summary =
+                UsageLog::new(config.config.usage_file()).summary(session::now(), days)?;
+            for line in summary.lines() {
+                println!("{line}");
+            }
+            return Ok(());
+        }
+        Some(Command::Stats) => {
+            let metrics = MetricsLog::load(&config.config.metrics_file())?;
+            if metrics.requests == 0 {
+                if config.metrics.enabled {
+                    println!("No requests recorded yet");
+                } else {
+                    println!(
+                        "Metrics are off, set `\"metrics\": {{ \"enabled\": true }}` in the config file to record them"
+                    );
+                }
+                return Ok(());
+            }
+            for line in metrics.lines() {
+                println!("{line}");
+            }
+            return Ok(());
+        }
+        Some(Command::ImportOpenapi {
+            spec,
+            base_url,
+            headers,
+        }) => {
+            let headers = headers
+                .iter()
+                .map(|header| {
+                    header
+                        .split_once(':')
+                        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                        .ok_or_else(|| eyre!("Expected a header as `Name: value`, got `{header}`"))
+                })
+                .collect::<Result<_>>()?;
+            let spec = openapi::parse(&std::fs::read_to_string(&spec)?)?;
+            let tools = openapi::import(&spec, base_url.as_deref(), &headers)?;
+            eprintln!(
+                "{} {}, add them to your config file:",
+                tools.len(),
+                if tools.len() == 1 { "tool" } else { "tools" }
+            );
+            let config = serde_json::json!({ "tools": { "custom": tools } });
+            println!("{}", serde_json::to_string_pretty(&config)?);
+            return Ok(());
+        }
+        _ => {}
+    }
+    config.config.create_dirs()?;
+    crate::logging::init(&config.config.log_dir())?;
+    if let Some(Command::Workflow { name, input }) = &args.command {
+        return headless::run_workflow(config.engine_settings(), name, &input.join(" ")).await;
+    }
+    if let Some(Command::Commit { print }) = &args.command {
+        return headless::commit(config.engine_settings(), &config.attachments, *print).await;
+    }
+    if let Some(prompt) = args.prompt {
+        return headless::run(config.engine_settings(), &prompt).await;
+    }
+
+    let language = locale::language(config.ui.locale.as_deref());
+    // Untranslated strings show in English, so a broken translation shouldn't keep lazychat from
+    // starting
+    let translations = locale::init(&language, &config.config.locales_dir());
+    let print_on_exit = args.print_on_exit || config.ui.print_on_exit;
+    let mut app = App::new(config, args.tick_rate, args.frame_rate)?;
+    if let Err(err) = translations {
+        tracing::error!("Failed to load the {language} translations: {err}");
+        app.notify(format!(
+            "Failed to load the {language} translations, using English: {err}"
+        ))?;
+    }
     app.run().await?;
+    if print_on_exit && let Some(transcript) = app.transcript() {
+        print!("{transcript}");
+    }
     Ok(())
 }
@@ -0,0 +1,516 @@
+//! Checks of the config file against the settings lazychat knows, so that typos and bad values
+//! are reported with their line and column instead of being ignored.
+
+use std::{fmt, path::Path};
+
+use serde_json::Value;
+
+use crate::{
+    action::Action,
+    app::Mode,
+    config::{parse_key_sequence, style_problem},
+};
+
+/// The shape of a config value: which keys an object may have.
+#[derive(Debug)]
+enum Schema {
+    /// Not checked further, e.g. a number or a JSON schema of tool parameters.
+    Any,
+    Object(&'static [(&'static str, Schema)]),
+    /// Keys chosen by the user, e.g. model ids, each with a value of the given shape.
+    Map(&'static Schema),
+    /// Key chords, e.g. `<F2>`, each with any value.
+    Keys,
+    KeyBindings,
+    Styles,
+}
+
+const MODEL_PARAMS: Schema = Schema::Object(&[
+    ("temperature", Schema::Any),
+    ("top_p", Schema::Any),
+    ("max_tokens", Schema::Any),
+    ("prompt_price", Schema::Any),
+]);
+const LENGTH_PRESET: Schema =
+    Schema::Object(&[("max_tokens", Schema::Any), ("instruction", Schema::Any)]);
+const CHAT: Schema = Schema::Object(&[
+    ("provider", Schema::Any),
+    ("model", Schema::Any),
+    ("system_prompt", Schema::Any),
+    ("temperature", Schema::Any),
+    ("top_p", Schema::Any),
+    ("max_tokens", Schema::Any),
+    (
+        "prompt_cache",
+        Schema::Object(&[("enabled", Schema::Any), ("min_tokens", Schema::Any)]),
+    ),
+    (
+        "rate_limit",
+        Schema::Object(&[
+            ("requests_per_minute", Schema::Any),
+            ("tokens_per_minute", Schema::Any),
+        ]),
+    ),
+    (
+        "lengths",
+        Schema::Object(&[
+            ("brief", LENGTH_PRESET),
+            ("normal", LENGTH_PRESET),
+            ("detailed", LENGTH_PRESET),
+        ]),
+    ),
+]);
+const LIMITS: Schema = Schema::Object(&[("soft", Schema::Any), ("hard", Schema::Any)]);
+const CUSTOM_TOOL: Schema = Schema::Object(&[
+    ("description", Schema::Any),
+    ("parameters", Schema::Any),
+    ("command", Schema::Any),
+    (
+        "http",
+        Schema::Object(&[
+            ("url", Schema::Any),
+            ("method", Schema::Any),
+            ("query", Schema::Any),
+            ("headers", Schema::Any),
+            ("body", Schema::Any),
+        ]),
+    ),
+]);
+const TOOLS: Schema = Schema::Object(&[
+    ("enabled", Schema::Any),
+    ("default_policy", Schema::Any),
+    ("policies", Schema::Any),
+    ("max_output_bytes", Schema::Any),
+    ("max_output_lines", Schema::Any),
+    ("workspace", Schema::Any),
+    (
+        "shell",
+        Schema::Object(&[
+            ("timeout_secs", Schema::Any),
+            ("max_cpu_secs", Schema::Any),
+            ("max_memory_mb", Schema::Any),
+            ("allow", Schema::Any),
+            ("deny", Schema::Any),
+        ]),
+    ),
+    ("custom", Schema::Map(&CUSTOM_TOOL)),
+    ("agent_steps", Schema::Any),
+]);
+const WORKFLOW: Schema = Schema::Object(&[("description", Schema::Any), ("steps", Schema::Any)]);
+const CONFIG: Schema = Schema::Object(&[
+    ("data_dir", Schema::Any),
+    ("config_dir", Schema::Any),
+    ("keybindings", Schema::KeyBindings),
+    ("styles", Schema::Styles),
+    (
+        "audio",
+        Schema::Object(&[
+            ("record_command", Schema::Any),
+            ("transcription_url", Schema::Any),
+            ("model", Schema::Any),
+            ("api_key_env", Schema::Any),
+            ("language", Schema::Any),
+        ]),
+    ),
+    (
+        "ui",
+        Schema::Object(&[
+            ("show_message_numbers", Schema::Any),
+            ("collapse_after_lines", Schema::Any),
+            ("line_numbers", Schema::Any),
+            ("undo_history", Schema::Any),
+            ("confirm_clear_lines", Schema::Any),
+            ("confirm_paste_chars", Schema::Any),
+            ("reduced_motion", Schema::Any),
+            ("stream_flush_ms", Schema::Any),
+            ("theme", Schema::Any),
+            ("locale", Schema::Any),
+            ("use_icons", Schema::Any),
+            ("print_on_exit", Schema::Any),
+        ]),
+    ),
+    ("chat", CHAT),
+    ("models", Schema::Map(&MODEL_PARAMS)),
+    (
+        "budget",
+        Schema::Object(&[("daily", LIMITS), ("monthly", LIMITS)]),
+    ),
+    (
+        "health",
+        Schema::Object(&[
+            ("interval_secs", Schema::Any),
+            ("slow_ms", Schema::Any),
+            ("timeout_ms", Schema::Any),
+        ]),
+    ),
+    ("tools", TOOLS),
+    ("templates", Schema::Any),
+    ("personas", Schema::Any),
+    ("quick_prompts", Schema::Keys),
+    ("snippets", Schema::Any),
+    ("workflows", Schema::Map(&WORKFLOW)),
+    (
+        "transcript",
+        Schema::Object(&[
+            ("enabled", Schema::Any),
+            ("max_bytes", Schema::Any),
+            ("max_files", Schema::Any),
+        ]),
+    ),
+    ("metrics", Schema::Object(&[("enabled", Schema::Any)])),
+    (
+        "share",
+        Schema::Object(&[
+            ("service", Schema::Any),
+            ("public", Schema::Any),
+            ("token_env", Schema::Any),
+            ("url", Schema::Any),
+            ("command", Schema::Any),
+        ]),
+    ),
+    (
+        "notes",
+        Schema::Object(&[
+            ("dir", Schema::Any),
+            ("daily", Schema::Any),
+            ("tags", Schema::Any),
+        ]),
+    ),
+    (
+        "attachments",
+        Schema::Object(&[
+            ("max_bytes", Schema::Any),
+            ("tree_depth", Schema::Any),
+            ("tree_max_entries", Schema::Any),
+            ("watch_clipboard", Schema::Any),
+            ("clipboard_poll_ms", Schema::Any),
+        ]),
+    ),
+    (
+        "rag",
+        Schema::Object(&[
+            ("embeddings", Schema::Any),
+            ("url", Schema::Any),
+            ("model", Schema::Any),
+            ("api_key_env", Schema::Any),
+            ("local_model", Schema::Any),
+            ("top_k", Schema::Any),
+            ("chunk_lines", Schema::Any),
+            ("max_file_bytes", Schema::Any),
+            ("max_chunks", Schema::Any),
+            ("batch_size", Schema::Any),
+        ]),
+    ),
+    (
+        "compaction",
+        Schema::Object(&[
+            ("auto_at_tokens", Schema::Any),
+            ("keep_messages", Schema::Any),
+        ]),
+    ),
+    (
+        "mock",
+        Schema::Object(&[
+            ("response", Schema::Any),
+            ("reasoning", Schema::Any),
+            ("latency_ms", Schema::Any),
+            ("word_delay_ms", Schema::Any),
+        ]),
+    ),
+]);
+
+/// Something wrong in the config file and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub file: String,
+    /// One-based, like editors count.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+/// Check the JSON5 or JSON config file in `config_dir`; other formats aren't checked.
+pub fn check_dir(config_dir: &Path) -> Vec<Problem> {
+    ["config.json5", "config.json"]
+        .iter()
+        .map(|file| config_dir.join(file))
+        .find(|path| path.exists())
+        .and_then(|path| {
+            let source = std::fs::read_to_string(&path).ok()?;
+            let file = path.file_name()?.to_string_lossy().into_owned();
+            Some(check(&file, &source))
+        })
+        .unwrap_or_default()
+}
+
+/// Check the JSON5 `source` of the config file `file`.
+pub fn check(file: &str, source: &str) -> Vec<Problem> {
+    let value: Value = match json5::from_str(source) {
+        Ok(value) => value,
+        Err(json5::Error::Message { msg, location }) => {
+            let (line, column) =
+                location.map_or((1, 1), |location| (location.line, location.column));
+            // Pest explains the error on the last line of its message, after `= `
+            let reason = msg
+                .lines()
+                .last()
+                .map(|line| line.trim_start_matches([' ', '=']).to_string())
+                .unwrap_or(msg);
+            return vec![Problem {
+                file: file.to_string(),
+                line,
+                column,
+                message: format!("syntax error: {reason}"),
+            }];
+        }
+    };
+    let mut checker = Checker {
+        file,
+        source,
+        problems: Vec::new(),
+    };
+    checker.check(&value, &CONFIG, &[], 0);
+    // Object keys come back sorted, report problems in the order of the file instead
+    checker
+        .problems
+        .sort_by_key(|problem| (problem.line, problem.column));
+    checker.problems
+}
+
+struct Checker<'a> {
+    file: &'a str,
+    source: &'a str,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    /// Check `value` at `path`, whose key was found at byte `offset` of the source.
+    fn check(&mut self, value: &Value, schema: &Schema, path: &[&str], offset: usize) {
+        let Some(object) = value.as_object() else {
+            return;
+        };
+        for (key, value) in object {
+            let key_offset = find_key(self.source, offset, key).unwrap_or(offset);
+            let mut key_path = path.to_vec();
+            key_path.push(key);
+            match schema {
+                Schema::Any => {}
+                Schema::Object(fields) => match fields.iter().find(|(name, _)| name == key) {
+                    Some((_, schema)) => self.check(value, schema, &key_path, key_offset),
+                    None => {
+                        let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+                        let hint = match closest(key, &names) {
+                            Some(name) => format!(", did you mean `{name}`?"),
+                            None => String::new(),
+                        };
+                        self.report(
+                            key_offset,
+                            format!("unknown setting `{}`{hint}", key_path.join(".")),
+                        );
+                    }
+                },
+                Schema::Map(schema) => self.check(value, schema, &key_path, key_offset),
+                Schema::Keys => {
+                    if let Err(err) = parse_key_sequence(key) {
+                        self.report(key_offset, format!("{}: `{key}`: {err}", path.join(".")));
+                    }
+                }
+                Schema::KeyBindings => self.check_keybindings(key, value, key_offset),
+                Schema::Styles => self.check_styles(key, value, key_offset),
+            }
+        }
+    }
+
+    fn check_mode(&mut self, mode: &str, offset: usize, section: &str) -> bool {
+        if serde_json::from_value::<Mode>(Value::from(mode)).is_ok() {
+            return true;
+        }
+        let hint = closest(
+            mode,
+            &["Home", "Input", "Chat", "Sidebar", "Dialog", "Menu"],
+        )
+        .map(|name| format!(", did you mean `{name}`?"))
+        .unwrap_or_default();
+        self.report(offset, format!("{section}: unknown mode `{mode}`{hint}"));
+        false
+    }
+
+    fn check_keybindings(&mut self, mode: &str, bindings: &Value, offset: usize) {
+        if !self.check_mode(mode, offset, "keybindings") {
+            return;
+        }
+        for (keys, action) in bindings.as_object().into_iter().flatten() {
+            let key_offset = find_key(self.source, offset, keys).unwrap_or(offset);
+            if let Err(err) = parse_key_sequence(keys) {
+                self.report(key_offset, format!("keybindings.{mode}: `{keys}`: {err}"));
+            }
+            if serde_json::from_value::<Action>(action.clone()).is_err() {
+                self.report(
+                    key_offset,
+                    format!("keybindings.{mode}: `{keys}` is bound to unknown action {action}"),
+                );
+            }
+        }
+    }
+
+    fn check_styles(&mut self, mode: &str, styles: &Value, offset: usize) {
+        if !self.check_mode(mode, offset, "styles") {
+            return;
+        }
+        for (name, style) in styles.as_object().into_iter().flatten() {
+            let key_offset = find_key(self.source, offset, name).unwrap_or(offset);
+            let problem = match style.as_str() {
+                Some(style) => style_problem(style),
+                None => Some(format!(
+                    "expected a style like \"bold white on blue\", got {style}"
+                )),
+            };
+            if let Some(problem) = problem {
+                self.report(key_offset, format!("styles.{mode}.{name}: {problem}"));
+            }
+        }
+    }
+
+    fn report(&mut self, offset: usize, message: String) {
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before, |start| &before[start + 1..])
+            .chars()
+            .count()
+            + 1;
+        self.problems.push(Problem {
+            file: self.file.to_string(),
+            line,
+            column,
+            message,
+        });
+    }
+}
+
+/// Where `key` is written as an object key at or after byte `from`, quoted or bare, skipping
+/// occurrences in comments.
+fn find_key(source: &str, from: usize, key: &str) -> Option<usize> {
+    let mut start = from;
+    while let Some(found) = source[start..].find(key).map(|index| start + index) {
+        start = found + key.len();
+        let line_start = source[..found].rfind('\n').map_or(0, |index| index + 1);
+        if source[line_start..found].contains("//") {
+            continue;
+        }
+        let before = source[..found].chars().next_back();
+        let after = &source[found + key.len()..];
+        let (quoted, rest) = match before {
+            Some(quote @ ('"' | '\'')) => match after.strip_prefix(quote) {
+                Some(rest) => (true, rest),
+                None => continue,
+            },
+            Some(c) if c.is_alphanumeric() || c == '_' => continue,
+            _ => (false, after),
+        };
+        if rest.trim_start().starts_with(':') {
+            return Some(if quoted { found - 1 } else { found });
+        }
+    }
+    None
+}
+
+/// The name in `names` closest to a misspelt `key`, if any is close enough.
+fn closest<'a>(key: &str, names: &[&'a str]) -> Option<&'a str> {
+    names
+        .iter()
+        .map(|name| {
+            (
+                edit_distance(&key.to_lowercase(), &name.to_lowercase()),
+                *name,
+            )
+        })
+        .filter(|(distance, name)| *distance <= (name.len() / 3).max(2))
+        .min()
+        .map(|(_, name)| name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn messages(source: &str) -> Vec<String> {
+        check("config.json5", source)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let source = r#"{
+  // "chat": { "modle": "ignored in comments" },
+  "chat": { "modle": "openai/gpt-4o" },
+  models: { "openai/gpt-4o": { max_token: 100 } },
+  "keybindings": {
+    "Home": { "<Ctrl-q>": "Qiut", "<Hyper-x>": "Quit" },
+    "Hom": {},
+  },
+  "styles": { "Home": { "title": "bold purple on blue" } },
+  "quick_prompts": { "<F2>": "Explain {{clipboard}}", "<Hyper-y>": "Why?" },
+}"#;
+        assert_eq!(
+            messages(source),
+            [
+                "config.json5:3:13: unknown setting `chat.modle`, did you mean `model`?",
+                "config.json5:4:32: unknown setting `models.openai/gpt-4o.max_token`, did you mean `max_tokens`?",
+                "config.json5:6:15: keybindings.Home: `<Ctrl-q>` is bound to unknown action \"Qiut\"",
+                "config.json5:6:35: keybindings.Home: `<Hyper-x>`: Unable to parse hyper-x",
+                "config.json5:7:5: keybindings: unknown mode `Hom`, did you mean `Home`?",
+                "config.json5:9:25: styles.Home.title: unknown color `purple`",
+                "config.json5:10:55: quick_prompts: `<Hyper-y>`: Unable to parse hyper-y",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let problems = check("config.json5", "{\n  \"chat\": { model: },\n}");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (2, 20));
+        assert!(
+            problems[0].message.starts_with("syntax error"),
+            "{problems:?}"
+        );
+    }
+
+    #[test]
+    fn test_default_config_is_clean() {
+        assert_eq!(
+            messages(include_str!("../.config/config.json5")),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::Serialize;
+
+use crate::core::ChatMessage;
+
+/// Aggregate statistics for a conversation.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ConversationStats {
+    pub messages_by_role: BTreeMap<String, usize>,
+    pub prompt_tokens: u64,
+    /// Prompt tokens served from the provider's prompt cache.
+    pub cached_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_cost: f64,
+    pub average_latency_ms: Option<u64>,
+    pub longest_response_tokens: Option<u64>,
+    pub longest_response_chars: usize,
+}
+
+impl ConversationStats {
+    pub fn from_history(history: &[ChatMessage]) -> Self {
+        let mut stats = Self::default();
+        let mut latencies = Vec::new();
+
+        for message in history {
+            *stats
+                .messages_by_role
+                .entry(message.role.clone())
+                .or_default() += 1;
+
+            let metadata = &message.metadata;
+            stats.prompt_tokens += metadata.prompt_tokens.unwrap_or_default();
+            stats.cached_tokens += metadata.cached_tokens.unwrap_or_default();
+            stats.completion_tokens += metadata.completion_tokens.unwrap_or_default();
+            stats.total_cost += metadata.cost.unwrap_or_default();
+            latencies.extend(metadata.latency_ms);
+            if metadata.completion_tokens > stats.longest_response_tokens {
+                stats.longest_response_tokens = metadata.completion_tokens;
+                stats.longest_response_chars = message.content.chars().count();
+            }
+        }
+
+        if !latencies.is_empty() {
+            stats.average_latency_ms = Some(latencies.iter().sum::<u64>() / latencies.len() as u64);
+        }
+        stats
+    }
+
+    /// Render the statistics as human readable lines for the stats popup.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec!["Messages".to_string()];
+        if self.messages_by_role.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+        for (role, count) in &self.messages_by_role {
+            lines.push(format!("  {role}: {count}"));
+        }
+        lines.push(String::new());
+        lines.push(format!("Tokens in:        {}", self.prompt_tokens));
+        if self.cached_tokens > 0 {
+            lines.push(format!("  from cache:     {}", self.cached_tokens));
+        }
+        lines.push(format!("Tokens out:       {}", self.completion_tokens));
+        lines.push(format!("Total cost:       ${:.6}", self.total_cost));
+        lines.push(format!(
+            "Average latency:  {}",
+            self.average_latency_ms
+                .map_or("-".to_string(), |ms| format!("{:.2}s", ms as f64 / 1000.0))
+        ));
+        lines.push(format!(
+            "Longest response: {}",
+            self.longest_response_tokens
+                .map_or("-".to_string(), |tokens| format!(
+                    "{tokens} tokens ({} chars)",
+                    self.longest_response_chars
+                ))
+        ));
+        lines
+    }
+
+    /// Write the statistics as JSON into `dir` and return the path of the new file.
+    pub fn export(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = dir.join(format!("stats-{timestamp}.json"));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::MessageMetadata;
+
+    #[test]
+    fn test_from_history() {
+        let history = vec![
+            ChatMessage {
+                role: "user".to_string(),
+                content: "hi".to_string(),
+                ..Default::default()
+            },
+            ChatMessage {
+                role: "AI".to_string(),
+                content: "hello there".to_string(),
+                metadata: MessageMetadata {
+                    prompt_tokens: Some(10),
+                    completion_tokens: Some(3),
+                    cost: Some(0.5),
+                    latency_ms: Some(1000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ChatMessage {
+                role: "AI".to_string(),
+                content: "bye".to_string(),
+                metadata: MessageMetadata {
+                    prompt_tokens: Some(20),
+                    completion_tokens: Some(1),
+                    cost: Some(0.25),
+                    latency_ms: Some(2000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ];
+        let stats = ConversationStats::from_history(&history);
+        assert_eq!(stats.messages_by_role["user"], 1);
+        assert_eq!(stats.messages_by_role["AI"], 2);
+        assert_eq!(stats.prompt_tokens, 30);
+        assert_eq!(stats.completion_tokens, 4);
+        assert_eq!(stats.total_cost, 0.75);
+        assert_eq!(stats.average_latency_ms, Some(1500));
+        assert_eq!(stats.longest_response_tokens, Some(3));
+        assert_eq!(stats.longest_response_chars, 11);
+    }
+}
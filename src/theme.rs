@@ -0,0 +1,131 @@
+//! Built-in color themes, chosen with `ui.theme`.
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    /// Bright text on black and bold roles, for low vision and washed-out screens.
+    HighContrast,
+    /// Blue, orange and purple instead of red and green, from the Okabe-Ito palette.
+    Deuteranopia,
+}
+
+/// Colors of the transcript, borders and notices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub user: Style,
+    pub assistant: Style,
+    pub tool: Style,
+    /// Border of the focused pane.
+    pub focus: Color,
+    /// Tool calls and agent progress.
+    pub accent: Color,
+    /// Hints, reasoning and other secondary text.
+    pub muted: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub success: Color,
+    /// Backgrounds telling the personas of a roundtable apart.
+    pub personas: [Color; 4],
+    /// Start every line of a message with a marker of its role, so that roles can be told apart
+    /// without their colors.
+    pub role_markers: bool,
+}
+
+impl ThemeName {
+    pub fn theme(self) -> Theme {
+        match self {
+            Self::Default => Theme {
+                user: Style::default().fg(Color::White).bg(Color::Black),
+                assistant: Style::default().fg(Color::Black).bg(Color::Blue),
+                tool: Style::default().fg(Color::Magenta).bg(Color::Black),
+                focus: Color::Blue,
+                accent: Color::Magenta,
+                muted: Color::DarkGray,
+                warning: Color::Yellow,
+                error: Color::Red,
+                success: Color::Green,
+                personas: [Color::Blue, Color::Cyan, Color::Yellow, Color::Magenta],
+                role_markers: false,
+            },
+            Self::HighContrast => Theme {
+                user: Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+                assistant: Style::default().fg(Color::Black).bg(Color::White),
+                tool: Style::default().fg(Color::Black).bg(Color::LightYellow),
+                focus: Color::LightYellow,
+                accent: Color::LightCyan,
+                muted: Color::Gray,
+                warning: Color::LightYellow,
+                error: Color::LightRed,
+                success: Color::LightGreen,
+                personas: [
+                    Color::White,
+                    Color::LightYellow,
+                    Color::LightCyan,
+                    Color::LightMagenta,
+                ],
+                role_markers: true,
+            },
+            Self::Deuteranopia => Theme {
+                user: Style::default().fg(Color::White).bg(Color::Black),
+                assistant: Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Rgb(86, 180, 233)),
+                tool: Style::default()
+                    .fg(Color::Rgb(204, 121, 167))
+                    .bg(Color::Black),
+                focus: Color::Rgb(0, 114, 178),
+                accent: Color::Rgb(204, 121, 167),
+                muted: Color::DarkGray,
+                warning: Color::Rgb(230, 159, 0),
+                error: Color::Rgb(213, 94, 0),
+                success: Color::Rgb(86, 180, 233),
+                personas: [
+                    Color::Rgb(86, 180, 233),
+                    Color::Rgb(230, 159, 0),
+                    Color::Rgb(204, 121, 167),
+                    Color::Rgb(240, 228, 66),
+                ],
+                role_markers: true,
+            },
+        }
+    }
+}
+
+impl Theme {
+    /// Style of the messages of `role`.
+    pub fn role(&self, role: &str) -> Style {
+        match role {
+            "user" => self.user,
+            "tool" => self.tool,
+            _ => self.assistant,
+        }
+    }
+
+    /// Style of the replies of the `index`th persona of a roundtable.
+    pub fn persona(&self, index: usize) -> Style {
+        self.assistant
+            .bg(self.personas[index % self.personas.len()])
+    }
+
+    /// Indentation of the lines after the first of a message of `role`, starting with the
+    /// role's marker if the theme has them.
+    pub fn indent(&self, role: &str, width: usize) -> String {
+        if !self.role_markers || width < 2 {
+            return " ".repeat(width);
+        }
+        let marker = match role {
+            "user" => '›',
+            "tool" => '┆',
+            _ => '┃',
+        };
+        format!("{marker}{}", " ".repeat(width - 1))
+    }
+}
@@ -0,0 +1,78 @@
+use tiktoken_rs::{
+    CoreBPE, cl100k_base_singleton, o200k_base_singleton,
+    tokenizer::{Tokenizer, get_tokenizer},
+};
+
+use crate::core::ChatMessage;
+
+/// Tokens added by the chat format around every message (role markers and separators).
+const TOKENS_PER_MESSAGE: usize = 4;
+/// Tokens used to prime the assistant's reply.
+const TOKENS_PER_REPLY: usize = 3;
+
+/// Pick the tokenizer for a model.
+///
+/// Models are usually addressed with a provider prefix (`openai/gpt-4o`), which is stripped
+/// before the lookup. Models without a known OpenAI tokenizer fall back to `cl100k_base`, which
+/// is a close enough approximation for most modern BPE vocabularies.
+fn bpe_for_model(model: &str) -> &'static CoreBPE {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    match get_tokenizer(name) {
+        Some(Tokenizer::O200kBase) => o200k_base_singleton(),
+        _ => cl100k_base_singleton(),
+    }
+}
+
+/// Count the tokens of a piece of text.
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    bpe_for_model(model).encode_with_special_tokens(text).len()
+}
+
+/// Count the tokens a chat completion request would use for its prompt.
+pub fn count_prompt_tokens(model: &str, system_prompt: &str, messages: &[ChatMessage]) -> usize {
+    let bpe = bpe_for_model(model);
+    let count = |role: &str, content: &str| {
+        TOKENS_PER_MESSAGE
+            + bpe.encode_with_special_tokens(role).len()
+            + bpe.encode_with_special_tokens(content).len()
+    };
+
+    let mut total = TOKENS_PER_REPLY;
+    if !system_prompt.is_empty() {
+        total += count("system", system_prompt);
+    }
+    for message in messages {
+        total += count(&message.role, &message.content);
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_empty_prompt() {
+        assert_eq!(count_prompt_tokens("gpt-4o", "", &[]), TOKENS_PER_REPLY);
+    }
+
+    #[test]
+    fn test_prompt_with_messages() {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "hello world".to_string(),
+            ..Default::default()
+        }];
+        // "user" is 1 token and "hello world" is 2 tokens in both vocabularies
+        assert_eq!(
+            count_prompt_tokens("openai/gpt-4o", "", &messages),
+            TOKENS_PER_REPLY + TOKENS_PER_MESSAGE + 3
+        );
+        assert_eq!(
+            count_prompt_tokens("mistralai/mistral-nemo", "", &messages),
+            TOKENS_PER_REPLY + TOKENS_PER_MESSAGE + 3
+        );
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)] // Remove this once you start using the code
 
 use std::{
-    io::{Stdout, stdout},
+    io::{IsTerminal, Stderr, Stdout, Write, stderr, stdout},
     ops::{Deref, DerefMut},
     time::Duration,
 };
@@ -42,8 +42,41 @@ pub enum Event {
     Resize(u16, u16),
 }
 
+/// Where the TUI is drawn: stdout, or stderr when stdout is piped or redirected, so that the
+/// transcript printed by `--print-on-exit` can be captured while the TUI shows in the terminal.
+pub enum Output {
+    Stdout(Stdout),
+    Stderr(Stderr),
+}
+
+impl Output {
+    pub fn new() -> Self {
+        if stdout().is_terminal() {
+            Self::Stdout(stdout())
+        } else {
+            Self::Stderr(stderr())
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::Stderr(stderr) => stderr.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::Stderr(stderr) => stderr.flush(),
+        }
+    }
+}
+
 pub struct Tui {
-    pub terminal: ratatui::Terminal<Backend<Stdout>>,
+    pub terminal: ratatui::Terminal<Backend<Output>>,
     pub task: JoinHandle<()>,
     pub cancellation_token: CancellationToken,
     pub event_rx: UnboundedReceiver<Event>,
@@ -58,7 +91,7 @@ impl Tui {
     pub fn new() -> Result<Self> {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
         Ok(Self {
-            terminal: ratatui::Terminal::new(Backend::new(stdout()))?,
+            terminal: ratatui::Terminal::new(Backend::new(Output::new()))?,
             task: tokio::spawn(async {}),
             cancellation_token: CancellationToken::new(),
             event_rx,
@@ -166,12 +199,12 @@ impl Tui {
 
     pub fn enter(&mut self) -> Result<()> {
         crossterm::terminal::enable_raw_mode()?;
-        crossterm::execute!(stdout(), EnterAlternateScreen, cursor::Hide)?;
+        crossterm::execute!(Output::new(), EnterAlternateScreen, cursor::Hide)?;
         if self.mouse {
-            crossterm::execute!(stdout(), EnableMouseCapture)?;
+            crossterm::execute!(Output::new(), EnableMouseCapture)?;
         }
         if self.paste {
-            crossterm::execute!(stdout(), EnableBracketedPaste)?;
+            crossterm::execute!(Output::new(), EnableBracketedPaste)?;
         }
         self.start();
         Ok(())
@@ -182,12 +215,12 @@ impl Tui {
         if crossterm::terminal::is_raw_mode_enabled()? {
             self.flush()?;
             if self.paste {
-                crossterm::execute!(stdout(), DisableBracketedPaste)?;
+                crossterm::execute!(Output::new(), DisableBracketedPaste)?;
             }
             if self.mouse {
-                crossterm::execute!(stdout(), DisableMouseCapture)?;
+                crossterm::execute!(Output::new(), DisableMouseCapture)?;
             }
-            crossterm::execute!(stdout(), LeaveAlternateScreen, cursor::Show)?;
+            crossterm::execute!(Output::new(), LeaveAlternateScreen, cursor::Show)?;
             crossterm::terminal::disable_raw_mode()?;
         }
         Ok(())
@@ -215,7 +248,7 @@ impl Tui {
 }
 
 impl Deref for Tui {
-    type Target = ratatui::Terminal<Backend<Stdout>>;
+    type Target = ratatui::Terminal<Backend<Output>>;
 
     fn deref(&self) -> &Self::Target {
         &self.terminal